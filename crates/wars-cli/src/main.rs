@@ -0,0 +1,136 @@
+//! `wars` — translate a `.wasm` or `.wat` file into a formatted `.rs` file.
+use std::{collections::BTreeMap, path::PathBuf};
+
+use anyhow::Context;
+use clap::{Parser, ValueEnum};
+use proc_macro2::{Span, TokenStream};
+use quote::quote;
+use syn::Ident;
+use wars::{Flags, FuelCosts, OptsCore};
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Backend {
+    Wasmparser,
+    Waffle,
+}
+
+#[derive(Parser)]
+#[command(name = "wars", version, about = "Translate a wasm module into Rust")]
+struct Cli {
+    /// Input `.wasm` or `.wat` file.
+    input: PathBuf,
+    /// Output `.rs` file; defaults to the input with an `.rs` extension.
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+    /// Name of the generated host trait (`Foo`, `FooImpl`, `FooData`).
+    #[arg(long)]
+    name: String,
+    /// Path of the runtime crate in the generated code.
+    #[arg(long, default_value = "::wars_rt")]
+    crate_path: String,
+    /// Extra `OptsCore::roots` entries, e.g. `--root tracing=::tracing`.
+    #[arg(long = "root", value_parser = parse_root)]
+    roots: Vec<(String, String)>,
+    #[arg(long, value_enum, default_value = "wasmparser")]
+    backend: Backend,
+    /// Emit `Flags::ASYNC` code.
+    #[arg(long = "async")]
+    is_async: bool,
+    /// Emit `Flags::LEGACY` code.
+    #[arg(long)]
+    legacy: bool,
+    /// Emit `Flags::FUEL` metering.
+    #[arg(long)]
+    fuel: bool,
+    /// Emit `Flags::EPOCH` deadline checks.
+    #[arg(long)]
+    epoch: bool,
+    /// Emit `Flags::CALL_DEPTH` accounting.
+    #[arg(long)]
+    call_depth: bool,
+    /// Emit `Flags::DYN_IMPORTS` import resolution.
+    #[arg(long)]
+    dyn_imports: bool,
+    /// Per-class fuel cost, e.g. `--fuel-cost call=10`.
+    #[arg(long = "fuel-cost", value_parser = parse_cost)]
+    fuel_costs: Vec<(String, u64)>,
+}
+
+fn parse_root(s: &str) -> Result<(String, String), String> {
+    s.split_once('=')
+        .map(|(k, v)| (k.to_owned(), v.to_owned()))
+        .ok_or_else(|| format!("expected `key=path`, got `{s}`"))
+}
+
+fn parse_cost(s: &str) -> Result<(String, u64), String> {
+    let (k, v) = s
+        .split_once('=')
+        .ok_or_else(|| format!("expected `class=cost`, got `{s}`"))?;
+    let v = v.parse().map_err(|e| format!("invalid cost `{v}`: {e}"))?;
+    Ok((k.to_owned(), v))
+}
+
+fn render(core: OptsCore<'_>, backend: Backend) -> anyhow::Result<TokenStream> {
+    match backend {
+        #[cfg(feature = "wasmparser")]
+        Backend::Wasmparser => core.inflate::<wars::WasmparserBackend>().render(),
+        #[cfg(feature = "waffle")]
+        Backend::Waffle => core.inflate::<wars::LegacyPortalWaffleBackend>().render(),
+        #[allow(unreachable_patterns)]
+        _ => anyhow::bail!("the selected backend is not enabled in this build of wars"),
+    }
+}
+
+fn main() -> anyhow::Result<()> {
+    let cli = Cli::parse();
+    let bytes = wat::parse_file(&cli.input)
+        .with_context(|| format!("reading {}", cli.input.display()))?;
+    let mut flags = Flags::empty();
+    if cli.is_async {
+        flags |= Flags::ASYNC;
+    }
+    if cli.legacy {
+        flags |= Flags::LEGACY;
+    }
+    if cli.fuel {
+        flags |= Flags::FUEL;
+    }
+    if cli.epoch {
+        flags |= Flags::EPOCH;
+    }
+    if cli.call_depth {
+        flags |= Flags::CALL_DEPTH;
+    }
+    if cli.dyn_imports {
+        flags |= Flags::DYN_IMPORTS;
+    }
+    let mut fuel = FuelCosts::default();
+    for (k, v) in cli.fuel_costs {
+        *fuel
+            .by_name(&k)
+            .ok_or_else(|| anyhow::anyhow!("unknown operator class `{k}`"))? = v;
+    }
+    let mut roots = BTreeMap::new();
+    for (k, v) in cli.roots {
+        let path: syn::Path =
+            syn::parse_str(&v).with_context(|| format!("invalid path for root `{k}`"))?;
+        roots.insert(k, quote! { #path });
+    }
+    let core = OptsCore {
+        crate_path: syn::parse_str(&cli.crate_path).context("invalid --crate-path")?,
+        bytes: &bytes,
+        name: Ident::new(&cli.name, Span::call_site()),
+        flags,
+        embed: quote! {},
+        data: BTreeMap::new(),
+        roots,
+        plugins: vec![],
+        fuel,
+    };
+    let tokens = render(core, cli.backend)?;
+    let file: syn::File = syn::parse2(tokens).context("backend produced unparseable Rust")?;
+    let output = cli.output.unwrap_or_else(|| cli.input.with_extension("rs"));
+    std::fs::write(&output, prettyplease::unparse(&file))
+        .with_context(|| format!("writing {}", output.display()))?;
+    Ok(())
+}
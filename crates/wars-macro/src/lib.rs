@@ -0,0 +1,230 @@
+//! `wasm!` — translate a wasm file at compile time and expand the ABI v0
+//! bindings in place.
+//!
+//! ```ignore
+//! wars_macro::wasm! {
+//!     name = Greeter,
+//!     path = "greeter.wasm",
+//!     flags = [ASYNC],
+//!     backend = wasmparser,
+//! }
+//! ```
+//!
+//! `path` is resolved relative to `CARGO_MANIFEST_DIR`.  With `flags =
+//! [FUEL]`, `fuel = { call = 10, memory = 2 }` overrides the per-class costs
+//! (`numeric`, `memory`, `call`, `control`, `other`).  The expansion also
+//! `include_bytes!`s the file so that editing the wasm triggers a rebuild.
+use std::{collections::BTreeMap, path::PathBuf};
+
+use proc_macro2::{Span, TokenStream};
+use quote::{quote, ToTokens};
+use syn::{
+    braced, bracketed,
+    parse::{Parse, ParseStream},
+    parse_macro_input,
+    punctuated::Punctuated,
+    Ident, LitInt, LitStr, Token, Type,
+};
+use wars::{Flags, FuelCosts, OptsCore};
+
+enum Backend {
+    Wasmparser,
+    Waffle,
+}
+
+struct Args {
+    name: Ident,
+    path: LitStr,
+    crate_path: syn::Path,
+    flags: Flags,
+    backend: Backend,
+    data: BTreeMap<Ident, TokenStream>,
+    roots: BTreeMap<String, TokenStream>,
+    fuel: FuelCosts,
+}
+
+struct DataField {
+    name: Ident,
+    ty: Type,
+}
+impl Parse for DataField {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let name = input.parse()?;
+        input.parse::<Token![:]>()?;
+        Ok(Self {
+            name,
+            ty: input.parse()?,
+        })
+    }
+}
+
+struct Root {
+    key: Ident,
+    path: syn::Path,
+}
+impl Parse for Root {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let key = input.parse()?;
+        input.parse::<Token![=]>()?;
+        Ok(Self {
+            key,
+            path: input.parse()?,
+        })
+    }
+}
+
+struct Cost {
+    class: Ident,
+    cost: LitInt,
+}
+impl Parse for Cost {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let class = input.parse()?;
+        input.parse::<Token![=]>()?;
+        Ok(Self {
+            class,
+            cost: input.parse()?,
+        })
+    }
+}
+
+impl Parse for Args {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let mut name = None;
+        let mut path = None;
+        let mut crate_path = syn::parse_quote!(::wars_rt);
+        let mut flags = Flags::default();
+        let mut backend = Backend::Wasmparser;
+        let mut data = BTreeMap::new();
+        let mut roots = BTreeMap::new();
+        let mut fuel = FuelCosts::default();
+        while !input.is_empty() {
+            let key: Ident = input.parse()?;
+            input.parse::<Token![=]>()?;
+            match key.to_string().as_str() {
+                "name" => name = Some(input.parse()?),
+                "path" => path = Some(input.parse()?),
+                "crate_path" => crate_path = input.parse()?,
+                "flags" => {
+                    let content;
+                    bracketed!(content in input);
+                    for f in Punctuated::<Ident, Token![,]>::parse_terminated(&content)? {
+                        flags |= Flags::from_name(&f.to_string()).ok_or_else(|| {
+                            syn::Error::new(f.span(), format!("unknown flag `{f}`"))
+                        })?;
+                    }
+                }
+                "backend" => {
+                    let b: Ident = input.parse()?;
+                    backend = match b.to_string().as_str() {
+                        "wasmparser" => Backend::Wasmparser,
+                        "waffle" => Backend::Waffle,
+                        _ => {
+                            return Err(syn::Error::new(
+                                b.span(),
+                                "expected `wasmparser` or `waffle`",
+                            ))
+                        }
+                    };
+                }
+                "data" => {
+                    let content;
+                    braced!(content in input);
+                    for f in Punctuated::<DataField, Token![,]>::parse_terminated(&content)? {
+                        data.insert(f.name, f.ty.into_token_stream());
+                    }
+                }
+                "roots" => {
+                    let content;
+                    braced!(content in input);
+                    for r in Punctuated::<Root, Token![,]>::parse_terminated(&content)? {
+                        roots.insert(r.key.to_string(), r.path.into_token_stream());
+                    }
+                }
+                "fuel" => {
+                    let content;
+                    braced!(content in input);
+                    for c in Punctuated::<Cost, Token![,]>::parse_terminated(&content)? {
+                        let slot = fuel.by_name(&c.class.to_string()).ok_or_else(|| {
+                            syn::Error::new(
+                                c.class.span(),
+                                format!("unknown operator class `{}`", c.class),
+                            )
+                        })?;
+                        *slot = c.cost.base10_parse()?;
+                    }
+                }
+                _ => {
+                    return Err(syn::Error::new(
+                        key.span(),
+                        format!("unknown option `{key}`"),
+                    ))
+                }
+            }
+            if input.is_empty() {
+                break;
+            }
+            input.parse::<Token![,]>()?;
+        }
+        Ok(Self {
+            name: name.ok_or_else(|| input.error("missing `name = ...`"))?,
+            path: path.ok_or_else(|| input.error("missing `path = \"...\"`"))?,
+            crate_path,
+            flags,
+            backend,
+            data,
+            roots,
+            fuel,
+        })
+    }
+}
+
+fn expand(args: Args) -> syn::Result<TokenStream> {
+    let dir = std::env::var("CARGO_MANIFEST_DIR")
+        .map_err(|e| syn::Error::new(Span::call_site(), e))?;
+    let full = PathBuf::from(dir).join(args.path.value());
+    let bytes = std::fs::read(&full)
+        .map_err(|e| syn::Error::new(args.path.span(), format!("{}: {e}", full.display())))?;
+    let core = OptsCore {
+        crate_path: args.crate_path,
+        bytes: &bytes,
+        name: args.name,
+        flags: args.flags,
+        embed: quote! {},
+        data: args.data,
+        roots: args.roots,
+        plugins: vec![],
+        fuel: args.fuel,
+    };
+    let body = match args.backend {
+        #[cfg(feature = "wasmparser")]
+        Backend::Wasmparser => core
+            .inflate::<wars::WasmparserBackend>()
+            .into_token_stream(),
+        #[cfg(feature = "waffle")]
+        Backend::Waffle => core
+            .inflate::<wars::LegacyPortalWaffleBackend>()
+            .into_token_stream(),
+        #[allow(unreachable_patterns)]
+        _ => {
+            return Err(syn::Error::new(
+                Span::call_site(),
+                "the selected backend is not enabled in wars-macro's features",
+            ))
+        }
+    };
+    let full = full.to_string_lossy();
+    Ok(quote! {
+        const _: &[u8] = include_bytes!(#full);
+        #body
+    })
+}
+
+#[proc_macro]
+pub fn wasm(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let args = parse_macro_input!(input as Args);
+    match expand(args) {
+        Ok(ts) => ts.into(),
+        Err(e) => e.to_compile_error().into(),
+    }
+}
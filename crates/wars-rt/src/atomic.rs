@@ -0,0 +1,634 @@
+//! Threads proposal: atomic memory access, `memory.atomic.wait` and
+//! `memory.atomic.notify`.
+//!
+//! Shared memories are `Arc<Mutex<_>>`, and every access to them takes the
+//! lock, so an access is atomic as long as it is one `Memory` call.
+//! Read-modify-write operators need [`AtomicMemory::rmw`] to keep the lock
+//! across the read and the write.  Unshared memories are wrapped in
+//! [`Exclusive`] by generated code; `&mut` access is already exclusive.
+//!
+//! Waiting goes through a process-wide [`Parker`], so instances that share a
+//! memory also share their wait queues.  With `std` the default is a
+//! condvar-based parker; hosts without `std`, or with their own scheduler,
+//! install one with [`set_parker`].  Async code waits with
+//! [`memoryatomicwait32async`], which queues a [`TaskWaiter`] and yields to
+//! the executor instead of blocking the thread.
+use core::{
+    future::Future,
+    pin::Pin,
+    sync::atomic::{AtomicBool, Ordering},
+    task::{Context, Poll, Waker},
+    time::Duration,
+};
+
+use alloc::sync::Arc;
+
+use crate::{Err, Memory, Trap};
+
+pub trait AtomicMemory: Memory {
+    /// Apply `f` to the `n` bytes at `a`, with no other access to them in
+    /// between.
+    fn rmw(&mut self, a: u64, n: u64, f: &mut dyn FnMut(&mut [u8])) -> anyhow::Result<()>;
+    /// Identifies the memory for wait/notify: every handle to one shared
+    /// memory returns the same key.  `None` for unshared memory, on which
+    /// `memory.atomic.wait` traps.
+    fn wait_key(&self) -> Option<usize>;
+}
+impl<T: AtomicMemory + ?Sized> AtomicMemory for alloc::boxed::Box<T> {
+    fn rmw(&mut self, a: u64, n: u64, f: &mut dyn FnMut(&mut [u8])) -> anyhow::Result<()> {
+        self.as_mut().rmw(a, n, f)
+    }
+    fn wait_key(&self) -> Option<usize> {
+        self.as_ref().wait_key()
+    }
+}
+#[cfg(feature = "std")]
+impl<T: Memory + ?Sized> AtomicMemory for alloc::sync::Arc<std::sync::Mutex<T>> {
+    fn rmw(&mut self, a: u64, n: u64, f: &mut dyn FnMut(&mut [u8])) -> anyhow::Result<()> {
+        let mut l = self.lock().unwrap();
+        let mut v = l.read(a, n)?.as_ref().as_ref().to_vec();
+        f(&mut v);
+        l.write(a, &v)
+    }
+    fn wait_key(&self) -> Option<usize> {
+        Some(alloc::sync::Arc::as_ptr(self) as *const u8 as usize)
+    }
+}
+#[cfg(not(feature = "std"))]
+impl<T: Memory + ?Sized> AtomicMemory for alloc::sync::Arc<spin::Mutex<T>> {
+    fn rmw(&mut self, a: u64, n: u64, f: &mut dyn FnMut(&mut [u8])) -> anyhow::Result<()> {
+        let mut l = self.lock();
+        let mut v = l.read(a, n)?.as_ref().as_ref().to_vec();
+        f(&mut v);
+        l.write(a, &v)
+    }
+    fn wait_key(&self) -> Option<usize> {
+        Some(alloc::sync::Arc::as_ptr(self) as *const u8 as usize)
+    }
+}
+
+/// An unshared memory used with atomic operators.
+#[repr(transparent)]
+pub struct Exclusive<'a, M: ?Sized>(pub &'a mut M);
+impl<M: Memory + ?Sized> Memory for Exclusive<'_, M> {
+    fn read<'a>(&'a self, a: u64, s: u64) -> anyhow::Result<alloc::boxed::Box<dyn AsRef<[u8]> + 'a>> {
+        self.0.read(a, s)
+    }
+    fn write(&mut self, a: u64, x: &[u8]) -> anyhow::Result<()> {
+        self.0.write(a, x)
+    }
+    fn size(&self) -> anyhow::Result<u64> {
+        self.0.size()
+    }
+    fn grow(&mut self, x: u64) -> anyhow::Result<()> {
+        self.0.grow(x)
+    }
+    fn max_size(&self) -> Option<u64> {
+        self.0.max_size()
+    }
+}
+impl<M: Memory + ?Sized> AtomicMemory for Exclusive<'_, M> {
+    fn rmw(&mut self, a: u64, n: u64, f: &mut dyn FnMut(&mut [u8])) -> anyhow::Result<()> {
+        let mut v = self.0.read(a, n)?.as_ref().as_ref().to_vec();
+        f(&mut v);
+        self.0.write(a, &v)
+    }
+    fn wait_key(&self) -> Option<usize> {
+        None
+    }
+}
+
+/// Result of [`Parker::park`], in `memory.atomic.wait` encoding.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Wait {
+    Woken = 0,
+    NotEqual = 1,
+    TimedOut = 2,
+}
+
+/// Blocks and wakes threads for `memory.atomic.wait` / `notify`.
+///
+/// Waiters are keyed by [`AtomicMemory::wait_key`] and address.  `park` must
+/// call `validate` and enqueue the caller as one step with respect to
+/// `unpark`, or a notify between the two is lost.
+pub trait Parker: Send + Sync {
+    fn park(
+        &self,
+        key: usize,
+        addr: u64,
+        validate: &mut dyn FnMut() -> anyhow::Result<bool>,
+        timeout: Option<Duration>,
+    ) -> anyhow::Result<Wait>;
+    /// Wake up to `count` waiters, returning how many were woken.
+    fn unpark(&self, key: usize, addr: u64, count: u32) -> u32;
+    /// Like `park`, but queue `w` and return at once; `unpark` counts it and
+    /// calls [`TaskWaiter::wake`].  Returns `false` if `validate` does.
+    fn enqueue(
+        &self,
+        key: usize,
+        addr: u64,
+        validate: &mut dyn FnMut() -> anyhow::Result<bool>,
+        w: Arc<TaskWaiter>,
+    ) -> anyhow::Result<bool> {
+        let _ = (key, addr, validate, w);
+        anyhow::bail!("this parker cannot wait in async code")
+    }
+    /// Take `w` back out of the queue, if `unpark` has not already.
+    fn dequeue(&self, key: usize, addr: u64, w: &Arc<TaskWaiter>) {
+        let _ = (key, addr, w);
+    }
+}
+
+/// A task waiting in async code, woken through its [`Waker`] rather than by
+/// blocking a thread.
+#[derive(Default)]
+pub struct TaskWaiter {
+    woken: AtomicBool,
+    waker: spin::Mutex<Option<Waker>>,
+}
+impl TaskWaiter {
+    /// Mark the waiter notified and wake its task.
+    pub fn wake(&self) {
+        self.woken.store(true, Ordering::SeqCst);
+        self.poke();
+    }
+    pub fn is_woken(&self) -> bool {
+        self.woken.load(Ordering::SeqCst)
+    }
+    /// Wake the task without notifying it, so it rechecks its timeout.
+    fn poke(&self) {
+        if let Some(w) = self.waker.lock().clone() {
+            w.wake();
+        }
+    }
+}
+
+static PARKER: spin::Once<&'static dyn Parker> = spin::Once::new();
+
+/// Install the process-wide parker.  Returns `false` if one was already in
+/// use, including the `std` default once anything has waited or notified.
+pub fn set_parker(p: &'static dyn Parker) -> bool {
+    let mut set = false;
+    PARKER.call_once(|| {
+        set = true;
+        p
+    });
+    set
+}
+pub fn parker() -> Option<&'static dyn Parker> {
+    #[cfg(feature = "std")]
+    return Some(*PARKER.call_once(|| &STD_PARKER));
+    #[cfg(not(feature = "std"))]
+    return PARKER.get().copied();
+}
+
+#[cfg(feature = "std")]
+static STD_PARKER: StdParker = StdParker::new();
+
+/// The default parker with `std`: one condvar per waiting thread.
+#[cfg(feature = "std")]
+pub struct StdParker {
+    queues: std::sync::Mutex<alloc::collections::BTreeMap<(usize, u64), alloc::vec::Vec<Queued>>>,
+}
+#[cfg(feature = "std")]
+enum Queued {
+    Thread(Arc<Waiter>),
+    Task(Arc<TaskWaiter>),
+}
+#[cfg(feature = "std")]
+#[derive(Default)]
+struct Waiter {
+    woken: std::sync::Mutex<bool>,
+    cv: std::sync::Condvar,
+}
+#[cfg(feature = "std")]
+impl StdParker {
+    pub const fn new() -> Self {
+        Self {
+            queues: std::sync::Mutex::new(alloc::collections::BTreeMap::new()),
+        }
+    }
+}
+#[cfg(feature = "std")]
+impl Parker for StdParker {
+    fn park(
+        &self,
+        key: usize,
+        addr: u64,
+        validate: &mut dyn FnMut() -> anyhow::Result<bool>,
+        timeout: Option<Duration>,
+    ) -> anyhow::Result<Wait> {
+        let w = Arc::new(Waiter::default());
+        {
+            let mut q = self.queues.lock().unwrap();
+            if !validate()? {
+                return Ok(Wait::NotEqual);
+            }
+            q.entry((key, addr))
+                .or_default()
+                .push(Queued::Thread(w.clone()));
+        }
+        let deadline = timeout.map(|t| std::time::Instant::now() + t);
+        let mut woken = w.woken.lock().unwrap();
+        while !*woken {
+            woken = match deadline {
+                None => w.cv.wait(woken).unwrap(),
+                Some(d) => {
+                    let now = std::time::Instant::now();
+                    if now >= d {
+                        break;
+                    }
+                    w.cv.wait_timeout(woken, d - now).unwrap().0
+                }
+            };
+        }
+        if *woken {
+            return Ok(Wait::Woken);
+        }
+        drop(woken);
+        // Timed out; dequeue, unless a notify got to us first.
+        let mut q = self.queues.lock().unwrap();
+        if let Some(v) = q.get_mut(&(key, addr)) {
+            v.retain(|x| !matches!(x, Queued::Thread(x) if Arc::ptr_eq(x, &w)));
+            if v.is_empty() {
+                q.remove(&(key, addr));
+            }
+        }
+        Ok(if *w.woken.lock().unwrap() {
+            Wait::Woken
+        } else {
+            Wait::TimedOut
+        })
+    }
+    fn unpark(&self, key: usize, addr: u64, count: u32) -> u32 {
+        let mut q = self.queues.lock().unwrap();
+        let Some(v) = q.get_mut(&(key, addr)) else {
+            return 0;
+        };
+        let n = (count as usize).min(v.len());
+        for w in v.drain(..n) {
+            match w {
+                Queued::Thread(w) => {
+                    *w.woken.lock().unwrap() = true;
+                    w.cv.notify_one();
+                }
+                Queued::Task(w) => w.wake(),
+            }
+        }
+        if v.is_empty() {
+            q.remove(&(key, addr));
+        }
+        n as u32
+    }
+    fn enqueue(
+        &self,
+        key: usize,
+        addr: u64,
+        validate: &mut dyn FnMut() -> anyhow::Result<bool>,
+        w: Arc<TaskWaiter>,
+    ) -> anyhow::Result<bool> {
+        let mut q = self.queues.lock().unwrap();
+        if !validate()? {
+            return Ok(false);
+        }
+        q.entry((key, addr)).or_default().push(Queued::Task(w));
+        Ok(true)
+    }
+    fn dequeue(&self, key: usize, addr: u64, w: &Arc<TaskWaiter>) {
+        let mut q = self.queues.lock().unwrap();
+        if let Some(v) = q.get_mut(&(key, addr)) {
+            v.retain(|x| !matches!(x, Queued::Task(x) if Arc::ptr_eq(x, w)));
+            if v.is_empty() {
+                q.remove(&(key, addr));
+            }
+        }
+    }
+}
+
+// ── Operators ────────────────────────────────────────────────────────────────
+// Same native-endian layout as the plain loads and stores.
+trait Word: Copy + PartialEq {
+    const BYTES: u64;
+    fn get(b: &[u8]) -> Self;
+    fn put(self, b: &mut [u8]);
+}
+macro_rules! word_impl {
+    ($($t:ty),*) => {
+        $(impl Word for $t {
+            const BYTES: u64 = core::mem::size_of::<$t>() as u64;
+            fn get(b: &[u8]) -> Self {
+                <$t>::from_ne_bytes(b.try_into().unwrap())
+            }
+            fn put(self, b: &mut [u8]) {
+                b.copy_from_slice(&self.to_ne_bytes())
+            }
+        })*
+    };
+}
+word_impl!(u8, u16, u32, u64);
+
+fn addr<T: TryInto<u64>>(b: T, n: u64) -> anyhow::Result<u64>
+where
+    T::Error: Err + Send + Sync + 'static,
+{
+    let b: u64 = b.try_into().map_err(Into::into)?;
+    if b % n != 0 {
+        return Err(Trap::UnalignedAtomic.into());
+    }
+    Ok(b)
+}
+fn load<W: Word, M: Memory + ?Sized>(a: &M, b: u64) -> anyhow::Result<W> {
+    Ok(W::get(a.read(b, W::BYTES)?.as_ref().as_ref()))
+}
+fn rmw<T: TryInto<u64>, M: AtomicMemory + ?Sized, W: Word>(
+    a: &mut M,
+    b: T,
+    f: impl Fn(W) -> W,
+) -> anyhow::Result<W>
+where
+    T::Error: Err + Send + Sync + 'static,
+{
+    let b = addr(b, W::BYTES)?;
+    let mut old = None;
+    a.rmw(b, W::BYTES, &mut |x| {
+        let o = W::get(x);
+        old = Some(o);
+        f(o).put(x)
+    })?;
+    Ok(old.unwrap())
+}
+
+macro_rules! atomic_access {
+    ($($p:ident $($w:literal)?: $t:ident, $n:ident;)*) => {
+        paste::paste! {
+            $(pub fn [<$p atomicload $($w u)?>]<T: TryInto<u64>, M: AtomicMemory + ?Sized>(a: &mut M, b: T) -> anyhow::Result<tuple_list::tuple_list_type!($t)>
+            where
+                T::Error: Err + Send + Sync + 'static,
+            {
+                let b = addr(b, $n::BYTES)?;
+                Ok(tuple_list::tuple_list!(load::<$n, M>(a, b)? as $t))
+            }
+            pub fn [<$p atomicstore $($w)?>]<T: TryInto<u64>, M: AtomicMemory + ?Sized>(a: &mut M, b: T, c: $t) -> anyhow::Result<()>
+            where
+                T::Error: Err + Send + Sync + 'static,
+            {
+                let b = addr(b, $n::BYTES)?;
+                a.write(b, &(c as $n).to_ne_bytes())
+            }
+            pub fn [<$p atomicrmw $($w)? add $($w u)?>]<T: TryInto<u64>, M: AtomicMemory + ?Sized>(a: &mut M, b: T, c: $t) -> anyhow::Result<tuple_list::tuple_list_type!($t)>
+            where
+                T::Error: Err + Send + Sync + 'static,
+            {
+                Ok(tuple_list::tuple_list!(rmw::<T, M, $n>(a, b, |x| x.wrapping_add(c as $n))? as $t))
+            }
+            pub fn [<$p atomicrmw $($w)? sub $($w u)?>]<T: TryInto<u64>, M: AtomicMemory + ?Sized>(a: &mut M, b: T, c: $t) -> anyhow::Result<tuple_list::tuple_list_type!($t)>
+            where
+                T::Error: Err + Send + Sync + 'static,
+            {
+                Ok(tuple_list::tuple_list!(rmw::<T, M, $n>(a, b, |x| x.wrapping_sub(c as $n))? as $t))
+            }
+            pub fn [<$p atomicrmw $($w)? and $($w u)?>]<T: TryInto<u64>, M: AtomicMemory + ?Sized>(a: &mut M, b: T, c: $t) -> anyhow::Result<tuple_list::tuple_list_type!($t)>
+            where
+                T::Error: Err + Send + Sync + 'static,
+            {
+                Ok(tuple_list::tuple_list!(rmw::<T, M, $n>(a, b, |x| x & c as $n)? as $t))
+            }
+            pub fn [<$p atomicrmw $($w)? or $($w u)?>]<T: TryInto<u64>, M: AtomicMemory + ?Sized>(a: &mut M, b: T, c: $t) -> anyhow::Result<tuple_list::tuple_list_type!($t)>
+            where
+                T::Error: Err + Send + Sync + 'static,
+            {
+                Ok(tuple_list::tuple_list!(rmw::<T, M, $n>(a, b, |x| x | c as $n)? as $t))
+            }
+            pub fn [<$p atomicrmw $($w)? xor $($w u)?>]<T: TryInto<u64>, M: AtomicMemory + ?Sized>(a: &mut M, b: T, c: $t) -> anyhow::Result<tuple_list::tuple_list_type!($t)>
+            where
+                T::Error: Err + Send + Sync + 'static,
+            {
+                Ok(tuple_list::tuple_list!(rmw::<T, M, $n>(a, b, |x| x ^ c as $n)? as $t))
+            }
+            pub fn [<$p atomicrmw $($w)? xchg $($w u)?>]<T: TryInto<u64>, M: AtomicMemory + ?Sized>(a: &mut M, b: T, c: $t) -> anyhow::Result<tuple_list::tuple_list_type!($t)>
+            where
+                T::Error: Err + Send + Sync + 'static,
+            {
+                Ok(tuple_list::tuple_list!(rmw::<T, M, $n>(a, b, |_| c as $n)? as $t))
+            }
+            pub fn [<$p atomicrmw $($w)? cmpxchg $($w u)?>]<T: TryInto<u64>, M: AtomicMemory + ?Sized>(a: &mut M, b: T, c: $t, d: $t) -> anyhow::Result<tuple_list::tuple_list_type!($t)>
+            where
+                T::Error: Err + Send + Sync + 'static,
+            {
+                Ok(tuple_list::tuple_list!(rmw::<T, M, $n>(a, b, |x| if x == c as $n { d as $n } else { x })? as $t))
+            }
+            )*
+        }
+    };
+}
+atomic_access! {
+    i32: u32, u32;
+    i32 8: u32, u8;
+    i32 16: u32, u16;
+    i64: u64, u64;
+    i64 8: u64, u8;
+    i64 16: u64, u16;
+    i64 32: u64, u32;
+}
+
+fn wait<T: TryInto<u64>, M: AtomicMemory + ?Sized, W: Word>(
+    a: &mut M,
+    b: T,
+    c: W,
+    timeout: u64,
+) -> anyhow::Result<tuple_list::tuple_list_type!(u32)>
+where
+    T::Error: Err + Send + Sync + 'static,
+{
+    let b = addr(b, W::BYTES)?;
+    load::<W, M>(a, b)?;
+    let Some(key) = a.wait_key() else {
+        return Err(Trap::ExpectedSharedMemory.into());
+    };
+    let Some(p) = parker() else {
+        anyhow::bail!("memory.atomic.wait needs a parker; see wars_rt::atomic::set_parker");
+    };
+    // A negative timeout waits forever.
+    let timeout = (timeout as i64 >= 0).then(|| Duration::from_nanos(timeout));
+    let r = p.park(key, b, &mut || Ok(load::<W, M>(a, b)? == c), timeout)?;
+    Ok(tuple_list::tuple_list!(r as u32))
+}
+pub fn memoryatomicwait32<T: TryInto<u64>, M: AtomicMemory + ?Sized>(
+    a: &mut M,
+    b: T,
+    c: u32,
+    timeout: u64,
+) -> anyhow::Result<tuple_list::tuple_list_type!(u32)>
+where
+    T::Error: Err + Send + Sync + 'static,
+{
+    wait(a, b, c, timeout)
+}
+pub fn memoryatomicwait64<T: TryInto<u64>, M: AtomicMemory + ?Sized>(
+    a: &mut M,
+    b: T,
+    c: u64,
+    timeout: u64,
+) -> anyhow::Result<tuple_list::tuple_list_type!(u32)>
+where
+    T::Error: Err + Send + Sync + 'static,
+{
+    wait(a, b, c, timeout)
+}
+/// `memory.atomic.wait` for async code: checks and queues like the blocking
+/// version, then returns a future that yields until notified or timed out.
+fn wait_async<T: TryInto<u64>, M: AtomicMemory + ?Sized, W: Word>(
+    a: &mut M,
+    b: T,
+    c: W,
+    timeout: u64,
+) -> anyhow::Result<WaitFuture>
+where
+    T::Error: Err + Send + Sync + 'static,
+{
+    let b = addr(b, W::BYTES)?;
+    load::<W, M>(a, b)?;
+    let Some(key) = a.wait_key() else {
+        return Err(Trap::ExpectedSharedMemory.into());
+    };
+    let Some(p) = parker() else {
+        anyhow::bail!("memory.atomic.wait needs a parker; see wars_rt::atomic::set_parker");
+    };
+    // A negative timeout waits forever.
+    let timeout = (timeout as i64 >= 0).then(|| Duration::from_nanos(timeout));
+    if timeout == Some(Duration::ZERO) {
+        let r = if load::<W, M>(a, b)? == c {
+            Wait::TimedOut
+        } else {
+            Wait::NotEqual
+        };
+        return Ok(WaitFuture::Done(r));
+    }
+    #[cfg(not(feature = "std"))]
+    if timeout.is_some() {
+        anyhow::bail!("a timed memory.atomic.wait in async code needs the `std` feature");
+    }
+    let w = Arc::new(TaskWaiter::default());
+    if !p.enqueue(key, b, &mut || Ok(load::<W, M>(a, b)? == c), w.clone())? {
+        return Ok(WaitFuture::Done(Wait::NotEqual));
+    }
+    #[cfg(feature = "std")]
+    let deadline = timeout.map(|t| {
+        let d = std::time::Instant::now() + t;
+        // Nothing else would wake the task once the deadline passes.
+        let w = w.clone();
+        std::thread::spawn(move || {
+            std::thread::sleep(t);
+            w.poke();
+        });
+        d
+    });
+    Ok(WaitFuture::Queued {
+        p,
+        key,
+        addr: b,
+        w,
+        #[cfg(feature = "std")]
+        deadline,
+    })
+}
+/// The future returned by [`memoryatomicwait32async`] and
+/// [`memoryatomicwait64async`].  Dropping it leaves the wait queue.
+pub enum WaitFuture {
+    Done(Wait),
+    Queued {
+        p: &'static dyn Parker,
+        key: usize,
+        addr: u64,
+        w: Arc<TaskWaiter>,
+        #[cfg(feature = "std")]
+        deadline: Option<std::time::Instant>,
+    },
+}
+impl Future for WaitFuture {
+    type Output = anyhow::Result<tuple_list::tuple_list_type!(u32)>;
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        let r = match this {
+            WaitFuture::Done(r) => *r,
+            WaitFuture::Queued {
+                p,
+                key,
+                addr,
+                w,
+                #[cfg(feature = "std")]
+                deadline,
+            } => {
+                *w.waker.lock() = Some(cx.waker().clone());
+                if w.is_woken() {
+                    Wait::Woken
+                } else {
+                    #[cfg(feature = "std")]
+                    let expired = deadline.is_some_and(|d| std::time::Instant::now() >= d);
+                    #[cfg(not(feature = "std"))]
+                    let expired = false;
+                    if !expired {
+                        return Poll::Pending;
+                    }
+                    // Timed out; dequeue, unless a notify got to us first.
+                    p.dequeue(*key, *addr, w);
+                    if w.is_woken() {
+                        Wait::Woken
+                    } else {
+                        Wait::TimedOut
+                    }
+                }
+            }
+        };
+        *this = WaitFuture::Done(r);
+        Poll::Ready(Ok(tuple_list::tuple_list!(r as u32)))
+    }
+}
+impl Drop for WaitFuture {
+    fn drop(&mut self) {
+        if let WaitFuture::Queued {
+            p, key, addr, w, ..
+        } = self
+        {
+            p.dequeue(*key, *addr, w);
+        }
+    }
+}
+pub fn memoryatomicwait32async<T: TryInto<u64>, M: AtomicMemory + ?Sized>(
+    a: &mut M,
+    b: T,
+    c: u32,
+    timeout: u64,
+) -> anyhow::Result<WaitFuture>
+where
+    T::Error: Err + Send + Sync + 'static,
+{
+    wait_async(a, b, c, timeout)
+}
+pub fn memoryatomicwait64async<T: TryInto<u64>, M: AtomicMemory + ?Sized>(
+    a: &mut M,
+    b: T,
+    c: u64,
+    timeout: u64,
+) -> anyhow::Result<WaitFuture>
+where
+    T::Error: Err + Send + Sync + 'static,
+{
+    wait_async(a, b, c, timeout)
+}
+pub fn memoryatomicnotify<T: TryInto<u64>, M: AtomicMemory + ?Sized>(
+    a: &mut M,
+    b: T,
+    count: u32,
+) -> anyhow::Result<tuple_list::tuple_list_type!(u32)>
+where
+    T::Error: Err + Send + Sync + 'static,
+{
+    let b = addr(b, 4)?;
+    load::<u32, M>(a, b)?;
+    let n = match (a.wait_key(), parker()) {
+        (Some(key), Some(p)) => p.unpark(key, b, count),
+        _ => 0,
+    };
+    Ok(tuple_list::tuple_list!(n))
+}
@@ -0,0 +1,58 @@
+//! Call-depth accounting for modules translated with `Flags::CALL_DEPTH`.
+//!
+//! Sync calls between wasm functions nest on the native stack, so unbounded
+//! guest recursion would overflow it and abort the host.  Generated code
+//! counts each call to a wasm function here first and fails with
+//! [`Trap::StackExhausted`] past the limit.  Tail calls replace their frame
+//! and are not counted.
+use alloc::sync::Arc;
+use core::sync::atomic::{AtomicU32, Ordering};
+
+use crate::Trap;
+
+/// Current call depth of a context, and how deep it may go.  Clones share
+/// the depth.
+#[derive(Clone, Debug)]
+pub struct DepthLimit {
+    depth: Arc<AtomicU32>,
+    pub max: u32,
+}
+impl Default for DepthLimit {
+    fn default() -> Self {
+        Self::new(Self::DEFAULT_MAX)
+    }
+}
+impl DepthLimit {
+    /// Conservative for the 2 MiB stack of a spawned thread; raise it for
+    /// threads with bigger stacks.
+    pub const DEFAULT_MAX: u32 = 1024;
+    pub fn new(max: u32) -> Self {
+        Self { depth: Arc::new(AtomicU32::new(0)), max }
+    }
+    pub fn depth(&self) -> u32 {
+        self.depth.load(Ordering::Relaxed)
+    }
+    /// Count one call.  The returned guard uncounts it when dropped, so an
+    /// async call whose future is dropped half way leaves the depth right.
+    pub fn enter(&mut self) -> anyhow::Result<DepthGuard> {
+        let guard = DepthGuard(self.depth.clone());
+        if guard.0.fetch_add(1, Ordering::Relaxed) >= self.max {
+            return Err(Trap::StackExhausted.into());
+        }
+        Ok(guard)
+    }
+}
+
+/// One call counted by [`DepthLimit::enter`].
+#[must_use]
+pub struct DepthGuard(Arc<AtomicU32>);
+impl Drop for DepthGuard {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// Implemented by host contexts of `Flags::CALL_DEPTH` modules.
+pub trait CallDepth {
+    fn call_depth(&mut self) -> &mut DepthLimit;
+}
@@ -0,0 +1,94 @@
+//! Epoch interruption for modules translated with `Flags::EPOCH`.
+//!
+//! A cheaper alternative to fuel: the host bumps a shared [`EpochCounter`]
+//! (typically from a timer thread), and generated code compares it against
+//! the context's deadline on function entry and at the top of every loop
+//! iteration.
+use alloc::sync::Arc;
+use core::future::Future;
+use core::pin::Pin;
+use core::sync::atomic::{AtomicU64, Ordering};
+use core::task::{Context, Poll};
+
+use crate::Trap;
+
+/// A counter shared between the host and the contexts it interrupts.
+#[derive(Clone, Default, Debug)]
+pub struct EpochCounter(Arc<AtomicU64>);
+impl EpochCounter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn increment(&self) {
+        self.0.fetch_add(1, Ordering::Relaxed);
+    }
+    pub fn current(&self) -> u64 {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// What happens when a context reaches its deadline.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OnDeadline {
+    /// Fail with [`Trap::Interrupt`].
+    Trap,
+    /// Move the deadline `delta` epochs past the current one.  `Flags::ASYNC`
+    /// code also yields to the executor first; sync code just carries on.
+    Yield { delta: u64 },
+}
+
+/// Per-context deadline state.
+#[derive(Clone, Debug)]
+pub struct EpochDeadline {
+    counter: EpochCounter,
+    deadline: u64,
+    pub on_deadline: OnDeadline,
+}
+impl EpochDeadline {
+    /// A deadline on `counter` that has already passed; call
+    /// [`Self::set_deadline`] before running guest code.
+    pub fn new(counter: EpochCounter) -> Self {
+        Self {
+            counter,
+            deadline: 0,
+            on_deadline: OnDeadline::Trap,
+        }
+    }
+    /// Expire `delta` epochs from now.
+    pub fn set_deadline(&mut self, delta: u64) {
+        self.deadline = self.counter.current().saturating_add(delta);
+    }
+    pub fn expired(&self) -> bool {
+        self.counter.current() >= self.deadline
+    }
+    /// Apply [`Self::on_deadline`] once the deadline has been reached.
+    pub fn reached(&mut self) -> anyhow::Result<()> {
+        match self.on_deadline {
+            OnDeadline::Trap => Err(Trap::Interrupt.into()),
+            OnDeadline::Yield { delta } => {
+                self.set_deadline(delta);
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Implemented by host contexts of `Flags::EPOCH` modules.
+pub trait Epoch {
+    fn epoch_deadline(&mut self) -> &mut EpochDeadline;
+}
+
+/// Returns `Pending` once, so an async export gives its executor a turn.
+#[derive(Default, Debug)]
+pub struct YieldNow(bool);
+impl Future for YieldNow {
+    type Output = ();
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        if self.0 {
+            return Poll::Ready(());
+        }
+        self.0 = true;
+        cx.waker().wake_by_ref();
+        Poll::Pending
+    }
+}
@@ -0,0 +1,57 @@
+//! Exception handling: tags and thrown exceptions.
+//!
+//! A wasm `throw` travels up the stack as an `anyhow::Error` wrapping an
+//! [`Exception`], the same channel traps use, so it passes through
+//! trampolined and async calls unchanged.  `try_table` handlers downcast it
+//! back (see `func::catch`); traps and host errors are never caught.
+use alloc::{sync::Arc, vec::Vec};
+use core::fmt;
+
+/// An exception tag.  Tags compare by identity: every instantiation of a
+/// module defines fresh ones, and an imported tag is the exporter's.
+#[derive(Clone)]
+pub struct Tag(Arc<TagId>);
+struct TagId;
+impl Tag {
+    pub fn new() -> Self {
+        Tag(Arc::new(TagId))
+    }
+}
+impl Default for Tag {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl PartialEq for Tag {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+impl Eq for Tag {}
+impl fmt::Debug for Tag {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Tag({:p})", Arc::as_ptr(&self.0))
+    }
+}
+
+/// A thrown wasm exception.  `V` is the inner value type of `func` or
+/// `func::unsync`.
+#[derive(Clone)]
+pub struct Exception<V> {
+    pub tag: Tag,
+    pub payload: Vec<V>,
+}
+impl<V> fmt::Debug for Exception<V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Exception")
+            .field("tag", &self.tag)
+            .field("payload_len", &self.payload.len())
+            .finish()
+    }
+}
+impl<V> fmt::Display for Exception<V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("uncaught wasm exception")
+    }
+}
+impl<V> core::error::Error for Exception<V> {}
@@ -0,0 +1,22 @@
+//! Fuel metering for modules translated with `Flags::FUEL`.
+//!
+//! Generated code charges a statically computed cost on entry to every
+//! function and at the head of every loop iteration.  The cost of a region is
+//! the sum of the per-class costs (`wars::FuelCosts`) of the operators in it,
+//! excluding nested loops, which charge for themselves.
+use crate::func::unsync::AsyncRec;
+use crate::Trap;
+
+/// The fuel counter of a host context.
+pub trait Fuel {
+    /// Take `n` units of fuel.  Returning `false` leaves the counter alone
+    /// and makes the caller run out: sync code traps with
+    /// [`Trap::OutOfFuel`], async code calls [`Fuel::refuel`].
+    fn consume_fuel(&mut self, n: u64) -> bool;
+    /// Called by `Flags::ASYNC` code when `consume_fuel` fails; the charge is
+    /// retried once this resolves to `Ok`.  Hosts that budget cooperatively
+    /// yield here and top the counter back up; the default traps.
+    fn refuel<'a>(&'a mut self) -> AsyncRec<'a, anyhow::Result<()>> {
+        AsyncRec::Ret(Err(Trap::OutOfFuel.into()))
+    }
+}
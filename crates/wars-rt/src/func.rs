@@ -8,13 +8,171 @@ use core::{
 use alloc::{sync::Arc, vec, vec::Vec};
 use anyhow::Context;
 use tramp::{tramp, BorrowRec, Thunk};
+/// `call_target` for `func` and `func::unsync`: the same code over each
+/// module's own `Df`, `Coe` and `indirect`.
+macro_rules! call_target {
+    () => {
+        /// Resolve the operand of `call_ref`.  A typed reference is used as
+        /// is; anything else goes through [`indirect`], so null traps with
+        /// [`Trap::NullReference`].
+        pub fn call_target<
+            A: CoeVec<C> + 'static,
+            B: CoeVec<C> + 'static,
+            C: CtxSpec + 'static,
+            T: Coe<C> + 'static,
+        >(
+            r: T,
+        ) -> anyhow::Result<Df<A, B, C>> {
+            let r = match castaway::cast!(r, Df<A, B, C>) {
+                Ok(f) => return Ok(f),
+                Err(r) => r,
+            };
+            let r = match castaway::cast!(r, Option<Df<A, B, C>>) {
+                Ok(f) => return f.ok_or_else(|| Trap::NullReference.into()),
+                Err(r) => r,
+            };
+            indirect(r.coe())
+        }
+    };
+}
+/// `lift`, `lower`, `link_call` and the [`Relink`] impls for `func` and
+/// `func::unsync`: moving values between the contexts of two linked
+/// modules, an importer `C` that reaches its provider `P` through
+/// `AsMut<P>`.  `$lt` and `$rec` are the module's `ForLt` marker and
+/// call-result type.
+macro_rules! link_values {
+    ($lt:ident, $rec:ident) => {
+        /// A value of the provider `P` as the importer `C` sees it.  A
+        /// function reference becomes one that runs the original on
+        /// `ctx.as_mut()`, [`lower`]ing its arguments and lifting its
+        /// results.  Externrefs, exceptions and GC objects belong to `P`'s
+        /// context and do not cross.
+        pub fn lift<P: CtxSpec + 'static, C: CtxSpec + AsMut<P> + 'static>(
+            v: Value<P>,
+        ) -> anyhow::Result<Value<C>> {
+            fn x<
+                C: CtxSpec,
+                T: for<'a> Fn(&'a mut C, Vec<value::Value<C, $lt<C>>>) -> $rec<'a, anyhow::Result<Vec<Value<C>>>>
+                    + Send
+                    + Sync
+                    + 'static,
+            >(
+                a: T,
+            ) -> T {
+                a
+            }
+            Ok(Value(match v.0 {
+                value::Value::I32(a) => value::Value::I32(a),
+                value::Value::I64(a) => value::Value::I64(a),
+                value::Value::F32(a) => value::Value::F32(a),
+                value::Value::F64(a) => value::Value::F64(a),
+                value::Value::V128(a) => value::Value::V128(a),
+                value::Value::Null => value::Value::Null,
+                value::Value::FunRef(f, shape) => value::Value::FunRef(
+                    Arc::new(x(move |ctx: &mut C, args: Vec<value::Value<C, $lt<C>>>| {
+                        let args = args
+                            .into_iter()
+                            .map(|a| lower::<C, P>(Value(a)).map(|a| a.0))
+                            .collect::<anyhow::Result<Vec<_>>>();
+                        let args = match args {
+                            Ok(a) => a,
+                            Err(e) => return ret(Err(e)),
+                        };
+                        map_rec(f(ctx.as_mut(), args), |r| {
+                            r.and_then(|r| r.into_iter().map(lift::<P, C>).collect())
+                        })
+                    })),
+                    shape,
+                ),
+                _ => anyhow::bail!("only numbers and function references cross between linked modules"),
+            }))
+        }
+        /// A value of the importer `C` handed to its provider `P`: numbers
+        /// and null only, since nothing in `P` can supply the `C` that one
+        /// of the importer's function references needs.
+        pub fn lower<C: CtxSpec, P: CtxSpec>(v: Value<C>) -> anyhow::Result<Value<P>> {
+            Ok(Value(match v.0 {
+                value::Value::I32(a) => value::Value::I32(a),
+                value::Value::I64(a) => value::Value::I64(a),
+                value::Value::F32(a) => value::Value::F32(a),
+                value::Value::F64(a) => value::Value::F64(a),
+                value::Value::V128(a) => value::Value::V128(a),
+                value::Value::Null => value::Value::Null,
+                _ => anyhow::bail!("a reference of the importing module cannot be passed to its provider"),
+            }))
+        }
+        /// Call the provider's export `go` on behalf of the importer `ctx`:
+        /// the arguments are [`lower`]ed to `P` and the results [`lift`]ed
+        /// back, so both sides keep their own signature types.
+        pub fn link_call<
+            'a,
+            P: CtxSpec + 'static,
+            C: CtxSpec + AsMut<P> + 'static,
+            A: CoeVec<C>,
+            A2: CoeVec<P>,
+            B: CoeVec<P> + $crate::func::Relink<P, C> + 'a,
+        >(
+            ctx: &'a mut C,
+            a: A,
+            go: impl FnOnce(&'a mut P, A2) -> $rec<'a, anyhow::Result<B>>,
+        ) -> $rec<'a, anyhow::Result<B::Out>>
+        where
+            B::Out: CoeVec<C>,
+        {
+            let a = a
+                .coe()
+                .into_iter()
+                .map(lower::<C, P>)
+                .collect::<anyhow::Result<Vec<_>>>()
+                .and_then(|a| A2::uncoe(a).map_err(|_| Trap::IndirectCallTypeMismatch.into()));
+            let a = match a {
+                Ok(a) => a,
+                Err(e) => return ret(Err(e)),
+            };
+            map_rec(go(ctx.as_mut(), a), |r| {
+                r.and_then(|b| {
+                    let b = b.coe().into_iter().map(lift::<P, C>).collect::<anyhow::Result<Vec<_>>>()?;
+                    B::Out::uncoe(b).map_err(|_| Trap::IndirectCallTypeMismatch.into())
+                })
+            })
+        }
+        impl<P: CtxSpec, C: CtxSpec> $crate::func::Relink<P, C> for Value<P> {
+            type Out = Value<C>;
+        }
+        impl<P: CtxSpec, C: CtxSpec, A: $crate::func::Relink<P, C>, B: $crate::func::Relink<P, C>>
+            $crate::func::Relink<P, C> for Df<A, B, P>
+        {
+            type Out = Df<A::Out, B::Out, C>;
+        }
+    };
+}
+/// The type a value of a provider context `P` has in an importer `C`: the
+/// same for numbers, the same shape over `C` for references.  This is what
+/// fixes the result types of `link_call`.
+pub trait Relink<P, C> {
+    type Out;
+}
+macro_rules! relink_same {
+    ($($t:ty),*) => {
+        $(impl<P, C> Relink<P, C> for $t {
+            type Out = $t;
+        })*
+    };
+}
+relink_same!((), u32, u64, f32, f64, u128);
+impl<P, C, H: Relink<P, C>, T: Relink<P, C>> Relink<P, C> for (H, T) {
+    type Out = (H::Out, T::Out);
+}
+impl<P, C, T: Relink<P, C>> Relink<P, C> for Option<T> {
+    type Out = Option<T::Out>;
+}
 pub mod unsync;
 pub mod value;
 pub fn ret<'a, T>(a: T) -> BorrowRec<'a, T> {
     BorrowRec::Ret(a)
 }
 pub use crate::CtxSpec;
-use crate::{func::value::ForLt, Traverse};
+use crate::{func::value::ForLt, Trap, Traverse};
 #[repr(transparent)]
 pub struct Value<C: CtxSpec>(pub value::Value<C, BorrowForLt<C>>);
 pub struct BorrowForLt<C: CtxSpec> {
@@ -41,6 +199,12 @@ impl<C: CtxSpec> Traverse<C> for Value<C> {
     ) -> Box<dyn Iterator<Item = &'a mut <C as CtxSpec>::ExternRef> + 'a> {
         self.0.traverse_mut()
     }
+    fn visit(&self, v: &mut dyn crate::Visitor<C>) {
+        self.0.visit(v)
+    }
+    fn visit_mut(&mut self, v: &mut dyn crate::VisitorMut<C>) {
+        self.0.visit_mut(v)
+    }
 }
 pub fn call_ref<'a, A: CoeVec<C> + 'static, B: CoeVec<C> + 'static, C: CtxSpec + 'a>(
     ctx: &'a mut C,
@@ -50,12 +214,98 @@ pub fn call_ref<'a, A: CoeVec<C> + 'static, B: CoeVec<C> + 'static, C: CtxSpec +
     // let go: Df<A, B, C> = cast(go);
     go(ctx, a)
 }
+/// Resolve a table entry for `call_indirect`.  Null entries trap with
+/// [`Trap::NullReference`]; non-function values, and callees whose
+/// signature does not match `A -> B`, trap with
+/// [`Trap::IndirectCallTypeMismatch`].  A known [`value::Shape`] is
+/// compared here, before the callee runs.
+pub fn indirect<A: CoeVec<C> + 'static, B: CoeVec<C> + 'static, C: CtxSpec + 'static>(
+    v: Value<C>,
+) -> anyhow::Result<Df<A, B, C>> {
+    match &v.0 {
+        value::Value::Null => Err(Trap::NullReference.into()),
+        value::Value::FunRef(_, Some(s)) if **s != shape::<A, B, C>() => {
+            Err(Trap::IndirectCallTypeMismatch.into())
+        }
+        value::Value::FunRef(..) => Df::<A, B, C>::uncoe(v),
+        _ => Err(Trap::IndirectCallTypeMismatch.into()),
+    }
+}
+call_target!();
+link_values!(BorrowForLt, BorrowRec);
+/// Call a function reference with a dynamically typed argument list.  As
+/// with every `CoeVec` list, `args` and the results are REVERSED.  Null
+/// traps with [`Trap::NullReference`], anything else that is not a
+/// function, or arguments the callee does not accept, with
+/// [`Trap::IndirectCallTypeMismatch`].
+pub fn call_value<'a, C: CtxSpec + 'static>(
+    ctx: &'a mut C,
+    f: Value<C>,
+    args: Vec<Value<C>>,
+) -> tramp::BorrowRec<'a, anyhow::Result<Vec<Value<C>>>> {
+    match f.0 {
+        value::Value::FunRef(f, _) => f(ctx, args.into_iter().map(|a| a.0).collect()),
+        value::Value::Null => BorrowRec::Ret(Err(Trap::NullReference.into())),
+        _ => BorrowRec::Ret(Err(Trap::IndirectCallTypeMismatch.into())),
+    }
+}
+/// A thrown exception, as carried in the error channel.
+pub type Exn<C> = crate::Exception<value::Value<C, BorrowForLt<C>>>;
+/// `throw`: wrap the payload as an exception error.
+pub fn throw<C: CtxSpec + 'static>(tag: crate::Tag, payload: Vec<Value<C>>) -> anyhow::Error
+where
+    C::ExternRef: Send + Sync + 'static,
+{
+    anyhow::Error::new(Exn::<C> {
+        tag,
+        payload: payload.into_iter().map(|v| v.0).collect(),
+    })
+}
+/// `throw_ref`; a null `exnref` traps with [`Trap::NullReference`].
+pub fn throw_ref<C: CtxSpec + 'static>(v: Value<C>) -> anyhow::Error
+where
+    C::ExternRef: Send + Sync + 'static,
+{
+    match v.0 {
+        value::Value::Exn(x) => anyhow::Error::new((*x).clone()),
+        value::Value::Null => Trap::NullReference.into(),
+        _ => anyhow::anyhow!("throw_ref of a non-exception value"),
+    }
+}
+/// Split a wasm exception out of an error for a handler.  Anything else,
+/// traps included, is handed back to keep unwinding.
+pub fn catch<C: CtxSpec + 'static>(e: anyhow::Error) -> Result<Exn<C>, anyhow::Error>
+where
+    C::ExternRef: Send + Sync + 'static,
+{
+    e.downcast::<Exn<C>>()
+}
+/// Re-raise a caught exception.
+pub fn rethrow<C: CtxSpec + 'static>(x: Exn<C>) -> anyhow::Error
+where
+    C::ExternRef: Send + Sync + 'static,
+{
+    anyhow::Error::new(x)
+}
+/// The `exnref` for a caught exception, as pushed by `catch_ref`.
+pub fn exnref<C: CtxSpec>(x: Exn<C>) -> Value<C> {
+    Value(value::Value::Exn(Arc::new(x)))
+}
 impl<C: CtxSpec> Clone for Value<C> {
     fn clone(&self) -> Self {
         Self(self.0.clone())
     }
 }
+/// The default value is the null reference, as used for fresh table slots.
+impl<C: CtxSpec> Default for Value<C> {
+    fn default() -> Self {
+        Self(value::Value::Null)
+    }
+}
 pub trait Coe<C: CtxSpec>: Sized {
+    /// How `Shape`s describe this type; everything but numbers is a
+    /// reference.
+    const SLOT: value::Slot = value::Slot::Ref;
     fn coe(self) -> Value<C>;
     fn uncoe(x: Value<C>) -> anyhow::Result<Self>;
 }
@@ -91,6 +341,7 @@ impl<C: CtxSpec, D: Coe<C>> Coe<C> for Option<D> {
 macro_rules! coe_impl_prim {
     ($a:tt in $b:ident) => {
         impl<C: CtxSpec> Coe<C> for $a {
+            const SLOT: value::Slot = value::Slot::$b;
             fn coe(self) -> Value<C> {
                 Value(value::Value::$b(self))
             }
@@ -107,6 +358,7 @@ coe_impl_prim!(u32 in I32);
 coe_impl_prim!(u64 in I64);
 coe_impl_prim!(f32 in F32);
 coe_impl_prim!(f64 in F64);
+coe_impl_prim!(u128 in V128);
 #[cfg(feature = "dumpster")]
 pub trait CoeField<C: CtxSpec>: Sized {
     fn coe(self) -> crate::gc::Field<Value<C>>;
@@ -198,6 +450,8 @@ pub trait CoeVec<C: CtxSpec>: Sized {
     const NUM: usize;
     fn coe(self) -> Vec<Value<C>>;
     fn uncoe(a: Vec<Value<C>>) -> anyhow::Result<Self>;
+    /// Append the `Slot`s of the list, in declaration order.
+    fn slots(v: &mut Vec<value::Slot>);
 }
 impl<C: CtxSpec> CoeVec<C> for () {
     fn coe(self) -> Vec<Value<C>> {
@@ -206,6 +460,7 @@ impl<C: CtxSpec> CoeVec<C> for () {
     fn uncoe(a: Vec<Value<C>>) -> anyhow::Result<Self> {
         Ok(())
     }
+    fn slots(_: &mut Vec<value::Slot>) {}
     const NUM: usize = 0;
 }
 impl<C: CtxSpec, A: Coe<C>, B: CoeVec<C>> CoeVec<C> for (A, B) {
@@ -222,8 +477,19 @@ impl<C: CtxSpec, A: Coe<C>, B: CoeVec<C>> CoeVec<C> for (A, B) {
         let z = B::uncoe(a)?;
         Ok((y, z))
     }
+    fn slots(v: &mut Vec<value::Slot>) {
+        v.push(A::SLOT);
+        B::slots(v);
+    }
     const NUM: usize = B::NUM + 1;
 }
+/// The `Shape` of `Df<A, B, C>`.
+pub fn shape<A: CoeVec<C>, B: CoeVec<C>, C: CtxSpec>() -> value::Shape {
+    let mut s = value::Shape::default();
+    A::slots(&mut s.params);
+    B::slots(&mut s.results);
+    s
+}
 pub fn map_rec<'a, T: 'a, U>(
     r: BorrowRec<'a, T>,
     go: impl FnOnce(T) -> U + 'a,
@@ -273,21 +539,23 @@ impl<C: CtxSpec + 'static, A: CoeVec<C> + 'static, B: CoeVec<C> + 'static> Coe<C
             move |ctx: &mut C, x: Vec<value::Value<C, BorrowForLt<C>>>| {
                 let x = match A::uncoe(unsafe { transmute::<_, Vec<Value<C>>>(x) }) {
                     Ok(x) => x,
-                    Err(e) => return BorrowRec::Ret(Err(e)),
+                    Err(_) => return BorrowRec::Ret(Err(Trap::IndirectCallTypeMismatch.into())),
                 };
                 let x = self(ctx, x);
                 map_rec(x, |a| a.map(|b| b.coe()))
             },
-        ))))
+        )), Some(Arc::new(shape::<A, B, C>()))))
     }
     fn uncoe(x: Value<C>) -> anyhow::Result<Self> {
-        let value::Value::FunRef(x) = x.0 else {
+        let value::Value::FunRef(x, _) = x.0 else {
             anyhow::bail!("invalid value")
         };
         Ok(Arc::new(move |ctx, a| {
             let v = a.coe();
             let v = x(ctx, unsafe{transmute(v)});
-            map_rec(v, |a| a.and_then(B::uncoe))
+            map_rec(v, |a| {
+                a.and_then(|b| B::uncoe(b).map_err(|_| Trap::IndirectCallTypeMismatch.into()))
+            })
         }))
     }
 }
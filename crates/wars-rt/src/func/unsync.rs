@@ -53,7 +53,7 @@ impl<'a, T> AsyncRec<'a, T> {
     }
 }
 pub use crate::CtxSpec;
-use crate::Traverse;
+use crate::{Trap, Traverse};
 use crate::func::value::ForLt;
 #[repr(transparent)]
 pub struct Value<C: CtxSpec>(pub super::value::Value<C, AsyncForLt<C>>);
@@ -89,13 +89,102 @@ impl<C: CtxSpec> Traverse<C> for Value<C> {
     ) -> Box<dyn Iterator<Item = &'a mut <C as CtxSpec>::ExternRef> + 'a> {
         self.0.traverse_mut()
     }
+    fn visit(&self, v: &mut dyn crate::Visitor<C>) {
+        self.0.visit(v)
+    }
+    fn visit_mut(&mut self, v: &mut dyn crate::VisitorMut<C>) {
+        self.0.visit_mut(v)
+    }
+}
+/// Resolve a table entry for `call_indirect`.  Null entries trap with
+/// [`Trap::NullReference`]; non-function values, and callees whose
+/// signature does not match `A -> B`, trap with
+/// [`Trap::IndirectCallTypeMismatch`].  A known [`value::Shape`] is
+/// compared here, before the callee runs.
+pub fn indirect<A: CoeVec<C> + 'static, B: CoeVec<C> + 'static, C: CtxSpec + 'static>(
+    v: Value<C>,
+) -> anyhow::Result<Df<A, B, C>> {
+    match &v.0 {
+        super::value::Value::Null => Err(Trap::NullReference.into()),
+        super::value::Value::FunRef(_, Some(s)) if **s != shape::<A, B, C>() => {
+            Err(Trap::IndirectCallTypeMismatch.into())
+        }
+        super::value::Value::FunRef(..) => Df::<A, B, C>::uncoe(v),
+        _ => Err(Trap::IndirectCallTypeMismatch.into()),
+    }
+}
+call_target!();
+link_values!(AsyncForLt, AsyncRec);
+/// Call a function reference with a dynamically typed argument list; see
+/// [`super::call_value`].
+pub fn call_value<'a, C: CtxSpec + 'static>(
+    ctx: &'a mut C,
+    f: Value<C>,
+    args: Vec<Value<C>>,
+) -> AsyncRec<'a, anyhow::Result<Vec<Value<C>>>> {
+    match f.0 {
+        super::value::Value::FunRef(f, _) => f(ctx, args.into_iter().map(|a| a.0).collect()),
+        super::value::Value::Null => AsyncRec::Ret(Err(Trap::NullReference.into())),
+        _ => AsyncRec::Ret(Err(Trap::IndirectCallTypeMismatch.into())),
+    }
+}
+/// A thrown exception, as carried in the error channel.
+pub type Exn<C> = crate::Exception<super::value::Value<C, AsyncForLt<C>>>;
+/// `throw`: wrap the payload as an exception error.
+pub fn throw<C: CtxSpec + 'static>(tag: crate::Tag, payload: Vec<Value<C>>) -> anyhow::Error
+where
+    C::ExternRef: Send + Sync + 'static,
+{
+    anyhow::Error::new(Exn::<C> {
+        tag,
+        payload: payload.into_iter().map(|v| v.0).collect(),
+    })
+}
+/// `throw_ref`; a null `exnref` traps with [`Trap::NullReference`].
+pub fn throw_ref<C: CtxSpec + 'static>(v: Value<C>) -> anyhow::Error
+where
+    C::ExternRef: Send + Sync + 'static,
+{
+    match v.0 {
+        super::value::Value::Exn(x) => anyhow::Error::new((*x).clone()),
+        super::value::Value::Null => Trap::NullReference.into(),
+        _ => anyhow::anyhow!("throw_ref of a non-exception value"),
+    }
+}
+/// Split a wasm exception out of an error for a handler.  Anything else,
+/// traps included, is handed back to keep unwinding.
+pub fn catch<C: CtxSpec + 'static>(e: anyhow::Error) -> Result<Exn<C>, anyhow::Error>
+where
+    C::ExternRef: Send + Sync + 'static,
+{
+    e.downcast::<Exn<C>>()
+}
+/// Re-raise a caught exception.
+pub fn rethrow<C: CtxSpec + 'static>(x: Exn<C>) -> anyhow::Error
+where
+    C::ExternRef: Send + Sync + 'static,
+{
+    anyhow::Error::new(x)
+}
+/// The `exnref` for a caught exception, as pushed by `catch_ref`.
+pub fn exnref<C: CtxSpec>(x: Exn<C>) -> Value<C> {
+    Value(super::value::Value::Exn(Arc::new(x)))
 }
 impl<C: CtxSpec> Clone for Value<C> {
     fn clone(&self) -> Self {
        Self(self.0.clone())
     }
 }
+/// The default value is the null reference, as used for fresh table slots.
+impl<C: CtxSpec> Default for Value<C> {
+    fn default() -> Self {
+        Self(super::value::Value::Null)
+    }
+}
 pub trait Coe<C: CtxSpec>: Sized {
+    /// How `Shape`s describe this type; everything but numbers is a
+    /// reference.
+    const SLOT: super::value::Slot = super::value::Slot::Ref;
     fn coe(self) -> Value<C>;
     fn uncoe(x: Value<C>) -> anyhow::Result<Self>;
 }
@@ -131,6 +220,7 @@ impl<C: CtxSpec, D: Coe<C>> Coe<C> for Option<D> {
 macro_rules! coe_impl_prim {
     ($a:tt in $b:ident) => {
         impl<C: CtxSpec> Coe<C> for $a {
+            const SLOT: super::value::Slot = super::value::Slot::$b;
             fn coe(self) -> Value<C> {
                 Value(super::value::Value::$b(self))
             }
@@ -147,6 +237,7 @@ coe_impl_prim!(u32 in I32);
 coe_impl_prim!(u64 in I64);
 coe_impl_prim!(f32 in F32);
 coe_impl_prim!(f64 in F64);
+coe_impl_prim!(u128 in V128);
 #[cfg(feature = "dumpster")]
 pub trait CoeField<C: CtxSpec>: Sized {
     fn coe(self) -> crate::gc::Field<Value<C>>;
@@ -238,6 +329,8 @@ pub trait CoeVec<C: CtxSpec>: Sized {
     const NUM: usize;
     fn coe(self) -> Vec<Value<C>>;
     fn uncoe(a: Vec<Value<C>>) -> anyhow::Result<Self>;
+    /// Append the `Slot`s of the list, in declaration order.
+    fn slots(v: &mut Vec<super::value::Slot>);
 }
 impl<C: CtxSpec> CoeVec<C> for () {
     fn coe(self) -> Vec<Value<C>> {
@@ -246,6 +339,7 @@ impl<C: CtxSpec> CoeVec<C> for () {
     fn uncoe(a: Vec<Value<C>>) -> anyhow::Result<Self> {
         Ok(())
     }
+    fn slots(_: &mut Vec<super::value::Slot>) {}
     const NUM: usize = 0;
 }
 impl<C: CtxSpec, A: Coe<C>, B: CoeVec<C>> CoeVec<C> for (A, B) {
@@ -262,8 +356,19 @@ impl<C: CtxSpec, A: Coe<C>, B: CoeVec<C>> CoeVec<C> for (A, B) {
         let z = B::uncoe(a)?;
         Ok((y, z))
     }
+    fn slots(v: &mut Vec<super::value::Slot>) {
+        v.push(A::SLOT);
+        B::slots(v);
+    }
     const NUM: usize = B::NUM + 1;
 }
+/// The `Shape` of `Df<A, B, C>`.
+pub fn shape<A: CoeVec<C>, B: CoeVec<C>, C: CtxSpec>() -> super::value::Shape {
+    let mut s = super::value::Shape::default();
+    A::slots(&mut s.params);
+    B::slots(&mut s.results);
+    s
+}
 pub fn map_rec<'a, T: 'a, U>(
     r: AsyncRec<'a, T>,
     go: impl FnOnce(T) -> U + Send + Sync + 'a,
@@ -302,14 +407,14 @@ impl<C: CtxSpec + 'static, A: CoeVec<C> + 'static, B: CoeVec<C> + 'static> Coe<C
         Value(super::value::Value::FunRef(Arc::new(x(move |ctx, x| {
             let x = match A::uncoe(unsafe{transmute(x)}) {
                 Ok(x) => x,
-                Err(e) => return AsyncRec::Ret(Err(e)),
+                Err(_) => return AsyncRec::Ret(Err(Trap::IndirectCallTypeMismatch.into())),
             };
             let x = self(ctx, x);
             map_rec(x, |a| a.map(|b| b.coe()))
-        }))))
+        })), Some(Arc::new(shape::<A, B, C>()))))
     }
     fn uncoe(x: Value<C>) -> anyhow::Result<Self> {
-        let super::value::Value::FunRef(x) = x.0 else {
+        let super::value::Value::FunRef(x, _) = x.0 else {
             anyhow::bail!("invalid value")
         };
         Ok(Arc::new(move |ctx, a| {
@@ -317,7 +422,9 @@ impl<C: CtxSpec + 'static, A: CoeVec<C> + 'static, B: CoeVec<C> + 'static> Coe<C
             let v = x(ctx, unsafe{
                 transmute(v)
             });
-            map_rec(v, |a| a.and_then(B::uncoe))
+            map_rec(v, |a| {
+                a.and_then(|b| B::uncoe(b).map_err(|_| Trap::IndirectCallTypeMismatch.into()))
+            })
         }))
     }
 }
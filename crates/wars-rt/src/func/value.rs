@@ -1,13 +1,39 @@
 use super::*;
+#[cfg(not(feature = "dumpster"))]
 pub trait ForLt<'a>{
     type ForLt;
 }
+/// `'static` so that a `Value` can live in a `dumpster::sync::Gc`.
+#[cfg(feature = "dumpster")]
+pub trait ForLt<'a>: 'static {
+    type ForLt;
+}
+/// A parameter or result of a function signature, as far as it can be
+/// compared before a call: number types exactly, references all alike
+/// (they are checked as they convert).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Slot {
+    I32,
+    I64,
+    F32,
+    F64,
+    V128,
+    Ref,
+}
+/// The signature of a function reference, in `Slot`s and declaration
+/// order.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Shape {
+    pub params: Vec<Slot>,
+    pub results: Vec<Slot>,
+}
 #[non_exhaustive]
 pub enum Value<C: CtxSpec,R: for<'a>ForLt<'a>> {
     I32(u32),
     I64(u64),
     F32(f32),
     F64(f64),
+    V128(u128),
     FunRef(
         Arc<
             dyn for<'a> Fn(
@@ -18,9 +44,13 @@ pub enum Value<C: CtxSpec,R: for<'a>ForLt<'a>> {
                 + Sync
                 + 'static,
         >,
+        /// The callee's signature, when known, so `call_indirect` can trap
+        /// on a mismatch before running it.
+        Option<Arc<Shape>>,
     ),
     Null,
     ExRef(C::ExternRef),
+    Exn(Arc<crate::Exception<Value<C, R>>>),
     #[cfg(feature = "dumpster")]
     Gc(crate::gc::GcCore<Value<C,R>>),
 }
@@ -41,6 +71,7 @@ impl<C: CtxSpec,R:for<'a>ForLt<'a>> Traverse<C> for Value<C,R> {
     fn traverse<'a>(&'a self) -> Box<dyn Iterator<Item = &'a <C as CtxSpec>::ExternRef> + 'a> {
         match self {
             Value::ExRef(e) => Box::new(once(e)),
+            Value::Exn(x) => Box::new(x.payload.iter().flat_map(|v| v.traverse())),
             #[cfg(feature = "dumpster")]
             Value::Gc(g) => g.traverse(),
             _ => Box::new(empty()),
@@ -51,11 +82,39 @@ impl<C: CtxSpec,R:for<'a>ForLt<'a>> Traverse<C> for Value<C,R> {
     ) -> Box<dyn Iterator<Item = &'a mut <C as CtxSpec>::ExternRef> + 'a> {
         match self {
             Value::ExRef(e) => Box::new(once(e)),
+            // Copy-on-write: exception identity is not observable.
+            Value::Exn(x) => Box::new(Arc::make_mut(x).payload.iter_mut().flat_map(|v| v.traverse_mut())),
             #[cfg(feature = "dumpster")]
             Value::Gc(g) => g.traverse_mut(),
             _ => Box::new(empty()),
         }
     }
+    fn visit(&self, v: &mut dyn crate::Visitor<C>) {
+        match self {
+            Value::ExRef(e) => v.visit(e),
+            Value::Exn(x) => {
+                if v.enter(crate::addr(x)) {
+                    x.payload.visit(v)
+                }
+            }
+            #[cfg(feature = "dumpster")]
+            Value::Gc(g) => g.visit(v),
+            _ => {}
+        }
+    }
+    fn visit_mut(&mut self, v: &mut dyn crate::VisitorMut<C>) {
+        match self {
+            Value::ExRef(e) => v.visit_mut(e),
+            // As in `traverse_mut`, a shared exception is copied first, so
+            // every holder ends up with rewritten externrefs.  Payloads
+            // cannot contain their own exception, so there is no cycle to
+            // end with `enter`.
+            Value::Exn(x) => Arc::make_mut(x).payload.visit_mut(v),
+            #[cfg(feature = "dumpster")]
+            Value::Gc(g) => g.visit_mut(v),
+            _ => {}
+        }
+    }
 }
 impl<C: CtxSpec,R: for<'a>ForLt<'a>> Clone for Value<C,R> {
     fn clone(&self) -> Self {
@@ -64,11 +123,213 @@ impl<C: CtxSpec,R: for<'a>ForLt<'a>> Clone for Value<C,R> {
             Self::I64(arg0) => Self::I64(arg0.clone()),
             Self::F32(arg0) => Self::F32(arg0.clone()),
             Self::F64(arg0) => Self::F64(arg0.clone()),
-            Self::FunRef(arg0) => Self::FunRef(arg0.clone()),
+            Self::V128(arg0) => Self::V128(arg0.clone()),
+            Self::FunRef(arg0, shape) => Self::FunRef(arg0.clone(), shape.clone()),
             Self::Null => Self::Null,
             Self::ExRef(e) => Self::ExRef(e.clone()),
+            Self::Exn(x) => Self::Exn(x.clone()),
             #[cfg(feature = "dumpster")]
             Self::Gc(c) => Self::Gc(c.clone()),
         }
     }
-}
\ No newline at end of file
+}/// GC operators for the wasmparser backend.  A null operand traps with
+/// [`Trap::NullReference`].
+#[cfg(feature = "dumpster")]
+impl<C: CtxSpec, R: for<'a> ForLt<'a>> Value<C, R> {
+    fn gc(&self) -> anyhow::Result<&crate::gc::GcCore<Self>> {
+        match self {
+            Value::Gc(g) => Ok(g),
+            Value::Null => Err(Trap::NullReference.into()),
+            _ => anyhow::bail!("not a gc reference"),
+        }
+    }
+    fn array(&self) -> anyhow::Result<&dumpster::sync::Gc<crate::gc::ArrayObj<Self>>> {
+        match self.gc()? {
+            crate::gc::GcCore::Array(a) => Ok(a),
+            _ => anyhow::bail!("not an array"),
+        }
+    }
+    pub fn new_struct(ty: &'static crate::gc::Rtt, fields: Vec<crate::gc::Field<Self>>) -> Self {
+        Value::Gc(crate::gc::GcCore::Struct(dumpster::sync::Gc::new(crate::gc::StructObj::new(ty, fields))))
+    }
+    pub fn new_array(ty: &'static crate::gc::Rtt, elems: crate::gc::ArrayData<Self>) -> Self {
+        Value::Gc(crate::gc::GcCore::Array(dumpster::sync::Gc::new(crate::gc::ArrayObj {
+            ty,
+            elems: crate::Mutex::new(elems),
+        })))
+    }
+    /// Pack `elems` into storage of layout `st`.
+    pub fn pack(st: crate::gc::Storage, elems: impl IntoIterator<Item = Self>) -> crate::gc::ArrayData<Self> {
+        use crate::gc::{ArrayData, Storage};
+        let elems = elems.into_iter();
+        match st {
+            Storage::Ref => ArrayData::Ref(elems.collect()),
+            Storage::I8 => ArrayData::I8(elems.map(|v| v.to_bits() as u8).collect()),
+            Storage::I16 => ArrayData::I16(elems.map(|v| v.to_bits() as u16).collect()),
+            Storage::I32 => ArrayData::I32(elems.map(|v| v.to_bits() as u32).collect()),
+            Storage::I64 => ArrayData::I64(elems.map(|v| v.to_bits() as u64).collect()),
+            Storage::F32 => ArrayData::F32(elems.map(|v| f32::from_bits(v.to_bits() as u32)).collect()),
+            Storage::F64 => ArrayData::F64(elems.map(|v| f64::from_bits(v.to_bits() as u64)).collect()),
+            Storage::V128 => ArrayData::V128(elems.map(|v| v.to_bits()).collect()),
+        }
+    }
+    /// `n` copies of `v` in layout `st`.  Traps with
+    /// [`Trap::AllocationTooLarge`] rather than aborting if they do not fit.
+    pub fn repeat(st: crate::gc::Storage, v: Self, n: u32) -> anyhow::Result<crate::gc::ArrayData<Self>> {
+        crate::gc::ArrayData::try_repeat(&Self::pack(st, core::iter::once(v)), n)
+            .ok_or_else(|| Trap::AllocationTooLarge.into())
+    }
+    /// Raw bits of a numeric value; 0 for references.
+    fn to_bits(&self) -> u128 {
+        match self {
+            Value::I32(x) => *x as u128,
+            Value::I64(x) => *x as u128,
+            Value::F32(x) => x.to_bits() as u128,
+            Value::F64(x) => x.to_bits() as u128,
+            Value::V128(x) => *x,
+            _ => 0,
+        }
+    }
+    /// Decode `n` elements of layout `st` from `data[off..]`.
+    pub fn decode(st: crate::gc::Storage, data: &[u8], off: u32, n: u32) -> anyhow::Result<crate::gc::ArrayData<Self>> {
+        if st == crate::gc::Storage::Ref {
+            anyhow::bail!("cannot decode references from a data segment");
+        }
+        let end = off as u64 + (n as u64) * (st.size() as u64);
+        if end > data.len() as u64 {
+            return Err(Trap::OutOfBoundsMemory.into());
+        }
+        let words = data[off as usize..end as usize].chunks(st.size()).map(|b| {
+            let mut w = [0u8; 16];
+            w[..b.len()].copy_from_slice(b);
+            Value::V128(u128::from_le_bytes(w))
+        });
+        Ok(Self::pack(st, words))
+    }
+    pub fn i31(x: u32) -> Self {
+        Value::Gc(crate::gc::GcCore::I31(crate::gc::I31::new(x)))
+    }
+    pub fn i31_get(&self, signed: bool) -> anyhow::Result<u32> {
+        match self.gc()? {
+            crate::gc::GcCore::I31(x) if signed => Ok(x.get_s()),
+            crate::gc::GcCore::I31(x) => Ok(x.get_u()),
+            _ => anyhow::bail!("not an i31"),
+        }
+    }
+    pub fn struct_get(&self, idx: usize) -> anyhow::Result<Self> {
+        self.gc()?.get_field(idx)
+    }
+    pub fn struct_set(&self, idx: usize, v: Self) -> anyhow::Result<()> {
+        self.gc()?.set_field(idx, v)
+    }
+    pub fn array_len(&self) -> anyhow::Result<u32> {
+        Ok(self.array()?.elems.lock().unwrap().len() as u32)
+    }
+    /// Packed elements come back zero-extended.
+    pub fn array_get(&self, i: u32) -> anyhow::Result<Self> {
+        use crate::gc::ArrayData;
+        let i = i as usize;
+        let e = self.array()?.elems.lock().unwrap();
+        let v = match &*e {
+            ArrayData::Ref(v) => v.get(i).cloned(),
+            ArrayData::I8(v) => v.get(i).map(|x| Value::I32(*x as u32)),
+            ArrayData::I16(v) => v.get(i).map(|x| Value::I32(*x as u32)),
+            ArrayData::I32(v) => v.get(i).map(|x| Value::I32(*x)),
+            ArrayData::I64(v) => v.get(i).map(|x| Value::I64(*x)),
+            ArrayData::F32(v) => v.get(i).map(|x| Value::F32(*x)),
+            ArrayData::F64(v) => v.get(i).map(|x| Value::F64(*x)),
+            ArrayData::V128(v) => v.get(i).map(|x| Value::V128(*x)),
+        };
+        v.ok_or_else(|| Trap::OutOfBoundsArray.into())
+    }
+    pub fn array_set(&self, i: u32, v: Self) -> anyhow::Result<()> {
+        use crate::gc::ArrayData;
+        let i = i as usize;
+        let b = v.to_bits();
+        let a = self.array()?;
+        let mut e = a.elems.lock().unwrap();
+        let slot = match &mut *e {
+            ArrayData::Ref(d) => d.get_mut(i).map(|x| *x = v),
+            ArrayData::I8(d) => d.get_mut(i).map(|x| *x = b as u8),
+            ArrayData::I16(d) => d.get_mut(i).map(|x| *x = b as u16),
+            ArrayData::I32(d) => d.get_mut(i).map(|x| *x = b as u32),
+            ArrayData::I64(d) => d.get_mut(i).map(|x| *x = b as u64),
+            ArrayData::F32(d) => d.get_mut(i).map(|x| *x = f32::from_bits(b as u32)),
+            ArrayData::F64(d) => d.get_mut(i).map(|x| *x = f64::from_bits(b as u64)),
+            ArrayData::V128(d) => d.get_mut(i).map(|x| *x = b),
+        };
+        slot.ok_or_else(|| Trap::OutOfBoundsArray.into())
+    }
+    pub fn array_fill(&self, off: u32, v: Self, n: u32) -> anyhow::Result<()> {
+        let a = self.array()?;
+        let mut e = a.elems.lock().unwrap();
+        let src = Self::pack(e.storage(), core::iter::repeat(v).take(n as usize));
+        if e.write(off, &src) {
+            Ok(())
+        } else {
+            Err(Trap::OutOfBoundsArray.into())
+        }
+    }
+    /// Write `src[src_off..][..n]` into this array at `dst`.
+    pub fn array_init(&self, dst: u32, src: &crate::gc::ArrayData<Self>, src_off: u32, n: u32) -> anyhow::Result<()> {
+        let src = src.slice(src_off, n).ok_or(Trap::OutOfBoundsArray)?;
+        if self.array()?.elems.lock().unwrap().write(dst, &src) {
+            Ok(())
+        } else {
+            Err(Trap::OutOfBoundsArray.into())
+        }
+    }
+    /// `array.copy`.  The source range is read out first, so `src` may be
+    /// this array.
+    pub fn array_copy(&self, dst: u32, src: &Self, src_off: u32, n: u32) -> anyhow::Result<()> {
+        let vals = src.array()?.elems.lock().unwrap().slice(src_off, n).ok_or(Trap::OutOfBoundsArray)?;
+        self.array_init(dst, &vals, 0, n)
+    }
+    /// `any.convert_extern`.  Host references are wrapped so they are
+    /// `any` but not `eq`; externalized GC values come back as they were.
+    pub fn any_convert_extern(self) -> Self {
+        match self {
+            Value::ExRef(_) => Value::Gc(crate::gc::GcCore::Extern(Box::new(self))),
+            v => v,
+        }
+    }
+    /// `extern.convert_any`: the inverse of [`Value::any_convert_extern`].
+    /// Any other GC value stays itself inside the `externref`.
+    pub fn extern_convert_any(self) -> Self {
+        match self {
+            Value::Gc(crate::gc::GcCore::Extern(v)) => *v,
+            v => v,
+        }
+    }
+    /// `ref.eq`: identity for structs and arrays, value equality for `i31`.
+    pub fn ref_eq(&self, other: &Self) -> bool {
+        use crate::gc::GcCore;
+        match (self, other) {
+            (Value::Null, Value::Null) => true,
+            (Value::Gc(GcCore::I31(a)), Value::Gc(GcCore::I31(b))) => a == b,
+            (Value::Gc(GcCore::Struct(a)), Value::Gc(GcCore::Struct(b))) => dumpster::sync::Gc::ptr_eq(a, b),
+            (Value::Gc(GcCore::Array(a)), Value::Gc(GcCore::Array(b))) => dumpster::sync::Gc::ptr_eq(a, b),
+            _ => false,
+        }
+    }
+    /// `ref.test`.  Function references carry no signature, so any of them
+    /// passes a test against `func` or a function type.
+    pub fn ref_test(&self, t: crate::gc::HeapTest<'_>, nullable: bool) -> bool {
+        use crate::gc::{GcCore, HeapTest};
+        match (self, t) {
+            (Value::Null, _) => nullable,
+            (_, HeapTest::Bottom) => false,
+            (Value::Gc(_) | Value::ExRef(_), HeapTest::Any | HeapTest::Extern) => true,
+            (Value::Gc(GcCore::Extern(_)), HeapTest::Eq) => false,
+            (Value::Gc(_), HeapTest::Eq) => true,
+            (Value::Gc(GcCore::I31(_)), HeapTest::I31) => true,
+            (Value::Gc(GcCore::Struct(_) | GcCore::Fields(_)), HeapTest::Struct) => true,
+            (Value::Gc(GcCore::Array(_)), HeapTest::Array) => true,
+            (Value::Gc(GcCore::Struct(s)), HeapTest::Type(t)) => s.ty.is_sub(t),
+            (Value::Gc(GcCore::Array(a)), HeapTest::Type(t)) => a.ty.is_sub(t),
+            (Value::FunRef(..), HeapTest::Func) => true,
+            (Value::Exn(_), HeapTest::Exn) => true,
+            _ => false,
+        }
+    }
+}
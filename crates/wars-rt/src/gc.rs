@@ -1,59 +1,324 @@
 use core::iter::{empty, once};
 use std::{
     boxed::Box,
-    sync::{Arc, Mutex},
+    sync::{Arc, Mutex, RwLock},
     vec::Vec,
 };
 use dumpster::{sync::Gc, Trace};
-use crate::{CtxSpec, Traverse};
+use crate::{CtxSpec, Trap, Traverse, Visitor, VisitorMut};
 // use ic_stable_structures::Vec;
 mod heapsize {
     pub trait HeapSize {}
     impl<T: ?Sized> HeapSize for T {}
 }
+/// A GC reference.  Structs and arrays are [`Gc`]s, so cycles between them
+/// are collected; `R` is the value type stored in them.
 #[derive(Clone)]
 #[non_exhaustive]
-pub enum GcCore<R> {
+pub enum GcCore<R: Trace + Send + Sync + 'static> {
     Fields(Vec<Field<R>>),
+    /// A struct allocated by the wasmparser backend.
+    Struct(Gc<StructObj<R>>),
+    /// An array allocated by the wasmparser backend.
+    Array(Gc<ArrayObj<R>>),
+    I31(I31),
+    /// An externref brought into `any` by `any.convert_extern`;
+    /// `extern.convert_any` gives the original back.
+    Extern(Box<R>),
 }
-impl<R: Clone> GcCore<R> {
-    pub fn get_field(&self, a: usize) -> R {
-        match self {
-            GcCore::Fields(vec) => match &vec[a] {
-                Field::Const(r) => r.clone(),
-                Field::Mut(arc) => arc.lock().unwrap().clone(),
-            },
+/// Address of a GC object, for [`Visitor::enter`].
+fn gc_addr<T: Trace + Send + Sync + 'static>(g: &Gc<T>) -> usize {
+    &**g as *const T as usize
+}
+/// Runtime type descriptor of a struct or array type.  Generated code keeps
+/// one `static` per type.
+#[derive(Debug)]
+pub struct Rtt {
+    /// Canonical id.  Types at the same position in structurally equal
+    /// rec groups get the same id, in any module.
+    pub id: u64,
+    /// Canonical ids of the supertype chain, root first, ending with `id`.
+    pub display: &'static [u64],
+}
+impl Rtt {
+    /// Whether `self` is a subtype of `sup`; constant time.
+    pub fn is_sub(&self, sup: &Rtt) -> bool {
+        match sup.display.len().checked_sub(1) {
+            Some(d) => self.display.get(d) == Some(&sup.id),
+            None => false,
+        }
+    }
+}
+/// An `i31ref`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct I31(u32);
+impl I31 {
+    /// Keeps the low 31 bits of `x`.
+    pub fn new(x: u32) -> Self {
+        I31(x & 0x7fff_ffff)
+    }
+    pub fn get_u(self) -> u32 {
+        self.0
+    }
+    pub fn get_s(self) -> u32 {
+        (((self.0 << 1) as i32) >> 1) as u32
+    }
+}
+pub struct StructObj<R> {
+    pub ty: &'static Rtt,
+    /// Behind a lock so that [`Traverse::visit_mut`] can rewrite externrefs
+    /// in immutable fields of a struct that other references share.
+    pub fields: RwLock<Vec<Field<R>>>,
+}
+impl<R> StructObj<R> {
+    pub fn new(ty: &'static Rtt, fields: Vec<Field<R>>) -> Self {
+        StructObj { ty, fields: RwLock::new(fields) }
+    }
+}
+pub struct ArrayObj<R> {
+    pub ty: &'static Rtt,
+    pub elems: Mutex<ArrayData<R>>,
+}
+unsafe impl<R: Trace> Trace for StructObj<R> {
+    fn accept<V: dumpster::Visitor>(&self, visitor: &mut V) -> Result<(), ()> {
+        // A held lock means the object is in use, so it is not garbage.
+        self.fields.try_read().map_err(|_| ())?.accept(visitor)
+    }
+}
+unsafe impl<R: Trace> Trace for ArrayObj<R> {
+    fn accept<V: dumpster::Visitor>(&self, visitor: &mut V) -> Result<(), ()> {
+        self.elems.accept(visitor)
+    }
+}
+/// Array elements.  Numeric and packed elements are stored unboxed.
+#[derive(Clone)]
+pub enum ArrayData<R> {
+    Ref(Vec<R>),
+    I8(Vec<u8>),
+    I16(Vec<u16>),
+    I32(Vec<u32>),
+    I64(Vec<u64>),
+    F32(Vec<f32>),
+    F64(Vec<f64>),
+    V128(Vec<u128>),
+}
+macro_rules! each {
+    ($e:expr, $v:ident => $body:expr) => {
+        match $e {
+            ArrayData::Ref($v) => $body,
+            ArrayData::I8($v) => $body,
+            ArrayData::I16($v) => $body,
+            ArrayData::I32($v) => $body,
+            ArrayData::I64($v) => $body,
+            ArrayData::F32($v) => $body,
+            ArrayData::F64($v) => $body,
+            ArrayData::V128($v) => $body,
         }
+    };
+}
+impl<R: Clone> ArrayData<R> {
+    pub fn len(&self) -> usize {
+        each!(self, v => v.len())
     }
-    pub fn set_field(&self, a: usize, r: R) {
+    pub fn storage(&self) -> Storage {
         match self {
-            GcCore::Fields(vec) => match &vec[a] {
-                Field::Const(_) => {}
-                Field::Mut(arc) => {
-                    *arc.lock().unwrap() = r;
+            ArrayData::Ref(_) => Storage::Ref,
+            ArrayData::I8(_) => Storage::I8,
+            ArrayData::I16(_) => Storage::I16,
+            ArrayData::I32(_) => Storage::I32,
+            ArrayData::I64(_) => Storage::I64,
+            ArrayData::F32(_) => Storage::F32,
+            ArrayData::F64(_) => Storage::F64,
+            ArrayData::V128(_) => Storage::V128,
+        }
+    }
+    /// `n` copies of the single element in `one`, or `None` if they cannot
+    /// be allocated.
+    pub fn try_repeat(one: &Self, n: u32) -> Option<Self> {
+        macro_rules! go {
+            ($($k:ident),*) => {
+                match one {
+                    $(ArrayData::$k(v) => {
+                        let mut out = Vec::new();
+                        out.try_reserve_exact(n as usize).ok()?;
+                        out.extend(core::iter::repeat(v.first()?.clone()).take(n as usize));
+                        ArrayData::$k(out)
+                    })*
                 }
-            },
+            };
+        }
+        Some(go!(Ref, I8, I16, I32, I64, F32, F64, V128))
+    }
+    /// A copy of `n` elements starting at `off`, or `None` if out of range.
+    pub fn slice(&self, off: u32, n: u32) -> Option<Self> {
+        let r = off as usize..(off as usize).checked_add(n as usize)?;
+        Some(match self {
+            ArrayData::Ref(v) => ArrayData::Ref(v.get(r)?.to_vec()),
+            ArrayData::I8(v) => ArrayData::I8(v.get(r)?.to_vec()),
+            ArrayData::I16(v) => ArrayData::I16(v.get(r)?.to_vec()),
+            ArrayData::I32(v) => ArrayData::I32(v.get(r)?.to_vec()),
+            ArrayData::I64(v) => ArrayData::I64(v.get(r)?.to_vec()),
+            ArrayData::F32(v) => ArrayData::F32(v.get(r)?.to_vec()),
+            ArrayData::F64(v) => ArrayData::F64(v.get(r)?.to_vec()),
+            ArrayData::V128(v) => ArrayData::V128(v.get(r)?.to_vec()),
+        })
+    }
+    /// Overwrite `src.len()` elements starting at `dst`.  Returns `false`
+    /// if the range is out of bounds or the layouts differ.
+    pub fn write(&mut self, dst: u32, src: &Self) -> bool {
+        let r = dst as usize..(dst as usize).saturating_add(src.len());
+        macro_rules! go {
+            ($($k:ident),*) => {
+                match (self, src) {
+                    $((ArrayData::$k(d), ArrayData::$k(s)) => match d.get_mut(r) {
+                        Some(d) => {
+                            d.clone_from_slice(s);
+                            true
+                        }
+                        None => false,
+                    },)*
+                    _ => false,
+                }
+            };
         }
+        go!(Ref, I8, I16, I32, I64, F32, F64, V128)
+    }
+}
+unsafe impl<R: Trace> Trace for ArrayData<R> {
+    fn accept<V: dumpster::Visitor>(&self, visitor: &mut V) -> Result<(), ()> {
+        match self {
+            ArrayData::Ref(v) => v.accept(visitor),
+            _ => Ok(()),
+        }
+    }
+}
+impl<R: Clone + Trace + Send + Sync + 'static> GcCore<R> {
+    /// Field `a` of a struct; [`Trap::CastFailure`] if this is not a
+    /// struct or has no such field.
+    pub fn get_field(&self, a: usize) -> anyhow::Result<R> {
+        self.with_field(a, |f| f.get())
+    }
+    /// As [`GcCore::get_field`].  Writes to immutable fields are dropped.
+    pub fn set_field(&self, a: usize, r: R) -> anyhow::Result<()> {
+        self.with_field(a, |f| f.set(r))
+    }
+    fn with_field<T>(&self, a: usize, go: impl FnOnce(&Field<R>) -> T) -> anyhow::Result<T> {
+        let f = match self {
+            GcCore::Fields(vec) => vec.get(a).map(go),
+            GcCore::Struct(s) => s.fields.read().unwrap().get(a).map(go),
+            _ => None,
+        };
+        f.ok_or_else(|| Trap::CastFailure.into())
     }
 }
-unsafe impl<R: Trace> Trace for GcCore<R> {
+unsafe impl<R: Trace + Send + Sync + 'static> Trace for GcCore<R> {
     fn accept<V: dumpster::Visitor>(&self, visitor: &mut V) -> Result<(), ()> {
         match self {
             GcCore::Fields(vec) => vec.accept(visitor),
+            GcCore::Struct(s) => s.accept(visitor),
+            GcCore::Array(a) => a.accept(visitor),
+            GcCore::I31(_) => Ok(()),
+            GcCore::Extern(r) => r.accept(visitor),
         }
     }
 }
-impl<C: CtxSpec, R: Traverse<C>> Traverse<C> for GcCore<R> {
+impl<C: CtxSpec, R: Traverse<C> + Trace + Send + Sync + 'static> Traverse<C> for GcCore<R> {
     fn traverse<'a>(&'a self) -> Box<dyn Iterator<Item = &'a C::ExternRef> + 'a> {
         return match self {
             GcCore::Fields(vec) => Box::new(vec.iter().flat_map(|a| a.traverse())),
+            // Behind a lock; see `visit`.
+            GcCore::Struct(_) | GcCore::Array(_) => Box::new(empty()),
+            GcCore::I31(_) => Box::new(empty()),
+            GcCore::Extern(r) => r.traverse(),
         };
     }
     fn traverse_mut<'a>(&'a mut self) -> Box<dyn Iterator<Item = &'a mut C::ExternRef> + 'a> {
         return match self {
             GcCore::Fields(vec) => Box::new(vec.iter_mut().flat_map(|a| a.traverse_mut())),
+            GcCore::Extern(r) => r.traverse_mut(),
+            // Shared objects are only reachable through `&`; see `visit_mut`.
+            _ => Box::new(empty()),
         };
     }
+    fn visit(&self, v: &mut dyn Visitor<C>) {
+        match self {
+            GcCore::Fields(vec) => vec.visit(v),
+            GcCore::Struct(s) => {
+                if v.enter(gc_addr(s)) {
+                    s.fields.read().unwrap().visit(v)
+                }
+            }
+            GcCore::Array(a) => {
+                if v.enter(gc_addr(a)) {
+                    if let ArrayData::Ref(xs) = &*a.elems.lock().unwrap() {
+                        xs.visit(v)
+                    }
+                }
+            }
+            GcCore::I31(_) => {}
+            GcCore::Extern(r) => r.visit(v),
+        }
+    }
+    fn visit_mut(&mut self, v: &mut dyn VisitorMut<C>) {
+        match self {
+            GcCore::Fields(vec) => vec.visit_mut(v),
+            GcCore::Struct(s) => {
+                if v.enter(gc_addr(s)) {
+                    s.fields.write().unwrap().visit_mut(v)
+                }
+            }
+            GcCore::Array(a) => {
+                if v.enter(gc_addr(a)) {
+                    if let ArrayData::Ref(xs) = &mut *a.elems.lock().unwrap() {
+                        xs.visit_mut(v)
+                    }
+                }
+            }
+            GcCore::I31(_) => {}
+            GcCore::Extern(r) => r.visit_mut(v),
+        }
+    }
+}
+/// Element layout of an array.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Storage {
+    Ref,
+    I8,
+    I16,
+    I32,
+    I64,
+    F32,
+    F64,
+    V128,
+}
+impl Storage {
+    /// Size in bytes of one element in a data segment; 0 for `Ref`.
+    pub fn size(self) -> usize {
+        match self {
+            Storage::Ref => 0,
+            Storage::I8 => 1,
+            Storage::I16 => 2,
+            Storage::I32 | Storage::F32 => 4,
+            Storage::I64 | Storage::F64 => 8,
+            Storage::V128 => 16,
+        }
+    }
+}
+/// The heap type a `ref.test`/`ref.cast` checks against.
+#[derive(Clone, Copy, Debug)]
+pub enum HeapTest<'a> {
+    Any,
+    Eq,
+    I31,
+    Struct,
+    Array,
+    Func,
+    Extern,
+    Exn,
+    /// `none`, `nofunc`, `noextern`, `noexn`: only null passes.
+    Bottom,
+    /// A concrete struct or array type.
+    Type(&'a Rtt),
 }
 #[derive(Clone)]
 #[non_exhaustive]
@@ -61,6 +326,29 @@ pub enum Field<R> {
     Const(R),
     Mut(Arc<Mutex<R>>),
 }
+impl<R: Clone> Field<R> {
+    pub fn new(mutable: bool, r: R) -> Self {
+        if mutable {
+            Field::Mut(Arc::new(Mutex::new(r)))
+        } else {
+            Field::Const(r)
+        }
+    }
+    pub fn get(&self) -> R {
+        match self {
+            Field::Const(r) => r.clone(),
+            Field::Mut(arc) => arc.lock().unwrap().clone(),
+        }
+    }
+    pub fn set(&self, r: R) {
+        match self {
+            Field::Const(_) => {}
+            Field::Mut(arc) => {
+                *arc.lock().unwrap() = r;
+            }
+        }
+    }
+}
 unsafe impl<R: Trace> Trace for Field<R> {
     fn accept<V: dumpster::Visitor>(&self, visitor: &mut V) -> Result<(), ()> {
         match self {
@@ -73,7 +361,8 @@ impl<C: CtxSpec, R: Traverse<C>> Traverse<C> for Field<R> {
     fn traverse<'a>(&'a self) -> Box<dyn Iterator<Item = &'a <C as CtxSpec>::ExternRef> + 'a> {
         match self {
             Field::Const(a) => a.traverse(),
-            Field::Mut(arc) => Box::new(empty()), //TODO: fix
+            // A borrow cannot outlive the lock guard; `visit` locks instead.
+            Field::Mut(_) => Box::new(empty()),
         }
     }
     fn traverse_mut<'a>(
@@ -81,7 +370,31 @@ impl<C: CtxSpec, R: Traverse<C>> Traverse<C> for Field<R> {
     ) -> Box<dyn Iterator<Item = &'a mut <C as CtxSpec>::ExternRef> + 'a> {
         match self {
             Field::Const(a) => a.traverse_mut(),
-            Field::Mut(arc) => Box::new(empty()), //TODO: fix
+            // Unshared, so the lock can be bypassed; otherwise see `visit_mut`.
+            Field::Mut(arc) => match Arc::get_mut(arc) {
+                Some(m) => m.get_mut().unwrap().traverse_mut(),
+                None => Box::new(empty()),
+            },
+        }
+    }
+    fn visit(&self, v: &mut dyn Visitor<C>) {
+        match self {
+            Field::Const(a) => a.visit(v),
+            Field::Mut(arc) => {
+                if v.enter(crate::addr(arc)) {
+                    arc.lock().unwrap().visit(v)
+                }
+            }
+        }
+    }
+    fn visit_mut(&mut self, v: &mut dyn VisitorMut<C>) {
+        match self {
+            Field::Const(a) => a.visit_mut(v),
+            Field::Mut(arc) => {
+                if v.enter(crate::addr(arc)) {
+                    arc.lock().unwrap().visit_mut(v)
+                }
+            }
         }
     }
 }
@@ -2,10 +2,32 @@
 extern crate alloc;
 pub use core::convert::Infallible;
 pub use either::Either;
+pub mod atomic;
+pub mod depth;
+pub mod epoch;
+pub mod exception;
+pub mod fuel;
 pub mod func;
+pub mod linker;
+pub mod meta;
+pub mod simd;
+pub mod trap;
 pub mod wasix;
+pub use atomic::*;
+pub use exception::{Exception, Tag};
+pub use depth::CallDepth;
+pub use epoch::Epoch;
+pub use fuel::Fuel;
+pub use linker::Linker;
+pub use meta::MetaType;
+pub use simd::*;
+pub use trap::Trap;
 #[cfg(feature = "dumpster")]
 pub mod gc;
+#[cfg(feature = "macro")]
+pub use wars_macro::wasm;
+#[cfg(feature = "wrl")]
+pub mod wrl;
 use alloc::{boxed::Box, sync::Arc, vec::Vec};
 use core::iter::empty;
 #[cfg(feature = "std")]
@@ -24,12 +46,82 @@ pub enum Pit<X, H> {
 // use as_ref::AsSlice;
 // use func::CtxSpec;
 pub use func::Value;
+#[cfg(not(feature = "dumpster"))]
 pub trait CtxSpec: Sized {
     type ExternRef: Clone;
 }
+/// With `dumpster`, GC objects are `dumpster::sync::Gc`s and externrefs can
+/// be stored in them, so both must be traceable, thread-safe and `'static`.
+#[cfg(feature = "dumpster")]
+pub trait CtxSpec: Sized + 'static {
+    type ExternRef: Clone + dumpster::Trace + Send + Sync + 'static;
+}
 pub trait Traverse<C: CtxSpec> {
     fn traverse<'a>(&'a self) -> Box<dyn Iterator<Item = &'a C::ExternRef> + 'a>;
     fn traverse_mut<'a>(&'a mut self) -> Box<dyn Iterator<Item = &'a mut C::ExternRef> + 'a>;
+    /// Walk every externref without allocating.  Unlike `traverse`, this
+    /// also reaches values behind the locks of mutable GC fields and arrays.
+    fn visit(&self, v: &mut dyn Visitor<C>) {
+        for r in self.traverse() {
+            v.visit(r);
+        }
+    }
+    /// Like [`Traverse::visit`], but the visitor may rewrite each externref.
+    fn visit_mut(&mut self, v: &mut dyn VisitorMut<C>) {
+        for r in self.traverse_mut() {
+            v.visit_mut(r);
+        }
+    }
+}
+/// Receives the externrefs found by [`Traverse::visit`].
+pub trait Visitor<C: CtxSpec> {
+    fn visit(&mut self, r: &C::ExternRef);
+    /// Called before walking into the shared object at `addr`.  Returning
+    /// `false` skips it; this is what ends a walk over a cyclic GC graph.
+    fn enter(&mut self, addr: usize) -> bool;
+}
+/// Receives the externrefs found by [`Traverse::visit_mut`].
+pub trait VisitorMut<C: CtxSpec> {
+    fn visit_mut(&mut self, r: &mut C::ExternRef);
+    /// As [`Visitor::enter`].
+    fn enter(&mut self, addr: usize) -> bool;
+}
+struct Walk<F> {
+    f: F,
+    seen: alloc::collections::BTreeSet<usize>,
+}
+impl<C: CtxSpec, F: FnMut(&C::ExternRef)> Visitor<C> for Walk<F> {
+    fn visit(&mut self, r: &C::ExternRef) {
+        (self.f)(r)
+    }
+    fn enter(&mut self, addr: usize) -> bool {
+        self.seen.insert(addr)
+    }
+}
+impl<C: CtxSpec, F: FnMut(&mut C::ExternRef)> VisitorMut<C> for Walk<F> {
+    fn visit_mut(&mut self, r: &mut C::ExternRef) {
+        (self.f)(r)
+    }
+    fn enter(&mut self, addr: usize) -> bool {
+        self.seen.insert(addr)
+    }
+}
+/// Call `f` on every externref reachable from `t`, entering each shared
+/// GC object once.
+pub fn for_each_extern<C: CtxSpec, T: Traverse<C> + ?Sized>(t: &T, f: impl FnMut(&C::ExternRef)) {
+    t.visit(&mut Walk { f, seen: Default::default() })
+}
+/// Call `f` on every externref reachable from `t` and let it rewrite them,
+/// e.g. to compact a handle table.
+pub fn for_each_extern_mut<C: CtxSpec, T: Traverse<C> + ?Sized>(
+    t: &mut T,
+    f: impl FnMut(&mut C::ExternRef),
+) {
+    t.visit_mut(&mut Walk { f, seen: Default::default() })
+}
+/// Address of a shared object, for [`Visitor::enter`].
+pub(crate) fn addr<T: ?Sized>(a: &Arc<T>) -> usize {
+    Arc::as_ptr(a) as *const () as usize
 }
 impl<C: CtxSpec, V: Traverse<C>> Traverse<C> for Vec<V> {
     fn traverse<'a>(&'a self) -> Box<dyn Iterator<Item = &'a <C as CtxSpec>::ExternRef> + 'a> {
@@ -40,6 +132,16 @@ impl<C: CtxSpec, V: Traverse<C>> Traverse<C> for Vec<V> {
     ) -> Box<dyn Iterator<Item = &'a mut <C as CtxSpec>::ExternRef> + 'a> {
         Box::new(self.iter_mut().flat_map(|x| x.traverse_mut()))
     }
+    fn visit(&self, v: &mut dyn Visitor<C>) {
+        for x in self.iter() {
+            x.visit(v);
+        }
+    }
+    fn visit_mut(&mut self, v: &mut dyn VisitorMut<C>) {
+        for x in self.iter_mut() {
+            x.visit_mut(v);
+        }
+    }
 }
 impl<C: CtxSpec> Traverse<C> for u32 {
     fn traverse<'a>(&'a self) -> Box<dyn Iterator<Item = &'a <C as CtxSpec>::ExternRef> + 'a> {
@@ -50,6 +152,8 @@ impl<C: CtxSpec> Traverse<C> for u32 {
     ) -> Box<dyn Iterator<Item = &'a mut <C as CtxSpec>::ExternRef> + 'a> {
         Box::new(empty())
     }
+    fn visit(&self, _: &mut dyn Visitor<C>) {}
+    fn visit_mut(&mut self, _: &mut dyn VisitorMut<C>) {}
 }
 impl<C: CtxSpec> Traverse<C> for u64 {
     fn traverse<'a>(&'a self) -> Box<dyn Iterator<Item = &'a <C as CtxSpec>::ExternRef> + 'a> {
@@ -60,13 +164,31 @@ impl<C: CtxSpec> Traverse<C> for u64 {
     ) -> Box<dyn Iterator<Item = &'a mut <C as CtxSpec>::ExternRef> + 'a> {
         Box::new(empty())
     }
+    fn visit(&self, _: &mut dyn Visitor<C>) {}
+    fn visit_mut(&mut self, _: &mut dyn VisitorMut<C>) {}
 }
 pub trait Memory {
     fn read<'a>(&'a self, a: u64, s: u64) -> anyhow::Result<Box<dyn AsRef<[u8]> + 'a>>;
     fn write(&mut self, a: u64, x: &[u8]) -> anyhow::Result<()>;
     fn size(&self) -> anyhow::Result<u64>;
+    /// Grow by `x` bytes.  Fail with [`GrowRefused`] when the memory simply
+    /// cannot grow that far; `memory.grow` then returns `-1`.  Any other
+    /// error is fatal.
     fn grow(&mut self, x: u64) -> anyhow::Result<()>;
+    /// The most bytes this memory may hold, if the host caps it.
+    fn max_size(&self) -> Option<u64> {
+        None
+    }
 }
+/// A [`Memory::grow`] that was refused rather than failed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct GrowRefused;
+impl core::fmt::Display for GrowRefused {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str("memory cannot grow")
+    }
+}
+impl core::error::Error for GrowRefused {}
 #[cfg(feature = "ic-stable-structures")]
 pub mod ic {
     use alloc::{boxed::Box, vec};
@@ -88,7 +210,7 @@ pub mod ic {
         }
         fn grow(&mut self, x: u64) -> anyhow::Result<()> {
             if self.0.grow((x + 65535) / 65536) == -1 {
-                anyhow::bail!("stable growth failed")
+                return Err(super::GrowRefused.into());
             }
             Ok(())
         }
@@ -96,17 +218,38 @@ pub mod ic {
 }
 impl Memory for Vec<u8> {
     fn read<'a>(&'a self, a: u64, s: u64) -> anyhow::Result<Box<dyn AsRef<[u8]> + 'a>> {
-        Ok(Box::new(&self[(a as usize)..][..(s as usize)]))
+        let r = usize::try_from(a)
+            .ok()
+            .zip(usize::try_from(s).ok())
+            .and_then(|(a, s)| self.get(a..a.checked_add(s)?));
+        match r {
+            Some(r) => Ok(Box::new(r)),
+            None => Err(Trap::OutOfBoundsMemory.into()),
+        }
     }
     fn write(&mut self, a: u64, x: &[u8]) -> anyhow::Result<()> {
-        self[(a as usize)..][..x.len()].copy_from_slice(x);
-        Ok(())
+        let r = usize::try_from(a)
+            .ok()
+            .and_then(|a| self.get_mut(a..a.checked_add(x.len())?));
+        match r {
+            Some(r) => {
+                r.copy_from_slice(x);
+                Ok(())
+            }
+            None => Err(Trap::OutOfBoundsMemory.into()),
+        }
     }
     fn size(&self) -> anyhow::Result<u64> {
         Ok(self.len() as u64)
     }
     fn grow(&mut self, x: u64) -> anyhow::Result<()> {
-        self.extend((0..x).map(|a| 0u8));
+        let Ok(x) = usize::try_from(x) else {
+            return Err(GrowRefused.into());
+        };
+        if self.try_reserve_exact(x).is_err() {
+            return Err(GrowRefused.into());
+        }
+        self.resize(self.len() + x, 0);
         Ok(())
     }
 }
@@ -123,9 +266,12 @@ impl<T: Memory + ?Sized> Memory for Box<T> {
     fn grow(&mut self, x: u64) -> anyhow::Result<()> {
         self.as_mut().grow(x)
     }
+    fn max_size(&self) -> Option<u64> {
+        self.as_ref().max_size()
+    }
 }
 #[cfg(feature = "std")]
-impl<T: Memory> Memory for Arc<std::sync::Mutex<T>> {
+impl<T: Memory + ?Sized> Memory for Arc<std::sync::Mutex<T>> {
     fn read<'a>(&'a self, a: u64, s: u64) -> anyhow::Result<Box<dyn AsRef<[u8]> + 'a>> {
         let l = self.lock().unwrap();
         let r = l.read(a, s)?;
@@ -143,9 +289,12 @@ impl<T: Memory> Memory for Arc<std::sync::Mutex<T>> {
         let mut l = self.lock().unwrap();
         return l.grow(x);
     }
+    fn max_size(&self) -> Option<u64> {
+        self.lock().unwrap().max_size()
+    }
 }
 #[cfg(not(feature = "std"))]
-impl<T: Memory> Memory for Arc<spin::Mutex<T>> {
+impl<T: Memory + ?Sized> Memory for Arc<spin::Mutex<T>> {
     fn read<'a>(&'a self, a: u64, s: u64) -> anyhow::Result<Box<dyn AsRef<[u8]> + 'a>> {
         let l = self.lock();
         let r = l.read(a, s)?;
@@ -163,6 +312,9 @@ impl<T: Memory> Memory for Arc<spin::Mutex<T>> {
         let mut l = self.lock();
         return l.grow(x);
     }
+    fn max_size(&self) -> Option<u64> {
+        self.lock().max_size()
+    }
 }
 // pub unsafe fn host_memory() -> impl Memory {
 //     struct W {}
@@ -191,6 +343,8 @@ pub mod _rexport {
     pub use tramp;
     pub use tuple_list;
     pub extern crate alloc;
+    #[cfg(feature = "dumpster")]
+    pub use dumpster;
 }
 macro_rules! int_ty{
     ($int:ty => $p:ident) => {
@@ -211,25 +365,41 @@ macro_rules! int_ty{
                 Ok(tuple_list::tuple_list!(a ^ b))
             }
             pub fn [<$p shl>](a: $int, b: $int) -> anyhow::Result<tuple_list::tuple_list_type!($int)> {
-                Ok(tuple_list::tuple_list!(a << b))
+                Ok(tuple_list::tuple_list!(a.wrapping_shl(b as u32)))
             }
             pub fn [<$p shru>](a: $int, b: $int) -> anyhow::Result<tuple_list::tuple_list_type!($int)> {
-                Ok(tuple_list::tuple_list!(a >> b))
+                Ok(tuple_list::tuple_list!(a.wrapping_shr(b as u32)))
             }
             pub fn [<$p shrs>](a: $int, b: $int) -> anyhow::Result<tuple_list::tuple_list_type!($int)> {
-                Ok(tuple_list::tuple_list!(((a as $p) >> b) as $int))
+                Ok(tuple_list::tuple_list!((a as $p).wrapping_shr(b as u32) as $int))
             }
             pub fn [<$p divu>](a: $int, b: $int) -> anyhow::Result<tuple_list::tuple_list_type!($int)> {
+                if b == 0 {
+                    return Err(Trap::IntegerDivideByZero.into());
+                }
                 Ok(tuple_list::tuple_list!(a / b))
             }
             pub fn [<$p divs>](a: $int, b: $int) -> anyhow::Result<tuple_list::tuple_list_type!($int)> {
-                Ok(tuple_list::tuple_list!(((a as $p) / (b as $p)) as $int))
+                if b == 0 {
+                    return Err(Trap::IntegerDivideByZero.into());
+                }
+                match (a as $p).checked_div(b as $p) {
+                    Some(c) => Ok(tuple_list::tuple_list!(c as $int)),
+                    None => Err(Trap::IntegerOverflow.into()),
+                }
             }
             pub fn [<$p remu>](a: $int, b: $int) -> anyhow::Result<tuple_list::tuple_list_type!($int)> {
+                if b == 0 {
+                    return Err(Trap::IntegerDivideByZero.into());
+                }
                 Ok(tuple_list::tuple_list!(a % b))
             }
             pub fn [<$p rems>](a: $int, b: $int) -> anyhow::Result<tuple_list::tuple_list_type!($int)> {
-                Ok(tuple_list::tuple_list!(((a as $p) % (b as $p)) as $int))
+                if b == 0 {
+                    return Err(Trap::IntegerDivideByZero.into());
+                }
+                // `MIN % -1` is 0 in wasm, not an overflow.
+                Ok(tuple_list::tuple_list!((a as $p).wrapping_rem(b as $p) as $int))
             }
             pub fn [<$p rotl>](a: $int, b: $int) -> anyhow::Result<tuple_list::tuple_list_type!($int)> {
                 Ok(tuple_list::tuple_list!(a.rotate_left((b & 0xffffffff) as u32)))
@@ -405,8 +575,264 @@ pub fn i64extendi32u(a: u32) -> anyhow::Result<tuple_list::tuple_list_type!(u64)
 pub fn i64extendi32s(a: u32) -> anyhow::Result<tuple_list::tuple_list_type!(u64)> {
     Ok(tuple_list::tuple_list!(a as i32 as i64 as u64))
 }
-pub fn i64truncf64s(a: f64) -> anyhow::Result<tuple_list::tuple_list_type!(u64)> {
-    Ok(tuple_list::tuple_list!(
-        unsafe { a.trunc().to_int_unchecked::<i64>() } as u64
-    ))
+// The bounds are the nearest representable floats just outside the target
+// range, so any input strictly between them truncates to a valid integer.
+macro_rules! trunc_ty {
+    ($name:ident, $f:ty => $i:ty as $r:ty, $lo:expr, $hi:expr) => {
+        /// Truncate towards zero, trapping on NaN and on results that do not
+        /// fit in the target integer.
+        pub fn $name(a: $f) -> anyhow::Result<tuple_list::tuple_list_type!($r)> {
+            if a.is_nan() {
+                return Err(Trap::InvalidConversionToInteger.into());
+            }
+            if !(a > $lo && a < $hi) {
+                return Err(Trap::IntegerOverflow.into());
+            }
+            Ok(tuple_list::tuple_list!(a as $i as $r))
+        }
+    };
+}
+trunc_ty!(i32truncf32s, f32 => i32 as u32, -2147483904.0, 2147483648.0);
+trunc_ty!(i32truncf32u, f32 => u32 as u32, -1.0, 4294967296.0);
+trunc_ty!(i32truncf64s, f64 => i32 as u32, -2147483649.0, 2147483648.0);
+trunc_ty!(i32truncf64u, f64 => u32 as u32, -1.0, 4294967296.0);
+trunc_ty!(i64truncf32s, f32 => i64 as u64, -9223373136366403584.0, 9223372036854775808.0);
+trunc_ty!(i64truncf32u, f32 => u64 as u64, -1.0, 18446744073709551616.0);
+trunc_ty!(i64truncf64s, f64 => i64 as u64, -9223372036854777856.0, 9223372036854775808.0);
+trunc_ty!(i64truncf64u, f64 => u64 as u64, -1.0, 18446744073709551616.0);
+// Floats.  Rounding goes through `libm` so that this works without `std`;
+// everything else is done on the bit patterns where wasm and Rust disagree.
+macro_rules! float_ty {
+    ($f:ident, $i:ident, $u:ty, $sign:expr, $ceil:ident, $floor:ident, $trunc:ident, $rint:ident, $sqrt:ident) => {
+        paste::paste! {
+            pub fn [<$f add>](a: $f, b: $f) -> anyhow::Result<tuple_list::tuple_list_type!($f)> {
+                Ok(tuple_list::tuple_list!(a + b))
+            }
+            pub fn [<$f sub>](a: $f, b: $f) -> anyhow::Result<tuple_list::tuple_list_type!($f)> {
+                Ok(tuple_list::tuple_list!(a - b))
+            }
+            pub fn [<$f mul>](a: $f, b: $f) -> anyhow::Result<tuple_list::tuple_list_type!($f)> {
+                Ok(tuple_list::tuple_list!(a * b))
+            }
+            pub fn [<$f div>](a: $f, b: $f) -> anyhow::Result<tuple_list::tuple_list_type!($f)> {
+                Ok(tuple_list::tuple_list!(a / b))
+            }
+            /// NaN if either input is NaN, and `-0 < +0`, unlike `f32::min`.
+            pub fn [<$f min>](a: $f, b: $f) -> anyhow::Result<tuple_list::tuple_list_type!($f)> {
+                Ok(tuple_list::tuple_list!(if a.is_nan() || b.is_nan() {
+                    a + b
+                } else if a == b {
+                    $f::from_bits(a.to_bits() | b.to_bits())
+                } else if a < b {
+                    a
+                } else {
+                    b
+                }))
+            }
+            /// NaN if either input is NaN, and `+0 > -0`, unlike `f32::max`.
+            pub fn [<$f max>](a: $f, b: $f) -> anyhow::Result<tuple_list::tuple_list_type!($f)> {
+                Ok(tuple_list::tuple_list!(if a.is_nan() || b.is_nan() {
+                    a + b
+                } else if a == b {
+                    $f::from_bits(a.to_bits() & b.to_bits())
+                } else if a > b {
+                    a
+                } else {
+                    b
+                }))
+            }
+            pub fn [<$f copysign>](a: $f, b: $f) -> anyhow::Result<tuple_list::tuple_list_type!($f)> {
+                Ok(tuple_list::tuple_list!($f::from_bits(
+                    (a.to_bits() & !$sign) | (b.to_bits() & $sign)
+                )))
+            }
+            pub fn [<$f abs>](a: $f) -> anyhow::Result<tuple_list::tuple_list_type!($f)> {
+                Ok(tuple_list::tuple_list!($f::from_bits(a.to_bits() & !$sign)))
+            }
+            pub fn [<$f neg>](a: $f) -> anyhow::Result<tuple_list::tuple_list_type!($f)> {
+                Ok(tuple_list::tuple_list!($f::from_bits(a.to_bits() ^ $sign)))
+            }
+            pub fn [<$f ceil>](a: $f) -> anyhow::Result<tuple_list::tuple_list_type!($f)> {
+                Ok(tuple_list::tuple_list!(libm::$ceil(a)))
+            }
+            pub fn [<$f floor>](a: $f) -> anyhow::Result<tuple_list::tuple_list_type!($f)> {
+                Ok(tuple_list::tuple_list!(libm::$floor(a)))
+            }
+            pub fn [<$f trunc>](a: $f) -> anyhow::Result<tuple_list::tuple_list_type!($f)> {
+                Ok(tuple_list::tuple_list!(libm::$trunc(a)))
+            }
+            /// Round to nearest, ties to even.
+            pub fn [<$f nearest>](a: $f) -> anyhow::Result<tuple_list::tuple_list_type!($f)> {
+                Ok(tuple_list::tuple_list!(libm::$rint(a)))
+            }
+            pub fn [<$f sqrt>](a: $f) -> anyhow::Result<tuple_list::tuple_list_type!($f)> {
+                Ok(tuple_list::tuple_list!(libm::$sqrt(a)))
+            }
+            //comparisons
+            pub fn [<$f eq>](a: $f, b: $f) -> anyhow::Result<tuple_list::tuple_list_type!(u32)> {
+                Ok(tuple_list::tuple_list!((a == b) as u32))
+            }
+            pub fn [<$f ne>](a: $f, b: $f) -> anyhow::Result<tuple_list::tuple_list_type!(u32)> {
+                Ok(tuple_list::tuple_list!((a != b) as u32))
+            }
+            pub fn [<$f lt>](a: $f, b: $f) -> anyhow::Result<tuple_list::tuple_list_type!(u32)> {
+                Ok(tuple_list::tuple_list!((a < b) as u32))
+            }
+            pub fn [<$f gt>](a: $f, b: $f) -> anyhow::Result<tuple_list::tuple_list_type!(u32)> {
+                Ok(tuple_list::tuple_list!((a > b) as u32))
+            }
+            pub fn [<$f le>](a: $f, b: $f) -> anyhow::Result<tuple_list::tuple_list_type!(u32)> {
+                Ok(tuple_list::tuple_list!((a <= b) as u32))
+            }
+            pub fn [<$f ge>](a: $f, b: $f) -> anyhow::Result<tuple_list::tuple_list_type!(u32)> {
+                Ok(tuple_list::tuple_list!((a >= b) as u32))
+            }
+            //conversions; `as` rounds to nearest, ties to even
+            pub fn [<$f converti32s>](a: u32) -> anyhow::Result<tuple_list::tuple_list_type!($f)> {
+                Ok(tuple_list::tuple_list!(a as i32 as $f))
+            }
+            pub fn [<$f converti32u>](a: u32) -> anyhow::Result<tuple_list::tuple_list_type!($f)> {
+                Ok(tuple_list::tuple_list!(a as $f))
+            }
+            pub fn [<$f converti64s>](a: u64) -> anyhow::Result<tuple_list::tuple_list_type!($f)> {
+                Ok(tuple_list::tuple_list!(a as i64 as $f))
+            }
+            pub fn [<$f converti64u>](a: u64) -> anyhow::Result<tuple_list::tuple_list_type!($f)> {
+                Ok(tuple_list::tuple_list!(a as $f))
+            }
+            pub fn [<$f reinterpret $i>](a: $u) -> anyhow::Result<tuple_list::tuple_list_type!($f)> {
+                Ok(tuple_list::tuple_list!($f::from_bits(a)))
+            }
+            pub fn [<$i reinterpret $f>](a: $f) -> anyhow::Result<tuple_list::tuple_list_type!($u)> {
+                Ok(tuple_list::tuple_list!(a.to_bits()))
+            }
+            //saturating truncation; `as` already saturates and maps NaN to 0
+            pub fn [<i32truncsat $f s>](a: $f) -> anyhow::Result<tuple_list::tuple_list_type!(u32)> {
+                Ok(tuple_list::tuple_list!(a as i32 as u32))
+            }
+            pub fn [<i32truncsat $f u>](a: $f) -> anyhow::Result<tuple_list::tuple_list_type!(u32)> {
+                Ok(tuple_list::tuple_list!(a as u32))
+            }
+            pub fn [<i64truncsat $f s>](a: $f) -> anyhow::Result<tuple_list::tuple_list_type!(u64)> {
+                Ok(tuple_list::tuple_list!(a as i64 as u64))
+            }
+            pub fn [<i64truncsat $f u>](a: $f) -> anyhow::Result<tuple_list::tuple_list_type!(u64)> {
+                Ok(tuple_list::tuple_list!(a as u64))
+            }
+            //LOADS and STORES
+            pub fn [<$f load>]<T: TryInto<u64>,M: Memory + ?Sized>(a: &mut M, b: T) -> anyhow::Result<tuple_list::tuple_list_type!($f)> where T::Error: Err + Send + Sync + 'static{
+                let r = a.read(b.try_into().map_err(Into::into)?,core::mem::size_of::<$f>().try_into().unwrap())?;
+                Ok(tuple_list::tuple_list!($f::from_ne_bytes(r.as_ref().as_ref().try_into().unwrap())))
+            }
+            pub fn [<$f store>]<T: TryInto<u64>,M: Memory + ?Sized>(a: &mut M, b: T, c: $f) -> anyhow::Result<()> where T::Error: Err + Send + Sync + 'static{
+                a.write(b.try_into().map_err(Into::into)?,&c.to_ne_bytes())?;
+                Ok(())
+            }
+        }
+    };
+}
+float_ty!(f32, i32, u32, 0x8000_0000u32, ceilf, floorf, truncf, rintf, sqrtf);
+float_ty!(f64, i64, u64, 0x8000_0000_0000_0000u64, ceil, floor, trunc, rint, sqrt);
+pub fn f32demotef64(a: f64) -> anyhow::Result<tuple_list::tuple_list_type!(f32)> {
+    Ok(tuple_list::tuple_list!(a as f32))
+}
+pub fn f64promotef32(a: f32) -> anyhow::Result<tuple_list::tuple_list_type!(f64)> {
+    Ok(tuple_list::tuple_list!(a as f64))
+}
+/// Bounds-checked `table.get`.
+pub fn table_get<V: Clone>(table: &[V], i: u64) -> anyhow::Result<V> {
+    match usize::try_from(i).ok().and_then(|i| table.get(i)) {
+        Some(v) => Ok(v.clone()),
+        None => Err(Trap::OutOfBoundsTable.into()),
+    }
+}
+/// Bounds-checked `table.set`.
+pub fn table_set<V>(table: &mut [V], i: u64, v: V) -> anyhow::Result<()> {
+    match usize::try_from(i).ok().and_then(|i| table.get_mut(i)) {
+        Some(slot) => {
+            *slot = v;
+            Ok(())
+        }
+        None => Err(Trap::OutOfBoundsTable.into()),
+    }
+}
+/// `table.fill`: the whole range is checked before anything is written.
+pub fn table_fill<V: Clone>(table: &mut [V], i: u64, v: V, n: u64) -> anyhow::Result<()> {
+    let Some(end) = i.checked_add(n).filter(|e| *e <= table.len() as u64) else {
+        return Err(Trap::OutOfBoundsTable.into());
+    };
+    table[(i as usize)..(end as usize)].fill(v);
+    Ok(())
+}
+/// The `n` entries of `table` starting at `i`, for `table.copy`.
+pub fn table_slice<V>(table: &[V], i: u64, n: u64) -> anyhow::Result<&[V]> {
+    let Some(end) = i.checked_add(n).filter(|e| *e <= table.len() as u64) else {
+        return Err(Trap::OutOfBoundsTable.into());
+    };
+    Ok(&table[(i as usize)..(end as usize)])
+}
+/// `memory.init`: copy `n` bytes of the passive segment `seg`, starting at
+/// `src`, into memory at `dst`.  Both ranges are checked before anything is
+/// written, as the bulk-memory proposal requires.
+pub fn memory_init<M: Memory + ?Sized>(
+    m: &mut M,
+    seg: &[u8],
+    dst: u64,
+    src: u64,
+    n: u64,
+) -> anyhow::Result<()> {
+    let Some(src_end) = src.checked_add(n).filter(|e| *e <= seg.len() as u64) else {
+        return Err(Trap::OutOfBoundsMemory.into())
+    };
+    let size = m.size()?;
+    if dst.checked_add(n).map_or(true, |e| e > size) {
+        return Err(Trap::OutOfBoundsMemory.into())
+    }
+    m.write(dst, &seg[(src as usize)..(src_end as usize)])
+}
+/// `memory.grow` by `delta` pages of `page_size` bytes.  Refuses (`None`,
+/// which wasm sees as `-1`) past `max` pages, past `cap` or
+/// [`Memory::max_size`] bytes, or when the memory reports [`GrowRefused`].
+/// Otherwise returns the old size in pages.
+pub fn memory_grow<M: Memory + ?Sized>(
+    m: &mut M,
+    delta: u64,
+    page_size: u64,
+    max: u64,
+    cap: Option<u64>,
+) -> anyhow::Result<Option<u64>> {
+    let old = m.size()? / page_size;
+    let Some(new) = old.checked_add(delta).filter(|n| *n <= max) else {
+        return Ok(None);
+    };
+    let Some(bytes) = new.checked_mul(page_size) else {
+        return Ok(None);
+    };
+    if cap.into_iter().chain(m.max_size()).any(|c| bytes > c) {
+        return Ok(None);
+    }
+    match m.grow(delta * page_size) {
+        Ok(()) => Ok(Some(old)),
+        Err(e) if e.is::<GrowRefused>() => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+/// `table.init` (and active element segments): copy `n` entries of `seg`,
+/// starting at `src`, into `table` at `dst`, after checking both ranges.
+pub fn table_init<V: Clone>(
+    table: &mut Vec<V>,
+    seg: &[V],
+    dst: u64,
+    src: u64,
+    n: u64,
+) -> anyhow::Result<()> {
+    let Some(src_end) = src.checked_add(n).filter(|e| *e <= seg.len() as u64) else {
+        return Err(Trap::OutOfBoundsTable.into())
+    };
+    let Some(dst_end) = dst.checked_add(n).filter(|e| *e <= table.len() as u64) else {
+        return Err(Trap::OutOfBoundsTable.into())
+    };
+    table[(dst as usize)..(dst_end as usize)]
+        .clone_from_slice(&seg[(src as usize)..(src_end as usize)]);
+    Ok(())
 }
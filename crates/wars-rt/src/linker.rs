@@ -0,0 +1,69 @@
+//! Name-based import resolution for modules translated with
+//! `Flags::DYN_IMPORTS`.
+//!
+//! Instead of one host-trait method per imported function, such a module
+//! asks the context for each import by `(module, name)` once, during
+//! `init()`, and keeps the function reference it gets back.  Signatures are
+//! checked through `CoeVec` when the import is called: a callee that does
+//! not take or return what the import declares traps with
+//! [`Trap::IndirectCallTypeMismatch`](crate::Trap::IndirectCallTypeMismatch).
+use alloc::{
+    collections::BTreeMap,
+    string::{String, ToString},
+};
+
+/// Resolves function imports.  `V` is `func::Value<Self>`, or
+/// `func::unsync::Value<Self>` for `Flags::ASYNC` modules.
+pub trait Linker<V> {
+    fn resolve(&mut self, module: &str, name: &str) -> Option<V>;
+}
+
+/// A ready-made `(module, name)` table for implementing [`Linker`], so one
+/// host type can serve modules with overlapping import sets.
+#[derive(Clone)]
+pub struct LinkTable<V> {
+    entries: BTreeMap<(String, String), V>,
+}
+impl<V> Default for LinkTable<V> {
+    fn default() -> Self {
+        Self {
+            entries: BTreeMap::new(),
+        }
+    }
+}
+impl<V: Clone> LinkTable<V> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Provide `module.name`, replacing any earlier definition.
+    pub fn define(&mut self, module: &str, name: &str, v: V) -> &mut Self {
+        self.entries.insert((module.to_string(), name.to_string()), v);
+        self
+    }
+    pub fn resolve(&self, module: &str, name: &str) -> Option<V> {
+        self.entries
+            .get(&(module.to_string(), name.to_string()))
+            .cloned()
+    }
+}
+
+/// An import the [`Linker`] did not provide, or one called before `init()`.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Unresolved {
+    pub module: String,
+    pub name: String,
+}
+impl Unresolved {
+    pub fn new(module: &str, name: &str) -> Self {
+        Self {
+            module: module.to_string(),
+            name: name.to_string(),
+        }
+    }
+}
+impl core::fmt::Display for Unresolved {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "unresolved import {}.{}", self.module, self.name)
+    }
+}
+impl core::error::Error for Unresolved {}
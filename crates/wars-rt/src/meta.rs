@@ -0,0 +1,20 @@
+//! Runtime descriptions of wasm value types, as listed in the export tables
+//! of generated code.
+use alloc::vec::Vec;
+/// A value type on a module's boundary.  `FunRef` carries the signature of
+/// a typed function reference; `Other` stands for everything with no finer
+/// description (v128, untyped function references, GC and exception
+/// references).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum MetaType {
+    I32,
+    I64,
+    F32,
+    F64,
+    ExternRef,
+    FunRef {
+        params: Vec<MetaType>,
+        returns: Vec<MetaType>,
+    },
+    Other,
+}
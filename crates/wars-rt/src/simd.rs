@@ -0,0 +1,600 @@
+//! SIMD (`v128`) operators, fixed-width and relaxed.
+//!
+//! A `v128` is a `u128` whose little-endian bytes are the wasm lanes, lane 0
+//! in the low bits.  Everything is written lane-wise and portable; integer
+//! add/sub use `core::arch` when the target has SSE2 or NEON enabled.
+//! Function names follow the operators, like the scalar helpers: `i8x16add`,
+//! `i16x8extmullowi8x16s`, `v128load32zero`.
+use crate::{Err, Memory};
+
+type V = anyhow::Result<tuple_list::tuple_list_type!(u128)>;
+
+/// A scalar that can be a `v128` lane.
+pub trait Lane: Copy {
+    const BYTES: usize;
+    fn read(b: &[u8]) -> Self;
+    fn write(self, b: &mut [u8]);
+}
+macro_rules! lane_impl {
+    ($($t:ty),*) => {
+        $(impl Lane for $t {
+            const BYTES: usize = core::mem::size_of::<$t>();
+            fn read(b: &[u8]) -> Self {
+                <$t>::from_le_bytes(b.try_into().unwrap())
+            }
+            fn write(self, b: &mut [u8]) {
+                b.copy_from_slice(&self.to_le_bytes())
+            }
+        })*
+    };
+}
+lane_impl!(i8, u8, i16, u16, i32, u32, i64, u64, f32, f64);
+
+/// Split a `v128` into its first `N` lanes.
+pub fn lanes<T: Lane, const N: usize>(a: u128) -> [T; N] {
+    let b = a.to_le_bytes();
+    core::array::from_fn(|i| T::read(&b[i * T::BYTES..][..T::BYTES]))
+}
+/// Build a `v128` from lanes; bytes past the last lane are zero.
+pub fn pack<T: Lane, const N: usize>(l: [T; N]) -> u128 {
+    let mut b = [0u8; 16];
+    for (i, x) in l.into_iter().enumerate() {
+        x.write(&mut b[i * T::BYTES..][..T::BYTES]);
+    }
+    u128::from_le_bytes(b)
+}
+fn map<T: Lane, U: Lane, const N: usize>(a: u128, f: impl Fn(T) -> U) -> u128 {
+    pack::<U, N>(lanes::<T, N>(a).map(f))
+}
+fn zip<T: Lane, U: Lane, const N: usize>(a: u128, b: u128, f: impl Fn(T, T) -> U) -> u128 {
+    let a = lanes::<T, N>(a);
+    let b = lanes::<T, N>(b);
+    pack::<U, N>(core::array::from_fn(|i| f(a[i], b[i])))
+}
+
+#[cfg(any(
+    all(target_arch = "x86_64", target_feature = "sse2"),
+    all(target_arch = "aarch64", target_feature = "neon")
+))]
+mod arch {
+    use core::mem::transmute;
+    macro_rules! arch_ops {
+        ($($name:ident => $x86:ident, $neon:ident, $neon_ty:ident;)*) => {
+            $(pub fn $name(a: u128, b: u128) -> u128 {
+                #[cfg(target_arch = "x86_64")]
+                unsafe {
+                    use core::arch::x86_64::*;
+                    transmute::<__m128i, u128>($x86(transmute(a), transmute(b)))
+                }
+                #[cfg(target_arch = "aarch64")]
+                unsafe {
+                    use core::arch::aarch64::*;
+                    transmute::<$neon_ty, u128>($neon(transmute(a), transmute(b)))
+                }
+            })*
+        };
+    }
+    arch_ops! {
+        addi8 => _mm_add_epi8, vaddq_u8, uint8x16_t;
+        addi16 => _mm_add_epi16, vaddq_u16, uint16x8_t;
+        addi32 => _mm_add_epi32, vaddq_u32, uint32x4_t;
+        addi64 => _mm_add_epi64, vaddq_u64, uint64x2_t;
+        subi8 => _mm_sub_epi8, vsubq_u8, uint8x16_t;
+        subi16 => _mm_sub_epi16, vsubq_u16, uint16x8_t;
+        subi32 => _mm_sub_epi32, vsubq_u32, uint32x4_t;
+        subi64 => _mm_sub_epi64, vsubq_u64, uint64x2_t;
+    }
+}
+
+// ── v128 ─────────────────────────────────────────────────────────────────────
+pub fn v128not(a: u128) -> V {
+    Ok(tuple_list::tuple_list!(!a))
+}
+pub fn v128and(a: u128, b: u128) -> V {
+    Ok(tuple_list::tuple_list!(a & b))
+}
+pub fn v128andnot(a: u128, b: u128) -> V {
+    Ok(tuple_list::tuple_list!(a & !b))
+}
+pub fn v128or(a: u128, b: u128) -> V {
+    Ok(tuple_list::tuple_list!(a | b))
+}
+pub fn v128xor(a: u128, b: u128) -> V {
+    Ok(tuple_list::tuple_list!(a ^ b))
+}
+pub fn v128bitselect(a: u128, b: u128, c: u128) -> V {
+    Ok(tuple_list::tuple_list!((a & c) | (b & !c)))
+}
+pub fn v128anytrue(a: u128) -> anyhow::Result<tuple_list::tuple_list_type!(u32)> {
+    Ok(tuple_list::tuple_list!((a != 0) as u32))
+}
+pub fn i8x16swizzle(a: u128, s: u128) -> V {
+    let a = lanes::<u8, 16>(a);
+    Ok(tuple_list::tuple_list!(map::<u8, u8, 16>(s, |i| {
+        a.get(i as usize).copied().unwrap_or(0)
+    })))
+}
+/// `i8x16.shuffle`; lane indices `16..32` select from `b`.
+pub fn i8x16shuffle(a: u128, b: u128, l: [u8; 16]) -> V {
+    let a = lanes::<u8, 16>(a);
+    let b = lanes::<u8, 16>(b);
+    Ok(tuple_list::tuple_list!(pack::<u8, 16>(l.map(|i| {
+        let i = (i & 31) as usize;
+        if i < 16 {
+            a[i]
+        } else {
+            b[i - 16]
+        }
+    }))))
+}
+
+// ── Integer shapes ───────────────────────────────────────────────────────────
+macro_rules! int_shape {
+    ($p:ident, $s:ident, $u:ident, $n:literal, $scalar:ty) => {
+        paste::paste! {
+            pub fn [<$p splat>](a: $scalar) -> V {
+                Ok(tuple_list::tuple_list!(pack::<$u, $n>([a as $u; $n])))
+            }
+            pub fn [<$p replacelane>](a: u128, x: $scalar, l: u8) -> V {
+                let mut v = lanes::<$u, $n>(a);
+                v[l as usize] = x as $u;
+                Ok(tuple_list::tuple_list!(pack::<$u, $n>(v)))
+            }
+            pub fn [<$p eq>](a: u128, b: u128) -> V {
+                Ok(tuple_list::tuple_list!(zip::<$u, $u, $n>(a, b, |x, y| if x == y { $u::MAX } else { 0 })))
+            }
+            pub fn [<$p ne>](a: u128, b: u128) -> V {
+                Ok(tuple_list::tuple_list!(zip::<$u, $u, $n>(a, b, |x, y| if x != y { $u::MAX } else { 0 })))
+            }
+            pub fn [<$p lts>](a: u128, b: u128) -> V {
+                Ok(tuple_list::tuple_list!(zip::<$s, $u, $n>(a, b, |x, y| if x < y { $u::MAX } else { 0 })))
+            }
+            pub fn [<$p ltu>](a: u128, b: u128) -> V {
+                Ok(tuple_list::tuple_list!(zip::<$u, $u, $n>(a, b, |x, y| if x < y { $u::MAX } else { 0 })))
+            }
+            pub fn [<$p gts>](a: u128, b: u128) -> V {
+                Ok(tuple_list::tuple_list!(zip::<$s, $u, $n>(a, b, |x, y| if x > y { $u::MAX } else { 0 })))
+            }
+            pub fn [<$p gtu>](a: u128, b: u128) -> V {
+                Ok(tuple_list::tuple_list!(zip::<$u, $u, $n>(a, b, |x, y| if x > y { $u::MAX } else { 0 })))
+            }
+            pub fn [<$p les>](a: u128, b: u128) -> V {
+                Ok(tuple_list::tuple_list!(zip::<$s, $u, $n>(a, b, |x, y| if x <= y { $u::MAX } else { 0 })))
+            }
+            pub fn [<$p leu>](a: u128, b: u128) -> V {
+                Ok(tuple_list::tuple_list!(zip::<$u, $u, $n>(a, b, |x, y| if x <= y { $u::MAX } else { 0 })))
+            }
+            pub fn [<$p ges>](a: u128, b: u128) -> V {
+                Ok(tuple_list::tuple_list!(zip::<$s, $u, $n>(a, b, |x, y| if x >= y { $u::MAX } else { 0 })))
+            }
+            pub fn [<$p geu>](a: u128, b: u128) -> V {
+                Ok(tuple_list::tuple_list!(zip::<$u, $u, $n>(a, b, |x, y| if x >= y { $u::MAX } else { 0 })))
+            }
+            pub fn [<$p abs>](a: u128) -> V {
+                Ok(tuple_list::tuple_list!(map::<$s, $s, $n>(a, |x| x.wrapping_abs())))
+            }
+            pub fn [<$p neg>](a: u128) -> V {
+                Ok(tuple_list::tuple_list!(map::<$s, $s, $n>(a, |x| x.wrapping_neg())))
+            }
+            pub fn [<$p popcnt>](a: u128) -> V {
+                Ok(tuple_list::tuple_list!(map::<$u, $u, $n>(a, |x| x.count_ones() as $u)))
+            }
+            pub fn [<$p alltrue>](a: u128) -> anyhow::Result<tuple_list::tuple_list_type!(u32)> {
+                Ok(tuple_list::tuple_list!(lanes::<$u, $n>(a).iter().all(|x| *x != 0) as u32))
+            }
+            pub fn [<$p bitmask>](a: u128) -> anyhow::Result<tuple_list::tuple_list_type!(u32)> {
+                let mut m = 0u32;
+                for (i, x) in lanes::<$s, $n>(a).into_iter().enumerate() {
+                    m |= ((x < 0) as u32) << i;
+                }
+                Ok(tuple_list::tuple_list!(m))
+            }
+            //shift counts are taken modulo the lane width
+            pub fn [<$p shl>](a: u128, s: u32) -> V {
+                Ok(tuple_list::tuple_list!(map::<$u, $u, $n>(a, |x| x.wrapping_shl(s))))
+            }
+            pub fn [<$p shrs>](a: u128, s: u32) -> V {
+                Ok(tuple_list::tuple_list!(map::<$s, $s, $n>(a, |x| x.wrapping_shr(s))))
+            }
+            pub fn [<$p shru>](a: u128, s: u32) -> V {
+                Ok(tuple_list::tuple_list!(map::<$u, $u, $n>(a, |x| x.wrapping_shr(s))))
+            }
+            pub fn [<$p add>](a: u128, b: u128) -> V {
+                #[cfg(any(
+                    all(target_arch = "x86_64", target_feature = "sse2"),
+                    all(target_arch = "aarch64", target_feature = "neon")
+                ))]
+                let r = arch::[<add $s>](a, b);
+                #[cfg(not(any(
+                    all(target_arch = "x86_64", target_feature = "sse2"),
+                    all(target_arch = "aarch64", target_feature = "neon")
+                )))]
+                let r = zip::<$u, $u, $n>(a, b, |x, y| x.wrapping_add(y));
+                Ok(tuple_list::tuple_list!(r))
+            }
+            pub fn [<$p sub>](a: u128, b: u128) -> V {
+                #[cfg(any(
+                    all(target_arch = "x86_64", target_feature = "sse2"),
+                    all(target_arch = "aarch64", target_feature = "neon")
+                ))]
+                let r = arch::[<sub $s>](a, b);
+                #[cfg(not(any(
+                    all(target_arch = "x86_64", target_feature = "sse2"),
+                    all(target_arch = "aarch64", target_feature = "neon")
+                )))]
+                let r = zip::<$u, $u, $n>(a, b, |x, y| x.wrapping_sub(y));
+                Ok(tuple_list::tuple_list!(r))
+            }
+            pub fn [<$p mul>](a: u128, b: u128) -> V {
+                Ok(tuple_list::tuple_list!(zip::<$u, $u, $n>(a, b, |x, y| x.wrapping_mul(y))))
+            }
+            pub fn [<$p addsats>](a: u128, b: u128) -> V {
+                Ok(tuple_list::tuple_list!(zip::<$s, $s, $n>(a, b, |x, y| x.saturating_add(y))))
+            }
+            pub fn [<$p addsatu>](a: u128, b: u128) -> V {
+                Ok(tuple_list::tuple_list!(zip::<$u, $u, $n>(a, b, |x, y| x.saturating_add(y))))
+            }
+            pub fn [<$p subsats>](a: u128, b: u128) -> V {
+                Ok(tuple_list::tuple_list!(zip::<$s, $s, $n>(a, b, |x, y| x.saturating_sub(y))))
+            }
+            pub fn [<$p subsatu>](a: u128, b: u128) -> V {
+                Ok(tuple_list::tuple_list!(zip::<$u, $u, $n>(a, b, |x, y| x.saturating_sub(y))))
+            }
+            pub fn [<$p mins>](a: u128, b: u128) -> V {
+                Ok(tuple_list::tuple_list!(zip::<$s, $s, $n>(a, b, |x, y| x.min(y))))
+            }
+            pub fn [<$p minu>](a: u128, b: u128) -> V {
+                Ok(tuple_list::tuple_list!(zip::<$u, $u, $n>(a, b, |x, y| x.min(y))))
+            }
+            pub fn [<$p maxs>](a: u128, b: u128) -> V {
+                Ok(tuple_list::tuple_list!(zip::<$s, $s, $n>(a, b, |x, y| x.max(y))))
+            }
+            pub fn [<$p maxu>](a: u128, b: u128) -> V {
+                Ok(tuple_list::tuple_list!(zip::<$u, $u, $n>(a, b, |x, y| x.max(y))))
+            }
+            pub fn [<$p avgru>](a: u128, b: u128) -> V {
+                Ok(tuple_list::tuple_list!(zip::<$u, $u, $n>(a, b, |x, y| {
+                    ((x as u128 + y as u128 + 1) >> 1) as $u
+                })))
+            }
+        }
+    };
+}
+int_shape!(i8x16, i8, u8, 16, u32);
+int_shape!(i16x8, i16, u16, 8, u32);
+int_shape!(i32x4, i32, u32, 4, u32);
+int_shape!(i64x2, i64, u64, 2, u64);
+
+pub fn i8x16extractlanes(a: u128, l: u8) -> anyhow::Result<tuple_list::tuple_list_type!(u32)> {
+    Ok(tuple_list::tuple_list!(lanes::<i8, 16>(a)[l as usize] as i32 as u32))
+}
+pub fn i8x16extractlaneu(a: u128, l: u8) -> anyhow::Result<tuple_list::tuple_list_type!(u32)> {
+    Ok(tuple_list::tuple_list!(lanes::<u8, 16>(a)[l as usize] as u32))
+}
+pub fn i16x8extractlanes(a: u128, l: u8) -> anyhow::Result<tuple_list::tuple_list_type!(u32)> {
+    Ok(tuple_list::tuple_list!(lanes::<i16, 8>(a)[l as usize] as i32 as u32))
+}
+pub fn i16x8extractlaneu(a: u128, l: u8) -> anyhow::Result<tuple_list::tuple_list_type!(u32)> {
+    Ok(tuple_list::tuple_list!(lanes::<u16, 8>(a)[l as usize] as u32))
+}
+pub fn i32x4extractlane(a: u128, l: u8) -> anyhow::Result<tuple_list::tuple_list_type!(u32)> {
+    Ok(tuple_list::tuple_list!(lanes::<u32, 4>(a)[l as usize]))
+}
+pub fn i64x2extractlane(a: u128, l: u8) -> anyhow::Result<tuple_list::tuple_list_type!(u64)> {
+    Ok(tuple_list::tuple_list!(lanes::<u64, 2>(a)[l as usize]))
+}
+pub fn i16x8q15mulrsats(a: u128, b: u128) -> V {
+    Ok(tuple_list::tuple_list!(zip::<i16, i16, 8>(a, b, |x, y| {
+        ((x as i32 * y as i32 + 0x4000) >> 15).clamp(i16::MIN as i32, i16::MAX as i32) as i16
+    })))
+}
+pub fn i32x4doti16x8s(a: u128, b: u128) -> V {
+    let a = lanes::<i16, 8>(a);
+    let b = lanes::<i16, 8>(b);
+    Ok(tuple_list::tuple_list!(pack::<i32, 4>(core::array::from_fn(|i| {
+        (a[2 * i] as i32 * b[2 * i] as i32).wrapping_add(a[2 * i + 1] as i32 * b[2 * i + 1] as i32)
+    }))))
+}
+/// Relaxed dot products read `b` as signed and saturate each pair sum.
+pub fn i16x8relaxeddoti8x16i7x16s(a: u128, b: u128) -> V {
+    let a = lanes::<i8, 16>(a);
+    let b = lanes::<i8, 16>(b);
+    Ok(tuple_list::tuple_list!(pack::<i16, 8>(core::array::from_fn(|i| {
+        (a[2 * i] as i16 * b[2 * i] as i16).saturating_add(a[2 * i + 1] as i16 * b[2 * i + 1] as i16)
+    }))))
+}
+pub fn i32x4relaxeddoti8x16i7x16adds(a: u128, b: u128, c: u128) -> V {
+    let (d, ()) = i16x8relaxeddoti8x16i7x16s(a, b)?;
+    let d = lanes::<i16, 8>(d);
+    let c = lanes::<i32, 4>(c);
+    Ok(tuple_list::tuple_list!(pack::<i32, 4>(core::array::from_fn(|i| {
+        (d[2 * i] as i32 + d[2 * i + 1] as i32).wrapping_add(c[i])
+    }))))
+}
+
+// ── Widening and narrowing ───────────────────────────────────────────────────
+// `$w` lanes are twice as wide as `$n` lanes; there are `$k` of them.
+macro_rules! widen {
+    ($w:ident, $ws:ident, $wu:ident, $n:ident, $ns:ident, $nu:ident, $k:literal) => {
+        paste::paste! {
+            pub fn [<$w extendlow $n s>](a: u128) -> V {
+                let v = lanes::<$ns, { 2 * $k }>(a);
+                Ok(tuple_list::tuple_list!(pack::<$ws, $k>(core::array::from_fn(|i| v[i] as $ws))))
+            }
+            pub fn [<$w extendhigh $n s>](a: u128) -> V {
+                let v = lanes::<$ns, { 2 * $k }>(a);
+                Ok(tuple_list::tuple_list!(pack::<$ws, $k>(core::array::from_fn(|i| v[i + $k] as $ws))))
+            }
+            pub fn [<$w extendlow $n u>](a: u128) -> V {
+                let v = lanes::<$nu, { 2 * $k }>(a);
+                Ok(tuple_list::tuple_list!(pack::<$wu, $k>(core::array::from_fn(|i| v[i] as $wu))))
+            }
+            pub fn [<$w extendhigh $n u>](a: u128) -> V {
+                let v = lanes::<$nu, { 2 * $k }>(a);
+                Ok(tuple_list::tuple_list!(pack::<$wu, $k>(core::array::from_fn(|i| v[i + $k] as $wu))))
+            }
+            pub fn [<$w extmullow $n s>](a: u128, b: u128) -> V {
+                let (a, b) = (lanes::<$ns, { 2 * $k }>(a), lanes::<$ns, { 2 * $k }>(b));
+                Ok(tuple_list::tuple_list!(pack::<$ws, $k>(core::array::from_fn(|i| (a[i] as $ws).wrapping_mul(b[i] as $ws)))))
+            }
+            pub fn [<$w extmulhigh $n s>](a: u128, b: u128) -> V {
+                let (a, b) = (lanes::<$ns, { 2 * $k }>(a), lanes::<$ns, { 2 * $k }>(b));
+                Ok(tuple_list::tuple_list!(pack::<$ws, $k>(core::array::from_fn(|i| (a[i + $k] as $ws).wrapping_mul(b[i + $k] as $ws)))))
+            }
+            pub fn [<$w extmullow $n u>](a: u128, b: u128) -> V {
+                let (a, b) = (lanes::<$nu, { 2 * $k }>(a), lanes::<$nu, { 2 * $k }>(b));
+                Ok(tuple_list::tuple_list!(pack::<$wu, $k>(core::array::from_fn(|i| (a[i] as $wu).wrapping_mul(b[i] as $wu)))))
+            }
+            pub fn [<$w extmulhigh $n u>](a: u128, b: u128) -> V {
+                let (a, b) = (lanes::<$nu, { 2 * $k }>(a), lanes::<$nu, { 2 * $k }>(b));
+                Ok(tuple_list::tuple_list!(pack::<$wu, $k>(core::array::from_fn(|i| (a[i + $k] as $wu).wrapping_mul(b[i + $k] as $wu)))))
+            }
+            pub fn [<$w extaddpairwise $n s>](a: u128) -> V {
+                let v = lanes::<$ns, { 2 * $k }>(a);
+                Ok(tuple_list::tuple_list!(pack::<$ws, $k>(core::array::from_fn(|i| v[2 * i] as $ws + v[2 * i + 1] as $ws))))
+            }
+            pub fn [<$w extaddpairwise $n u>](a: u128) -> V {
+                let v = lanes::<$nu, { 2 * $k }>(a);
+                Ok(tuple_list::tuple_list!(pack::<$wu, $k>(core::array::from_fn(|i| v[2 * i] as $wu + v[2 * i + 1] as $wu))))
+            }
+            /// Lanes of `a` then `b`, saturated to the narrow signed range.
+            pub fn [<$n narrow $w s>](a: u128, b: u128) -> V {
+                let (a, b) = (lanes::<$ws, $k>(a), lanes::<$ws, $k>(b));
+                Ok(tuple_list::tuple_list!(pack::<$ns, { 2 * $k }>(core::array::from_fn(|i| {
+                    let x = if i < $k { a[i] } else { b[i - $k] };
+                    x.clamp($ns::MIN as $ws, $ns::MAX as $ws) as $ns
+                }))))
+            }
+            /// Lanes of `a` then `b` (read as signed), saturated to the narrow
+            /// unsigned range.
+            pub fn [<$n narrow $w u>](a: u128, b: u128) -> V {
+                let (a, b) = (lanes::<$ws, $k>(a), lanes::<$ws, $k>(b));
+                Ok(tuple_list::tuple_list!(pack::<$nu, { 2 * $k }>(core::array::from_fn(|i| {
+                    let x = if i < $k { a[i] } else { b[i - $k] };
+                    x.clamp(0, $nu::MAX as $ws) as $nu
+                }))))
+            }
+        }
+    };
+}
+widen!(i16x8, i16, u16, i8x16, i8, u8, 8);
+widen!(i32x4, i32, u32, i16x8, i16, u16, 4);
+widen!(i64x2, i64, u64, i32x4, i32, u32, 2);
+
+// ── Float shapes ─────────────────────────────────────────────────────────────
+macro_rules! float_shape {
+    ($p:ident, $f:ident, $u:ident, $n:literal, $sign:expr, $ceil:ident, $floor:ident, $trunc:ident, $rint:ident, $sqrt:ident) => {
+        paste::paste! {
+            pub fn [<$p splat>](a: $f) -> V {
+                Ok(tuple_list::tuple_list!(pack::<$f, $n>([a; $n])))
+            }
+            pub fn [<$p extractlane>](a: u128, l: u8) -> anyhow::Result<tuple_list::tuple_list_type!($f)> {
+                Ok(tuple_list::tuple_list!(lanes::<$f, $n>(a)[l as usize]))
+            }
+            pub fn [<$p replacelane>](a: u128, x: $f, l: u8) -> V {
+                let mut v = lanes::<$f, $n>(a);
+                v[l as usize] = x;
+                Ok(tuple_list::tuple_list!(pack::<$f, $n>(v)))
+            }
+            pub fn [<$p eq>](a: u128, b: u128) -> V {
+                Ok(tuple_list::tuple_list!(zip::<$f, $u, $n>(a, b, |x, y| if x == y { $u::MAX } else { 0 })))
+            }
+            pub fn [<$p ne>](a: u128, b: u128) -> V {
+                Ok(tuple_list::tuple_list!(zip::<$f, $u, $n>(a, b, |x, y| if x != y { $u::MAX } else { 0 })))
+            }
+            pub fn [<$p lt>](a: u128, b: u128) -> V {
+                Ok(tuple_list::tuple_list!(zip::<$f, $u, $n>(a, b, |x, y| if x < y { $u::MAX } else { 0 })))
+            }
+            pub fn [<$p gt>](a: u128, b: u128) -> V {
+                Ok(tuple_list::tuple_list!(zip::<$f, $u, $n>(a, b, |x, y| if x > y { $u::MAX } else { 0 })))
+            }
+            pub fn [<$p le>](a: u128, b: u128) -> V {
+                Ok(tuple_list::tuple_list!(zip::<$f, $u, $n>(a, b, |x, y| if x <= y { $u::MAX } else { 0 })))
+            }
+            pub fn [<$p ge>](a: u128, b: u128) -> V {
+                Ok(tuple_list::tuple_list!(zip::<$f, $u, $n>(a, b, |x, y| if x >= y { $u::MAX } else { 0 })))
+            }
+            pub fn [<$p abs>](a: u128) -> V {
+                Ok(tuple_list::tuple_list!(map::<$u, $u, $n>(a, |x| x & !$sign)))
+            }
+            pub fn [<$p neg>](a: u128) -> V {
+                Ok(tuple_list::tuple_list!(map::<$u, $u, $n>(a, |x| x ^ $sign)))
+            }
+            pub fn [<$p ceil>](a: u128) -> V {
+                Ok(tuple_list::tuple_list!(map::<$f, $f, $n>(a, libm::$ceil)))
+            }
+            pub fn [<$p floor>](a: u128) -> V {
+                Ok(tuple_list::tuple_list!(map::<$f, $f, $n>(a, libm::$floor)))
+            }
+            pub fn [<$p trunc>](a: u128) -> V {
+                Ok(tuple_list::tuple_list!(map::<$f, $f, $n>(a, libm::$trunc)))
+            }
+            pub fn [<$p nearest>](a: u128) -> V {
+                Ok(tuple_list::tuple_list!(map::<$f, $f, $n>(a, libm::$rint)))
+            }
+            pub fn [<$p sqrt>](a: u128) -> V {
+                Ok(tuple_list::tuple_list!(map::<$f, $f, $n>(a, libm::$sqrt)))
+            }
+            pub fn [<$p add>](a: u128, b: u128) -> V {
+                Ok(tuple_list::tuple_list!(zip::<$f, $f, $n>(a, b, |x, y| x + y)))
+            }
+            pub fn [<$p sub>](a: u128, b: u128) -> V {
+                Ok(tuple_list::tuple_list!(zip::<$f, $f, $n>(a, b, |x, y| x - y)))
+            }
+            pub fn [<$p mul>](a: u128, b: u128) -> V {
+                Ok(tuple_list::tuple_list!(zip::<$f, $f, $n>(a, b, |x, y| x * y)))
+            }
+            pub fn [<$p div>](a: u128, b: u128) -> V {
+                Ok(tuple_list::tuple_list!(zip::<$f, $f, $n>(a, b, |x, y| x / y)))
+            }
+            pub fn [<$p min>](a: u128, b: u128) -> V {
+                Ok(tuple_list::tuple_list!(zip::<$f, $f, $n>(a, b, |x, y| crate::[<$f min>](x, y).map_or(x, |r| r.0))))
+            }
+            pub fn [<$p max>](a: u128, b: u128) -> V {
+                Ok(tuple_list::tuple_list!(zip::<$f, $f, $n>(a, b, |x, y| crate::[<$f max>](x, y).map_or(x, |r| r.0))))
+            }
+            /// `b < a ? b : a`, without NaN propagation.
+            pub fn [<$p pmin>](a: u128, b: u128) -> V {
+                Ok(tuple_list::tuple_list!(zip::<$f, $f, $n>(a, b, |x, y| if y < x { y } else { x })))
+            }
+            /// `a < b ? b : a`, without NaN propagation.
+            pub fn [<$p pmax>](a: u128, b: u128) -> V {
+                Ok(tuple_list::tuple_list!(zip::<$f, $f, $n>(a, b, |x, y| if x < y { y } else { x })))
+            }
+            /// `a * b + c`, rounded twice.
+            pub fn [<$p relaxedmadd>](a: u128, b: u128, c: u128) -> V {
+                let c = lanes::<$f, $n>(c);
+                let ab = lanes::<$f, $n>(zip::<$f, $f, $n>(a, b, |x, y| x * y));
+                Ok(tuple_list::tuple_list!(pack::<$f, $n>(core::array::from_fn(|i| ab[i] + c[i]))))
+            }
+            /// `-(a * b) + c`, rounded twice.
+            pub fn [<$p relaxednmadd>](a: u128, b: u128, c: u128) -> V {
+                let c = lanes::<$f, $n>(c);
+                let ab = lanes::<$f, $n>(zip::<$f, $f, $n>(a, b, |x, y| x * y));
+                Ok(tuple_list::tuple_list!(pack::<$f, $n>(core::array::from_fn(|i| -ab[i] + c[i]))))
+            }
+        }
+    };
+}
+float_shape!(f32x4, f32, u32, 4, 0x8000_0000u32, ceilf, floorf, truncf, rintf, sqrtf);
+float_shape!(f64x2, f64, u64, 2, 0x8000_0000_0000_0000u64, ceil, floor, trunc, rint, sqrt);
+
+// ── Conversions ──────────────────────────────────────────────────────────────
+// `as` saturates and maps NaN to 0, which is what `trunc_sat` wants.
+pub fn i32x4truncsatf32x4s(a: u128) -> V {
+    Ok(tuple_list::tuple_list!(map::<f32, i32, 4>(a, |x| x as i32)))
+}
+pub fn i32x4truncsatf32x4u(a: u128) -> V {
+    Ok(tuple_list::tuple_list!(map::<f32, u32, 4>(a, |x| x as u32)))
+}
+pub fn f32x4converti32x4s(a: u128) -> V {
+    Ok(tuple_list::tuple_list!(map::<i32, f32, 4>(a, |x| x as f32)))
+}
+pub fn f32x4converti32x4u(a: u128) -> V {
+    Ok(tuple_list::tuple_list!(map::<u32, f32, 4>(a, |x| x as f32)))
+}
+pub fn i32x4truncsatf64x2szero(a: u128) -> V {
+    Ok(tuple_list::tuple_list!(map::<f64, i32, 2>(a, |x| x as i32)))
+}
+pub fn i32x4truncsatf64x2uzero(a: u128) -> V {
+    Ok(tuple_list::tuple_list!(map::<f64, u32, 2>(a, |x| x as u32)))
+}
+pub fn f64x2convertlowi32x4s(a: u128) -> V {
+    Ok(tuple_list::tuple_list!(map::<i32, f64, 2>(a, |x| x as f64)))
+}
+pub fn f64x2convertlowi32x4u(a: u128) -> V {
+    Ok(tuple_list::tuple_list!(map::<u32, f64, 2>(a, |x| x as f64)))
+}
+pub fn f32x4demotef64x2zero(a: u128) -> V {
+    Ok(tuple_list::tuple_list!(map::<f64, f32, 2>(a, |x| x as f32)))
+}
+pub fn f64x2promotelowf32x4(a: u128) -> V {
+    Ok(tuple_list::tuple_list!(map::<f32, f64, 2>(a, |x| x as f64)))
+}
+
+// ── Memory ───────────────────────────────────────────────────────────────────
+// Unlike the scalar loads these are explicitly little-endian, since lane
+// order is part of the value.
+fn read<T: TryInto<u64>, M: Memory + ?Sized, const K: usize>(a: &mut M, b: T) -> anyhow::Result<[u8; K]>
+where
+    T::Error: Err + Send + Sync + 'static,
+{
+    let r = a.read(b.try_into().map_err(Into::into)?, K as u64)?;
+    Ok(r.as_ref().as_ref().try_into().unwrap())
+}
+pub fn v128load<T: TryInto<u64>, M: Memory + ?Sized>(a: &mut M, b: T) -> V
+where
+    T::Error: Err + Send + Sync + 'static,
+{
+    Ok(tuple_list::tuple_list!(u128::from_le_bytes(read::<T, M, 16>(a, b)?)))
+}
+pub fn v128store<T: TryInto<u64>, M: Memory + ?Sized>(a: &mut M, b: T, c: u128) -> anyhow::Result<()>
+where
+    T::Error: Err + Send + Sync + 'static,
+{
+    a.write(b.try_into().map_err(Into::into)?, &c.to_le_bytes())
+}
+macro_rules! load_ext {
+    ($($name:ident: $from:ident as $to:ident, $k:literal;)*) => {
+        $(pub fn $name<T: TryInto<u64>, M: Memory + ?Sized>(a: &mut M, b: T) -> V
+        where
+            T::Error: Err + Send + Sync + 'static,
+        {
+            let v = lanes::<$from, $k>(u64::from_le_bytes(read::<T, M, 8>(a, b)?) as u128);
+            Ok(tuple_list::tuple_list!(pack::<$to, $k>(v.map(|x| x as $to))))
+        })*
+    };
+}
+load_ext! {
+    v128load8x8s: i8 as i16, 8;
+    v128load8x8u: u8 as u16, 8;
+    v128load16x4s: i16 as i32, 4;
+    v128load16x4u: u16 as u32, 4;
+    v128load32x2s: i32 as i64, 2;
+    v128load32x2u: u32 as u64, 2;
+}
+macro_rules! load_lane {
+    ($($bits:literal: $t:ident, $k:literal;)*) => {
+        paste::paste! {
+            $(pub fn [<v128load $bits splat>]<T: TryInto<u64>, M: Memory + ?Sized>(a: &mut M, b: T) -> V
+            where
+                T::Error: Err + Send + Sync + 'static,
+            {
+                let x = $t::from_le_bytes(read::<T, M, { $bits / 8 }>(a, b)?);
+                Ok(tuple_list::tuple_list!(pack::<$t, $k>([x; $k])))
+            }
+            pub fn [<v128load $bits lane>]<T: TryInto<u64>, M: Memory + ?Sized>(a: &mut M, b: T, v: u128, l: u8) -> V
+            where
+                T::Error: Err + Send + Sync + 'static,
+            {
+                let mut v = lanes::<$t, $k>(v);
+                v[l as usize] = $t::from_le_bytes(read::<T, M, { $bits / 8 }>(a, b)?);
+                Ok(tuple_list::tuple_list!(pack::<$t, $k>(v)))
+            }
+            pub fn [<v128store $bits lane>]<T: TryInto<u64>, M: Memory + ?Sized>(a: &mut M, b: T, v: u128, l: u8) -> anyhow::Result<()>
+            where
+                T::Error: Err + Send + Sync + 'static,
+            {
+                a.write(b.try_into().map_err(Into::into)?, &lanes::<$t, $k>(v)[l as usize].to_le_bytes())
+            })*
+        }
+    };
+}
+load_lane! {
+    8: u8, 16;
+    16: u16, 8;
+    32: u32, 4;
+    64: u64, 2;
+}
+pub fn v128load32zero<T: TryInto<u64>, M: Memory + ?Sized>(a: &mut M, b: T) -> V
+where
+    T::Error: Err + Send + Sync + 'static,
+{
+    Ok(tuple_list::tuple_list!(u32::from_le_bytes(read::<T, M, 4>(a, b)?) as u128))
+}
+pub fn v128load64zero<T: TryInto<u64>, M: Memory + ?Sized>(a: &mut M, b: T) -> V
+where
+    T::Error: Err + Send + Sync + 'static,
+{
+    Ok(tuple_list::tuple_list!(u64::from_le_bytes(read::<T, M, 8>(a, b)?) as u128))
+}
@@ -0,0 +1,60 @@
+//! Wasm traps.
+//!
+//! Runtime helpers and generated code report traps as a [`Trap`] wrapped in
+//! the usual `anyhow::Error`, so hosts can `downcast_ref::<Trap>()` to tell
+//! them apart from errors raised by their own imports.
+use core::fmt;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Trap {
+    IntegerDivideByZero,
+    IntegerOverflow,
+    OutOfBoundsMemory,
+    OutOfBoundsTable,
+    IndirectCallTypeMismatch,
+    Unreachable,
+    InvalidConversionToInteger,
+    NullReference,
+    StackExhausted,
+    UnalignedAtomic,
+    ExpectedSharedMemory,
+    OutOfBoundsArray,
+    CastFailure,
+    AllocationTooLarge,
+    OutOfFuel,
+    Interrupt,
+}
+impl Trap {
+    /// The message the spec test suite uses for this trap.
+    pub fn message(&self) -> &'static str {
+        match self {
+            Trap::IntegerDivideByZero => "integer divide by zero",
+            Trap::IntegerOverflow => "integer overflow",
+            Trap::OutOfBoundsMemory => "out of bounds memory access",
+            Trap::OutOfBoundsTable => "out of bounds table access",
+            Trap::IndirectCallTypeMismatch => "indirect call type mismatch",
+            Trap::Unreachable => "unreachable",
+            Trap::InvalidConversionToInteger => "invalid conversion to integer",
+            Trap::NullReference => "null reference",
+            Trap::StackExhausted => "call stack exhausted",
+            Trap::UnalignedAtomic => "unaligned atomic",
+            Trap::ExpectedSharedMemory => "expected shared memory",
+            Trap::OutOfBoundsArray => "out of bounds array access",
+            Trap::CastFailure => "cast failure",
+            Trap::AllocationTooLarge => "allocation too large",
+            Trap::OutOfFuel => "all fuel consumed",
+            Trap::Interrupt => "interrupt",
+        }
+    }
+    /// Whether `e` is (or wraps) a trap, as opposed to a host error.
+    pub fn of(e: &anyhow::Error) -> Option<Trap> {
+        e.downcast_ref::<Trap>().copied()
+    }
+}
+impl fmt::Display for Trap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.message())
+    }
+}
+impl core::error::Error for Trap {}
@@ -1,29 +1,85 @@
-use std::sync::Arc;
-use wasm_runtime_layer::{AsContextMut, ExternRef, FuncType};
-use crate::func::CtxSpec;
-#[derive(Clone)]
-pub enum MetaType {
-    I32,
-    I64,
-    F32,
-    F64,
-    ExternRef,
-    FunRef {
-        params: Vec<MetaType>,
-        returns: Vec<MetaType>,
-    },
-}
+//! Bridge to [`wasm_runtime_layer`] engines (wasmi, wasmtime, …), so
+//! translated and interpreted modules can call each other.
+//!
+//! `translate_in` turns a translated value into a `wasm_runtime_layer` one,
+//! wrapping function references as host functions of the engine;
+//! `translate_out` goes the other way.  Argument and result lists of
+//! `func::Value` closures are reversed, as everywhere in `func`; lists of
+//! the wrong length trap with `Trap::IndirectCallTypeMismatch`.
+//!
+//! A translated instance stands in for an engine one through
+//! [`WrlInstance`], which serves its function exports by name, and
+//! [`resolve_import`], which satisfies its imports from engine `Imports`.
+//! It is not an engine backend: translated modules are not loadable through
+//! `wasm_runtime_layer::Module`, only their function exports cross, and only
+//! synchronous (non-`Flags::ASYNC`) modules fit, since engine host functions
+//! cannot await.
+use crate::func::{value, CtxSpec};
+use crate::{GrowRefused, Trap};
+use alloc::{
+    boxed::Box,
+    collections::BTreeMap,
+    string::{String, ToString},
+    sync::Arc,
+    vec,
+    vec::Vec,
+};
+use wasm_runtime_layer::{
+    AsContext, AsContextMut, Extern, ExternRef, FuncType, Imports, ValueType,
+};
+pub use crate::MetaType;
 impl MetaType {
-    fn wrl(&self) -> wasm_runtime_layer::ValueType {
-        match self {
+    /// The engine-side value type; `Other` has none.
+    pub fn wrl(&self) -> anyhow::Result<wasm_runtime_layer::ValueType> {
+        Ok(match self {
             MetaType::I32 => wasm_runtime_layer::ValueType::I32,
             MetaType::I64 => wasm_runtime_layer::ValueType::I64,
             MetaType::F32 => wasm_runtime_layer::ValueType::F32,
             MetaType::F64 => wasm_runtime_layer::ValueType::F64,
             MetaType::ExternRef => wasm_runtime_layer::ValueType::ExternRef,
-            MetaType::FunRef { params, returns } => wasm_runtime_layer::ValueType::FuncRef,
+            MetaType::FunRef { .. } => wasm_runtime_layer::ValueType::FuncRef,
+            MetaType::Other => anyhow::bail!("type cannot cross into a wasm_runtime_layer engine"),
+        })
+    }
+    /// The description of an engine value type.  An engine `funcref`
+    /// carries no signature, so it is `Other`: only null crosses through it.
+    pub fn from_wrl(ty: &ValueType) -> Self {
+        match ty {
+            ValueType::I32 => MetaType::I32,
+            ValueType::I64 => MetaType::I64,
+            ValueType::F32 => MetaType::F32,
+            ValueType::F64 => MetaType::F64,
+            ValueType::ExternRef => MetaType::ExternRef,
+            ValueType::FuncRef => MetaType::Other,
         }
     }
+    /// The `Shape` of a `FunRef` with this signature, unless `Other`
+    /// leaves a slot unknown.
+    fn shape(params: &[MetaType], returns: &[MetaType]) -> Option<value::Shape> {
+        let slots = |tys: &[MetaType]| {
+            tys.iter()
+                .map(|t| match t {
+                    MetaType::I32 => Some(value::Slot::I32),
+                    MetaType::I64 => Some(value::Slot::I64),
+                    MetaType::F32 => Some(value::Slot::F32),
+                    MetaType::F64 => Some(value::Slot::F64),
+                    MetaType::ExternRef | MetaType::FunRef { .. } => Some(value::Slot::Ref),
+                    MetaType::Other => None,
+                })
+                .collect::<Option<Vec<_>>>()
+        };
+        Some(value::Shape {
+            params: slots(params)?,
+            results: slots(returns)?,
+        })
+    }
+    /// The engine-side signature of a `FunRef`.
+    pub fn func_type(params: &[MetaType], returns: &[MetaType]) -> anyhow::Result<FuncType> {
+        Ok(FuncType::new(
+            params.iter().map(|a| a.wrl()).collect::<anyhow::Result<Vec<_>>>()?,
+            returns.iter().map(|a| a.wrl()).collect::<anyhow::Result<Vec<_>>>()?,
+        ))
+    }
 }
 pub trait Native: Sized {
     fn to_val(&self, v: &mut wasm_runtime_layer::Value);
@@ -50,7 +106,10 @@ impl<T: Native, U: Natives> Natives for (T, U) {
         self.1.to_val(&mut v[1..]);
     }
     fn from_val(v: &[wasm_runtime_layer::Value]) -> anyhow::Result<Self> {
-        Ok((T::from_val(&v[0])?, U::from_val(&v[1..])?))
+        let Some(first) = v.first() else {
+            anyhow::bail!("list too small")
+        };
+        Ok((T::from_val(first)?, U::from_val(&v[1..])?))
     }
 }
 macro_rules! native {
@@ -74,6 +133,9 @@ native!(f32 as F32);
 native!(f64 as F64);
 native!(Option<wasm_runtime_layer::Func> as FuncRef);
 native!(Option<wasm_runtime_layer::ExternRef> as ExternRef);
+/// Convert a translated value for an engine.  A function reference becomes
+/// a host function of type `wrl_ty` that calls back into the translated
+/// code with the context found through the store's `D: AsMut<C>`.
 pub fn translate_in<C: CtxSpec + AsContextMut<UserState = D> + 'static, D: AsMut<C>>(
     val: &crate::func::Value<C>,
     ctx: &mut C,
@@ -82,32 +144,31 @@ pub fn translate_in<C: CtxSpec + AsContextMut<UserState = D> + 'static, D: AsMut
 where
     C::ExternRef: Send + Sync + 'static,
 {
-    Ok(match val {
-        crate::Value::I32(a) => wasm_runtime_layer::Value::I32(*a as i32),
-        crate::Value::I64(a) => wasm_runtime_layer::Value::I64(*a as i64),
-        crate::Value::F32(a) => wasm_runtime_layer::Value::F32(*a),
-        crate::Value::F64(a) => wasm_runtime_layer::Value::F64(*a),
-        crate::Value::FunRef(f) => {
-            // let wasm_runtime_layer::ValueType::FuncRef()
+    Ok(match &val.0 {
+        value::Value::I32(a) => wasm_runtime_layer::Value::I32(*a as i32),
+        value::Value::I64(a) => wasm_runtime_layer::Value::I64(*a as i64),
+        value::Value::F32(a) => wasm_runtime_layer::Value::F32(*a),
+        value::Value::F64(a) => wasm_runtime_layer::Value::F64(*a),
+        value::Value::FunRef(f, _) => {
             let MetaType::FunRef { params, returns } = wrl_ty.clone() else {
-                unreachable!()
+                anyhow::bail!("function reference passed as {wrl_ty:?}")
             };
-            let ty = FuncType::new(
-                params.iter().map(|a| a.wrl()),
-                returns.iter().map(|a| a.wrl()),
-            );
+            let ty = MetaType::func_type(&params, &returns)?;
             let f = f.clone();
             wasm_runtime_layer::Value::FuncRef(Some(wasm_runtime_layer::Func::new(
-                ctx,
+                &mut *ctx,
                 ty,
                 move |mut ctx, args, rets| {
                     let args2 = args
                         .iter()
                         .zip(params.iter())
                         .rev()
-                        .map(|(x, y)| translate_out(x, ctx.data_mut().as_mut(), y))
+                        .map(|(x, y)| Ok(translate_out(x, ctx.data_mut().as_mut(), y)?.0))
                         .collect::<anyhow::Result<Vec<_>>>()?;
                     let v = tramp::tramp(f(ctx.data_mut().as_mut(), args2))?;
+                    if v.len() != returns.len() {
+                        return Err(Trap::IndirectCallTypeMismatch.into());
+                    }
                     for ((w, v), t) in v.iter().rev().zip(rets.iter_mut()).zip(returns.iter()) {
                         *v = translate_in(w, ctx.data_mut().as_mut(), t)?;
                     }
@@ -115,16 +176,22 @@ where
                 },
             )))
         }
-        crate::Value::Null => match wrl_ty {
+        value::Value::Null => match wrl_ty {
             MetaType::ExternRef => wasm_runtime_layer::Value::ExternRef(None),
-            MetaType::FunRef { params, returns } => wasm_runtime_layer::Value::FuncRef(None),
-            _ => anyhow::bail!("invalid null"),
+            MetaType::FunRef { .. } => wasm_runtime_layer::Value::FuncRef(None),
+            _ => anyhow::bail!("null passed as {wrl_ty:?}"),
         },
-        crate::Value::ExRef(e) => {
-            wasm_runtime_layer::Value::ExternRef(Some(ExternRef::new(ctx, e.clone())))
+        value::Value::ExRef(e) => {
+            wasm_runtime_layer::Value::ExternRef(Some(ExternRef::new(&mut *ctx, e.clone())))
         }
+        // `wasm_runtime_layer` has no vectors, exceptions or GC objects.
+        _ => anyhow::bail!("value cannot cross into a wasm_runtime_layer engine"),
     })
 }
+/// Convert an engine value for translated code.  A function reference
+/// becomes a `FunRef` that calls the engine function through `C`.  An
+/// externref created by another store, or holding something other than
+/// `C::ExternRef`, reads as null.
 pub fn translate_out<C: CtxSpec + AsContextMut<UserState = D> + 'static, D: AsMut<C>>(
     val: &wasm_runtime_layer::Value,
     ctx: &mut C,
@@ -133,49 +200,219 @@ pub fn translate_out<C: CtxSpec + AsContextMut<UserState = D> + 'static, D: AsMu
 where
     C::ExternRef: Send + Sync + 'static,
 {
-    Ok(match val {
-        wasm_runtime_layer::Value::I32(i) => crate::Value::I32(*i as u32),
-        wasm_runtime_layer::Value::I64(i) => crate::Value::I64(*i as u64),
-        wasm_runtime_layer::Value::F32(f) => crate::Value::F32(*f),
-        wasm_runtime_layer::Value::F64(f) => crate::Value::F64(*f),
+    Ok(crate::func::Value(match val {
+        wasm_runtime_layer::Value::I32(i) => value::Value::I32(*i as u32),
+        wasm_runtime_layer::Value::I64(i) => value::Value::I64(*i as u64),
+        wasm_runtime_layer::Value::F32(f) => value::Value::F32(*f),
+        wasm_runtime_layer::Value::F64(f) => value::Value::F64(*f),
         wasm_runtime_layer::Value::FuncRef(f) => match f {
-            None => crate::Value::Null,
+            None => value::Value::Null,
             Some(a) => {
                 let a = a.clone();
                 let MetaType::FunRef { params, returns } = wrl_ty.clone() else {
-                    unreachable!()
+                    anyhow::bail!("function reference read as {wrl_ty:?}")
                 };
-                crate::Value::FunRef(Arc::new(move |ctx, args| {
+                let shape = MetaType::shape(&params, &returns).map(Arc::new);
+                value::Value::FunRef(Arc::new(move |ctx: &mut C, args: Vec<_>| {
+                    if args.len() != params.len() {
+                        return tramp::BorrowRec::Ret(Err(Trap::IndirectCallTypeMismatch.into()));
+                    }
                     let args_in: anyhow::Result<Vec<_>> = args
-                        .iter()
+                        .into_iter()
                         .rev()
                         .zip(params.iter())
-                        .map(|(x, y)| translate_in(x, &mut *ctx, y))
+                        .map(|(x, y)| translate_in(&crate::func::Value(x), &mut *ctx, y))
                         .collect();
-                    let mut results =
-                        vec![wasm_runtime_layer::Value::I32(0); a.ty(&mut *ctx).results().len()];
                     let args_in = match args_in {
                         Ok(a) => a,
                         Err(e) => return tramp::BorrowRec::Ret(Err(e)),
                     };
+                    let mut results = vec![wasm_runtime_layer::Value::I32(0); returns.len()];
                     tramp::BorrowRec::Ret(match a.call(&mut *ctx, &args_in, &mut results) {
                         Err(e) => Err(e),
                         Ok(_) => results
                             .iter()
                             .zip(returns.iter())
                             .rev()
-                            .map(|(x, y)| translate_out(x, ctx, y))
+                            .map(|(x, y)| translate_out(x, &mut *ctx, y))
                             .collect(),
                     })
-                }))
+                }), shape)
             }
         },
         wasm_runtime_layer::Value::ExternRef(x) => match x
             .as_ref()
             .and_then(|a| a.downcast::<C::ExternRef, _, _>(ctx.as_context()).ok())
         {
-            None => crate::Value::Null,
-            Some(x) => crate::Value::ExRef(x.clone()),
+            None => value::Value::Null,
+            Some(x) => value::Value::ExRef(x.clone()),
         },
-    })
+    }))
+}
+/// Turn an entry of a generated `get_export` table into an engine extern.
+pub fn export_extern<C: CtxSpec + AsContextMut<UserState = D> + 'static, D: AsMut<C>>(
+    ctx: &mut C,
+    (val, ty): (crate::func::Value<C>, MetaType),
+) -> anyhow::Result<Extern>
+where
+    C::ExternRef: Send + Sync + 'static,
+{
+    match translate_in(&val, ctx, &ty)? {
+        wasm_runtime_layer::Value::FuncRef(Some(f)) => Ok(Extern::Func(f)),
+        _ => anyhow::bail!("export is not a function"),
+    }
+}
+/// Register the exports `names` of a translated module under `module`, so
+/// engine modules instantiated with `imports` can link against it.
+/// `resolve` is normally the generated `FooImpl::get_export`.
+pub fn define_exports<'n, C: CtxSpec + AsContextMut<UserState = D> + 'static, D: AsMut<C>>(
+    ctx: &mut C,
+    imports: &mut Imports,
+    module: &str,
+    names: impl IntoIterator<Item = &'n str>,
+    mut resolve: impl FnMut(&mut C, &str) -> Option<(crate::func::Value<C>, MetaType)>,
+) -> anyhow::Result<()>
+where
+    C::ExternRef: Send + Sync + 'static,
+{
+    for name in names {
+        let Some(export) = resolve(ctx, name) else {
+            anyhow::bail!("no function export named {name}")
+        };
+        let e = export_extern(ctx, export)?;
+        imports.define(module, name, e);
+    }
+    Ok(())
+}
+/// A translated instance as engine code sees a `wasm_runtime_layer`
+/// instance: its function exports by name, as engine externs.
+#[derive(Clone)]
+pub struct WrlInstance {
+    exports: BTreeMap<String, Extern>,
+}
+impl WrlInstance {
+    /// Resolve the function exports listed in `exports` through `resolve`;
+    /// normally `FooImpl::exports()` and `FooImpl::get_export`.  Exports
+    /// whose signature has no engine type (`MetaType::Other`) are left out.
+    pub fn new<'n, C: CtxSpec + AsContextMut<UserState = D> + 'static, D: AsMut<C>>(
+        ctx: &mut C,
+        exports: impl IntoIterator<Item = (&'n str, MetaType)>,
+        mut resolve: impl FnMut(&mut C, &str) -> Option<(crate::func::Value<C>, MetaType)>,
+    ) -> anyhow::Result<Self>
+    where
+        C::ExternRef: Send + Sync + 'static,
+    {
+        let mut map = BTreeMap::new();
+        for (name, ty) in exports {
+            let MetaType::FunRef { params, returns } = &ty else {
+                continue;
+            };
+            if MetaType::func_type(params, returns).is_err() {
+                continue;
+            }
+            let Some(export) = resolve(ctx, name) else {
+                anyhow::bail!("no function export named {name}")
+            };
+            map.insert(name.to_string(), export_extern(ctx, export)?);
+        }
+        Ok(Self { exports: map })
+    }
+    pub fn get_export(&self, name: &str) -> Option<Extern> {
+        self.exports.get(name).cloned()
+    }
+    pub fn exports(&self) -> impl Iterator<Item = (&str, &Extern)> {
+        self.exports.iter().map(|(n, e)| (n.as_str(), e))
+    }
+    /// Register every export under `module`, so engine modules
+    /// instantiated with `imports` link against this instance.
+    pub fn define(&self, imports: &mut Imports, module: &str) {
+        for (name, e) in self.exports.iter() {
+            imports.define(module, name, e.clone());
+        }
+    }
+}
+/// Satisfy the import `module.name` of translated code with the engine
+/// function `imports` holds under that name, typed by the function's own
+/// signature: the body of `Linker::resolve` for a `Flags::DYN_IMPORTS`
+/// module, or a lookup for a host-trait import method.  `None` when
+/// `imports` has no function there.
+pub fn resolve_import<C: CtxSpec + AsContextMut<UserState = D> + 'static, D: AsMut<C>>(
+    ctx: &mut C,
+    imports: &Imports,
+    module: &str,
+    name: &str,
+) -> Option<crate::func::Value<C>>
+where
+    C::ExternRef: Send + Sync + 'static,
+{
+    let Some(Extern::Func(f)) = imports.get_export(module, name) else {
+        return None;
+    };
+    let ty = f.ty(ctx.as_context());
+    let meta = |tys: &[ValueType]| tys.iter().map(MetaType::from_wrl).collect();
+    import_func(ctx, &f, meta(ty.params()), meta(ty.results())).ok()
+}
+/// Satisfy an import of translated code with an engine function: the
+/// result can be stored and called through `func::call_ref` with the
+/// import's `tuple_list` types.
+pub fn import_func<C: CtxSpec + AsContextMut<UserState = D> + 'static, D: AsMut<C>>(
+    ctx: &mut C,
+    func: &wasm_runtime_layer::Func,
+    params: Vec<MetaType>,
+    returns: Vec<MetaType>,
+) -> anyhow::Result<crate::func::Value<C>>
+where
+    C::ExternRef: Send + Sync + 'static,
+{
+    translate_out(
+        &wasm_runtime_layer::Value::FuncRef(Some(func.clone())),
+        ctx,
+        &MetaType::FunRef { params, returns },
+    )
+}
+/// An engine's memory, usable wherever translated code wants a
+/// [`crate::Memory`] (e.g. from an imported-memory method), so both sides
+/// share the same bytes.  `store` is anything that reaches the memory's
+/// store, such as `&mut Store<…>` or a `StoreContextMut`.
+pub struct WrlMemory<S> {
+    pub memory: wasm_runtime_layer::Memory,
+    pub store: S,
+}
+impl<S: AsContextMut> crate::Memory for WrlMemory<S> {
+    fn read<'a>(&'a self, a: u64, s: u64) -> anyhow::Result<Box<dyn AsRef<[u8]> + 'a>> {
+        // Check before allocating: `s` comes straight from the guest.
+        match a.checked_add(s) {
+            Some(end) if end <= crate::Memory::size(self)? => {}
+            _ => return Err(Trap::OutOfBoundsMemory.into()),
+        }
+        let a = usize::try_from(a).map_err(|_| Trap::OutOfBoundsMemory)?;
+        let mut v = vec![0u8; usize::try_from(s).map_err(|_| Trap::OutOfBoundsMemory)?];
+        self.memory
+            .read(&self.store, a, &mut v)
+            .map_err(|_| Trap::OutOfBoundsMemory)?;
+        Ok(Box::new(v))
+    }
+    fn write(&mut self, a: u64, x: &[u8]) -> anyhow::Result<()> {
+        let a = usize::try_from(a).map_err(|_| Trap::OutOfBoundsMemory)?;
+        self.memory
+            .write(&mut self.store, a, x)
+            .map_err(|_| Trap::OutOfBoundsMemory)?;
+        Ok(())
+    }
+    fn size(&self) -> anyhow::Result<u64> {
+        Ok(self.memory.current_pages(&self.store) as u64 * 65536)
+    }
+    fn grow(&mut self, x: u64) -> anyhow::Result<()> {
+        let Ok(pages) = u32::try_from(x.div_ceil(65536)) else {
+            return Err(GrowRefused.into());
+        };
+        self.memory
+            .grow(&mut self.store, pages)
+            .map_err(|_| GrowRefused)?;
+        Ok(())
+    }
+    fn max_size(&self) -> Option<u64> {
+        let max = self.memory.ty(&self.store).maximum_pages()?;
+        Some(max as u64 * 65536)
+    }
 }
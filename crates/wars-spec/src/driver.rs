@@ -0,0 +1,649 @@
+//! Lowering of one `.wast` file into the `main.rs` of a driver crate.
+//!
+//! Every module in the file is translated with the chosen backend and wrapped
+//! in its own `mod mN` together with a minimal host.  Directives that need
+//! the module to run become statements in `main`, each printing one
+//! `wars-spec: <index> ok|fail <why>` line.
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    panic::AssertUnwindSafe,
+    sync::Arc,
+};
+
+use proc_macro2::{Literal, TokenStream};
+use quote::{format_ident, quote};
+use syn::Ident;
+use wars::{Flags, OptsCore, Plugin};
+use wasmparser::{ExternalKind, Parser, Payload, TypeRef};
+use wast::{
+    core::{NanPattern, V128Pattern, WastArgCore, WastRetCore},
+    parser::{self, ParseBuffer},
+    token::{F32, F64},
+    QuoteWat, Wast, WastArg, WastDirective, WastExecute, WastInvoke, WastRet,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Backend {
+    Waffle,
+    Wasmparser,
+}
+impl Backend {
+    pub const ALL: [Backend; 2] = [Backend::Waffle, Backend::Wasmparser];
+    pub fn name(self) -> &'static str {
+        match self {
+            Backend::Waffle => "waffle",
+            Backend::Wasmparser => "wasmparser",
+        }
+    }
+    pub fn from_name(a: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|b| b.name() == a)
+    }
+}
+
+/// The result of one directive.
+#[derive(Clone, Debug)]
+pub enum Outcome {
+    Pass,
+    Fail(String),
+    /// The harness (not the backend) cannot express this directive yet.
+    Skip(String),
+}
+
+/// A lowered `.wast` file.
+pub struct Lowered {
+    /// Directives decided during lowering, without running anything.
+    pub decided: BTreeMap<usize, Outcome>,
+    /// Directives the driver reports on when it runs.
+    pub pending: BTreeSet<usize>,
+    /// Source of the driver's `main.rs`.
+    pub driver: TokenStream,
+}
+
+/// Handles the `spectest.print*` imports, which are all no-ops.
+struct SpecTest;
+impl Plugin for SpecTest {
+    fn pre(&self, _: &mut OptsCore) -> anyhow::Result<()> {
+        Ok(())
+    }
+    fn import(
+        &self,
+        opts: &OptsCore,
+        module: &str,
+        name: &str,
+        _: Vec<TokenStream>,
+    ) -> anyhow::Result<Option<TokenStream>> {
+        let root = &opts.crate_path;
+        Ok((module == "spectest" && name.starts_with("print"))
+            .then(|| quote! { #root::func::ret(Ok(())) }))
+    }
+    fn post(&self, _: &OptsCore) -> anyhow::Result<TokenStream> {
+        Ok(quote! {})
+    }
+}
+
+/// Translate `bytes` with `backend`, turning errors and panics into a reason.
+pub fn translate(bytes: &[u8], backend: Backend) -> Result<TokenStream, String> {
+    let core = OptsCore {
+        crate_path: syn::parse_quote!(::wars_rt),
+        bytes,
+        name: format_ident!("M"),
+        // Bounded, so `assert_exhaustion` traps instead of overflowing the
+        // driver's stack.
+        flags: Flags::CALL_DEPTH,
+        embed: quote! {},
+        data: BTreeMap::new(),
+        roots: BTreeMap::new(),
+        plugins: vec![Arc::new(SpecTest)],
+        fuel: Default::default(),
+    };
+    let r = std::panic::catch_unwind(AssertUnwindSafe(move || match backend {
+        Backend::Waffle => core.inflate::<wars::LegacyPortalWaffleBackend>().render(),
+        Backend::Wasmparser => core.inflate::<wars::WasmparserBackend>().render(),
+    }));
+    let ts = match r {
+        Ok(Ok(ts)) => ts,
+        Ok(Err(e)) => return Err(format!("translation failed: {e:#}")),
+        Err(_) => return Err("backend panicked".to_owned()),
+    };
+    // One unparseable module would break the whole driver crate.
+    syn::parse2::<syn::File>(ts.clone())
+        .map_err(|e| format!("backend emitted unparseable Rust: {e}"))?;
+    Ok(ts)
+}
+
+/// Same rule as the generated code uses for export and import names.
+fn bindname(a: &str) -> String {
+    let mut v = String::new();
+    for k in a.chars() {
+        if k.is_alphanumeric() {
+            v.push(k)
+        } else {
+            v.push_str(&format!("_{}_", k as u32));
+        }
+    }
+    v
+}
+
+fn rust_ident(a: &str) -> Result<Ident, String> {
+    syn::parse_str::<Ident>(&bindname(a))
+        .map_err(|_| format!("export `{a}` is not a valid Rust identifier"))
+}
+
+#[derive(Default)]
+struct Exports {
+    funcs: BTreeSet<String>,
+    globals: BTreeSet<String>,
+}
+
+enum ModState {
+    Ready { k: usize, exports: Exports },
+    Broken(Outcome),
+}
+
+#[derive(Default)]
+struct Lowerer {
+    mods: Vec<ModState>,
+    current: Option<usize>,
+    named: BTreeMap<String, usize>,
+    items: Vec<TokenStream>,
+    stmts: Vec<TokenStream>,
+    decided: BTreeMap<usize, Outcome>,
+    pending: BTreeSet<usize>,
+}
+
+enum Expect<'a> {
+    Nothing,
+    Trap,
+    /// `Trap::StackExhausted` in particular.
+    Exhaustion,
+    Return(Vec<WastRet<'a>>),
+}
+
+/// What one lane (or scalar) of a result must look like.
+enum Lane {
+    Exact(u64),
+    CanonicalNan,
+    ArithmeticNan,
+}
+
+pub fn lower(src: &str, backend: Backend) -> anyhow::Result<Lowered> {
+    let buf = ParseBuffer::new(src)?;
+    let wast: Wast = parser::parse(&buf)?;
+    let mut l = Lowerer::default();
+    for (i, d) in wast.directives.into_iter().enumerate() {
+        l.directive(i, d, backend);
+    }
+    Ok(l.finish())
+}
+
+impl Lowerer {
+    fn directive(&mut self, i: usize, d: WastDirective<'_>, backend: Backend) {
+        match d {
+            WastDirective::Module(mut m) => {
+                let name = m.name().map(|n| n.name().to_owned());
+                let st = self.module(&mut m, backend);
+                match &st {
+                    ModState::Ready { k, .. } => {
+                        let body = self.instantiate(*k, true);
+                        self.driver(i, body);
+                    }
+                    ModState::Broken(o) => {
+                        self.decided.insert(i, o.clone());
+                    }
+                }
+                let id = self.mods.len();
+                self.mods.push(st);
+                self.current = Some(id);
+                if let Some(n) = name {
+                    self.named.insert(n, id);
+                }
+            }
+            WastDirective::Register { .. } => {}
+            WastDirective::Invoke(inv) => self.invoke(i, inv, Expect::Nothing),
+            WastDirective::AssertReturn { exec, results, .. } => match exec {
+                WastExecute::Invoke(inv) => self.invoke(i, inv, Expect::Return(results)),
+                WastExecute::Get { module, global, .. } => {
+                    self.get(i, module.map(|m| m.name()), global, results)
+                }
+                _ => self.skip(i, "assert_return on a module"),
+            },
+            WastDirective::AssertTrap { exec, .. } => match exec {
+                WastExecute::Invoke(inv) => self.invoke(i, inv, Expect::Trap),
+                WastExecute::Wat(w) => match self.module(&mut QuoteWat::Wat(w), backend) {
+                    ModState::Ready { k, .. } => {
+                        let body = self.instantiate(k, false);
+                        self.driver(i, body);
+                    }
+                    ModState::Broken(o) => {
+                        self.decided.insert(i, o);
+                    }
+                },
+                _ => self.skip(i, "assert_trap on a global"),
+            },
+            WastDirective::AssertInvalid { mut module, .. }
+            | WastDirective::AssertMalformed { mut module, .. } => {
+                let o = match module.encode() {
+                    // Rejected by the text parser; says nothing about wars.
+                    Err(_) => Outcome::Skip("malformed text".to_owned()),
+                    Ok(bytes) => match translate(&bytes, backend) {
+                        Ok(_) => Outcome::Fail("an invalid module was translated".to_owned()),
+                        Err(_) => Outcome::Pass,
+                    },
+                };
+                self.decided.insert(i, o);
+            }
+            WastDirective::AssertExhaustion { call, .. } => {
+                self.invoke(i, call, Expect::Exhaustion)
+            }
+            WastDirective::AssertUnlinkable { .. } => self.skip(i, "linking is not supported"),
+            _ => self.skip(i, "unsupported directive"),
+        }
+    }
+
+    fn skip(&mut self, i: usize, why: &str) {
+        self.decided.insert(i, Outcome::Skip(why.to_owned()));
+    }
+
+    fn driver(&mut self, i: usize, body: TokenStream) {
+        self.pending.insert(i);
+        let i = Literal::usize_unsuffixed(i);
+        self.stmts.push(quote! {
+            report(#i, ::std::panic::catch_unwind(::std::panic::AssertUnwindSafe(
+                || -> Result<(), String> { #body }
+            )));
+        });
+    }
+
+    /// Translate a module and emit `mod mN` for it.
+    fn module(&mut self, m: &mut QuoteWat<'_>, backend: Backend) -> ModState {
+        let bytes = match m.encode() {
+            Ok(b) => b,
+            Err(e) => return ModState::Broken(Outcome::Skip(format!("malformed text: {e}"))),
+        };
+        let (exports, imports_memory) = match scan(&bytes) {
+            Ok(a) => a,
+            Err(o) => return ModState::Broken(o),
+        };
+        let ts = match translate(&bytes, backend) {
+            Ok(ts) => ts,
+            Err(e) => return ModState::Broken(Outcome::Fail(e)),
+        };
+        let k = self.items.len();
+        let mk = format_ident!("m{k}");
+        let mem = imports_memory.then(|| {
+            quote! {
+                fn spectest_memory<'a>(&'a mut self) -> &'a mut (impl ::wars_rt::Memory + 'a) {
+                    &mut self.memory
+                }
+            }
+        });
+        self.items.push(quote! {
+            pub mod #mk {
+                #![allow(warnings)]
+                #ts
+                pub struct Host {
+                    data: MData<Host>,
+                    memory: ::std::vec::Vec<u8>,
+                    depth: ::wars_rt::depth::DepthLimit,
+                }
+                impl ::wars_rt::CtxSpec for Host {
+                    type ExternRef = ::core::convert::Infallible;
+                }
+                impl M for Host {
+                    type _ExternRef = ::core::convert::Infallible;
+                    fn data(&mut self) -> &mut MData<Self> {
+                        &mut self.data
+                    }
+                    #mem
+                }
+                impl ::wars_rt::CallDepth for Host {
+                    fn call_depth(&mut self) -> &mut ::wars_rt::depth::DepthLimit {
+                        &mut self.depth
+                    }
+                }
+                pub fn new() -> Host {
+                    Host {
+                        data: Default::default(),
+                        memory: ::std::vec![0u8; 65536],
+                        depth: Default::default(),
+                    }
+                }
+            }
+        });
+        ModState::Ready { k, exports }
+    }
+
+    fn instantiate(&self, k: usize, keep: bool) -> TokenStream {
+        let mk = format_ident!("m{k}");
+        let inst = format_ident!("inst{k}");
+        let init = quote! { <#mk::Host as #mk::MImpl>::init(&mut h) };
+        if keep {
+            quote! {
+                let mut h = #mk::new();
+                #init.map_err(|e| format!("instantiation failed: {e:#}"))?;
+                #inst = Some(h);
+                Ok(())
+            }
+        } else {
+            quote! {
+                let mut h = #mk::new();
+                expect_trap(#init)
+            }
+        }
+    }
+
+    /// The module an action refers to, or why it cannot run.
+    fn target(&self, name: Option<&str>) -> Result<(usize, &Exports), Outcome> {
+        let id = match name {
+            Some(n) => self.named.get(n).copied(),
+            None => self.current,
+        };
+        match id.map(|id| &self.mods[id]) {
+            Some(ModState::Ready { k, exports }) => Ok((*k, exports)),
+            Some(ModState::Broken(Outcome::Skip(why))) => Err(Outcome::Skip(why.clone())),
+            Some(ModState::Broken(_)) => Err(Outcome::Fail("module failed to translate".to_owned())),
+            None => Err(Outcome::Skip("no such module".to_owned())),
+        }
+    }
+
+    fn invoke(&mut self, i: usize, inv: WastInvoke<'_>, expect: Expect<'_>) {
+        let (k, exports) = match self.target(inv.module.map(|m| m.name())) {
+            Ok(a) => a,
+            Err(o) => {
+                self.decided.insert(i, o);
+                return;
+            }
+        };
+        if !exports.funcs.contains(inv.name) {
+            self.decided
+                .insert(i, Outcome::Fail(format!("no exported function `{}`", inv.name)));
+            return;
+        }
+        let Some(args) = inv.args.iter().map(arg).collect::<Option<Vec<_>>>() else {
+            return self.skip(i, "reference-typed arguments");
+        };
+        let mk = format_ident!("m{k}");
+        let inst = format_ident!("inst{k}");
+        let f = match rust_ident(inv.name) {
+            Ok(f) => f,
+            Err(e) => {
+                self.decided.insert(i, Outcome::Fail(e));
+                return;
+            }
+        };
+        let call = quote! {
+            let h = #inst.as_mut().ok_or_else(|| "module failed to instantiate".to_owned())?;
+            let r = ::wars_rt::_rexport::tramp::tramp(<#mk::Host as #mk::MImpl>::#f(
+                h,
+                ::wars_rt::_rexport::tuple_list::tuple_list!(#(#args),*),
+            ));
+        };
+        let body = match expect {
+            Expect::Nothing => quote! {
+                #call
+                r.map(|_| ()).map_err(|e| format!("{e:#}"))
+            },
+            Expect::Trap => quote! {
+                #call
+                expect_trap(r)
+            },
+            Expect::Exhaustion => quote! {
+                #call
+                match r {
+                    Err(e) if ::wars_rt::Trap::of(&e) == Some(::wars_rt::Trap::StackExhausted) => Ok(()),
+                    Err(e) => Err(format!("expected call stack exhaustion, got: {e:#}")),
+                    Ok(_) => Err("expected call stack exhaustion".to_owned()),
+                }
+            },
+            Expect::Return(results) => {
+                let Some(check) = check_results(&results) else {
+                    return self.skip(i, "reference-typed results");
+                };
+                quote! {
+                    #call
+                    let r = r.map_err(|e| format!("unexpected trap: {e:#}"))?;
+                    #check
+                }
+            }
+        };
+        self.driver(i, body);
+    }
+
+    fn get(&mut self, i: usize, module: Option<&str>, global: &str, results: Vec<WastRet<'_>>) {
+        let (k, exports) = match self.target(module) {
+            Ok(a) => a,
+            Err(o) => {
+                self.decided.insert(i, o);
+                return;
+            }
+        };
+        if !exports.globals.contains(global) {
+            self.decided
+                .insert(i, Outcome::Fail(format!("no exported global `{global}`")));
+            return;
+        }
+        let g = match rust_ident(global) {
+            Ok(g) => g,
+            Err(e) => {
+                self.decided.insert(i, Outcome::Fail(e));
+                return;
+            }
+        };
+        let Some(check) = check_results(&results) else {
+            return self.skip(i, "reference-typed results");
+        };
+        let mk = format_ident!("m{k}");
+        let inst = format_ident!("inst{k}");
+        self.driver(
+            i,
+            quote! {
+                let h = #inst.as_mut().ok_or_else(|| "module failed to instantiate".to_owned())?;
+                let r = ::wars_rt::_rexport::tuple_list::tuple_list!(*<#mk::Host as #mk::M>::#g(h));
+                #check
+            },
+        );
+    }
+
+    fn finish(self) -> Lowered {
+        let items = self.items;
+        let insts = (0..items.len()).map(|k| {
+            let mk = format_ident!("m{k}");
+            let inst = format_ident!("inst{k}");
+            quote! { let mut #inst: Option<#mk::Host> = None; }
+        });
+        let stmts = self.stmts;
+        Lowered {
+            decided: self.decided,
+            pending: self.pending,
+            driver: quote! {
+                #![allow(warnings)]
+                #(#items)*
+                fn report(i: usize, r: ::std::thread::Result<Result<(), String>>) {
+                    match r {
+                        Ok(Ok(())) => println!("wars-spec: {i} ok"),
+                        Ok(Err(e)) => println!("wars-spec: {i} fail {}", e.replace('\n', " ")),
+                        Err(_) => println!("wars-spec: {i} fail panicked"),
+                    }
+                }
+                fn expect_trap<T>(r: ::wars_rt::_rexport::anyhow::Result<T>) -> Result<(), String> {
+                    match r {
+                        Ok(_) => Err("expected a trap".to_owned()),
+                        Err(e) => match ::wars_rt::Trap::of(&e) {
+                            Some(_) => Ok(()),
+                            None => Err(format!("expected a trap, got: {e:#}")),
+                        },
+                    }
+                }
+                fn main() {
+                    ::std::panic::set_hook(Box::new(|_| {}));
+                    // Unoptimised frames are big; leave room for the full
+                    // `DepthLimit::DEFAULT_MAX` calls.
+                    ::std::thread::Builder::new()
+                        .stack_size(256 << 20)
+                        .spawn(run)
+                        .unwrap()
+                        .join()
+                        .unwrap();
+                }
+                fn run() {
+                    #(#insts)*
+                    #(#stmts)*
+                }
+            },
+        }
+    }
+}
+
+/// Exported names, and whether the module imports `spectest.memory`.
+/// Modules needing any other import are skipped, since there is no linker.
+fn scan(bytes: &[u8]) -> Result<(Exports, bool), Outcome> {
+    let mut exports = Exports::default();
+    let mut memory = false;
+    let bad = |e: wasmparser::BinaryReaderError| Outcome::Fail(format!("{e}"));
+    for p in Parser::new(0).parse_all(bytes) {
+        match p.map_err(bad)? {
+            Payload::ImportSection(r) => {
+                for imp in r {
+                    let imp = imp.map_err(bad)?;
+                    match (imp.module, imp.name, imp.ty) {
+                        ("spectest", n, TypeRef::Func(_)) if n.starts_with("print") => {}
+                        ("spectest", "memory", TypeRef::Memory(m)) if !m.shared => memory = true,
+                        (m, n, _) => {
+                            return Err(Outcome::Skip(format!("imports `{m}.{n}`")));
+                        }
+                    }
+                }
+            }
+            Payload::ExportSection(r) => {
+                for e in r {
+                    let e = e.map_err(bad)?;
+                    match e.kind {
+                        ExternalKind::Func => {
+                            exports.funcs.insert(e.name.to_owned());
+                        }
+                        ExternalKind::Global => {
+                            exports.globals.insert(e.name.to_owned());
+                        }
+                        _ => {}
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok((exports, memory))
+}
+
+fn arg(a: &WastArg<'_>) -> Option<TokenStream> {
+    let WastArg::Core(a) = a else { return None };
+    Some(match a {
+        WastArgCore::I32(v) => {
+            let v = Literal::u32_suffixed(*v as u32);
+            quote! { #v }
+        }
+        WastArgCore::I64(v) => {
+            let v = Literal::u64_suffixed(*v as u64);
+            quote! { #v }
+        }
+        WastArgCore::F32(F32 { bits }) => {
+            let v = Literal::u32_suffixed(*bits);
+            quote! { f32::from_bits(#v) }
+        }
+        WastArgCore::F64(F64 { bits }) => {
+            let v = Literal::u64_suffixed(*bits);
+            quote! { f64::from_bits(#v) }
+        }
+        WastArgCore::V128(c) => {
+            let v = Literal::u128_suffixed(u128::from_le_bytes(c.to_le_bytes()));
+            quote! { #v }
+        }
+        _ => return None,
+    })
+}
+
+/// Checks for `r`, a tuple list of results; `None` if a result can't be
+/// expressed yet.
+fn check_results(results: &[WastRet<'_>]) -> Option<TokenStream> {
+    let names: Vec<Ident> = (0..results.len()).map(|j| format_ident!("r{j}")).collect();
+    let checks = results
+        .iter()
+        .zip(names.iter())
+        .enumerate()
+        .map(|(j, (ret, r))| {
+            let WastRet::Core(ret) = ret else { return None };
+            let ok = check(r, ret)?;
+            Some(quote! {
+                if !(#ok) {
+                    return Err(format!("result {}: got {:?}", #j, #r));
+                }
+            })
+        })
+        .collect::<Option<Vec<_>>>()?;
+    Some(quote! {
+        let ::wars_rt::_rexport::tuple_list::tuple_list!(#(#names),*) = r;
+        #(#checks)*
+        Ok(())
+    })
+}
+
+fn check(r: &Ident, ret: &WastRetCore<'_>) -> Option<TokenStream> {
+    let nan32 = |p: &NanPattern<F32>| match p {
+        NanPattern::CanonicalNan => Lane::CanonicalNan,
+        NanPattern::ArithmeticNan => Lane::ArithmeticNan,
+        NanPattern::Value(F32 { bits }) => Lane::Exact(*bits as u64),
+    };
+    let nan64 = |p: &NanPattern<F64>| match p {
+        NanPattern::CanonicalNan => Lane::CanonicalNan,
+        NanPattern::ArithmeticNan => Lane::ArithmeticNan,
+        NanPattern::Value(F64 { bits }) => Lane::Exact(*bits),
+    };
+    Some(match ret {
+        WastRetCore::I32(v) => lane(quote! { (#r as u64) }, 32, &Lane::Exact(*v as u32 as u64)),
+        WastRetCore::I64(v) => lane(quote! { #r }, 64, &Lane::Exact(*v as u64)),
+        WastRetCore::F32(p) => lane(quote! { (#r.to_bits() as u64) }, 32, &nan32(p)),
+        WastRetCore::F64(p) => lane(quote! { #r.to_bits() }, 64, &nan64(p)),
+        WastRetCore::V128(p) => {
+            let lanes: Vec<(u32, Lane)> = match p {
+                V128Pattern::I8x16(v) => v.iter().map(|x| (8, Lane::Exact(*x as u8 as u64))).collect(),
+                V128Pattern::I16x8(v) => v.iter().map(|x| (16, Lane::Exact(*x as u16 as u64))).collect(),
+                V128Pattern::I32x4(v) => v.iter().map(|x| (32, Lane::Exact(*x as u32 as u64))).collect(),
+                V128Pattern::I64x2(v) => v.iter().map(|x| (64, Lane::Exact(*x as u64))).collect(),
+                V128Pattern::F32x4(v) => v.iter().map(|x| (32, nan32(x))).collect(),
+                V128Pattern::F64x2(v) => v.iter().map(|x| (64, nan64(x))).collect(),
+            };
+            let checks = lanes.iter().enumerate().map(|(l, (w, p))| {
+                let shift = Literal::u32_unsuffixed(l as u32 * w);
+                let mask = Literal::u64_suffixed(if *w == 64 { u64::MAX } else { (1u64 << w) - 1 });
+                lane(quote! { (((#r >> #shift) as u64) & #mask) }, *w, p)
+            });
+            quote! { true #(&& #checks)* }
+        }
+        WastRetCore::Either(alts) => {
+            let alts = alts.iter().map(|a| check(r, a)).collect::<Option<Vec<_>>>()?;
+            quote! { false #(|| (#alts))* }
+        }
+        _ => return None,
+    })
+}
+
+/// `bits` (a `u64` expression holding a `width`-bit lane) matches `p`.
+fn lane(bits: TokenStream, width: u32, p: &Lane) -> TokenStream {
+    let (canon_mask, canon, arith) = match width {
+        32 => (0x7fff_ffffu64, 0x7fc0_0000u64, 0x7fc0_0000u64),
+        _ => (0x7fff_ffff_ffff_ffff, 0x7ff8_0000_0000_0000, 0x7ff8_0000_0000_0000),
+    };
+    match p {
+        Lane::Exact(v) => {
+            let v = Literal::u64_suffixed(*v);
+            quote! { (#bits == #v) }
+        }
+        Lane::CanonicalNan => {
+            let (m, c) = (Literal::u64_suffixed(canon_mask), Literal::u64_suffixed(canon));
+            quote! { ((#bits & #m) == #c) }
+        }
+        Lane::ArithmeticNan => {
+            let a = Literal::u64_suffixed(arith);
+            quote! { ((#bits & #a) == #a) }
+        }
+    }
+}
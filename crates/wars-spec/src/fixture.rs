@@ -0,0 +1,137 @@
+//! Hand-written end-to-end checks for what the spec suite cannot reach.
+//!
+//! A fixture translates one or more modules with the flags and plugins it
+//! needs, splices them into a crate next to a host and a `main` that asserts
+//! on them, and builds and runs that crate the same way the spec drivers are.
+use std::{collections::BTreeMap, path::Path, sync::Arc};
+
+use proc_macro2::TokenStream;
+use quote::{format_ident, quote};
+use wars::{Flags, OptsCore, Plugin};
+use wast::{
+    parser::{self, ParseBuffer},
+    Wat,
+};
+
+use crate::Backend;
+
+/// One module of a fixture.
+pub struct Module<'a> {
+    /// Module text.
+    pub wat: &'a str,
+    /// Name of the generated host trait (`M` for `M`, `MImpl`, `MData`).
+    pub name: &'a str,
+    pub flags: Flags,
+    pub plugins: Vec<Arc<dyn Plugin + 'a>>,
+}
+impl<'a> Module<'a> {
+    pub fn new(wat: &'a str, name: &'a str, flags: Flags) -> Self {
+        Self {
+            wat,
+            name,
+            flags,
+            plugins: vec![],
+        }
+    }
+    pub fn plugin(mut self, p: impl Plugin + 'a) -> Self {
+        self.plugins.push(Arc::new(p));
+        self
+    }
+
+    /// Translate with `backend`.
+    pub fn translate(self, backend: Backend) -> anyhow::Result<TokenStream> {
+        let buf = ParseBuffer::new(self.wat)?;
+        let bytes = {
+            let mut wat = parser::parse::<Wat>(&buf)?;
+            wat.encode()?
+        };
+        let core = OptsCore {
+            crate_path: syn::parse_quote!(::wars_rt),
+            bytes: &bytes,
+            name: format_ident!("{}", self.name),
+            flags: self.flags,
+            embed: quote! {},
+            data: BTreeMap::new(),
+            roots: BTreeMap::new(),
+            plugins: self.plugins,
+            fuel: Default::default(),
+        };
+        match backend {
+            Backend::Waffle => core.inflate::<wars::LegacyPortalWaffleBackend>().render(),
+            Backend::Wasmparser => core.inflate::<wars::WasmparserBackend>().render(),
+        }
+    }
+}
+
+/// The module a fixture's translated code `ts` lives in, named after the
+/// trait `name` lower-cased (`M` is `mod m`), with a `Host` owning the
+/// `*Data` plus any `fields`, and any `impls` the fixture needs on top.
+pub fn host(ts: TokenStream, name: &str, fields: TokenStream, impls: TokenStream) -> TokenStream {
+    let m = format_ident!("{}", name.to_lowercase());
+    let t = format_ident!("{name}");
+    let data = format_ident!("{name}Data");
+    quote! {
+        mod #m {
+            #ts
+            pub struct Host {
+                pub data: #data<Host>,
+                #fields
+            }
+            impl ::wars_rt::CtxSpec for Host {
+                type ExternRef = ::core::convert::Infallible;
+            }
+            impl #t for Host {
+                type _ExternRef = ::core::convert::Infallible;
+                fn data(&mut self) -> &mut #data<Self> {
+                    &mut self.data
+                }
+            }
+            #impls
+        }
+    }
+}
+
+/// A `block_on` for async fixtures: generated futures never wait on
+/// anything external, so polling with a no-op waker until ready suffices.
+pub fn block_on() -> TokenStream {
+    quote! {
+        fn block_on<F: ::core::future::Future>(f: F) -> F::Output {
+            struct Noop;
+            impl ::std::task::Wake for Noop {
+                fn wake(self: ::std::sync::Arc<Self>) {}
+            }
+            let waker = ::std::task::Waker::from(::std::sync::Arc::new(Noop));
+            let mut cx = ::core::task::Context::from_waker(&waker);
+            let mut f = ::core::pin::pin!(f);
+            loop {
+                if let ::core::task::Poll::Ready(r) =
+                    ::core::future::Future::poll(f.as_mut(), &mut cx)
+                {
+                    return r;
+                }
+            }
+        }
+    }
+}
+
+/// Build `main` into the crate `work_dir/fixtures/<name>` with `wars-rt`'s
+/// `features` and the extra `[dependencies]` lines `deps`, run it, and fail
+/// unless it exits successfully.
+pub fn run(
+    work_dir: &Path,
+    name: &str,
+    features: &[&str],
+    deps: &[&str],
+    main: TokenStream,
+) -> anyhow::Result<String> {
+    let dir = work_dir.join("fixtures").join(name);
+    let out = crate::cargo_run(work_dir, &dir, features, deps, main.to_string())?;
+    let stdout = String::from_utf8_lossy(&out.stdout).into_owned();
+    if !out.status.success() {
+        let err = String::from_utf8_lossy(&out.stderr);
+        let err: Vec<&str> = err.lines().collect();
+        let tail = err[err.len().saturating_sub(40)..].join("\n");
+        anyhow::bail!("fixture `{name}` failed:\n{stdout}{tail}");
+    }
+    Ok(stdout)
+}
@@ -0,0 +1,365 @@
+//! Runs the WebAssembly spec test suite through both wars backends.
+//!
+//! Each `.wast` file under `testsuite/` is lowered once per backend into a
+//! driver crate (see [`driver`]), which cargo then builds and runs.  Some
+//! directives are scored during lowering without running anything:
+//! `assert_invalid`, modules that fail to translate, and directives the
+//! harness cannot express yet.
+//!
+//! Features the suite cannot reach, such as dynamic imports, are covered by
+//! the hand-written checks in [`fixture`] instead (`--test fixtures`).
+//!
+//! Knobs, all environment variables:
+//!
+//! | Variable | Effect |
+//! |----------|--------|
+//! | `WARS_SPEC_FILTER` | only files whose path contains this string |
+//! | `WARS_SPEC_BACKEND` | `waffle` or `wasmparser`; both by default |
+//! | `WARS_SPEC_VERBOSE` | print every failure |
+//! | `WARS_SPEC_BLESS` | rewrite `baseline.txt` from this run |
+//!
+//! A run fails when any proposal passes fewer directives on a backend than
+//! `baseline.txt` records; bless the new counts after an intended change.
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+pub mod driver;
+pub mod fixture;
+pub use driver::{Backend, Outcome};
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Tally {
+    pub pass: usize,
+    pub fail: usize,
+    pub skip: usize,
+}
+impl Tally {
+    fn add(&mut self, o: &Outcome) {
+        match o {
+            Outcome::Pass => self.pass += 1,
+            Outcome::Fail(_) => self.fail += 1,
+            Outcome::Skip(_) => self.skip += 1,
+        }
+    }
+}
+
+/// Pass/fail/skip counts keyed by proposal, then backend.
+pub type Report = BTreeMap<(String, Backend), Tally>;
+
+/// Pass counts a run may not drop below, keyed like [`Report`].
+pub type Baseline = BTreeMap<(String, Backend), usize>;
+
+pub struct Config {
+    /// Root of the vendored suite; `proposals/<name>/` subdirectories are
+    /// reported per proposal, everything else as `core`.
+    pub testsuite: PathBuf,
+    /// Where driver crates and their shared target directory go.
+    pub work_dir: PathBuf,
+    pub filter: Option<String>,
+    pub backends: Vec<Backend>,
+    pub verbose: bool,
+    /// The checked-in [`Baseline`].
+    pub baseline: PathBuf,
+    /// Rewrite `baseline` instead of checking against it.
+    pub bless: bool,
+}
+impl Config {
+    pub fn from_env(work_dir: impl Into<PathBuf>) -> Self {
+        let backends = match std::env::var("WARS_SPEC_BACKEND") {
+            Ok(b) => Backend::from_name(&b).into_iter().collect(),
+            Err(_) => Backend::ALL.to_vec(),
+        };
+        let manifest = Path::new(env!("CARGO_MANIFEST_DIR"));
+        Self {
+            testsuite: manifest.join("testsuite"),
+            work_dir: work_dir.into(),
+            filter: std::env::var("WARS_SPEC_FILTER").ok(),
+            backends,
+            verbose: std::env::var_os("WARS_SPEC_VERBOSE").is_some(),
+            baseline: manifest.join("baseline.txt"),
+            bless: std::env::var_os("WARS_SPEC_BLESS").is_some(),
+        }
+    }
+}
+
+fn wast_files(dir: &Path, out: &mut Vec<PathBuf>) -> std::io::Result<()> {
+    for e in std::fs::read_dir(dir)? {
+        let p = e?.path();
+        if p.is_dir() {
+            wast_files(&p, out)?;
+        } else if p.extension().is_some_and(|x| x == "wast") {
+            out.push(p);
+        }
+    }
+    Ok(())
+}
+
+fn proposal(rel: &Path) -> String {
+    let mut c = rel.components().map(|c| c.as_os_str().to_string_lossy());
+    match (c.next(), c.next()) {
+        (Some(p), Some(name)) if p == "proposals" => name.into_owned(),
+        _ => "core".to_owned(),
+    }
+}
+
+/// Silences the panic hook until dropped, then puts the previous one back,
+/// however [`run`] returns.
+struct QuietPanics(Option<Box<dyn Fn(&std::panic::PanicHookInfo<'_>) + Send + Sync>>);
+impl QuietPanics {
+    fn new() -> Self {
+        let hook = std::panic::take_hook();
+        std::panic::set_hook(Box::new(|_| {}));
+        Self(Some(hook))
+    }
+}
+impl Drop for QuietPanics {
+    fn drop(&mut self) {
+        // The hook cannot be replaced while unwinding.
+        if let (Some(hook), false) = (self.0.take(), std::thread::panicking()) {
+            std::panic::set_hook(hook);
+        }
+    }
+}
+
+pub fn run(cfg: &Config) -> anyhow::Result<Report> {
+    let mut files = vec![];
+    if cfg.testsuite.is_dir() {
+        wast_files(&cfg.testsuite, &mut files)?;
+    }
+    // An empty suite would pass vacuously.
+    if files.is_empty() {
+        anyhow::bail!(
+            "no .wast files under {}; see testsuite/README.md",
+            cfg.testsuite.display()
+        );
+    }
+    files.sort();
+    if let Some(f) = &cfg.filter {
+        files.retain(|p| p.to_string_lossy().contains(f.as_str()));
+    }
+    let mut report = Report::new();
+    // Backends panic on unsupported input; those panics are scored, not shown.
+    let _quiet = QuietPanics::new();
+    for path in files.iter() {
+        let rel = path.strip_prefix(&cfg.testsuite).unwrap_or(path);
+        let prop = proposal(rel);
+        let src = std::fs::read_to_string(path)?;
+        for &backend in cfg.backends.iter() {
+            let tally = report.entry((prop.clone(), backend)).or_default();
+            let outcomes = match driver::lower(&src, backend) {
+                Ok(l) => run_lowered(cfg, rel, backend, l),
+                Err(e) => BTreeMap::from([(0, Outcome::Fail(format!("unparseable wast: {e}")))]),
+            };
+            for (i, o) in outcomes.iter() {
+                tally.add(o);
+                if let (true, Outcome::Fail(why)) = (cfg.verbose, o) {
+                    println!("FAIL {} [{}] #{i}: {why}", rel.display(), backend.name());
+                }
+            }
+        }
+    }
+    Ok(report)
+}
+
+fn run_lowered(
+    cfg: &Config,
+    rel: &Path,
+    backend: Backend,
+    l: driver::Lowered,
+) -> BTreeMap<usize, Outcome> {
+    let mut outcomes = l.decided;
+    if l.pending.is_empty() {
+        return outcomes;
+    }
+    let dir = cfg
+        .work_dir
+        .join(rel.with_extension(""))
+        .join(backend.name());
+    let reported = match build_and_run(cfg, &dir, l.driver.to_string()) {
+        Ok(r) => r,
+        Err(e) => {
+            if cfg.verbose {
+                println!("driver {}: {e}", dir.display());
+            }
+            BTreeMap::new()
+        }
+    };
+    for i in l.pending {
+        let o = reported.get(&i).cloned().unwrap_or_else(|| {
+            Outcome::Fail("no result from driver (build failure or crash)".to_owned())
+        });
+        outcomes.insert(i, o);
+    }
+    outcomes
+}
+
+/// Write the driver crate to `dir`, `cargo run` it and parse its report lines.
+fn build_and_run(
+    cfg: &Config,
+    dir: &Path,
+    main: String,
+) -> anyhow::Result<BTreeMap<usize, Outcome>> {
+    let out = cargo_run(&cfg.work_dir, dir, &["std"], &[], main)?;
+    let mut reported = BTreeMap::new();
+    for line in String::from_utf8_lossy(&out.stdout).lines() {
+        let Some(rest) = line.strip_prefix("wars-spec: ") else {
+            continue;
+        };
+        let mut parts = rest.splitn(3, ' ');
+        let (Some(i), Some(kind)) = (parts.next(), parts.next()) else {
+            continue;
+        };
+        let Ok(i) = i.parse::<usize>() else { continue };
+        let o = match kind {
+            "ok" => Outcome::Pass,
+            _ => Outcome::Fail(parts.next().unwrap_or_default().to_owned()),
+        };
+        reported.insert(i, o);
+    }
+    if reported.is_empty() && !out.status.success() {
+        let err = String::from_utf8_lossy(&out.stderr);
+        anyhow::bail!("{}", err.lines().take(20).collect::<Vec<_>>().join("\n"));
+    }
+    Ok(reported)
+}
+
+/// Write a crate with `main` as its `main.rs`, a `wars-rt` dependency with
+/// `features` and the extra `[dependencies]` lines `deps` to `dir`, then
+/// `cargo run` it.  All such crates share `work_dir/target`.
+///
+/// The crate starts from the workspace lockfile and builds `--offline`: its
+/// dependencies are all dev-dependencies of this crate, so `cargo test` has
+/// already fetched the same versions.
+fn cargo_run(
+    work_dir: &Path,
+    dir: &Path,
+    features: &[&str],
+    deps: &[&str],
+    main: String,
+) -> anyhow::Result<std::process::Output> {
+    std::fs::create_dir_all(dir.join("src"))?;
+    let rt = Path::new(env!("CARGO_MANIFEST_DIR")).join("../wars-rt");
+    std::fs::write(
+        dir.join("Cargo.toml"),
+        format!(
+            "[package]\nname = \"wars-spec-driver\"\nversion = \"0.0.0\"\nedition = \"2021\"\npublish = false\n\n\
+             [dependencies]\nwars-rt = {{ path = {:?}, features = {:?} }}\n{}\n\n[workspace]\n",
+            rt.display().to_string(),
+            features,
+            deps.join("\n")
+        ),
+    )?;
+    std::fs::write(dir.join("src/main.rs"), main)?;
+    let lock = Path::new(env!("CARGO_MANIFEST_DIR")).join("../../Cargo.lock");
+    if lock.exists() {
+        std::fs::copy(&lock, dir.join("Cargo.lock"))?;
+    }
+    let cargo = std::env::var("CARGO").unwrap_or_else(|_| "cargo".to_owned());
+    Ok(Command::new(cargo)
+        .args(["run", "--quiet", "--offline", "--manifest-path"])
+        .arg(dir.join("Cargo.toml"))
+        .env("CARGO_TARGET_DIR", work_dir.join("target"))
+        .output()?)
+}
+
+pub fn print_report(report: &Report) {
+    if report.is_empty() {
+        println!("wars-spec: no .wast files matched WARS_SPEC_FILTER");
+        return;
+    }
+    println!(
+        "{:<28} {:<11} {:>6} {:>6} {:>6} {:>7}",
+        "proposal", "backend", "pass", "fail", "skip", "rate"
+    );
+    for ((prop, backend), t) in report.iter() {
+        let run = t.pass + t.fail;
+        let rate = if run == 0 {
+            0.0
+        } else {
+            100.0 * t.pass as f64 / run as f64
+        };
+        println!(
+            "{:<28} {:<11} {:>6} {:>6} {:>6} {:>6.1}%",
+            prop,
+            backend.name(),
+            t.pass,
+            t.fail,
+            t.skip,
+            rate
+        );
+    }
+}
+
+/// Parse a baseline: one `proposal backend pass` row per line, `#` comments.
+pub fn read_baseline(path: &Path) -> anyhow::Result<Baseline> {
+    let mut baseline = Baseline::new();
+    for (n, line) in std::fs::read_to_string(path)?.lines().enumerate() {
+        let line = line.split('#').next().unwrap_or_default().trim();
+        if line.is_empty() {
+            continue;
+        }
+        let bad = || anyhow::anyhow!("{}:{}: bad baseline row `{line}`", path.display(), n + 1);
+        let [prop, backend, pass] = line.split_whitespace().collect::<Vec<_>>()[..] else {
+            return Err(bad());
+        };
+        let backend = Backend::from_name(backend).ok_or_else(bad)?;
+        baseline.insert((prop.to_owned(), backend), pass.parse().map_err(|_| bad())?);
+    }
+    Ok(baseline)
+}
+
+/// Rows of `baseline` that `report` passes fewer directives of, and rows of
+/// `report` the baseline has no count for yet.  Backends `cfg` did not run
+/// are left out, and a filtered run, which only saw part of each proposal,
+/// has nothing to compare.
+pub fn regressions(cfg: &Config, baseline: &Baseline, report: &Report) -> Vec<String> {
+    if cfg.filter.is_some() {
+        return vec![];
+    }
+    let below = baseline
+        .iter()
+        .filter(|((_, backend), _)| cfg.backends.contains(backend))
+        .filter_map(|((prop, backend), &want)| {
+            let got = report.get(&(prop.clone(), *backend)).map_or(0, |t| t.pass);
+            (got < want).then(|| format!("{prop} [{}]: {got} passed, baseline {want}", backend.name()))
+        });
+    let missing = report
+        .keys()
+        .filter(|k| !baseline.contains_key(*k))
+        .map(|(prop, backend)| format!("{prop} [{}]: no baseline row", backend.name()));
+    below.chain(missing).collect()
+}
+
+/// Replace the rows of `cfg.baseline` that this run covers with its pass
+/// counts.  A filtered run only saw part of each proposal, so it may not
+/// bless.
+pub fn bless(cfg: &Config, report: &Report) -> anyhow::Result<()> {
+    if cfg.filter.is_some() {
+        anyhow::bail!("WARS_SPEC_BLESS needs a full run; unset WARS_SPEC_FILTER");
+    }
+    let mut baseline = if cfg.baseline.exists() {
+        read_baseline(&cfg.baseline)?
+    } else {
+        Baseline::new()
+    };
+    baseline.retain(|(_, backend), _| !cfg.backends.contains(backend));
+    for ((prop, backend), t) in report.iter() {
+        baseline.insert((prop.clone(), *backend), t.pass);
+    }
+    let mut out = String::from(BASELINE_HEADER);
+    for ((prop, backend), pass) in baseline.iter() {
+        out += &format!("{prop} {} {pass}\n", backend.name());
+    }
+    std::fs::write(&cfg.baseline, out)?;
+    Ok(())
+}
+
+const BASELINE_HEADER: &str = "\
+# Spec-suite pass counts per proposal and backend; `cargo test -p wars-spec
+# --test spec` fails when a run drops below any of them.  Regenerate with
+# WARS_SPEC_BLESS=1 after an intended change.
+# proposal backend pass
+";
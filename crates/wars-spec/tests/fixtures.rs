@@ -0,0 +1,605 @@
+//! `cargo test -p wars-spec --test fixtures`: generated code for features
+//! the spec suite cannot reach, translated, compiled and run.
+use proc_macro2::TokenStream;
+use quote::quote;
+use wars::Flags;
+use wars_spec::{
+    fixture::{self, Module},
+    Backend,
+};
+
+fn work_dir() -> &'static str {
+    env!("CARGO_TARGET_TMPDIR")
+}
+
+/// `::wars_rt::func` or `::wars_rt::func::unsync`, as the generated code uses.
+fn fp(flags: Flags) -> TokenStream {
+    if flags.contains(Flags::ASYNC) {
+        quote! { ::wars_rt::func::unsync }
+    } else {
+        quote! { ::wars_rt::func }
+    }
+}
+
+/// `call` on a generated method: trampolined, or awaited on `block_on`.
+fn drive(flags: Flags, call: TokenStream) -> TokenStream {
+    if flags.contains(Flags::ASYNC) {
+        quote! { block_on(#call.go()) }
+    } else {
+        quote! { ::wars_rt::_rexport::tramp::tramp(#call) }
+    }
+}
+
+/// `init` is a plain `Result` unless the module is async.
+fn init(flags: Flags, h: TokenStream, m: TokenStream) -> TokenStream {
+    let call = quote! { <#m::Host as #m::MImpl>::init(&mut #h) };
+    if flags.contains(Flags::ASYNC) {
+        quote! { block_on(#call.go()) }
+    } else {
+        call
+    }
+}
+
+const DYN_IMPORTS: &str = r#"
+(module
+  (import "env" "add" (func $add (param i32 i32) (result i32)))
+  (func (export "call") (param i32) (result i32)
+    local.get 0
+    i32.const 1
+    call $add)
+  (func (export "tail") (param i32) (result i32)
+    local.get 0
+    i32.const 2
+    return_call $add))
+"#;
+
+/// Calls and tail calls to an import resolved through `Linker` at init.
+fn dyn_imports(backend: Backend, flags: Flags) -> anyhow::Result<()> {
+    let flags = flags | Flags::DYN_IMPORTS;
+    let ts = Module::new(DYN_IMPORTS, "M", flags).translate(backend)?;
+    let fp = fp(flags);
+    let tl = quote! { ::wars_rt::_rexport::tuple_list };
+    let init_m = init(flags, quote! { h }, quote! { m });
+    let init_bare = init(flags, quote! { bare }, quote! { m });
+    let call = drive(flags, quote! { <m::Host as m::MImpl>::call(&mut h, #tl::tuple_list!(41u32)) });
+    let tail = drive(flags, quote! { <m::Host as m::MImpl>::tail(&mut h, #tl::tuple_list!(40u32)) });
+    let host = fixture::host(
+        ts,
+        "M",
+        quote! { pub link: bool, },
+        quote! {
+            impl ::wars_rt::Linker<#fp::Value<Host>> for Host {
+                fn resolve(&mut self, module: &str, name: &str) -> Option<#fp::Value<Host>> {
+                    if !self.link || (module, name) != ("env", "add") {
+                        return None;
+                    }
+                    let f = #fp::da::<
+                        #tl::tuple_list_type!(u32, u32),
+                        #tl::tuple_list_type!(u32),
+                        Host,
+                        _,
+                    >(|_, (a, (b, ()))| #fp::ret(Ok(#tl::tuple_list!(a.wrapping_add(b)))));
+                    Some(#fp::cast::<_, #fp::Value<Host>, Host>(f))
+                }
+            }
+        },
+    );
+    let block_on = fixture::block_on();
+    let main = quote! {
+        #![allow(warnings)]
+        use ::wars_rt::func::unsync::UnwrappedAsyncRec;
+        #host
+        #block_on
+        fn main() {
+            let mut bare = m::Host { data: Default::default(), link: false };
+            let e = #init_bare.expect_err("init must fail on an unresolved import");
+            assert!(e.downcast_ref::<::wars_rt::linker::Unresolved>().is_some(), "{e:#}");
+
+            let mut h = m::Host { data: Default::default(), link: true };
+            #init_m.unwrap();
+            assert_eq!(#call.unwrap(), #tl::tuple_list!(42u32));
+            assert_eq!(#tail.unwrap(), #tl::tuple_list!(42u32));
+        }
+    };
+    let name = format!("dyn-imports-{}-{}", backend.name(), flags.bits());
+    fixture::run(work_dir().as_ref(), &name, &["std"], &[], main)?;
+    Ok(())
+}
+
+#[test]
+fn dyn_imports_sync() -> anyhow::Result<()> {
+    for backend in Backend::ALL {
+        dyn_imports(backend, Flags::empty())?;
+    }
+    Ok(())
+}
+
+#[test]
+fn dyn_imports_async() -> anyhow::Result<()> {
+    for backend in Backend::ALL {
+        dyn_imports(backend, Flags::ASYNC)?;
+    }
+    Ok(())
+}
+
+const PROVIDER: &str = r#"
+(module
+  (memory (export "memory") 1)
+  (table (export "table") 2 funcref)
+  (elem (i32.const 0) $double)
+  (elem declare func $square)
+  (func $double (export "double") (param i32) (result i32)
+    local.get 0
+    i32.const 2
+    i32.mul)
+  (func $square (param i32) (result i32)
+    local.get 0
+    local.get 0
+    i32.mul)
+  (func (export "arm")
+    i32.const 1
+    ref.func $square
+    table.set)
+  (func (export "store") (param i32 i32)
+    local.get 0
+    local.get 1
+    i32.store))
+"#;
+
+const IMPORTER: &str = r#"
+(module
+  (type $i2i (func (param i32) (result i32)))
+  (import "lib" "double" (func $double (param i32) (result i32)))
+  (import "lib" "memory" (memory 1))
+  (import "lib" "table" (table 2 funcref))
+  (func (export "dispatch") (param i32 i32) (result i32)
+    local.get 1
+    local.get 0
+    call_indirect (type $i2i))
+  (func (export "quad") (param i32) (result i32)
+    local.get 0
+    call $double
+    call $double)
+  (func (export "tail") (param i32) (result i32)
+    local.get 0
+    return_call $double)
+  (func (export "load") (param i32) (result i32)
+    local.get 0
+    i32.load))
+"#;
+
+/// Two translated modules wired together with `wars::link::Link`.
+fn link(backend: Backend) -> anyhow::Result<()> {
+    let lib = Module::new(PROVIDER, "Lib", Flags::empty()).translate(backend)?;
+    let app = Module::new(IMPORTER, "App", Flags::empty())
+        .plugin(wars::link::Link::new(
+            "lib",
+            syn::parse_quote!(crate::lib::Lib),
+            syn::parse_quote!(crate::lib::Host),
+        ))
+        .translate(backend)?;
+    let lib = fixture::host(lib, "Lib", quote! {}, quote! {});
+    let app = fixture::host(
+        app,
+        "App",
+        quote! { pub lib: crate::lib::Host, },
+        quote! {
+            impl ::core::convert::AsMut<crate::lib::Host> for Host {
+                fn as_mut(&mut self) -> &mut crate::lib::Host {
+                    &mut self.lib
+                }
+            }
+        },
+    );
+    let tl = quote! { ::wars_rt::_rexport::tuple_list };
+    let tramp = quote! { ::wars_rt::_rexport::tramp::tramp };
+    let main = quote! {
+        #![allow(warnings)]
+        #lib
+        #app
+        use app::AppImpl;
+        use lib::LibImpl;
+        fn main() {
+            let mut h = app::Host {
+                data: Default::default(),
+                lib: lib::Host { data: Default::default() },
+            };
+            h.lib.init().unwrap();
+            h.init().unwrap();
+            assert_eq!(#tramp(h.quad(#tl::tuple_list!(3u32))).unwrap(), #tl::tuple_list!(12u32));
+            assert_eq!(#tramp(h.tail(#tl::tuple_list!(7u32))).unwrap(), #tl::tuple_list!(14u32));
+            // Both modules see the provider's memory.
+            #tramp(h.lib.store(#tl::tuple_list!(16u32, 99u32))).unwrap();
+            assert_eq!(#tramp(h.load(#tl::tuple_list!(16u32))).unwrap(), #tl::tuple_list!(99u32));
+            // The importer calls the provider's table entries, and sees
+            // the provider fill slot 1 later on.
+            assert_eq!(#tramp(h.dispatch(#tl::tuple_list!(0u32, 5u32))).unwrap(), #tl::tuple_list!(10u32));
+            let e = #tramp(h.dispatch(#tl::tuple_list!(1u32, 5u32))).expect_err("slot 1 starts out null");
+            assert_eq!(e.downcast_ref::<::wars_rt::Trap>(), Some(&::wars_rt::Trap::NullReference));
+            #tramp(h.lib.arm(())).unwrap();
+            assert_eq!(#tramp(h.dispatch(#tl::tuple_list!(1u32, 5u32))).unwrap(), #tl::tuple_list!(25u32));
+        }
+    };
+    let name = format!("link-{}", backend.name());
+    fixture::run(work_dir().as_ref(), &name, &["std"], &[], main)?;
+    Ok(())
+}
+
+#[test]
+fn link_two_modules() -> anyhow::Result<()> {
+    for backend in Backend::ALL {
+        link(backend)?;
+    }
+    Ok(())
+}
+
+const EXPORTS: &str = r#"
+(module
+  (func (export "sub") (param i32 i32) (result i32 i32)
+    local.get 0
+    local.get 1
+    i32.sub
+    local.get 0)
+  (func (export "id") (param funcref) (result funcref)
+    local.get 0))
+"#;
+
+/// `call_export` in declaration order, its arity check, and `exports()`
+/// listing an export with no finer `MetaType`.
+fn call_export(backend: Backend, flags: Flags) -> anyhow::Result<()> {
+    let ts = Module::new(EXPORTS, "M", flags).translate(backend)?;
+    let host = fixture::host(ts, "M", quote! {}, quote! {});
+    let fp = fp(flags);
+    let init_h = init(flags, quote! { h }, quote! { m });
+    let i32v = |n: u32| quote! { #fp::Value(::wars_rt::func::value::Value::I32(#n)) };
+    let (a, b) = (i32v(10), i32v(3));
+    let sub = drive(flags, quote! { h.call_export("sub", vec![#a, #b]) });
+    let short = drive(flags, quote! { h.call_export("sub", vec![#a]) });
+    let block_on = fixture::block_on();
+    let main = quote! {
+        #![allow(warnings)]
+        use ::wars_rt::func::unsync::UnwrappedAsyncRec;
+        use ::wars_rt::func::value::Value as V;
+        use ::wars_rt::MetaType;
+        use m::MImpl;
+        #host
+        #block_on
+        fn main() {
+            let mut h = m::Host { data: Default::default() };
+            #init_h.unwrap();
+            let r = #sub.unwrap();
+            assert!(matches!(r.as_slice(), [#fp::Value(V::I32(7)), #fp::Value(V::I32(10))]));
+            let e = #short.expect_err("a short argument list must trap");
+            assert_eq!(e.downcast_ref::<::wars_rt::Trap>(), Some(&::wars_rt::Trap::IndirectCallTypeMismatch));
+            let exports = <m::Host as MImpl>::exports();
+            let id = MetaType::FunRef { params: vec![MetaType::Other], returns: vec![MetaType::Other] };
+            assert!(exports.contains(&("id", id)), "{exports:?}");
+        }
+    };
+    let name = format!("call-export-{}-{}", backend.name(), flags.bits());
+    fixture::run(work_dir().as_ref(), &name, &["std"], &[], main)?;
+    Ok(())
+}
+
+#[test]
+fn call_export_sync() -> anyhow::Result<()> {
+    for backend in Backend::ALL {
+        call_export(backend, Flags::empty())?;
+    }
+    Ok(())
+}
+
+#[test]
+fn call_export_async() -> anyhow::Result<()> {
+    for backend in Backend::ALL {
+        call_export(backend, Flags::ASYNC)?;
+    }
+    Ok(())
+}
+
+const INDIRECT: &str = r#"
+(module
+  (type $i2i (func (param i32) (result i32)))
+  (global $hits (mut i32) (i32.const 0))
+  (table funcref (elem $bump))
+  (func $bump (param i32) (result i64)
+    global.get $hits
+    i32.const 1
+    i32.add
+    global.set $hits
+    i64.const 0)
+  (func (export "call") (param i32) (result i32)
+    local.get 0
+    i32.const 0
+    call_indirect (type $i2i))
+  (func (export "hits") (result i32)
+    global.get $hits))
+"#;
+
+/// A `call_indirect` whose callee has the wrong result type traps without
+/// running the callee.
+fn indirect_mismatch(backend: Backend, flags: Flags) -> anyhow::Result<()> {
+    let ts = Module::new(INDIRECT, "M", flags).translate(backend)?;
+    let host = fixture::host(ts, "M", quote! {}, quote! {});
+    let tl = quote! { ::wars_rt::_rexport::tuple_list };
+    let init_h = init(flags, quote! { h }, quote! { m });
+    let call = drive(flags, quote! { <m::Host as m::MImpl>::call(&mut h, #tl::tuple_list!(5u32)) });
+    let hits = drive(flags, quote! { <m::Host as m::MImpl>::hits(&mut h, ()) });
+    let block_on = fixture::block_on();
+    let main = quote! {
+        #![allow(warnings)]
+        use ::wars_rt::func::unsync::UnwrappedAsyncRec;
+        #host
+        #block_on
+        fn main() {
+            let mut h = m::Host { data: Default::default() };
+            #init_h.unwrap();
+            let e = #call.expect_err("a mismatched call_indirect must trap");
+            assert_eq!(e.downcast_ref::<::wars_rt::Trap>(), Some(&::wars_rt::Trap::IndirectCallTypeMismatch));
+            assert_eq!(#hits.unwrap(), #tl::tuple_list!(0u32));
+        }
+    };
+    let name = format!("indirect-mismatch-{}-{}", backend.name(), flags.bits());
+    fixture::run(work_dir().as_ref(), &name, &["std"], &[], main)?;
+    Ok(())
+}
+
+#[test]
+fn indirect_mismatch_sync() -> anyhow::Result<()> {
+    for backend in Backend::ALL {
+        indirect_mismatch(backend, Flags::empty())?;
+    }
+    Ok(())
+}
+
+#[test]
+fn indirect_mismatch_async() -> anyhow::Result<()> {
+    for backend in Backend::ALL {
+        indirect_mismatch(backend, Flags::ASYNC)?;
+    }
+    Ok(())
+}
+
+const WAIT: &str = r#"
+(module
+  (memory 1 1 shared)
+  (func (export "wait") (param i32 i64) (result i32)
+    i32.const 0
+    local.get 0
+    local.get 1
+    memory.atomic.wait32))
+"#;
+
+/// In async code `memory.atomic.wait` yields to the executor rather than
+/// blocking, and still honours its value check and timeout.
+#[test]
+fn async_wait() -> anyhow::Result<()> {
+    let flags = Flags::ASYNC;
+    let ts = Module::new(WAIT, "M", flags).translate(Backend::Wasmparser)?;
+    let host = fixture::host(ts, "M", quote! {}, quote! {});
+    let tl = quote! { ::wars_rt::_rexport::tuple_list };
+    let init_h = init(flags, quote! { h }, quote! { m });
+    let block_on = fixture::block_on();
+    let main = quote! {
+        #![allow(warnings)]
+        use ::wars_rt::func::unsync::UnwrappedAsyncRec;
+        #host
+        #block_on
+        fn main() {
+            let mut h = m::Host { data: Default::default() };
+            #init_h.unwrap();
+            // The value differs: "not-equal" at once.
+            let r = block_on(<m::Host as m::MImpl>::wait(&mut h, #tl::tuple_list!(1u32, u64::MAX)).go());
+            assert_eq!(r.unwrap(), #tl::tuple_list!(1u32));
+            // A 10ms wait is pending on its first poll, then times out.
+            let start = ::std::time::Instant::now();
+            let r = {
+                let mut f = ::core::pin::pin!(<m::Host as m::MImpl>::wait(&mut h, #tl::tuple_list!(0u32, 10_000_000u64)).go());
+                struct Noop;
+                impl ::std::task::Wake for Noop {
+                    fn wake(self: ::std::sync::Arc<Self>) {}
+                }
+                let waker = ::std::task::Waker::from(::std::sync::Arc::new(Noop));
+                let mut cx = ::core::task::Context::from_waker(&waker);
+                assert!(::core::future::Future::poll(f.as_mut(), &mut cx).is_pending());
+                block_on(f)
+            };
+            assert_eq!(r.unwrap(), #tl::tuple_list!(2u32));
+            assert!(start.elapsed() >= ::std::time::Duration::from_millis(10));
+        }
+    };
+    fixture::run(work_dir().as_ref(), "async-wait", &["std"], &[], main)?;
+    Ok(())
+}
+
+/// The `wrl` bridge against a wasmi engine: an engine function called as a
+/// translated function reference, and an engine memory read through
+/// `WrlMemory`.
+#[test]
+fn wrl_bridge() -> anyhow::Result<()> {
+    let main = quote! {
+        #![allow(warnings)]
+        use ::wars_rt::func::value::Value as V;
+        use ::wars_rt::func::{call_value, Value};
+        use ::wars_rt::wrl::{import_func, MetaType, WrlMemory};
+        use ::wars_rt::{Memory, Trap};
+        use ::wars_rt::_rexport::tramp::tramp;
+        use ::wasm_runtime_layer::{
+            AsContext, AsContextMut, Engine, Func, FuncType, MemoryType, Store, StoreContext,
+            StoreContextMut, ValueType,
+        };
+        type E = ::wasmi_runtime_layer::Engine;
+        struct Data;
+        impl AsMut<Host> for Data {
+            fn as_mut(&mut self) -> &mut Host {
+                unreachable!("no engine function calls back into translated code here")
+            }
+        }
+        struct Host {
+            store: Store<Data, E>,
+        }
+        impl ::wars_rt::CtxSpec for Host {
+            type ExternRef = ::core::convert::Infallible;
+        }
+        impl AsContext for Host {
+            type Engine = E;
+            type UserState = Data;
+            fn as_context(&self) -> StoreContext<'_, Data, E> {
+                self.store.as_context()
+            }
+        }
+        impl AsContextMut for Host {
+            fn as_context_mut(&mut self) -> StoreContextMut<'_, Data, E> {
+                self.store.as_context_mut()
+            }
+        }
+        fn main() {
+            let engine = Engine::new(E::default());
+            let mut h = Host { store: Store::new(&engine, Data) };
+            let sub = Func::new(
+                &mut h.store,
+                FuncType::new([ValueType::I32, ValueType::I32], [ValueType::I32]),
+                |_, args, rets| {
+                    let [::wasm_runtime_layer::Value::I32(a), ::wasm_runtime_layer::Value::I32(b)] = args else {
+                        ::wars_rt::_rexport::anyhow::bail!("bad arguments")
+                    };
+                    rets[0] = ::wasm_runtime_layer::Value::I32(a - b);
+                    Ok(())
+                },
+            );
+            let f = import_func(&mut h, &sub, vec![MetaType::I32, MetaType::I32], vec![MetaType::I32]).unwrap();
+            // Reversed, as every `CoeVec` list: 10 - 3.
+            let r = tramp(call_value(&mut h, f.clone(), vec![Value(V::I32(3)), Value(V::I32(10))])).unwrap();
+            assert!(matches!(r.as_slice(), [Value(V::I32(7))]));
+            let e = tramp(call_value(&mut h, f, vec![Value(V::I32(3))])).expect_err("arity");
+            assert_eq!(e.downcast_ref::<Trap>(), Some(&Trap::IndirectCallTypeMismatch));
+
+            let memory = ::wasm_runtime_layer::Memory::new(&mut h.store, MemoryType::new(1, None)).unwrap();
+            let mut m = WrlMemory { memory, store: &mut h.store };
+            m.write(65532, &[1, 2, 3, 4]).unwrap();
+            assert_eq!(m.read(65532, 4).unwrap().as_ref().as_ref(), &[1, 2, 3, 4]);
+            for (a, s) in [(65533, 4), (0, u64::MAX), (u64::MAX, 2)] {
+                let e = m.read(a, s).err().expect("out of bounds");
+                assert_eq!(e.downcast_ref::<Trap>(), Some(&Trap::OutOfBoundsMemory));
+            }
+        }
+    };
+    fixture::run(
+        work_dir().as_ref(),
+        "wrl-bridge",
+        &["std", "wrl"],
+        // Dev-dependencies of this crate, so already in the lockfile.
+        &["wasm_runtime_layer = \"0.4\"", "wasmi_runtime_layer = \"0.31\""],
+        main,
+    )?;
+    Ok(())
+}
+
+const WRL_INSTANCE: &str = r#"
+(module
+  (import "env" "sub" (func $sub (param i32 i32) (result i32)))
+  (func (export "call") (param i32) (result i32)
+    local.get 0
+    i32.const 3
+    call $sub)
+  (func (export "id") (param funcref) (result funcref)
+    local.get 0))
+"#;
+
+/// A translated module standing in for an engine instance: its imports
+/// resolved from engine `Imports` through `resolve_import`, and its
+/// exports served by `WrlInstance`, leaving out the one with no engine
+/// signature.
+#[test]
+fn wrl_instance() -> anyhow::Result<()> {
+    for backend in Backend::ALL {
+        let flags = Flags::DYN_IMPORTS;
+        let ts = Module::new(WRL_INSTANCE, "M", flags).translate(backend)?;
+        let tl = quote! { ::wars_rt::_rexport::tuple_list };
+        let init_h = init(flags, quote! { h }, quote! { m });
+        let host = fixture::host(
+            ts,
+            "M",
+            quote! {
+                pub store: ::wasm_runtime_layer::Store<Data, E>,
+                pub imports: ::wasm_runtime_layer::Imports,
+            },
+            quote! {
+                use ::wasm_runtime_layer::{AsContext, AsContextMut, StoreContext, StoreContextMut};
+                pub type E = ::wasmi_runtime_layer::Engine;
+                pub struct Data;
+                impl AsMut<Host> for Data {
+                    fn as_mut(&mut self) -> &mut Host {
+                        unreachable!("no engine function calls back into translated code here")
+                    }
+                }
+                impl AsContext for Host {
+                    type Engine = E;
+                    type UserState = Data;
+                    fn as_context(&self) -> StoreContext<'_, Data, E> {
+                        self.store.as_context()
+                    }
+                }
+                impl AsContextMut for Host {
+                    fn as_context_mut(&mut self) -> StoreContextMut<'_, Data, E> {
+                        self.store.as_context_mut()
+                    }
+                }
+                impl ::wars_rt::Linker<::wars_rt::func::Value<Host>> for Host {
+                    fn resolve(&mut self, module: &str, name: &str) -> Option<::wars_rt::func::Value<Host>> {
+                        let imports = self.imports.clone();
+                        ::wars_rt::wrl::resolve_import(self, &imports, module, name)
+                    }
+                }
+            },
+        );
+        let main = quote! {
+            #![allow(warnings)]
+            use ::wars_rt::wrl::{resolve_import, WrlInstance};
+            use ::wars_rt::_rexport::tramp::tramp;
+            use ::wasm_runtime_layer::{Engine, Extern, Func, FuncType, Imports, Store, ValueType};
+            use m::MImpl;
+            #host
+            fn main() {
+                let engine = Engine::new(m::E::default());
+                let mut store = Store::new(&engine, m::Data);
+                let sub = Func::new(
+                    &mut store,
+                    FuncType::new([ValueType::I32, ValueType::I32], [ValueType::I32]),
+                    |_, args, rets| {
+                        let [::wasm_runtime_layer::Value::I32(a), ::wasm_runtime_layer::Value::I32(b)] = args else {
+                            ::wars_rt::_rexport::anyhow::bail!("bad arguments")
+                        };
+                        rets[0] = ::wasm_runtime_layer::Value::I32(a - b);
+                        Ok(())
+                    },
+                );
+                let mut imports = Imports::new();
+                imports.define("env", "sub", Extern::Func(sub));
+                let mut h = m::Host { data: Default::default(), store, imports };
+                #init_h.unwrap();
+                let r = tramp(<m::Host as MImpl>::call(&mut h, #tl::tuple_list!(41u32))).unwrap();
+                assert_eq!(r, #tl::tuple_list!(38u32));
+                assert!(resolve_import(&mut h, &Imports::new(), "env", "sub").is_none());
+
+                let inst = WrlInstance::new(&mut h, <m::Host as MImpl>::exports(), <m::Host as MImpl>::get_export).unwrap();
+                assert!(matches!(inst.get_export("call"), Some(Extern::Func(_))));
+                assert!(inst.get_export("id").is_none());
+                let names: Vec<&str> = inst.exports().map(|(n, _)| n).collect();
+                assert_eq!(names, ["call"]);
+                let mut linked = Imports::new();
+                inst.define(&mut linked, "m");
+                assert!(matches!(linked.get_export("m", "call"), Some(Extern::Func(_))));
+            }
+        };
+        let name = format!("wrl-instance-{}", backend.name());
+        fixture::run(
+            work_dir().as_ref(),
+            &name,
+            &["std", "wrl"],
+            &["wasm_runtime_layer = \"0.4\"", "wasmi_runtime_layer = \"0.31\""],
+            main,
+        )?;
+    }
+    Ok(())
+}
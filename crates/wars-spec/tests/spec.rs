@@ -0,0 +1,22 @@
+//! `cargo test -p wars-spec --test spec`; see the `wars_spec` crate docs for
+//! the environment variables it reads.
+fn main() -> anyhow::Result<()> {
+    let cfg = wars_spec::Config::from_env(env!("CARGO_TARGET_TMPDIR"));
+    let report = wars_spec::run(&cfg)?;
+    wars_spec::print_report(&report);
+    if cfg.bless {
+        return wars_spec::bless(&cfg, &report);
+    }
+    let baseline = wars_spec::read_baseline(&cfg.baseline)?;
+    let regressed = wars_spec::regressions(&cfg, &baseline, &report);
+    for r in regressed.iter() {
+        println!("baseline: {r}");
+    }
+    if !regressed.is_empty() {
+        anyhow::bail!(
+            "{} proposal/backend pairs do not match baseline.txt; bless with WARS_SPEC_BLESS=1 if intended",
+            regressed.len()
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,22 @@
+#!/bin/sh
+# Vendor WebAssembly/testsuite at commit $1: the core .wast files plus the
+# proposals below, replacing whatever is here, and record the commit in
+# README.md.
+set -eu
+rev=${1:?usage: update.sh <testsuite commit>}
+proposals="custom-page-sizes exception-handling extended-const function-references gc memory64 multi-memory relaxed-simd tail-call threads"
+here=$(cd "$(dirname "$0")" && pwd)
+tmp=$(mktemp -d)
+trap 'rm -rf "$tmp"' EXIT
+curl -fsSL "https://github.com/WebAssembly/testsuite/archive/$rev.tar.gz" |
+    tar -xz -C "$tmp" --strip-components=1
+find "$here" -name '*.wast' -delete
+rm -rf "$here/proposals"
+cp "$tmp"/*.wast "$here/"
+for p in $proposals; do
+    [ -d "$tmp/proposals/$p" ] || continue
+    mkdir -p "$here/proposals/$p"
+    cp "$tmp/proposals/$p"/*.wast "$here/proposals/$p/"
+done
+sed -i.bak "s/^Commit: .*/Commit: \`$rev\`/" "$here/README.md"
+rm "$here/README.md.bak"
@@ -0,0 +1,167 @@
+//! Build-script front-end: translate wasm files into `OUT_DIR/<Name>.rs`.
+//!
+//! ```ignore
+//! // build.rs
+//! fn main() -> anyhow::Result<()> {
+//!     wars::build::Builder::new()
+//!         .module(wars::build::Module::new("Greeter", "wasm/greeter.wasm").flags(wars::Flags::ASYNC))
+//!         .generate()
+//! }
+//! // lib.rs
+//! include!(concat!(env!("OUT_DIR"), "/Greeter.rs"));
+//! ```
+use std::path::PathBuf;
+
+use super::*;
+
+/// Which backend a [`Module`] is translated with.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Translator {
+    #[cfg(feature = "wasmparser")]
+    Wasmparser,
+    #[cfg(feature = "waffle")]
+    Waffle,
+}
+impl Default for Translator {
+    fn default() -> Self {
+        #[cfg(feature = "wasmparser")]
+        return Translator::Wasmparser;
+        #[cfg(all(feature = "waffle", not(feature = "wasmparser")))]
+        return Translator::Waffle;
+        #[cfg(not(any(feature = "waffle", feature = "wasmparser")))]
+        panic!("wars was built without any backend feature")
+    }
+}
+
+/// One wasm file to translate; mirrors the fields of [`OptsCore`].
+#[derive(Clone)]
+pub struct Module {
+    pub name: Ident,
+    pub path: PathBuf,
+    pub flags: Flags,
+    pub translator: Translator,
+    pub crate_path: Option<syn::Path>,
+    pub embed: TokenStream,
+    pub data: BTreeMap<Ident, TokenStream>,
+    pub roots: BTreeMap<String, TokenStream>,
+    pub plugins: Vec<Arc<dyn Plugin>>,
+    pub fuel: FuelCosts,
+}
+impl Module {
+    pub fn new(name: &str, path: impl Into<PathBuf>) -> Self {
+        Self {
+            name: Ident::new(name, Span::call_site()),
+            path: path.into(),
+            flags: Flags::empty(),
+            translator: Translator::default(),
+            crate_path: None,
+            embed: TokenStream::new(),
+            data: BTreeMap::new(),
+            roots: BTreeMap::new(),
+            plugins: vec![],
+            fuel: FuelCosts::default(),
+        }
+    }
+    pub fn flags(mut self, flags: Flags) -> Self {
+        self.flags |= flags;
+        self
+    }
+    pub fn translator(mut self, translator: Translator) -> Self {
+        self.translator = translator;
+        self
+    }
+    pub fn crate_path(mut self, path: syn::Path) -> Self {
+        self.crate_path = Some(path);
+        self
+    }
+    pub fn embed(mut self, embed: TokenStream) -> Self {
+        self.embed = embed;
+        self
+    }
+    pub fn data(mut self, field: Ident, ty: TokenStream) -> Self {
+        self.data.insert(field, ty);
+        self
+    }
+    pub fn root(mut self, key: &str, path: TokenStream) -> Self {
+        self.roots.insert(key.to_owned(), path);
+        self
+    }
+    pub fn plugin(mut self, plugin: Arc<dyn Plugin>) -> Self {
+        self.plugins.push(plugin);
+        self
+    }
+    /// Per-class costs charged under [`Flags::FUEL`].
+    pub fn fuel(mut self, fuel: FuelCosts) -> Self {
+        self.fuel = fuel;
+        self
+    }
+}
+
+/// Translates a set of [`Module`]s for a downstream `build.rs`.
+#[derive(Clone)]
+pub struct Builder {
+    pub crate_path: syn::Path,
+    pub out_dir: Option<PathBuf>,
+    pub modules: Vec<Module>,
+}
+impl Default for Builder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl Builder {
+    pub fn new() -> Self {
+        Self {
+            crate_path: syn::parse_quote!(::wars_rt),
+            out_dir: None,
+            modules: vec![],
+        }
+    }
+    /// Default runtime path for modules that don't set their own.
+    pub fn crate_path(mut self, path: syn::Path) -> Self {
+        self.crate_path = path;
+        self
+    }
+    /// Output directory; defaults to `$OUT_DIR`.
+    pub fn out_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.out_dir = Some(dir.into());
+        self
+    }
+    pub fn module(mut self, module: Module) -> Self {
+        self.modules.push(module);
+        self
+    }
+    /// Translate every module into `<out_dir>/<Name>.rs` and print the
+    /// matching `cargo:rerun-if-changed` lines.
+    pub fn generate(&self) -> anyhow::Result<()> {
+        let out_dir = match &self.out_dir {
+            Some(d) => d.clone(),
+            None => PathBuf::from(std::env::var("OUT_DIR")?),
+        };
+        for m in self.modules.iter() {
+            println!("cargo:rerun-if-changed={}", m.path.display());
+            let bytes = std::fs::read(&m.path)
+                .map_err(|e| anyhow::anyhow!("{}: {e}", m.path.display()))?;
+            let core = OptsCore {
+                crate_path: m.crate_path.clone().unwrap_or_else(|| self.crate_path.clone()),
+                bytes: &bytes,
+                name: m.name.clone(),
+                flags: m.flags,
+                embed: m.embed.clone(),
+                data: m.data.clone(),
+                roots: m.roots.clone(),
+                plugins: m.plugins.clone(),
+                fuel: m.fuel,
+            };
+            let ts = match m.translator {
+                #[cfg(feature = "wasmparser")]
+                Translator::Wasmparser => core.inflate::<WasmparserBackend>().render()?,
+                #[cfg(feature = "waffle")]
+                Translator::Waffle => core.inflate::<LegacyPortalWaffleBackend>().render()?,
+            };
+            std::fs::write(out_dir.join(format!("{}.rs", m.name)), ts.to_string())?;
+        }
+        Ok(())
+    }
+}
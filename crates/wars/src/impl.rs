@@ -15,7 +15,26 @@ pub(crate) fn mangle_value(a: Value, b: usize) -> Ident {
 }
 pub(crate) use crate::shared::bindname;
 
-type Opts<'a> = OptsLt<'a, Module<'static>, LegacyPortalWaffleBackend>;
+type Opts<'a> = OptsLt<'a, Lowered, LegacyPortalWaffleBackend>;
+
+/// A waffle module plus what [`crate::prepass`] took out of the wasm before
+/// waffle saw it.  Derefs to the module.
+#[derive(Clone)]
+pub(crate) struct Lowered {
+    pub(crate) module: Module<'static>,
+    pub(crate) pre: crate::prepass::Prepass,
+}
+impl std::ops::Deref for Lowered {
+    type Target = Module<'static>;
+    fn deref(&self) -> &Module<'static> {
+        &self.module
+    }
+}
+impl std::ops::DerefMut for Lowered {
+    fn deref_mut(&mut self) -> &mut Module<'static> {
+        &mut self.module
+    }
+}
 
 pub(crate) fn alloc(opts: &Opts<'_>) -> TokenStream {
     crate::shared::alloc(&opts.core)
@@ -198,6 +217,26 @@ pub(crate) fn import(
     // }
     // if self.flags.contains(Flags::PIT) {
     // };
+    if opts.core.flags.contains(Flags::DYN_IMPORTS) {
+        let f = opts
+            .module
+            .imports
+            .iter()
+            .find_map(|i| match i.kind {
+                ImportKind::Func(f) if i.module == module && i.name == name => Some(f),
+                _ => None,
+            })
+            .ok_or_else(|| anyhow::anyhow!("no function import {module}.{name}"))?;
+        let g = render_generics(opts, &quote! {C}, &opts.module.signatures[opts.module.funcs[f].sig()]);
+        return Ok(crate::shared::render_dyn_import(
+            &opts.core,
+            &crate::shared::link_field(module, name),
+            &g,
+            module,
+            name,
+            params,
+        ));
+    }
     let id = format_ident!("{}_{}", bindname(module), bindname(name));
     return Ok(quote! {
         ctx.#id(#root::_rexport::tuple_list::tuple_list!(#(#params),*))
@@ -239,7 +278,7 @@ pub(crate) fn render_ty(opts: &Opts<'_>, ctx: &TokenStream, ty: Type) -> TokenSt
         }
     }
     // Everything else goes through the generic shared helper.
-    crate::shared::render_ty(&opts.core, ctx, ty)
+    crate::shared::render_ty(&opts.core, &(), ctx, ty)
 }
 
 /// Convert a `&SignatureData` into a `FuncSig<'_, Type>` for use with
@@ -436,6 +475,20 @@ fn render_statements(
             },
             waffle::ValueDef::Operator(o, vals, _) => {
                 let vals = &b.arg_pool[*vals];
+                // Every SIMD operator takes or yields a v128.
+                let simd = b.values[a]
+                    .tys(&b.type_pool)
+                    .iter()
+                    .chain(vals.iter().flat_map(|v| b.values[*v].tys(&b.type_pool)))
+                    .any(|t| matches!(t, Type::V128));
+                if simd {
+                    anyhow::bail!("unsupported operator {o} in function {f}: SIMD needs the wasmparser backend");
+                }
+                // The prepass lowers the threads proposal, so an atomic
+                // operator here comes from a later one.
+                if o.to_string().contains("atomic") {
+                    anyhow::bail!("unsupported operator {o} in function {f}");
+                }
                 match o{
                     Operator::I32Const { value } => quote! {
                         #root::_rexport::tuple_list::tuple_list!(#value)
@@ -443,102 +496,11 @@ fn render_statements(
                     Operator::I64Const { value } => quote!{
                         #root::_rexport::tuple_list::tuple_list!(#value)
                     },
-                    Operator::Call { function_index } => {
-                        match opts.module.funcs[*function_index].body(){
-                            Some(_) => {
-                                let func = fname(opts, *function_index);
-                                let vals = vals.iter().map(|a|format_ident!("{a}"));
-                                let tramp = if opts.core.flags.contains(Flags::ASYNC) {
-                                    quote! { x.go().await }
-                                } else {
-                                    quote! { #root::_rexport::tramp::tramp(x) }
-                                };
-                                let fp_ts2 = fp(opts);
-                                quote! {
-                                    {
-                                        let x = #func(ctx,#root::_rexport::tuple_list::tuple_list!(#(#fp_ts::cast::<_,_,C>(#vals .clone())),*));
-                                        match #tramp {
-                                            Ok(a) => a,
-                                            Err(e) => return #fp_ts2::ret(Err(e))
-                                        }
-                                    }
-                                }
-                            },
-                            None => {
-                                let i = opts
-                                .module
-                                .imports
-                                .iter()
-                                .find(|a| a.kind == ImportKind::Func(*function_index))
-                                .unwrap();
-                                let x = import(
-                                    opts,
-                                    i.module.as_str(),
-                                    i.name.as_str(),
-                                    vals.iter().map(|a|format_ident!("{a}")).map(|a| quote! {#a}),
-                                )?;
-                                let alloc_ts = alloc(opts);
-                                let fp_ts2 = fp(opts);
-                                let tramp = if opts.core.flags.contains(Flags::ASYNC) {
-                                    quote! { #alloc_ts::boxed::Box::pin(#x.go()).await }
-                                } else {
-                                    quote! { #root::_rexport::tramp::tramp(#x) }
-                                };
-                                quote! {
-                                    match #tramp {
-                                        Ok(a) => a,
-                                        Err(e) => return #fp_ts2::ret(Err(e))
-                                    }
-                                }
-                            }
-                        }
+                    Operator::Call { function_index } if opts.module.pre.intrinsics.contains_key(&(function_index.index() as u32)) => {
+                        render_intrinsic(opts, opts.module.pre.intrinsics[&(function_index.index() as u32)], vals, b.values[a].tys(&b.type_pool))?
                     },
-                    Operator::CallRef { sig_index } => {
-                        let mut vals = vals.to_owned();
-                        let r = vals.pop().expect(" a ref to call");
-                        let vals = vals.iter().map(|a|format_ident!("{a}"));
-                        let r = format_ident!("{r}");
-                        let g = render_generics(opts, &quote! {c}, &opts.module.signatures[*sig_index]);
-                        let fp_ts2 = fp(opts);
-                        let tramp = if opts.core.flags.contains(Flags::ASYNC) {
-                            quote! { x.go().await }
-                        } else {
-                            quote! { #root::_rexport::tramp::tramp(x) }
-                        };
-                        quote! {
-                            {
-                                let x = #fp_ts2::call_ref::<#g,C>(ctx,#fp_ts2(#r.clone()),#root::_rexport::tuple_list::tuple_list!(#(#fp_ts::cast::<_,_,C>(#vals .clone())),*));
-                                match #tramp {
-                                    Ok(a) => a,
-                                    Err(e) => return #fp_ts2::ret(Err(e))
-                                }
-                            }
-                        }
-                    },
-                    Operator::CallIndirect { sig_index, table_index } => {
-                        let t = format_ident!("{table_index}");
-                        let mut vals = vals.to_owned();
-                        let r = vals.pop().expect("a table index to call");
-                        let vals = vals.iter().map(|a|format_ident!("{a}"));
-                        let r = format_ident!("{r}");
-                        let r = quote! { ctx.#t()[#r as usize] };
-                        let g = render_generics(opts, &quote! {c}, &opts.module.signatures[*sig_index]);
-                        let fp_ts2 = fp(opts);
-                        let tramp = if opts.core.flags.contains(Flags::ASYNC) {
-                            quote! { x.go().await }
-                        } else {
-                            quote! { #root::_rexport::tramp::tramp(x) }
-                        };
-                        quote! {
-                            {
-                                let r = #r.clone();
-                                let x = #fp_ts2::call_ref::<#g,C>(ctx,#fp_ts2::cast(r),#root::_rexport::tuple_list::tuple_list!(#(#fp_ts::cast::<_,_,C>(#vals .clone())),*));
-                                match #tramp {
-                                    Ok(a) => a,
-                                    Err(e) => return #fp_ts2::ret(Err(e))
-                                }
-                            }
-                        }
+                    Operator::Call { .. } | Operator::CallRef { .. } | Operator::CallIndirect { .. } => {
+                        render_call_op(opts, o, vals, &quote! { return #fp_ts::ret(Err(e)) })?
                     },
                     Operator::RefFunc { func_index } => {
                         render_fun_ref(opts, &quote! {C},*func_index)
@@ -567,18 +529,20 @@ fn render_statements(
                             None => 65536usize,
                             Some(a) => 2usize.pow(*a),
                         };
+                        let d = &opts.module.memories[*mem_idx];
+                        let max = crate::shared::max_pages(d.maximum_pages.map(|a| a as u64), d.memory64, n as u64);
+                        let n = n as u64;
+                        let limit = format_ident!("{m}_limit");
                         let fp_ts2 = fp(opts);
                         quote! {
                             {
-                                let vn = (match #root::Memory::size(ctx.#m()){
-                                    Ok(a) => a,
-                                    Err(e) => return #fp_ts2::ret(Err(e))
-                                }) / #n;
-                                match #root::Memory::grow(ctx.#m(),(#a .clone() as u64) * #n){
-                                    Ok(a) => a,
+                                let cap = ctx.#limit();
+                                let vn = match #root::memory_grow(ctx.#m(), #a .clone() as u64, #n, #max, cap){
+                                    Ok(Some(a)) => a as #rt,
+                                    Ok(None) => #rt::MAX,
                                     Err(e) => return #fp_ts2::ret(Err(e))
                                 };
-                                #root::_rexport::tuple_list::tuple_list!(vn as #rt)
+                                #root::_rexport::tuple_list::tuple_list!(vn)
                             }
                         }
                     },
@@ -642,8 +606,12 @@ fn render_statements(
                         let table = format_ident!("{table_index}");
                         let [i,..] = vals else { unreachable!() };
                         let i = format_ident!("{i}");
+                        let fp_ts2 = fp(opts);
                         quote! {
-                            { (ctx.#table()[#i as usize].clone(),()) }
+                            (match #root::table_get(ctx.#table(), #i as u64) {
+                                Ok(a) => a,
+                                Err(e) => return #fp_ts2::ret(Err(e))
+                            },())
                         }
                     },
                     Operator::TableSet { table_index } => {
@@ -654,8 +622,10 @@ fn render_statements(
                         let fp_ts2 = fp(opts);
                         quote! {
                             {
-                                ctx.#table()[#i as usize] = #fp_ts2::cast::<_,_,C>(#j.clone());
-                                ()
+                                match #root::table_set(ctx.#table(), #i as u64, #fp_ts2::cast::<_,_,C>(#j.clone())) {
+                                    Ok(()) => (),
+                                    Err(e) => return #fp_ts2::ret(Err(e))
+                                }
                             }
                         }
                     },
@@ -699,10 +669,10 @@ fn render_statements(
                         let i = format_ident!("{i}");
                         let fp_ts2 = fp(opts);
                         quote! {
-                            #fp_ts2::cast::<_,_,C>(match #i.clone().0{
-                                #fp_ts2::value::Value::Gc(g) => g.get_field(#idx),
-                                _ => todo!()
-                            })
+                            #fp_ts2::cast::<_,_,C>(#fp_ts2::Value::<C>(match #i.clone().0.struct_get(#idx) {
+                                Ok(a) => a,
+                                Err(e) => return #fp_ts2::ret(Err(e))
+                            }))
                         }
                     }
                     Operator::StructSet { sig, idx } => {
@@ -711,9 +681,9 @@ fn render_statements(
                         let j = format_ident!("{j}");
                         let fp_ts2 = fp(opts);
                         quote! {
-                            match #i.clone().0{
-                                #fp_ts2::value::Value::Gc(g) => g.set_field(#idx,#fp_ts2::cast::<_,_,C>(#j.clone())),
-                                _ => todo!()
+                            match #i.clone().0.struct_set(#idx, #fp_ts2::cast::<_,#fp_ts2::Value<C>,C>(#j.clone()).0) {
+                                Ok(()) => (),
+                                Err(e) => return #fp_ts2::ret(Err(e))
                             }
                         }
                     }
@@ -870,7 +840,7 @@ fn render_term(
                     let func = fname(opts, *func);
                     if opts.core.flags.contains(Flags::ASYNC) {
                         quote! {
-                            #func(ctx,#root::_rexport::tuple_list::tuple_list!(#(#values),*))
+                            return #root::func::unsync::AsyncRec::wrap(#func(ctx,#root::_rexport::tuple_list::tuple_list!(#(#values),*)))
                         }
                     } else {
                         quote! {
@@ -896,7 +866,9 @@ fn render_term(
                             .map(|a| quote! {#a}),
                     )?;
                     if opts.core.flags.contains(Flags::ASYNC) {
-                        x
+                        quote! {
+                            return #root::func::unsync::AsyncRec::wrap(#x)
+                        }
                     } else {
                         quote! {
                             return #root::_rexport::tramp::BorrowRec::Call(#root::_rexport::tramp::Thunk::new(move||{#x}))
@@ -914,17 +886,23 @@ fn render_term(
                 quote! { #fp_ts::cast::<_,_,C>(#a) }
             });
             let r = format_ident!("{r}");
-            let r = quote! { ctx.#t()[#r as usize] };
             let g = render_generics(opts, &quote! {c}, &opts.module.signatures[*sig]);
+            let resolve = quote! {
+                let r = match #root::table_get(ctx.#t(), #r as u64).and_then(#fp_ts::indirect::<#g,C>) {
+                    Ok(a) => a,
+                    Err(e) => return #fp_ts::ret(Err(e))
+                };
+            };
             if opts.core.flags.contains(Flags::ASYNC) {
                 quote! {
-                    return #fp_ts::call_ref::<#g,C>(ctx,#fp_ts::cast(r),#root::_rexport::tuple_list::tuple_list!(#(#fp_ts::cast::<_,_,C>(#vals .clone())),*))
+                    #resolve
+                    return #fp_ts::call_ref::<#g,C>(ctx,r,#root::_rexport::tuple_list::tuple_list!(#(#fp_ts::cast::<_,_,C>(#vals .clone())),*))
                 }
             } else {
                 quote! {
-                    let r = #r.clone();
+                    #resolve
                     return #root::_rexport::tramp::BorrowRec::Call(#root::_rexport::tramp::Thunk::new(move||{
-                        #fp_ts::call_ref::<#g,C>(ctx,#fp_ts::cast(r),#root::_rexport::tuple_list::tuple_list!(#(#fp_ts::cast::<_,_,C>(#vals .clone())),*))
+                        #fp_ts::call_ref::<#g,C>(ctx,r,#root::_rexport::tuple_list::tuple_list!(#(#fp_ts::cast::<_,_,C>(#vals .clone())),*))
                     }))
                 }
             }
@@ -950,13 +928,419 @@ fn render_term(
                 }
             }
         }
-        waffle::Terminator::Unreachable => quote! {
-            unreachable!()
-        },
+        waffle::Terminator::Unreachable => {
+            let fp_ts = fp(opts);
+            quote! {
+                return #fp_ts::ret(Err(#root::Trap::Unreachable.into()))
+            }
+        }
         waffle::Terminator::None => panic!("none block terminator"),
-        _ => todo!(),
+        // The prepass lowers exception handling to calls, or rejects it.
+        _ => anyhow::bail!("unsupported terminator in function {f}"),
+    })
+}
+/// A `call`, `call_ref` or `call_indirect`, yielding its results; a trap
+/// or exception `e` goes to `on_err` instead.
+fn render_call_op(
+    opts: &Opts<'_>,
+    o: &Operator,
+    vals: &[Value],
+    on_err: &TokenStream,
+) -> anyhow::Result<TokenStream> {
+    let root = opts.core.crate_path.clone();
+    let fp_ts = fp(opts);
+    Ok(match o {
+        Operator::Call { function_index } => {
+            match opts.module.funcs[*function_index].body(){
+                Some(_) => {
+                    let func = fname(opts, *function_index);
+                    let vals = vals.iter().map(|a|format_ident!("{a}"));
+                    let tramp = if opts.core.flags.contains(Flags::ASYNC) {
+                        quote! { x.go().await }
+                    } else {
+                        quote! { #root::_rexport::tramp::tramp(x) }
+                    };
+                    crate::shared::render_call(&opts.core, quote! {
+                        {
+                            let x = #func(ctx,#root::_rexport::tuple_list::tuple_list!(#(#fp_ts::cast::<_,_,C>(#vals .clone())),*));
+                            #tramp
+                        }
+                    }, on_err)
+                },
+                None => {
+                    let i = opts
+                    .module
+                    .imports
+                    .iter()
+                    .find(|a| a.kind == ImportKind::Func(*function_index))
+                    .unwrap();
+                    let x = import(
+                        opts,
+                        i.module.as_str(),
+                        i.name.as_str(),
+                        vals.iter().map(|a|format_ident!("{a}")).map(|a| quote! {#a}),
+                    )?;
+                    let alloc_ts = alloc(opts);
+                    let tramp = if opts.core.flags.contains(Flags::ASYNC) {
+                        quote! { #alloc_ts::boxed::Box::pin(#x.go()).await }
+                    } else {
+                        quote! { #root::_rexport::tramp::tramp(#x) }
+                    };
+                    quote! {
+                        match #tramp {
+                            Ok(a) => a,
+                            Err(e) => #on_err
+                        }
+                    }
+                }
+            }
+        },
+        Operator::CallRef { sig_index } => {
+            let mut vals = vals.to_owned();
+            let r = vals.pop().expect(" a ref to call");
+            let vals = vals.iter().map(|a|format_ident!("{a}"));
+            let r = format_ident!("{r}");
+            let g = render_generics(opts, &quote! {c}, &opts.module.signatures[*sig_index]);
+            let fp_ts2 = fp(opts);
+            let tramp = if opts.core.flags.contains(Flags::ASYNC) {
+                quote! { x.go().await }
+            } else {
+                quote! { #root::_rexport::tramp::tramp(x) }
+            };
+            crate::shared::render_call(&opts.core, quote! {
+                {
+                    let x = #fp_ts2::call_ref::<#g,C>(ctx,#fp_ts2(#r.clone()),#root::_rexport::tuple_list::tuple_list!(#(#fp_ts::cast::<_,_,C>(#vals .clone())),*));
+                    #tramp
+                }
+            }, on_err)
+        },
+        Operator::CallIndirect { sig_index, table_index } => {
+            let t = format_ident!("{table_index}");
+            let mut vals = vals.to_owned();
+            let r = vals.pop().expect("a table index to call");
+            let vals = vals.iter().map(|a|format_ident!("{a}"));
+            let r = format_ident!("{r}");
+            let g = render_generics(opts, &quote! {c}, &opts.module.signatures[*sig_index]);
+            let fp_ts2 = fp(opts);
+            let tramp = if opts.core.flags.contains(Flags::ASYNC) {
+                quote! { x.go().await }
+            } else {
+                quote! { #root::_rexport::tramp::tramp(x) }
+            };
+            let call = crate::shared::render_call(&opts.core, quote! {
+                {
+                    let x = #fp_ts2::call_ref::<#g,C>(ctx,r,#root::_rexport::tuple_list::tuple_list!(#(#fp_ts::cast::<_,_,C>(#vals .clone())),*));
+                    #tramp
+                }
+            }, on_err);
+            quote! {
+                {
+                    let r = match #root::table_get(ctx.#t(), #r as u64).and_then(#fp_ts2::indirect::<#g,C>) {
+                        Ok(a) => a,
+                        Err(e) => #on_err
+                    };
+                    #call
+                }
+            }
+        },
+        _ => unreachable!("not a call: {o}"),
     })
 }
+/// A call to a [`crate::prepass`] stub: the `wars_rt` helper it stands for,
+/// on the stub's arguments `args`, yielding results of types `rets`.
+fn render_intrinsic(
+    opts: &Opts<'_>,
+    i: crate::prepass::Intrinsic,
+    args: &[Value],
+    rets: &[Type],
+) -> anyhow::Result<TokenStream> {
+    use crate::prepass::{Callee, Intrinsic};
+    let root = opts.core.crate_path.clone();
+    let fp_ts = fp(opts);
+    let vals: Vec<_> = args.iter().map(|a| format_ident!("{a}")).collect();
+    Ok(match i {
+        Intrinsic::Throw { tag } => {
+            let tn = format_ident!("tag{tag}");
+            let alloc_ts = alloc(opts);
+            quote! {
+                {
+                    let e = #fp_ts::throw::<C>(ctx.#tn(), #alloc_ts::vec![
+                        #(#fp_ts::cast::<_, #fp_ts::Value<C>, C>(#vals.clone())),*
+                    ]);
+                    return #fp_ts::ret(Err(e))
+                }
+            }
+        }
+        Intrinsic::TryCall { site } => {
+            let site = &opts.module.pre.sites[site as usize];
+            let alloc_ts = alloc(opts);
+            // The call's outcome, with its trap or exception kept.
+            let outcome = if let Callee::Throw { tag } = site.callee {
+                let tn = format_ident!("tag{tag}");
+                quote! {
+                    Err(#fp_ts::throw::<C>(ctx.#tn(), #alloc_ts::vec![
+                        #(#fp_ts::cast::<_, #fp_ts::Value<C>, C>(#vals.clone())),*
+                    ]))
+                }
+            } else {
+                let o = match site.callee {
+                    Callee::Func(f) => Operator::Call { function_index: Func::new(f as usize) },
+                    Callee::Indirect { ty, table } => Operator::CallIndirect {
+                        sig_index: Signature::new(ty as usize),
+                        table_index: waffle::Table::new(table as usize),
+                    },
+                    Callee::Ref { ty } => Operator::CallRef { sig_index: Signature::new(ty as usize) },
+                    Callee::Throw { .. } => unreachable!(),
+                };
+                let call = render_call_op(opts, &o, args, &quote! { break 'call Err(e) })?;
+                quote! { 'call: { Ok(#call) } }
+            };
+            let tys: Vec<_> = rets.iter().map(|t| render_ty(opts, &quote! {C}, *t)).collect();
+            let (res_tys, slot_tys) = tys[..tys.len() - 1].split_at(site.results);
+            let res: Vec<_> = (0..site.results).map(|i| format_ident!("r{i}")).collect();
+            let default = |t: &TokenStream| quote! { <#t as ::core::default::Default>::default() };
+            // Results, payload slots, then which clause caught.
+            let ok: Vec<_> = res
+                .iter()
+                .map(|r| quote! { #r })
+                .chain(slot_tys.iter().map(default))
+                .chain(once(quote! { 0u32 }))
+                .collect();
+            let mut chain = quote! {
+                {
+                    let e = #fp_ts::rethrow::<C>(x);
+                    return #fp_ts::ret(Err(e))
+                }
+            };
+            for (k, (tag, slots)) in site.clauses.iter().enumerate().rev() {
+                let code = k as u32 + 1;
+                let caught: Vec<_> = res_tys
+                    .iter()
+                    .map(default)
+                    .chain(slot_tys.iter().enumerate().map(|(j, t)| {
+                        if slots.contains(&j) {
+                            let j = j - slots.start;
+                            quote! { #fp_ts::cast::<_, #t, C>(#fp_ts::Value::<C>(x.payload[#j].clone())) }
+                        } else {
+                            default(t)
+                        }
+                    }))
+                    .chain(once(quote! { #code }))
+                    .collect();
+                let caught = quote! { #root::_rexport::tuple_list::tuple_list!(#(#caught),*) };
+                chain = match tag {
+                    Some(t) => {
+                        let tn = format_ident!("tag{t}");
+                        quote! { if x.tag == ctx.#tn() { #caught } else #chain }
+                    }
+                    None => quote! { { #caught } },
+                };
+            }
+            quote! {
+                {
+                    let r: #root::_rexport::anyhow::Result<
+                        #root::_rexport::tuple_list::tuple_list_type!(#(#res_tys),*)
+                    > = #outcome;
+                    match r {
+                        Ok(#root::_rexport::tuple_list::tuple_list!(#(#res),*)) => {
+                            #root::_rexport::tuple_list::tuple_list!(#(#ok),*)
+                        }
+                        Err(e) => {
+                            let x = match #fp_ts::catch::<C>(e) {
+                                Ok(x) => x,
+                                Err(e) => return #fp_ts::ret(Err(e))
+                            };
+                            #chain
+                        }
+                    }
+                }
+            }
+        }
+        Intrinsic::MemoryInit { data, mem: m } => {
+            let m = mem(opts, Memory::new(m as usize))?;
+            // Active segments are already dropped, so they read as empty.
+            let seg = if opts.module.pre.passive_data.contains_key(&data) {
+                let n = format_ident!("data{data}");
+                quote! { ctx.data().#n }
+            } else {
+                quote! { &[] }
+            };
+            let [dst, src, len] = &vals[..] else { unreachable!() };
+            quote! {
+                {
+                    let seg: &'static [u8] = #seg;
+                    match #root::memory_init(#m, seg, #dst as u64, #src as u64, #len as u64) {
+                        Ok(()) => (),
+                        Err(e) => return #fp_ts::ret(Err(e))
+                    }
+                }
+            }
+        }
+        Intrinsic::TableInit { elem, table } => {
+            let t = format_ident!("{}", waffle::Table::new(table as usize));
+            let alloc_ts = alloc(opts);
+            // Active and declared segments are dropped after instantiation.
+            let seg = if opts.module.pre.passive_elems.contains_key(&elem) {
+                let n = format_ident!("elem{elem}");
+                quote! { ctx.data().#n.clone() }
+            } else {
+                quote! { #alloc_ts::vec::Vec::new() }
+            };
+            let [dst, src, len] = &vals[..] else { unreachable!() };
+            quote! {
+                {
+                    let seg: #alloc_ts::vec::Vec<#fp_ts::Value<C>> = #seg;
+                    match #root::table_init(ctx.#t(), &seg, #dst as u64, #src as u64, #len as u64) {
+                        Ok(()) => (),
+                        Err(e) => return #fp_ts::ret(Err(e))
+                    }
+                }
+            }
+        }
+        Intrinsic::ElemDrop { elem } => {
+            if opts.module.pre.passive_elems.contains_key(&elem) {
+                let n = format_ident!("elem{elem}");
+                let alloc_ts = alloc(opts);
+                quote! {
+                    {
+                        ctx.data().#n = #alloc_ts::vec::Vec::new();
+                        ()
+                    }
+                }
+            } else {
+                quote! { () }
+            }
+        }
+        Intrinsic::Atomic { op, mem: m, offset } => {
+            let shared = opts.module.memories[Memory::new(m as usize)].shared;
+            let m = mem(opts, Memory::new(m as usize))?;
+            // Unshared memories are wrapped so they satisfy `AtomicMemory`.
+            let m = if shared {
+                m
+            } else {
+                quote! { &mut #root::Exclusive(#m) }
+            };
+            // Async code yields to the executor instead of blocking in a wait.
+            let wait_async = op.starts_with("memoryatomicwait") && opts.core.flags.contains(Flags::ASYNC);
+            let op = if wait_async {
+                format_ident!("{op}async")
+            } else {
+                format_ident!("{op}")
+            };
+            let [ptr, args @ ..] = &vals[..] else { unreachable!() };
+            let mut call = quote! {
+                #root::#op(#m, (#ptr as u64).wrapping_add(#offset) #(, #fp_ts::cast::<_,_,C>(#args.clone()))*)
+            };
+            if wait_async {
+                call = quote! {
+                    match #call {
+                        Ok(f) => f.await,
+                        Err(e) => Err(e),
+                    }
+                };
+            }
+            quote! {
+                match #call {
+                    Ok(a) => a,
+                    Err(e) => return #fp_ts::ret(Err(e))
+                }
+            }
+        }
+        Intrinsic::DataDrop { data } => {
+            if opts.module.pre.passive_data.contains_key(&data) {
+                let n = format_ident!("data{data}");
+                quote! {
+                    {
+                        ctx.data().#n = &[];
+                        ()
+                    }
+                }
+            } else {
+                quote! { () }
+            }
+        }
+    })
+}
+/// Tables some function body writes with `table.set`, `table.grow` or
+/// `table.init`.
+fn written_tables(m: &Lowered) -> BTreeSet<waffle::Table> {
+    let mut out: BTreeSet<_> = m
+        .pre
+        .intrinsics
+        .values()
+        .filter_map(|i| match i {
+            crate::prepass::Intrinsic::TableInit { table, .. } => Some(waffle::Table::new(*table as usize)),
+            _ => None,
+        })
+        .collect();
+    for f in m.funcs.values() {
+        let Some(b) = f.body() else { continue };
+        for v in b.values.values() {
+            if let waffle::ValueDef::Operator(
+                Operator::TableSet { table_index } | Operator::TableGrow { table_index },
+                _,
+                _,
+            ) = v
+            {
+                out.insert(*table_index);
+            }
+        }
+    }
+    out
+}
+/// Which [`FuelCosts`] class `o` is charged under.  Branches are waffle
+/// terminators, charged once per block as `control`.
+fn op_class(opts: &Opts<'_>, o: &Operator) -> OpClass {
+    match o {
+        Operator::Call { function_index } if opts.module.pre.intrinsics.contains_key(&(function_index.index() as u32)) => {
+            opts.module.pre.intrinsics[&(function_index.index() as u32)].class()
+        }
+        Operator::Call { .. } | Operator::CallRef { .. } | Operator::CallIndirect { .. } => {
+            OpClass::Call
+        }
+        _ if waffle::op_traits::mem_count(o) != 0 => OpClass::Memory,
+        Operator::TableGet { .. }
+        | Operator::TableSet { .. }
+        | Operator::TableSize { .. }
+        | Operator::TableGrow { .. }
+        | Operator::RefFunc { .. }
+        | Operator::StructNew { .. }
+        | Operator::StructGet { .. }
+        | Operator::StructSet { .. } => OpClass::Other,
+        _ => OpClass::Numeric,
+    }
+}
+/// Static fuel charge of `x`: every block in it, including untaken arms, but
+/// not the bodies of nested loops, which charge per iteration themselves.
+fn shape_fuel(opts: &Opts<'_>, f: Func, x: &ShapedBlock<Block>) -> u64 {
+    let b = opts.module.funcs[f].body().unwrap();
+    let costs = &opts.core.fuel;
+    let sub = |a: &Option<Box<ShapedBlock<Block>>>| a.as_ref().map_or(0, |a| shape_fuel(opts, f, a));
+    match x {
+        ShapedBlock::Simple(s) => {
+            let own = if s.label.is_invalid() {
+                0
+            } else {
+                b.blocks[s.label]
+                    .insts
+                    .iter()
+                    .filter_map(|a| a.pure_core())
+                    .map(|v| match &b.values[v] {
+                        waffle::ValueDef::Operator(o, _, _) => costs.cost(op_class(opts, o)),
+                        _ => 0,
+                    })
+                    .fold(costs.control, u64::saturating_add)
+            };
+            own.saturating_add(sub(&s.immediate)).saturating_add(sub(&s.next))
+        }
+        ShapedBlock::Loop(l) => sub(&l.next),
+        ShapedBlock::Multiple(k) => k
+            .handled
+            .iter()
+            .map(|h| shape_fuel(opts, f, &h.inner))
+            .fold(0, u64::saturating_add),
+    }
+}
 pub(crate) fn render_relooped_block(
     opts: &Opts<'_>,
     f: Func,
@@ -1071,6 +1455,10 @@ pub(crate) fn render_relooped_block(
         }
         ShapedBlock::Loop(l) => {
             let r = render_relooped_block(opts, f, &l.inner.as_ref())?;
+            let fp_ts = fp(opts);
+            let on_err = quote! { return #fp_ts::ret(Err(e)) };
+            let fuel = crate::shared::render_fuel(&opts.core, shape_fuel(opts, f, &l.inner), &on_err);
+            let epoch = crate::shared::render_epoch(&opts.core, &on_err);
             let next = l
                 .next
                 .as_ref()
@@ -1080,6 +1468,8 @@ pub(crate) fn render_relooped_block(
             let l = Lifetime::new(&format!("'l{}", l.loop_id), Span::call_site());
             quote! {
                 #l : loop{
+                    #fuel
+                    #epoch
                     #r
                 };
                 #next;
@@ -1199,9 +1589,15 @@ pub(crate) fn render_fn(opts: &Opts<'_>, f: Func) -> anyhow::Result<TokenStream>
     });
     let reloop = waffle_func_reloop::go(b);
     let x = render_relooped_block(opts, f, reloop.as_ref())?;
+    let fp_ts = fp(opts);
+    let on_err = quote! { return #fp_ts::ret(Err(e)) };
+    let fuel = crate::shared::render_fuel(&opts.core, shape_fuel(opts, f, reloop.as_ref()), &on_err);
+    let epoch = crate::shared::render_epoch(&opts.core, &on_err);
     let mut b = quote! {
         let mut cff: usize = 0;
         #(let mut #bpvalues);*;
+        #fuel
+        #epoch
         #x;
         panic!("should have returned");
     };
@@ -1221,28 +1617,36 @@ pub(crate) fn render_fn(opts: &Opts<'_>, f: Func) -> anyhow::Result<TokenStream>
 }
 
 impl<'a, X: AsRef<[u8]>> OptsLt<'a, X, LegacyPortalWaffleBackend> {
-    pub(crate) fn to_waffle_mod(&self) -> OptsLt<'a, Module<'static>, LegacyPortalWaffleBackend> {
+    /// Parse into waffle IR, after [`crate::prepass`] has replaced the
+    /// operators waffle has no IR for.  Anything else it lacks (exnref,
+    /// SIMD, …) fails here or in rendering.
+    pub(crate) fn to_waffle_mod(
+        &self,
+    ) -> anyhow::Result<OptsLt<'a, Lowered, LegacyPortalWaffleBackend>> {
         let opts = self;
-        let mut module =
-            waffle::Module::from_wasm_bytes(opts.module.as_ref(), &Default::default()).unwrap();
-        module.expand_all_funcs().unwrap();
+        let (bytes, pre) = crate::prepass::run(opts.module.as_ref())?;
+        let mut module = waffle::Module::from_wasm_bytes(&bytes, &Default::default())
+            .map_err(|e| anyhow::anyhow!("waffle frontend: {e}; try the wasmparser backend"))?;
+        module
+            .expand_all_funcs()
+            .map_err(|e| anyhow::anyhow!("waffle frontend: {e}; try the wasmparser backend"))?;
         let mut module = module.without_orig_bytes();
         // module.per_func_body(|b|unswitch::go(b)); //TODO: reloop better and make it not needed
         // eprintln!("{}",module.display());
         module.per_func_body(|f| maxssa::run(f, None, &CFGInfo::new(f)));
         let opts = OptsLt {
             // crate_path: opts.crate_path.clone(),
-            module,
+            module: Lowered { module, pre },
             backend: self.backend.clone(),
             core: self.core.clone(), // tpit: opts.tpit.clone(),
                                      // cfg: opts.cfg.clone(),
         };
-        return opts;
+        return Ok(opts);
     }
 }
 
 pub(crate) fn go(
-    opts: &OptsLt<'_, Module<'static>, LegacyPortalWaffleBackend>,
+    opts: &OptsLt<'_, Lowered, LegacyPortalWaffleBackend>,
 ) -> anyhow::Result<proc_macro2::TokenStream> {
     let mut opts = opts.clone();
     let mut ps = vec![];
@@ -1266,6 +1670,7 @@ pub(crate) fn go(
         .module
         .funcs
         .iter()
+        .filter(|a| !opts.module.pre.intrinsics.contains_key(&(a.index() as u32)))
         .map(|a| render_fn(&opts, a))
         .collect::<anyhow::Result<Vec<_>>>()?;
     let mut z = vec![];
@@ -1280,17 +1685,75 @@ pub(crate) fn go(
         });
     }
     let mut init = vec![];
+    let written_tables = written_tables(&opts.module);
     for (t, d) in opts.module.tables.entries() {
         let n = Ident::new(&t.to_string(), Span::call_site());
         let alloc_ts = alloc(&opts);
         let fp_ts = fp(&opts);
+        let mut plugged = None;
+        let imported = opts.module.imports.iter().find(|i| i.kind == ImportKind::Table(t));
+        if let Some(i) = imported {
+            for pl in opts.core.plugins.iter() {
+                if let Some(i) = pl.table_import(&opts.core, &i.module, &i.name)? {
+                    plugged = Some(i);
+                    break;
+                }
+            }
+            if plugged.as_ref().is_some_and(|p| p.view) {
+                if crate::shared::WasmTy::is_extern(d.ty) {
+                    anyhow::bail!("table import {}.{} is only a view, so it must hold funcrefs", i.module, i.name);
+                }
+                if written_tables.contains(&t) || d.func_elements.is_some() {
+                    anyhow::bail!("table import {}.{} is only a view, but this module writes it", i.module, i.name);
+                }
+            }
+            // The table belongs to someone else: only overwrite the slots
+            // this module's elements name, and never resize it.
+            if let Some(e) = d.func_elements.as_ref() {
+                let sets = e.iter().enumerate().filter(|(_, x)| !x.is_invalid()).map(|(i, x)| {
+                    let i = i as u64;
+                    let f = render_fun_ref(&opts, &quote! {C}, *x);
+                    quote! {
+                        #root::table_init(ctx.#n(), &[#fp_ts::Coe::coe(#f)], #i, 0, 1)?;
+                    }
+                });
+                init.push(quote! { #(#sets)* });
+            }
+        }
+        if let Some(TableImport { expr, view: false }) = &plugged {
+            fs.push(quote! {
+                fn #n(&mut self) -> &mut #alloc_ts::vec::Vec<#fp_ts::Value<Self>>{
+                    let ctx = self;
+                    return #expr;
+                }
+            });
+            continue;
+        }
         z.push(quote! {
             #n: #alloc_ts::vec::Vec<#fp_ts::Value<Target>>
         });
         fields.push(n.clone());
         sfields.push(n.clone());
-        if let Some(e) = d.func_elements.as_ref() {
-            let e = e.iter().map(|x| render_fun_ref(&opts, &quote! {C}, *x));
+        if let Some(TableImport { expr, view: true }) = &plugged {
+            // A view keeps its copy in `*Data`, refreshed on every access.
+            fs.push(quote! {
+                fn #n(&mut self) -> &mut #alloc_ts::vec::Vec<#fp_ts::Value<Self>>{
+                    let ctx = self;
+                    let fresh = #expr;
+                    let view = &mut ctx.data().#n;
+                    *view = fresh;
+                    return view;
+                }
+            });
+            continue;
+        }
+        if let Some(e) = d.func_elements.as_ref().filter(|_| imported.is_none()) {
+            // Invalid entries are `ref.null` element items.
+            let e = e.iter().map(|x| if x.is_invalid() {
+                quote! { Option::<#fp_ts::Value<C>>::None }
+            } else {
+                render_fun_ref(&opts, &quote! {C}, *x)
+            });
             init.push(if opts.core.flags.contains(Flags::ASYNC) {
                 quote! {
                     #(ctx.data().#n.push(#root::func::unsync::Coe::coe(#e)));*;
@@ -1353,25 +1816,35 @@ pub(crate) fn go(
                 });
             }
             Some((a, b)) => {
-                if opts.core.plugins.iter().any(|p| {
-                    p.mem_import(&opts.core, &a, &b)
-                        .ok()
-                        .and_then(|a| a)
-                        .is_some()
-                }) {
+                let mut p = if opts.core.flags.contains(Flags::LEGACY) {
+                    quote! {dyn #root::Memory + 'a}
                 } else {
-                    let m = Ident::new(&format!("{a}_{b}"), Span::call_site());
-                    let mut p = if opts.core.flags.contains(Flags::LEGACY) {
-                        quote! {dyn #root::Memory + 'a}
-                    } else {
-                        quote! {
-                            impl #root::Memory + 'a
+                    quote! {
+                        impl #root::Memory + 'a
+                    }
+                };
+                if d.shared {
+                    let alloc_ts = alloc(&opts);
+                    p = quote! { #alloc_ts::sync::Arc<#root::Mutex<#p>> };
+                };
+                let mut plugged = None;
+                for pl in opts.core.plugins.iter() {
+                    if let Some(i) = pl.mem_import(&opts.core, &a, &b)? {
+                        plugged = Some(i.expr);
+                        break;
+                    }
+                }
+                if let Some(e) = plugged {
+                    // The plugin supplies the memory, so only the entity-index
+                    // method is emitted.
+                    fs.push(quote! {
+                        fn #n<'a>(&'a mut self) -> &'a mut (#p){
+                            let ctx = self;
+                            return #e;
                         }
-                    };
-                    if d.shared {
-                        let alloc_ts = alloc(&opts);
-                        p = quote! { #alloc_ts::sync::Arc<#root::Mutex<#p>> };
-                    };
+                    });
+                } else {
+                    let m = Ident::new(&format!("{a}_{b}"), Span::call_site());
                     fs.push(quote! {
                         fn #m<'a>(&'a mut self) -> &'a mut (#p);
                         fn #n<'a>(&'a mut self) -> &'a mut (#p){
@@ -1381,6 +1854,13 @@ pub(crate) fn go(
                 }
             }
         }
+        // Host cap in bytes, on top of the declared maximum.
+        let limit = format_ident!("{me}_limit");
+        fs.push(quote! {
+            fn #limit(&self) -> Option<u64> {
+                None
+            }
+        });
         let pk = d.initial_pages * 65536;
         let pk = pk as u64;
         init.push(quote! {
@@ -1400,9 +1880,96 @@ pub(crate) fn go(
             }
         }
     }
+    // Passive element segments, emptied by `elem.drop`.
+    for (e, items) in opts.module.pre.passive_elems.iter() {
+        let n = format_ident!("elem{e}");
+        let alloc_ts = alloc(&opts);
+        let fp_ts = fp(&opts);
+        z.push(quote! {
+            #n: #alloc_ts::vec::Vec<#fp_ts::Value<Target>>
+        });
+        fields.push(n.clone());
+        sfields.push(n.clone());
+        let items = items.iter().map(|i| match i {
+            crate::prepass::ElemItem::Func(f) => {
+                let f = render_fun_ref(&opts, &quote! {C}, Func::new(*f as usize));
+                quote! { #fp_ts::Coe::coe(#f) }
+            }
+            crate::prepass::ElemItem::Null => quote! { #fp_ts::Value::<C>::default() },
+            crate::prepass::ElemItem::Global(g) => {
+                let g = format_ident!("{}", waffle::Global::new(*g as usize));
+                quote! { #fp_ts::cast::<_, #fp_ts::Value<C>, C>(ctx.#g().clone()) }
+            }
+        });
+        init.push(quote! {
+            ctx.data().#n = #alloc_ts::vec![#(#items),*]
+        });
+    }
+    // Passive data segments: a slice into a static, emptied by `data.drop`.
+    let mut statics = vec![];
+    for (d, bytes) in opts.module.pre.passive_data.iter() {
+        let n = format_ident!("data{d}");
+        let st = format_ident!("DATA{d}");
+        let len = bytes.len();
+        z.push(quote! {
+            #n: &'static [u8]
+        });
+        fields.push(n.clone());
+        statics.push(quote! {
+            static #st: [u8; #len] = [#(#bytes),*];
+        });
+        init.push(quote! {
+            ctx.data().#n = &#st
+        });
+    }
+    // Tags: a fresh identity per instance, or the importer's.
+    for (t, import) in opts.module.pre.tags.iter().enumerate() {
+        let n = format_ident!("tag{t}");
+        match import {
+            None => {
+                z.push(quote! {
+                    #n: #root::Tag
+                });
+                fields.push(n.clone());
+                fs.push(quote! {
+                    fn #n(&mut self) -> #root::Tag {
+                        self.data().#n.clone()
+                    }
+                });
+            }
+            Some((module, name)) => {
+                let m = format_ident!("{}_{}", bindname(module), bindname(name));
+                fs.push(quote! {
+                    fn #m(&mut self) -> #root::Tag;
+                    fn #n(&mut self) -> #root::Tag {
+                        self.#m()
+                    }
+                });
+            }
+        }
+    }
+    for (name, t) in opts.module.pre.tag_exports.iter() {
+        let n = format_ident!("tag{t}");
+        let mn = Ident::new(&bindname(name), Span::call_site());
+        fs.push(quote! {
+            fn #mn(&mut self) -> #root::Tag {
+                self.#n()
+            }
+        });
+    }
     let mut fs2 = vec![];
     let mut fs3 = vec![];
+    let mut export_table = vec![];
     for xp in opts.module.exports.iter() {
+        if let ExportKind::Func(f) = &xp.kind {
+            let sig = sig_to_funcsig(&opts.module.signatures[opts.module.funcs[*f].sig()]);
+            export_table.push(crate::shared::ExportEntry {
+                name: xp.name.clone(),
+                fun_ref: render_fun_ref(&opts, &quote! {C}, *f),
+                meta: crate::shared::render_sig_meta(&opts.core, &(), sig.clone()),
+                params: sig.params.len(),
+            });
+        }
         let xp = Export {
             name: bindname(&xp.name),
             kind: xp.kind.clone(),
@@ -1429,13 +1996,13 @@ pub(crate) fn go(
                 })
             }
             ExportKind::Table(t) => {
-                let d = &opts.module.tables[*t];
-                let tt = render_ty(&opts, &quote! {Self}, d.ty);
+                let alloc_ts = alloc(&opts);
+                let fp_ts = fp(&opts);
                 let x = Ident::new(&t.to_string(), Span::call_site());
                 let mn = Ident::new(&xp.name, Span::call_site());
                 let i = quote! {
-                    fn #mn(&mut self) -> &mut #{alloc(&opts)}::vec::Vec<#tt>{
-                        return &mut self.z().#x;
+                    fn #mn(&mut self) -> &mut #alloc_ts::vec::Vec<#fp_ts::Value<Self>>{
+                        return self.#x();
                     }
                 };
                 fs.push(i);
@@ -1471,10 +2038,14 @@ pub(crate) fn go(
                 };
                 fs.push(i);
             }
-            _ => todo!(),
+            _ => anyhow::bail!("unsupported export {}", xp.name),
         }
     }
-    for i in opts.module.imports.iter() {
+    let (decl, table) = crate::shared::render_export_table(&opts.core, &export_table);
+    fs3.push(decl);
+    fs2.push(table);
+    let mut links = vec![];
+    'imports: for i in opts.module.imports.iter() {
         if let ImportKind::Func(f) = &i.kind {
             for plugin in opts.core.plugins.iter() {
                 if plugin
@@ -1491,9 +2062,29 @@ pub(crate) fn go(
                     )?
                     .is_some()
                 {
-                    continue;
+                    continue 'imports;
                 }
             }
+            if opts.core.flags.contains(Flags::DYN_IMPORTS) {
+                // Resolved through the Linker at init() time instead.
+                let n = crate::shared::link_field(&i.module, &i.name);
+                let sig = &opts.module.signatures[opts.module.funcs[*f].sig()];
+                let fp_ts = fp(&opts);
+                let g = render_generics(&opts, &quote! {Target}, sig);
+                z.push(quote! {
+                    #n: Option<#fp_ts::Df<#g, Target>>
+                });
+                fields.push(n.clone());
+                let g = render_generics(&opts, &quote! {C}, sig);
+                links.push(crate::shared::render_link(
+                    &opts.core,
+                    &n,
+                    &quote! { #fp_ts::Df<#g, C> },
+                    &i.module,
+                    &i.name,
+                ));
+                continue;
+            }
             let name = format_ident!("{}_{}", bindname(&i.module), bindname(&i.name));
             fs.push(render_self_sig_import(
                 &opts,
@@ -1502,6 +2093,8 @@ pub(crate) fn go(
             ));
         }
     }
+    // Dynamic imports: resolved first, so the start function can call them.
+    init.splice(0..0, links);
     let defaults = fields.iter().map(|a| {
         quote! {
             #a: Default::default()
@@ -1520,6 +2113,8 @@ pub(crate) fn go(
     } else {
         quote! {}
     };
+    let meter_bounds = crate::shared::meter_bounds(&opts.core);
+    let link_bounds = crate::shared::link_bounds(&opts.core);
     let plugin_bounds = opts.core.plugins.iter().map(|p| {
         let b = p.bounds(&opts.core)?;
         anyhow::Ok(match b { None => quote!{}, Some(a) => quote!{+ #a} })
@@ -1530,7 +2125,64 @@ pub(crate) fn go(
     }).collect::<anyhow::Result<Vec<_>>>()?;
     let traverse_chains = sfields.iter().map(|a| quote!{ #root::Traverse::<Target>::traverse(&self.#a) });
     let traverse_mut_chains = sfields.iter().map(|a| quote!{ #root::Traverse::<Target>::traverse_mut(&mut self.#a) });
+    let visits = sfields.iter().map(|a| quote!{ #root::Traverse::<Target>::visit(&self.#a, v); });
+    let visits_mut = sfields.iter().map(|a| quote!{ #root::Traverse::<Target>::visit_mut(&mut self.#a, v); });
     let post_plugins = opts.core.plugins.iter().map(|a| a.post(&opts.core)).collect::<anyhow::Result<Vec<_>>>()?;
+    // Start function: runs after memories, globals and tables are set up.
+    let start_call = opts.module.start_func.map(|s| {
+        let f = fname(&opts, s);
+        if opts.core.flags.contains(Flags::ASYNC) {
+            quote! {
+                #f(ctx, #root::_rexport::tuple_list::tuple_list!()).go().await?;
+            }
+        } else {
+            quote! {
+                #root::_rexport::tramp::tramp(#f(ctx, #root::_rexport::tuple_list::tuple_list!()))?;
+            }
+        }
+    });
+    let (init_decl, init_impl) = if opts.core.flags.contains(Flags::ASYNC) {
+        (
+            quote! {
+                fn init<'a>(&'a mut self) -> #root::func::unsync::AsyncRec<'a, #root::_rexport::anyhow::Result<()>> where Self: 'static;
+            },
+            quote! {
+                fn init<'a>(&'a mut self) -> #root::func::unsync::AsyncRec<'a, #root::_rexport::anyhow::Result<()>> where Self: 'static{
+                    let ctx = self;
+                    #root::func::unsync::AsyncRec::wrap(async move {
+                        #root::func::unsync::AsyncRec::Ret(async move {
+                            #(#init);*;
+                            #start_call
+                            Ok::<(), #root::_rexport::anyhow::Error>(())
+                        }.await)
+                    })
+                }
+            },
+        )
+    } else {
+        (
+            quote! {
+                fn init(&mut self) -> #root::_rexport::anyhow::Result<()> where Self: 'static;
+            },
+            quote! {
+                fn init(&mut self) -> #root::_rexport::anyhow::Result<()> where Self: 'static{
+                    let ctx = self;
+                    #(#init);*;
+                    #start_call
+                    return Ok(())
+                }
+            },
+        )
+    };
+    // Thrown exceptions carry their payload through `anyhow::Error`.
+    let uses_eh = opts.module.pre.intrinsics.values().any(|i| {
+        matches!(i, crate::prepass::Intrinsic::Throw { .. } | crate::prepass::Intrinsic::TryCall { .. })
+    });
+    let eh_exref = if uses_eh {
+        quote! { + Send + Sync + 'static }
+    } else {
+        quote! {}
+    };
     Ok(quote! {
         pub struct #data<Target: #name + ?Sized>{
             #(#z),*
@@ -1542,26 +2194,29 @@ pub(crate) fn go(
             fn traverse_mut<'a>(&'a mut self) -> #alloc_ts::boxed::Box<dyn Iterator<Item = &'a mut Target::ExternRef> + 'a>{
                 return #alloc_ts::boxed::Box::new(::core::iter::empty()#(.chain(#traverse_mut_chains))*);
             }
+            fn visit(&self, v: &mut dyn #root::Visitor<Target>){
+                #(#visits)*
+            }
+            fn visit_mut(&mut self, v: &mut dyn #root::VisitorMut<Target>){
+                #(#visits_mut)*
+            }
         }
-        pub trait #name: #fp_ts::CtxSpec<ExternRef = Self::_ExternRef> #async_send_sync #(#plugin_bounds)* {
-            type _ExternRef: Clone #(#plugin_exref_bounds)*;
+        pub trait #name: #fp_ts::CtxSpec<ExternRef = Self::_ExternRef> #async_send_sync #meter_bounds #link_bounds #(#plugin_bounds)* {
+            type _ExternRef: Clone #eh_exref #(#plugin_exref_bounds)*;
             fn data(&mut self) -> &mut #data<Self>;
             #(#fs)*
         }
         pub trait #name_impl: #name{
             #(#fs3)*
-            fn init(&mut self) -> #root::_rexport::anyhow::Result<()> where Self: 'static;
+            #init_decl
         }
         const _: () = {
             use #root::Memory;
             impl<C: #name> #name_impl for C{
                 #(#fs2)*
-                fn init(&mut self) -> #root::_rexport::anyhow::Result<()> where Self: 'static{
-                    let ctx = self;
-                    #(#init);*;
-                    return Ok(())
-                }
+                #init_impl
             }
+            #(#statics)*
             #(#funcs)*
         };
         impl<Target: #name + ?Sized> Default for #data<Target>{
@@ -1581,7 +2236,7 @@ pub(crate) fn go(
         #(#post_plugins)*
     })
 }
-impl<'a> OptsLt<'a, Module<'static>, LegacyPortalWaffleBackend> {
+impl<'a> OptsLt<'a, Lowered, LegacyPortalWaffleBackend> {
     pub(crate) fn to_tokens(&self, tokens: &mut TokenStream) {
         match go(self) {
             Ok(a) => a.to_tokens(tokens),
@@ -1591,8 +2246,20 @@ impl<'a> OptsLt<'a, Module<'static>, LegacyPortalWaffleBackend> {
         }
     }
 }
+impl<'a, X: AsRef<[u8]>> OptsLt<'a, X, LegacyPortalWaffleBackend> {
+    /// Translate the module, returning any error instead of embedding it as
+    /// a `compile_error!` the way [`ToTokens`] does.
+    pub fn render(&self) -> anyhow::Result<TokenStream> {
+        go(&self.to_waffle_mod()?)
+    }
+}
 impl<'a, X: AsRef<[u8]>> ToTokens for OptsLt<'a, X, LegacyPortalWaffleBackend> {
     fn to_tokens(&self, tokens: &mut TokenStream) {
-        self.to_waffle_mod().to_tokens(tokens);
+        match self.to_waffle_mod() {
+            Ok(m) => m.to_tokens(tokens),
+            Err(e) => syn::Error::new(Span::call_site(), e)
+                .to_compile_error()
+                .to_tokens(tokens),
+        }
     }
 }
@@ -9,10 +9,23 @@ use sha3::Digest;
 use syn::{Ident, Lifetime};
 
 pub(crate) mod pit;
+pub mod build;
+pub mod link;
 pub struct MemImport {
     pub expr: TokenStream,
     // pub(crate) r#type: TokenStream
 }
+/// A plugin-supplied table: `expr` evaluates to a
+/// `&mut Vec<Value<Self>>`, with the host context bound as `ctx`.
+///
+/// With `view`, `expr` instead evaluates to a fresh `Vec<Value<Self>>`
+/// copy of a funcref table kept elsewhere.  The module keeps that copy in
+/// its own `*Data`, refreshes it on every access, and fails translation if
+/// it writes the table.
+pub struct TableImport {
+    pub expr: TokenStream,
+    pub view: bool,
+}
 pub trait Plugin {
     fn pre(&self, module: &mut OptsCore) -> anyhow::Result<()>;
     fn import(
@@ -30,6 +43,14 @@ pub trait Plugin {
     ) -> anyhow::Result<Option<MemImport>> {
         Ok(None)
     }
+    fn table_import(
+        &self,
+        opts: &OptsCore,
+        module: &str,
+        name: &str,
+    ) -> anyhow::Result<Option<TableImport>> {
+        Ok(None)
+    }
     fn post(&self, opts: &OptsCore) -> anyhow::Result<TokenStream>;
     fn bounds(&self, opts: &OptsCore) -> anyhow::Result<Option<TokenStream>> {
         Ok(None)
@@ -47,8 +68,12 @@ bitflags::bitflags! {
         // const WASIX = 0x8;
         // const BIND = 0x10;
         // const PIT = 0x20;
+        const FUEL = 0x40;
+        const EPOCH = 0x80;
         // const UNSANDBOXED = 0x2;
         const NEW_ABI = 0x100;
+        const CALL_DEPTH = 0x200;
+        const DYN_IMPORTS = 0x400;
     }
 }
 #[cfg(feature = "waffle")]
@@ -74,6 +99,7 @@ pub struct OptsCore<'a> {
     pub data: BTreeMap<Ident, TokenStream>,
     pub roots: BTreeMap<String, TokenStream>,
     pub plugins: Vec<Arc<dyn Plugin + 'a>>,
+    pub fuel: FuelCosts,
 }
 impl<'a> OptsCore<'a> {
     pub fn inflate<K: Backend>(self) -> OptsLt<'a, &'a [u8], K> {
@@ -85,6 +111,64 @@ impl<'a> OptsCore<'a> {
     }
 }
 pub type Opts<B,K> = OptsLt<'static, B, K>;
+/// Operator classes that [`FuelCosts`] prices separately.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum OpClass {
+    /// Arithmetic, conversions, constants, SIMD lanes, locals and globals.
+    Numeric,
+    /// Loads, stores, atomics and `memory.*`.
+    Memory,
+    /// Direct, indirect and reference calls, including tail calls.
+    Call,
+    /// Blocks, branches, returns and exception control.
+    Control,
+    /// Tables, references and GC objects.
+    Other,
+}
+/// Cost of each [`OpClass`] under [`Flags::FUEL`].  The default charges one
+/// unit per operator.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct FuelCosts {
+    pub numeric: u64,
+    pub memory: u64,
+    pub call: u64,
+    pub control: u64,
+    pub other: u64,
+}
+impl Default for FuelCosts {
+    fn default() -> Self {
+        Self {
+            numeric: 1,
+            memory: 1,
+            call: 1,
+            control: 1,
+            other: 1,
+        }
+    }
+}
+impl FuelCosts {
+    pub fn cost(&self, class: OpClass) -> u64 {
+        match class {
+            OpClass::Numeric => self.numeric,
+            OpClass::Memory => self.memory,
+            OpClass::Call => self.call,
+            OpClass::Control => self.control,
+            OpClass::Other => self.other,
+        }
+    }
+    /// The cost slot named `class` (`numeric`, `memory`, `call`, `control`
+    /// or `other`), for front-ends parsing overrides.
+    pub fn by_name(&mut self, class: &str) -> Option<&mut u64> {
+        Some(match class {
+            "numeric" => &mut self.numeric,
+            "memory" => &mut self.memory,
+            "call" => &mut self.call,
+            "control" => &mut self.control,
+            "other" => &mut self.other,
+            _ => return None,
+        })
+    }
+}
 #[derive(Clone)]
 #[cfg(feature = "waffle")]
 pub struct LegacyPortalWaffleBackend;
@@ -99,6 +183,8 @@ impl Backend for WasmparserBackend {}
 pub(crate) const INTRINSIC: &'static str = "wars_intrinsic/";
 #[cfg(feature = "waffle")]
 pub(crate) mod r#impl;
+#[cfg(feature = "waffle")]
+pub(crate) mod prepass;
 #[cfg(feature = "wasmparser")]
 pub(crate) mod new_backend;
 pub(crate) mod shared;
@@ -0,0 +1,155 @@
+//! Linking translated modules to each other.
+//!
+//! ```ignore
+//! // B imports `env.malloc` and `env.memory` from A, translated as `Libc`.
+//! wars::build::Module::new("App", "wasm/app.wasm")
+//!     .plugin(Arc::new(wars::link::Link::new("env", parse_quote!(crate::libc::Libc), parse_quote!(LibcHost))))
+//! ```
+use std::collections::BTreeSet;
+
+use super::*;
+use crate::shared::bindname;
+
+/// A [`Plugin`] implementing the imports from one module name with the
+/// exports of another module translated by wars, the *provider*.
+///
+/// The provider instance lives inside the importing context: the host
+/// trait gains an `AsMut<via>` bound, where `via` implements the provider's
+/// host trait.  A function import `module.name` calls the provider's export
+/// `name` through its `FooImpl` trait; a memory import `module.name` is the
+/// provider's exported memory `name`, so both modules share its bytes.
+///
+/// The two instances have separate contexts, so references are converted
+/// as they cross (`func::lift`, `func::lower`).  A provider function
+/// reference reaches the importer as one that calls it on `as_mut(ctx)`;
+/// the importer's own references cannot reach the provider, since nothing
+/// there could supply the importer's context, and passing one traps.
+///
+/// A funcref table import `module.name` is a view of the provider's
+/// exported table `name`: the importer sees the provider's current entries,
+/// converted, but translation fails if the importer writes the table
+/// (`table.set`, `table.grow`, element segments, …).
+///
+/// Both modules must be translated with the same `Flags::ASYNC` and
+/// `Flags::LEGACY` settings, and the provider must be `init()`ed before the
+/// importer.
+pub struct Link {
+    /// The import module name served, e.g. `env`.
+    pub module: String,
+    /// The provider's host trait, e.g. `crate::libc::Libc`.  Its impl trait
+    /// is the same path with `Impl` appended.
+    pub provider: syn::Path,
+    /// The concrete provider context reached through `AsMut`.
+    pub via: syn::Type,
+    /// Names to serve; `None` serves every import from `module`.
+    pub names: Option<BTreeSet<String>>,
+}
+impl Link {
+    pub fn new(module: &str, provider: syn::Path, via: syn::Type) -> Self {
+        Self {
+            module: module.to_owned(),
+            provider,
+            via,
+            names: None,
+        }
+    }
+    /// Serve only `name`; may be called several times.
+    pub fn name(mut self, name: &str) -> Self {
+        self.names
+            .get_or_insert_with(BTreeSet::new)
+            .insert(name.to_owned());
+        self
+    }
+    fn serves(&self, module: &str, name: &str) -> bool {
+        module == self.module && self.names.as_ref().map_or(true, |n| n.contains(name))
+    }
+    fn provider_impl(&self) -> syn::Path {
+        let mut p = self.provider.clone();
+        if let Some(last) = p.segments.last_mut() {
+            last.ident = format_ident!("{}Impl", last.ident);
+        }
+        p
+    }
+    fn target(&self) -> TokenStream {
+        let via = &self.via;
+        quote! { ::core::convert::AsMut::<#via>::as_mut(ctx) }
+    }
+}
+impl Plugin for Link {
+    fn pre(&self, _: &mut OptsCore) -> anyhow::Result<()> {
+        Ok(())
+    }
+    fn import(
+        &self,
+        opts: &OptsCore,
+        module: &str,
+        name: &str,
+        params: Vec<TokenStream>,
+    ) -> anyhow::Result<Option<TokenStream>> {
+        if !self.serves(module, name) {
+            return Ok(None);
+        }
+        let root = &opts.crate_path;
+        let fp = crate::shared::fp(opts);
+        let provider = self.provider_impl();
+        let export = format_ident!("{}", bindname(name));
+        let via = &self.via;
+        Ok(Some(quote! {
+            #fp::link_call::<#via, _, _, _, _>(
+                ctx,
+                #root::_rexport::tuple_list::tuple_list!(#(#params),*),
+                |p, a| #provider::#export(p, a),
+            )
+        }))
+    }
+    fn mem_import(
+        &self,
+        _: &OptsCore,
+        module: &str,
+        name: &str,
+    ) -> anyhow::Result<Option<MemImport>> {
+        if !self.serves(module, name) {
+            return Ok(None);
+        }
+        let provider = &self.provider;
+        let export = format_ident!("{}", bindname(name));
+        let target = self.target();
+        Ok(Some(MemImport {
+            expr: quote! { #provider::#export(#target) },
+        }))
+    }
+    fn table_import(
+        &self,
+        opts: &OptsCore,
+        module: &str,
+        name: &str,
+    ) -> anyhow::Result<Option<TableImport>> {
+        if !self.serves(module, name) {
+            return Ok(None);
+        }
+        // The provider's table holds `Value<via>`, so the importer can only
+        // have a converted copy of it.  Funcref entries always lift.
+        let root = &opts.crate_path;
+        let fp = crate::shared::fp(opts);
+        let provider = &self.provider;
+        let export = format_ident!("{}", bindname(name));
+        let target = self.target();
+        let via = &self.via;
+        Ok(Some(TableImport {
+            expr: quote! {
+                #provider::#export(#target)
+                    .iter()
+                    .map(|v| #fp::lift::<#via, Self>(v.clone()).unwrap_or_default())
+                    .collect::<#root::_rexport::alloc::vec::Vec<_>>()
+            },
+            view: true,
+        }))
+    }
+    fn post(&self, _: &OptsCore) -> anyhow::Result<TokenStream> {
+        Ok(quote! {})
+    }
+    fn bounds(&self, _: &OptsCore) -> anyhow::Result<Option<TokenStream>> {
+        let via = &self.via;
+        Ok(Some(quote! { ::core::convert::AsMut<#via> }))
+    }
+}
@@ -5,13 +5,14 @@
 //! collects all section data, and emits ABI v0 Rust tokens in a single pass.
 
 use super::*;
-use crate::shared::{self, bindname, alloc, fp, FuncSig, FuncSigOwned, WasmTy};
+use crate::shared::{self, bindname, alloc, fp, FuncSig, FuncSigOwned, TypeSpace, WasmTy};
 use proc_macro2::{Span, TokenStream};
 use quote::{format_ident, quote, ToTokens};
 use syn::{Ident, Lifetime};
 use wasmparser::{
-    CompositeInnerType, ElementItems, ElementKind, ExternalKind, GlobalType, MemoryType,
-    Operator, Parser, Payload, RefType, TableType, TypeRef, ValType,
+    AbstractHeapType, CompositeInnerType, ElementItems, ElementKind, ExternalKind, FieldType,
+    GlobalType, HeapType, MemoryType, Operator, Parser, Payload, RefType, StorageType, TableType,
+    TypeRef, ValType,
 };
 
 // ─── Parsed module ────────────────────────────────────────────────────────────
@@ -30,12 +31,23 @@ enum ImportKind {
     Table(u32),  // table index
     Memory(u32), // memory index
     Global(u32), // global index
+    Tag(u32),    // tag index
 }
 
 /// Everything we need from the wasm binary, collected in one streaming pass.
 struct ParsedModule {
     /// All function types from the type section (by type-section index).
     types: Vec<FuncSigOwned<ValType>>,
+    /// Whether each type-section entry is a function type; the others have
+    /// an empty placeholder in `types`.
+    is_func_type: Vec<bool>,
+    /// Field layout of every struct and array type (`None` for function types).
+    gc_layouts: Vec<Option<GcLayout>>,
+    /// Declared supertype of every type-section entry.
+    supertypes: Vec<Option<u32>>,
+    /// Canonical id of every type-section entry, shared by equivalent types
+    /// across modules.
+    canon_ids: Vec<u64>,
     /// All imports, in order.
     imports: Vec<ImportEntry>,
     /// type-section index for every function (imports first, then defined).
@@ -46,15 +58,19 @@ struct ParsedModule {
     memory_types: Vec<MemoryType>,
     /// global types (imports first, then defined).
     global_types: Vec<GlobalType>,
+    /// type-section index of every tag's signature (imports first, then defined).
+    tag_types: Vec<u32>,
     /// Exports, in order.
     exports: Vec<(String, ExternalKind, u32)>,
     /// Optional start function index.
-    #[allow(dead_code)]
     start: Option<u32>,
-    /// Active element segments: (table_idx, offset_expr_bytes, func_indices).
+    /// Element segments, indexed by element-section index.
     elements: Vec<ElementSeg>,
     /// Active data segments: (memory_idx, offset, bytes).
     data_segs: Vec<DataSeg>,
+    /// Passive data segments: (data-section index, bytes).  Active segments
+    /// are dropped once `init()` has written them, so only these need storage.
+    passive_data: Vec<(u32, Vec<u8>)>,
     /// Function bodies (raw bytes), one per *defined* function.
     /// `defined_bodies[i]` corresponds to function index `n_func_imports + i`.
     defined_bodies: Vec<(Vec<(u32, ValType)>, Vec<u8>)>, // (locals, op_bytes)
@@ -66,18 +82,43 @@ struct ParsedModule {
     n_table_imports: u32,
     /// Number of imported globals.
     n_global_imports: u32,
+    /// Number of imported tags.
+    n_tag_imports: u32,
+    /// Whether any function body throws or catches.  Exception values have
+    /// to be `Send + Sync` to travel in `anyhow::Error`.
+    uses_eh: bool,
+    /// Tables some function body or active element segment writes.
+    written_tables: BTreeSet<u32>,
     /// Best-effort function names from the name section.
     func_names: std::collections::HashMap<u32, String>,
     /// Constant-expression init values for *defined* globals (index 0 = first defined global).
     global_init_vals: Vec<Option<TokenStream>>,
 }
 
+enum GcLayout {
+    Struct(Vec<FieldType>),
+    Array(FieldType),
+}
+
 struct ElementSeg {
-    table_idx: u32,
-    /// Evaluated constant offset (we only handle i32.const initialiser).
-    offset: u32,
-    /// Function indices referenced by the segment.
-    func_indices: Vec<u32>,
+    mode: ElementMode,
+    items: Vec<ElementItem>,
+}
+
+enum ElementMode {
+    /// Written into `table_idx` at `offset` during `init()`, then dropped.
+    Active { table_idx: u32, offset: u32 },
+    /// Kept in the `*Data` struct until `elem.drop`.
+    Passive,
+    /// Only forward-declares `ref.func` targets; dropped from the start.
+    Declared,
+}
+
+/// One constant element expression.
+enum ElementItem {
+    Func(u32),
+    Null,
+    Global(u32),
 }
 
 struct DataSeg {
@@ -89,20 +130,29 @@ struct DataSeg {
 impl ParsedModule {
     fn parse(bytes: &[u8]) -> anyhow::Result<Self> {
         let mut types: Vec<FuncSigOwned<ValType>> = vec![];
+        let mut is_func_type: Vec<bool> = vec![];
+        let mut gc_layouts: Vec<Option<GcLayout>> = vec![];
+        let mut supertypes: Vec<Option<u32>> = vec![];
+        let mut canon_ids: Vec<u64> = vec![];
         let mut imports: Vec<ImportEntry> = vec![];
         let mut func_type_idx: Vec<u32> = vec![];
         let mut table_types: Vec<TableType> = vec![];
         let mut memory_types: Vec<MemoryType> = vec![];
         let mut global_types: Vec<GlobalType> = vec![];
+        let mut tag_types: Vec<u32> = vec![];
         let mut exports: Vec<(String, ExternalKind, u32)> = vec![];
         let mut start: Option<u32> = None;
         let mut elements: Vec<ElementSeg> = vec![];
         let mut data_segs: Vec<DataSeg> = vec![];
+        let mut passive_data: Vec<(u32, Vec<u8>)> = vec![];
         let mut defined_bodies: Vec<(Vec<(u32, ValType)>, Vec<u8>)> = vec![];
         let mut n_func_imports = 0u32;
         let mut n_table_imports = 0u32;
         let mut n_mem_imports = 0u32;
         let mut n_global_imports = 0u32;
+        let mut n_tag_imports = 0u32;
+        let mut uses_eh = false;
+        let mut written_tables: BTreeSet<u32> = BTreeSet::new();
         let mut func_names: std::collections::HashMap<u32, String> = Default::default();
         let mut global_init_vals: Vec<Option<TokenStream>> = vec![];
 
@@ -112,8 +162,9 @@ impl ParsedModule {
                 Payload::TypeSection(r) => {
                     for rec_group in r {
                         let rec_group = rec_group?;
+                        canon_ids.extend(canon_rec_group(&rec_group, &canon_ids));
                         for sub_ty in rec_group.types() {
-                            // Only care about func types; skip everything else.
+                            // Struct and array types get an empty placeholder signature.
                             let sig = match &sub_ty.composite_type.inner {
                                 CompositeInnerType::Func(f) => FuncSigOwned::<ValType> {
                                     params: f.params().to_vec(),
@@ -121,6 +172,13 @@ impl ParsedModule {
                                 },
                                 _ => FuncSigOwned::<ValType> { params: vec![], returns: vec![] },
                             };
+                            is_func_type.push(matches!(sub_ty.composite_type.inner, CompositeInnerType::Func(_)));
+                            gc_layouts.push(match &sub_ty.composite_type.inner {
+                                CompositeInnerType::Struct(st) => Some(GcLayout::Struct(st.fields.to_vec())),
+                                CompositeInnerType::Array(at) => Some(GcLayout::Array(at.0)),
+                                _ => None,
+                            });
+                            supertypes.push(sub_ty.supertype_idx.and_then(|i| i.as_module_index()));
                             types.push(sig);
                         }
                     }
@@ -153,7 +211,12 @@ impl ParsedModule {
                                 n_global_imports += 1;
                                 k
                             }
-                            TypeRef::Tag(_) => continue,
+                            TypeRef::Tag(t) => {
+                                tag_types.push(t.func_type_idx);
+                                let k = ImportKind::Tag(n_tag_imports);
+                                n_tag_imports += 1;
+                                k
+                            }
                         };
                         imports.push(ImportEntry {
                             module: imp.module.to_string(),
@@ -187,6 +250,11 @@ impl ParsedModule {
                         global_init_vals.push(init_val);
                     }
                 }
+                Payload::TagSection(r) => {
+                    for t in r {
+                        tag_types.push(t?.func_type_idx);
+                    }
+                }
                 Payload::ExportSection(r) => {
                     for e in r {
                         let e = e?;
@@ -199,40 +267,45 @@ impl ParsedModule {
                 Payload::ElementSection(r) => {
                     for elem in r {
                         let elem = elem?;
-                        // Only handle active segments with function indices.
-                        let (table_idx, offset) = match elem.kind {
+                        let mode = match elem.kind {
                             ElementKind::Active { table_index, offset_expr } => {
-                                let tidx = table_index.unwrap_or(0);
+                                let table_idx = table_index.unwrap_or(0);
                                 // Parse constant offset — only i32.const supported.
                                 let offset = const_i32_expr(offset_expr.get_binary_reader())?;
-                                (tidx, offset)
+                                written_tables.insert(table_idx);
+                                ElementMode::Active { table_idx, offset }
                             }
-                            _ => continue,
+                            ElementKind::Passive => ElementMode::Passive,
+                            ElementKind::Declared => ElementMode::Declared,
                         };
-                        let func_indices = match elem.items {
-                            ElementItems::Functions(r) => {
-                                r.into_iter().collect::<Result<Vec<_>, _>>()?
-                            }
+                        let items = match elem.items {
+                            ElementItems::Functions(r) => r
+                                .into_iter()
+                                .map(|f| f.map(ElementItem::Func))
+                                .collect::<Result<Vec<_>, _>>()?,
                             ElementItems::Expressions(_, r) => {
-                                let mut idxs = vec![];
+                                let mut items = vec![];
                                 for item in r {
                                     let item = item?;
-                                    idxs.push(ref_expr_func_idx(item.get_binary_reader())?);
+                                    items.push(ref_expr_item(item.get_binary_reader())?);
                                 }
-                                idxs
+                                items
                             }
                         };
-                        elements.push(ElementSeg { table_idx, offset, func_indices });
+                        elements.push(ElementSeg { mode, items });
                     }
                 }
                 Payload::DataSection(r) => {
-                    for seg in r {
+                    for (data_idx, seg) in r.into_iter().enumerate() {
                         let seg = seg?;
                         let (memory_idx, offset) = match seg.kind {
                             wasmparser::DataKind::Active { memory_index, offset_expr } => {
                                 (memory_index, const_i32_expr(offset_expr.get_binary_reader())? as u64)
                             }
-                            wasmparser::DataKind::Passive => continue,
+                            wasmparser::DataKind::Passive => {
+                                passive_data.push((data_idx as u32, seg.data.to_vec()));
+                                continue;
+                            }
                         };
                         data_segs.push(DataSeg {
                             memory_idx,
@@ -248,6 +321,24 @@ impl ParsedModule {
                     for l in lr {
                         locals.push(l?);
                     }
+                    let mut ops = body.get_operators_reader()?;
+                    while !ops.eof() {
+                        match ops.read()? {
+                            Operator::Throw { .. }
+                            | Operator::ThrowRef
+                            | Operator::TryTable { .. }
+                            | Operator::Try { .. }
+                            | Operator::Rethrow { .. } => uses_eh = true,
+                            Operator::TableSet { table }
+                            | Operator::TableGrow { table }
+                            | Operator::TableFill { table }
+                            | Operator::TableInit { table, .. }
+                            | Operator::TableCopy { dst_table: table, .. } => {
+                                written_tables.insert(table);
+                            }
+                            _ => {}
+                        }
+                    }
                     // Store the full body bytes (includes locals prefix).
                     let op_bytes = body.as_bytes().to_vec();
                     defined_bodies.push((locals, op_bytes));
@@ -273,20 +364,29 @@ impl ParsedModule {
 
         Ok(ParsedModule {
             types,
+            is_func_type,
+            gc_layouts,
+            supertypes,
+            canon_ids,
             imports,
             func_type_idx,
             table_types,
             memory_types,
             global_types,
+            tag_types,
             exports,
             start,
             elements,
             data_segs,
+            passive_data,
             defined_bodies,
             n_func_imports,
             n_mem_imports,
             n_table_imports,
             n_global_imports,
+            n_tag_imports,
+            uses_eh,
+            written_tables,
             func_names,
             global_init_vals,
         })
@@ -314,6 +414,167 @@ impl ParsedModule {
             .unwrap_or_else(|| String::new());
         format_ident!("func{}_{}", func_idx, bindname(&raw))
     }
+
+    /// Field types of struct type `idx`.
+    fn struct_fields(&self, idx: u32) -> anyhow::Result<&[FieldType]> {
+        match self.gc_layouts.get(idx as usize) {
+            Some(Some(GcLayout::Struct(f))) => Ok(f),
+            _ => anyhow::bail!("type {idx} is not a struct type"),
+        }
+    }
+
+    /// Element type of array type `idx`.
+    fn array_elem(&self, idx: u32) -> anyhow::Result<FieldType> {
+        match self.gc_layouts.get(idx as usize) {
+            Some(Some(GcLayout::Array(f))) => Ok(*f),
+            _ => anyhow::bail!("type {idx} is not an array type"),
+        }
+    }
+
+    /// Canonical ids of `idx` and its supertypes, root first.
+    fn display(&self, idx: u32) -> Vec<u64> {
+        let mut chain = vec![];
+        let mut cur = Some(idx);
+        while let Some(c) = cur {
+            chain.push(self.canon_ids[c as usize]);
+            // Supertypes always come first; this also stops on bad input.
+            cur = self.supertypes[c as usize].filter(|&s| s < c);
+        }
+        chain.reverse();
+        chain
+    }
+}
+
+impl TypeSpace<ValType> for ParsedModule {
+    fn type_sig(&self, idx: u32) -> Option<FuncSig<'_, ValType>> {
+        let is_func = self.is_func_type.get(idx as usize).copied().unwrap_or(false);
+        is_func.then(|| self.types[idx as usize].as_ref())
+    }
+}
+
+/// Canonical ids for the types of one rec group.  References into the group
+/// are written by position and references out of it by canonical id, so
+/// equivalent groups hash the same in any module (iso-recursive equality).
+fn canon_rec_group(group: &wasmparser::RecGroup, canon_ids: &[u64]) -> Vec<u64> {
+    let mut key = CanonKey { base: canon_ids.len() as u32, canon_ids, bytes: vec![] };
+    key.u32(group.types().len() as u32);
+    for sub_ty in group.types() {
+        key.sub_type(sub_ty);
+    }
+    (0..group.types().len() as u32)
+        .map(|pos| {
+            let mut h = sha3::Sha3_256::new();
+            h.update(&key.bytes);
+            h.update(pos.to_le_bytes());
+            u64::from_le_bytes(h.finalize()[..8].try_into().unwrap())
+        })
+        .collect()
+}
+
+/// The structural key of a rec group: its types written out in the shape
+/// of the binary format (same opcodes, fixed-width integers), with type
+/// indices resolved as in `canon_rec_group`.
+struct CanonKey<'a> {
+    base: u32,
+    canon_ids: &'a [u64],
+    bytes: Vec<u8>,
+}
+
+impl CanonKey<'_> {
+    fn u8(&mut self, x: u8) {
+        self.bytes.push(x);
+    }
+
+    fn u32(&mut self, x: u32) {
+        self.bytes.extend(x.to_le_bytes());
+    }
+
+    fn index(&mut self, i: Option<u32>) {
+        match i {
+            None => self.u8(0),
+            Some(i) if i >= self.base => {
+                self.u8(1);
+                self.u32(i - self.base);
+            }
+            Some(i) => {
+                self.u8(2);
+                self.bytes.extend(self.canon_ids[i as usize].to_le_bytes());
+            }
+        }
+    }
+
+    fn val(&mut self, v: ValType) {
+        match v {
+            ValType::I32 => self.u8(0x7f),
+            ValType::I64 => self.u8(0x7e),
+            ValType::F32 => self.u8(0x7d),
+            ValType::F64 => self.u8(0x7c),
+            ValType::V128 => self.u8(0x7b),
+            ValType::Ref(r) => {
+                self.u8(if r.is_nullable() { 0x63 } else { 0x64 });
+                match r.heap_type() {
+                    HeapType::Abstract { shared, ty } => {
+                        use wasmparser::AbstractHeapType as A;
+                        self.u8(if shared { 0x65 } else { 0 });
+                        self.u8(match ty {
+                            A::Func => 0x70,
+                            A::Extern => 0x6f,
+                            A::Any => 0x6e,
+                            A::None => 0x71,
+                            A::NoExtern => 0x72,
+                            A::NoFunc => 0x73,
+                            A::Eq => 0x6d,
+                            A::Struct => 0x6b,
+                            A::Array => 0x6a,
+                            A::I31 => 0x6c,
+                            A::Exn => 0x69,
+                            A::NoExn => 0x74,
+                            A::Cont => 0x68,
+                            A::NoCont => 0x75,
+                        });
+                    }
+                    _ => self.index(r.type_index().and_then(|i| i.as_module_index())),
+                }
+            }
+        }
+    }
+
+    fn field(&mut self, f: &FieldType) {
+        match f.element_type {
+            StorageType::I8 => self.u8(0x78),
+            StorageType::I16 => self.u8(0x77),
+            StorageType::Val(v) => self.val(v),
+        }
+        self.u8(f.mutable as u8);
+    }
+
+    fn sub_type(&mut self, sub_ty: &wasmparser::SubType) {
+        self.u8(if sub_ty.is_final { 0x4f } else { 0x50 });
+        self.index(sub_ty.supertype_idx.and_then(|i| i.as_module_index()));
+        self.u8(if sub_ty.composite_type.shared { 0x65 } else { 0 });
+        match &sub_ty.composite_type.inner {
+            CompositeInnerType::Func(f) => {
+                self.u8(0x60);
+                self.u32(f.params().len() as u32);
+                f.params().iter().for_each(|v| self.val(*v));
+                self.u32(f.results().len() as u32);
+                f.results().iter().for_each(|v| self.val(*v));
+            }
+            CompositeInnerType::Struct(st) => {
+                self.u8(0x5f);
+                self.u32(st.fields.len() as u32);
+                st.fields.iter().for_each(|f| self.field(f));
+            }
+            CompositeInnerType::Array(at) => {
+                self.u8(0x5e);
+                self.field(&at.0);
+            }
+            CompositeInnerType::Cont(ct) => {
+                self.u8(0x5d);
+                self.index(ct.0.as_module_index());
+            }
+        }
+    }
 }
 
 // ── Small helpers for constant-expression parsing ─────────────────────────────
@@ -350,6 +611,7 @@ fn const_val_expr(reader: wasmparser::BinaryReader<'_>) -> Option<TokenStream> {
                 let bits = value.bits();
                 return Some(quote! { f64::from_bits(#bits) });
             }
+            Operator::V128Const { value } => return Some(v128_const(*value.bytes())),
             Operator::End => break,
             _ => return None,
         }
@@ -357,18 +619,20 @@ fn const_val_expr(reader: wasmparser::BinaryReader<'_>) -> Option<TokenStream> {
     None
 }
 
-fn ref_expr_func_idx(reader: wasmparser::BinaryReader<'_>) -> anyhow::Result<u32> {
+fn ref_expr_item(reader: wasmparser::BinaryReader<'_>) -> anyhow::Result<ElementItem> {
     let mut ops = wasmparser::OperatorsReader::new(reader);
-    let mut idx = 0u32;
+    let mut item = ElementItem::Null;
     while !ops.eof() {
         let op = ops.read()?;
         match op {
-            Operator::RefFunc { function_index } => idx = function_index,
+            Operator::RefFunc { function_index } => item = ElementItem::Func(function_index),
+            Operator::RefNull { .. } => item = ElementItem::Null,
+            Operator::GlobalGet { global_index } => item = ElementItem::Global(global_index),
             Operator::End => break,
-            _ => {}
+            other => anyhow::bail!("unsupported element expression {other:?}"),
         }
     }
-    Ok(idx)
+    Ok(item)
 }
 
 // ─── Code generation ──────────────────────────────────────────────────────────
@@ -392,6 +656,7 @@ fn emit(core: &OptsCore<'_>, m: &ParsedModule) -> anyhow::Result<TokenStream> {
     let mut data_fields: Vec<TokenStream> = vec![];   // struct field declarations
     let mut field_names: Vec<Ident> = vec![];         // all field idents (for Default/Clone)
     let mut traverse_fields: Vec<Ident> = vec![];     // fields that need Traverse chain
+    let mut field_defaults: BTreeMap<Ident, TokenStream> = BTreeMap::new(); // fields without a `Default`
 
     // Extra user-supplied fields.
     for (k, v) in core.data.iter() {
@@ -399,9 +664,34 @@ fn emit(core: &OptsCore<'_>, m: &ParsedModule) -> anyhow::Result<TokenStream> {
         field_names.push(k.clone());
     }
 
+    // Tables a plugin supplies live outside `*Data`, except for the copy
+    // a view keeps.
+    let mut plugged_tables: BTreeMap<u32, TableImport> = BTreeMap::new();
+    for imp in m.imports.iter() {
+        if let ImportKind::Table(t_idx) = imp.kind {
+            let plugged = core.plugins.iter()
+                .find_map(|p| p.table_import(core, &imp.module, &imp.name).transpose())
+                .transpose()?;
+            if let Some(t) = plugged {
+                if t.view {
+                    if !m.table_types[t_idx as usize].element_type.is_func_ref() {
+                        anyhow::bail!("table import {}.{} is only a view, so it must hold funcrefs", imp.module, imp.name);
+                    }
+                    if m.written_tables.contains(&t_idx) {
+                        anyhow::bail!("table import {}.{} is only a view, but this module writes it", imp.module, imp.name);
+                    }
+                }
+                plugged_tables.insert(t_idx, t);
+            }
+        }
+    }
+
     // Tables.
     for t_idx in 0..m.table_types.len() {
         let t_idx = t_idx as u32;
+        if plugged_tables.get(&t_idx).is_some_and(|t| !t.view) {
+            continue;
+        }
         let n = format_ident!("table{t_idx}");
         data_fields.push(quote! {
             pub #n: #alloc_ts::vec::Vec<#fp_ts::Value<Target>>
@@ -414,9 +704,16 @@ fn emit(core: &OptsCore<'_>, m: &ParsedModule) -> anyhow::Result<TokenStream> {
     for g_idx in 0..m.global_types.len() {
         let g = &m.global_types[g_idx];
         let n = format_ident!("global{g_idx}");
-        let t = shared::render_ty(core, &quote! { Target }, g.content_type);
+        let t = shared::render_ty(core, m, &quote! { Target }, g.content_type);
         data_fields.push(quote! { pub #n: #t });
         field_names.push(n.clone());
+        if g.content_type.type_ref().filter(|(nullable, _)| !nullable).is_some() {
+            field_defaults.insert(n.clone(), default_val(core, m, &quote! { Target }, g.content_type));
+        }
+        // Typed function references carry no externrefs.
+        if matches!(g.content_type, ValType::Ref(_)) && g.content_type.type_ref().is_none() {
+            traverse_fields.push(n);
+        }
     }
 
     // Owned memories.
@@ -436,8 +733,62 @@ fn emit(core: &OptsCore<'_>, m: &ParsedModule) -> anyhow::Result<TokenStream> {
         field_names.push(n.clone());
     }
 
+    // Defined tags: a fresh identity per instance.
+    for tag_idx in m.n_tag_imports..m.tag_types.len() as u32 {
+        let n = format_ident!("tag{tag_idx}");
+        data_fields.push(quote! { pub #n: #root::Tag });
+        field_names.push(n);
+    }
+
+    // Passive data segments: a slice into a static, emptied by `data.drop`.
+    for (d_idx, _) in m.passive_data.iter() {
+        let n = format_ident!("data{d_idx}");
+        data_fields.push(quote! { pub #n: &'static [u8] });
+        field_names.push(n);
+    }
+
+    // Passive element segments, emptied by `elem.drop`.
+    for (e_idx, elem) in m.elements.iter().enumerate() {
+        if let ElementMode::Passive = elem.mode {
+            let n = format_ident!("elem{e_idx}");
+            data_fields.push(quote! {
+                pub #n: #alloc_ts::vec::Vec<#fp_ts::Value<Target>>
+            });
+            field_names.push(n.clone());
+            traverse_fields.push(n);
+        }
+    }
+
+    // Function imports resolved through the Linker at init() time.
+    let mut links: Vec<(u32, &ImportEntry)> = vec![];
+    if core.flags.contains(Flags::DYN_IMPORTS) {
+        for imp in m.imports.iter() {
+            let ImportKind::Func(func_idx) = imp.kind else {
+                continue;
+            };
+            let plugin_handles = core.plugins.iter().any(|p| {
+                p.import(&core, &imp.module, &imp.name, vec![])
+                    .ok()
+                    .and_then(|x| x)
+                    .is_some()
+            });
+            if plugin_handles {
+                continue;
+            }
+            let n = shared::link_field(&imp.module, &imp.name);
+            let sig = m.func_sig(func_idx);
+            let generics = shared::render_generics(core, m, &quote! { Target }, sig.as_ref());
+            data_fields.push(quote! { pub #n: Option<#fp_ts::Df<#generics, Target>> });
+            field_names.push(n);
+            links.push((func_idx, imp));
+        }
+    }
+
     let embed_field = &core.embed;
-    let defaults = field_names.iter().map(|n| quote! { #n: Default::default() });
+    let defaults = field_names.iter().map(|n| match field_defaults.get(n) {
+        Some(d) => quote! { #n: #d },
+        None => quote! { #n: Default::default() },
+    });
     let clones = field_names.iter().map(|n| quote! { #n: self.#n.clone() });
     let traverse_chain = traverse_fields.iter().map(|n| {
         quote! { .chain(#root::Traverse::<Target>::traverse(&self.#n)) }
@@ -445,6 +796,12 @@ fn emit(core: &OptsCore<'_>, m: &ParsedModule) -> anyhow::Result<TokenStream> {
     let traverse_mut_chain = traverse_fields.iter().map(|n| {
         quote! { .chain(#root::Traverse::<Target>::traverse_mut(&mut self.#n)) }
     });
+    let visits = traverse_fields.iter().map(|n| {
+        quote! { #root::Traverse::<Target>::visit(&self.#n, v); }
+    });
+    let visits_mut = traverse_fields.iter().map(|n| {
+        quote! { #root::Traverse::<Target>::visit_mut(&mut self.#n, v); }
+    });
 
     // ── Host trait methods ───────────────────────────────────────────────────
     let mut trait_methods: Vec<TokenStream> = vec![];
@@ -457,16 +814,30 @@ fn emit(core: &OptsCore<'_>, m: &ParsedModule) -> anyhow::Result<TokenStream> {
     for t_idx in 0..m.table_types.len() {
         let t_idx = t_idx as u32;
         let n = format_ident!("table{t_idx}");
+        let body = match plugged_tables.get(&t_idx) {
+            Some(TableImport { expr, view: false }) => quote! {
+                let ctx = self;
+                #expr
+            },
+            Some(TableImport { expr, view: true }) => quote! {
+                let ctx = self;
+                let fresh = #expr;
+                let view = &mut ctx.data().#n;
+                *view = fresh;
+                view
+            },
+            None => quote! { &mut self.data().#n },
+        };
         trait_methods.push(quote! {
             fn #n(&mut self) -> &mut #alloc_ts::vec::Vec<#fp_ts::Value<Self>> {
-                &mut self.data().#n
+                #body
             }
         });
     }
 
     // One method per global.
     for g_idx in 0..m.global_types.len() {
-        let g_ty = shared::render_ty(core, &quote! { Self }, m.global_types[g_idx].content_type);
+        let g_ty = shared::render_ty(core, m, &quote! { Self }, m.global_types[g_idx].content_type);
         let n = format_ident!("global{g_idx}");
         trait_methods.push(quote! {
             fn #n<'a>(&'a mut self) -> &'a mut #g_ty {
@@ -508,13 +879,57 @@ fn emit(core: &OptsCore<'_>, m: &ParsedModule) -> anyhow::Result<TokenStream> {
                 if d.shared {
                     p_ty = quote! { #alloc_ts::sync::Arc<#root::Mutex<#p_ty>> };
                 }
-                // User must impl this.
+                // A plugin may supply the memory instead.
+                let plugged = core.plugins.iter()
+                    .find_map(|p| p.mem_import(core, &imp.module, &imp.name).transpose())
+                    .transpose()?;
+                if let Some(plugged) = plugged {
+                    let e = plugged.expr;
+                    trait_methods.push(quote! {
+                        fn #n<'a>(&'a mut self) -> &'a mut (#p_ty) {
+                            let ctx = self;
+                            #e
+                        }
+                    });
+                } else {
+                    // User must impl this.
+                    trait_methods.push(quote! {
+                        fn #imp_name<'a>(&'a mut self) -> &'a mut (#p_ty);
+                    });
+                    // Alias by entity index.
+                    trait_methods.push(quote! {
+                        fn #n<'a>(&'a mut self) -> &'a mut (#p_ty) {
+                            self.#imp_name()
+                        }
+                    });
+                }
+            }
+        }
+        // Host cap in bytes, on top of the declared maximum.
+        let limit = format_ident!("memory{me_idx}_limit");
+        trait_methods.push(quote! {
+            fn #limit(&self) -> Option<u64> {
+                None
+            }
+        });
+    }
+
+    // One method per tag; imported tags come from a named method.
+    for tag_idx in 0..m.tag_types.len() as u32 {
+        let n = format_ident!("tag{tag_idx}");
+        match m.imports.iter().find(|i| i.kind == ImportKind::Tag(tag_idx)) {
+            None => trait_methods.push(quote! {
+                fn #n(&mut self) -> #root::Tag {
+                    self.data().#n.clone()
+                }
+            }),
+            Some(imp) => {
+                let imp_name = format_ident!("{}_{}", bindname(&imp.module), bindname(&imp.name));
                 trait_methods.push(quote! {
-                    fn #imp_name<'a>(&'a mut self) -> &'a mut (#p_ty);
+                    fn #imp_name(&mut self) -> #root::Tag;
                 });
-                // Alias by entity index.
                 trait_methods.push(quote! {
-                    fn #n<'a>(&'a mut self) -> &'a mut (#p_ty) {
+                    fn #n(&mut self) -> #root::Tag {
                         self.#imp_name()
                     }
                 });
@@ -532,18 +947,19 @@ fn emit(core: &OptsCore<'_>, m: &ParsedModule) -> anyhow::Result<TokenStream> {
                     .and_then(|x| x)
                     .is_some()
             });
-            if plugin_handles {
+            if plugin_handles || core.flags.contains(Flags::DYN_IMPORTS) {
                 continue;
             }
             let mname = format_ident!("{}_{}", bindname(&imp.module), bindname(&imp.name));
             let sig = m.func_sig(func_idx);
-            trait_methods.push(shared::render_self_sig_import(core, mname, sig.as_ref()));
+            trait_methods.push(shared::render_self_sig_import(core, m, mname, sig.as_ref()));
         }
     }
 
     // ── FooImpl trait: export declarations ───────────────────────────────────
     let mut impl_trait_methods: Vec<TokenStream> = vec![];
     let mut blanket_methods: Vec<TokenStream> = vec![];
+    let mut export_table: Vec<shared::ExportEntry> = vec![];
 
     for (exp_name, exp_kind, exp_idx) in &m.exports {
         match exp_kind {
@@ -552,16 +968,21 @@ fn emit(core: &OptsCore<'_>, m: &ParsedModule) -> anyhow::Result<TokenStream> {
                 let sig = m.func_sig(func_idx);
                 let rust_name = format_ident!("{}", bindname(exp_name));
                 let free_fn = m.fname(func_idx);
-                impl_trait_methods.push(shared::render_self_sig_import(core, rust_name.clone(), sig.as_ref()));
-                blanket_methods.push(shared::render_export(core, rust_name, free_fn, sig.as_ref()));
+                impl_trait_methods.push(shared::render_self_sig_import(core, m, rust_name.clone(), sig.as_ref()));
+                blanket_methods.push(shared::render_export(core, m, rust_name, free_fn, sig.as_ref()));
+                export_table.push(shared::ExportEntry {
+                    name: exp_name.clone(),
+                    fun_ref: render_fun_ref(core, m, func_idx),
+                    meta: shared::render_sig_meta(core, m, sig.as_ref()),
+                    params: sig.params.len(),
+                });
             }
             ExternalKind::Table => {
                 let t_idx = *exp_idx;
                 let n = format_ident!("table{t_idx}");
                 let mn = format_ident!("{}", bindname(exp_name));
-                let t_ty = shared::render_ty(core, &quote! { Self }, ValType::Ref(m.table_types[t_idx as usize].element_type));
                 trait_methods.push(quote! {
-                    fn #mn(&mut self) -> &mut #alloc_ts::vec::Vec<#t_ty> {
+                    fn #mn(&mut self) -> &mut #alloc_ts::vec::Vec<#fp_ts::Value<Self>> {
                         self.#n()
                     }
                 });
@@ -570,7 +991,7 @@ fn emit(core: &OptsCore<'_>, m: &ParsedModule) -> anyhow::Result<TokenStream> {
                 let g_idx = *exp_idx;
                 let n = format_ident!("global{g_idx}");
                 let mn = format_ident!("{}", bindname(exp_name));
-                let g_ty = shared::render_ty(core, &quote! { Self }, m.global_types[g_idx as usize].content_type);
+                let g_ty = shared::render_ty(core, m, &quote! { Self }, m.global_types[g_idx as usize].content_type);
                 trait_methods.push(quote! {
                     fn #mn(&mut self) -> &mut #g_ty {
                         self.#n()
@@ -596,13 +1017,40 @@ fn emit(core: &OptsCore<'_>, m: &ParsedModule) -> anyhow::Result<TokenStream> {
                     }
                 });
             }
+            ExternalKind::Tag => {
+                let n = format_ident!("tag{exp_idx}");
+                let mn = format_ident!("{}", bindname(exp_name));
+                trait_methods.push(quote! {
+                    fn #mn(&mut self) -> #root::Tag {
+                        self.#n()
+                    }
+                });
+            }
             _ => {}
         }
     }
 
+    let (decl, table) = shared::render_export_table(core, &export_table);
+    impl_trait_methods.push(decl);
+    blanket_methods.push(table);
+
     // ── init() body ──────────────────────────────────────────────────────────
     let mut init_stmts: Vec<TokenStream> = vec![];
 
+    // Dynamic imports: resolved first, so the start function can call them.
+    for (func_idx, imp) in links.iter() {
+        let n = shared::link_field(&imp.module, &imp.name);
+        let sig = m.func_sig(*func_idx);
+        let generics = shared::render_generics(core, m, &quote! { C }, sig.as_ref());
+        init_stmts.push(shared::render_link(
+            core,
+            &n,
+            &quote! { #fp_ts::Df<#generics, C> },
+            &imp.module,
+            &imp.name,
+        ));
+    }
+
     // Memory: grow + data segments.
     for me_idx in 0..m.memory_types.len() {
         let me_idx_u = me_idx as u32;
@@ -625,11 +1073,38 @@ fn emit(core: &OptsCore<'_>, m: &ParsedModule) -> anyhow::Result<TokenStream> {
         }
     }
 
+    // Passive data segments: point the *Data slices at their statics.
+    let mut data_statics: Vec<TokenStream> = vec![];
+    for (d_idx, bytes) in m.passive_data.iter() {
+        let n = format_ident!("data{d_idx}");
+        let st = format_ident!("DATA{d_idx}");
+        let len = bytes.len();
+        data_statics.push(quote! {
+            static #st: [u8; #len] = [#(#bytes),*];
+        });
+        init_stmts.push(quote! {
+            ctx.data().#n = &#st;
+        });
+    }
+
+    // Struct and array types: one runtime descriptor each.
+    for (t_idx, layout) in m.gc_layouts.iter().enumerate() {
+        if layout.is_none() {
+            continue;
+        }
+        let st = format_ident!("RTT{t_idx}");
+        let id = m.canon_ids[t_idx];
+        let display = m.display(t_idx as u32);
+        data_statics.push(quote! {
+            static #st: #root::gc::Rtt = #root::gc::Rtt { id: #id, display: &[#(#display),*] };
+        });
+    }
+
     // Globals: set to initialiser value (constants only).
     for (g_def_idx, g_abs_idx) in (m.n_global_imports..m.global_types.len() as u32).enumerate() {
         let gn = format_ident!("global{g_abs_idx}");
         if let Some(val_ts) = m.global_init_vals.get(g_def_idx) {
-            let g_ty = shared::render_ty(core, &quote! { C }, m.global_types[g_abs_idx as usize].content_type);
+            let g_ty = shared::render_ty(core, m, &quote! { C }, m.global_types[g_abs_idx as usize].content_type);
             let val = val_ts.clone();
             init_stmts.push(quote! {
                 *ctx.#gn() = (#val as #g_ty);
@@ -637,22 +1112,46 @@ fn emit(core: &OptsCore<'_>, m: &ParsedModule) -> anyhow::Result<TokenStream> {
         }
     }
 
-    // Tables: element segments.
-    for elem in m.elements.iter() {
-        let t_n = format_ident!("table{}", elem.table_idx);
-        let offset = elem.offset as usize;
-        let pushes = elem.func_indices.iter().enumerate().map(|(slot, &fidx)| {
-            let abs = offset + slot;
-            let fun_ref = render_fun_ref(core, m, fidx);
-            quote! {
-                while ctx.#t_n().len() <= #abs {
-                    ctx.#t_n().push(Default::default());
+    // Tables: grow to the declared minimum, then apply element segments.
+    // An imported table belongs to someone else and must already be big
+    // enough.
+    for (t_idx, t) in m.table_types.iter().enumerate() {
+        let t_n = format_ident!("table{t_idx}");
+        let min = t.initial as usize;
+        let imported = m.imports.iter().any(|i| i.kind == ImportKind::Table(t_idx as u32));
+        if imported {
+            init_stmts.push(quote! {
+                if ctx.#t_n().len() < #min {
+                    #root::_rexport::anyhow::bail!("imported table {} is smaller than {}", #t_idx, #min);
                 }
-                ctx.#t_n()[#abs] = #fp_ts::cast::<_,_,C>(#fun_ref);
+            });
+            continue;
+        }
+        init_stmts.push(quote! {
+            while ctx.#t_n().len() < #min {
+                ctx.#t_n().push(Default::default());
             }
         });
-        for p in pushes {
-            init_stmts.push(p);
+    }
+    for (e_idx, elem) in m.elements.iter().enumerate() {
+        let items: Vec<TokenStream> = elem.items.iter().map(|i| render_elem_item(core, m, i)).collect();
+        match elem.mode {
+            ElementMode::Active { table_idx, offset } => {
+                let t_n = format_ident!("table{table_idx}");
+                let offset = offset as u64;
+                init_stmts.push(quote! {
+                    let _seg: #alloc_ts::vec::Vec<#fp_ts::Value<C>> = #alloc_ts::vec![#(#items),*];
+                    let _seg_len = _seg.len() as u64;
+                    #root::table_init(ctx.#t_n(), &_seg, #offset, 0, _seg_len)?;
+                });
+            }
+            ElementMode::Passive => {
+                let n = format_ident!("elem{e_idx}");
+                init_stmts.push(quote! {
+                    ctx.data().#n = #alloc_ts::vec![#(#items),*];
+                });
+            }
+            ElementMode::Declared => {}
         }
     }
 
@@ -664,19 +1163,55 @@ fn emit(core: &OptsCore<'_>, m: &ParsedModule) -> anyhow::Result<TokenStream> {
         free_fns.push(ts);
     }
 
-    // init() declaration in the FooImpl trait.
-    impl_trait_methods.push(quote! {
-        fn init(&mut self) -> #root::_rexport::anyhow::Result<()> where Self: 'static;
-    });
-    // init() implementation in the blanket impl.
-    blanket_methods.push(quote! {
-        fn init(&mut self) -> #root::_rexport::anyhow::Result<()> where Self: 'static {
-            let ctx = self;
-            #(#init_stmts)*
-            Ok(())
+    // Start function: runs last, once memories, globals and tables are set up.
+    let start_call = m.start.map(|s| {
+        let fname = m.fname(s);
+        if core.flags.contains(Flags::ASYNC) {
+            quote! {
+                #fname(ctx, #root::_rexport::tuple_list::tuple_list!()).go().await?;
+            }
+        } else {
+            quote! {
+                #root::_rexport::tramp::tramp(#fname(ctx, #root::_rexport::tuple_list::tuple_list!()))?;
+            }
         }
     });
 
+    if core.flags.contains(Flags::ASYNC) {
+        // The start function may await, so async init returns an `AsyncRec`.
+        impl_trait_methods.push(quote! {
+            fn init<'a>(&'a mut self) -> #root::func::unsync::AsyncRec<'a, #root::_rexport::anyhow::Result<()>>
+            where Self: 'static;
+        });
+        blanket_methods.push(quote! {
+            fn init<'a>(&'a mut self) -> #root::func::unsync::AsyncRec<'a, #root::_rexport::anyhow::Result<()>>
+            where Self: 'static {
+                let ctx = self;
+                #root::func::unsync::AsyncRec::wrap(async move {
+                    #root::func::unsync::AsyncRec::Ret(async move {
+                        #(#init_stmts)*
+                        #start_call
+                        Ok::<(), #root::_rexport::anyhow::Error>(())
+                    }.await)
+                })
+            }
+        });
+    } else {
+        // init() declaration in the FooImpl trait.
+        impl_trait_methods.push(quote! {
+            fn init(&mut self) -> #root::_rexport::anyhow::Result<()> where Self: 'static;
+        });
+        // init() implementation in the blanket impl.
+        blanket_methods.push(quote! {
+            fn init(&mut self) -> #root::_rexport::anyhow::Result<()> where Self: 'static {
+                let ctx = self;
+                #(#init_stmts)*
+                #start_call
+                Ok(())
+            }
+        });
+    }
+
     // ── Plugin post ──────────────────────────────────────────────────────────
     let plugin_post = core.plugins.iter()
         .map(|p| p.post(core))
@@ -697,11 +1232,20 @@ fn emit(core: &OptsCore<'_>, m: &ParsedModule) -> anyhow::Result<TokenStream> {
         .map(|b| quote! { + #b })
         .collect();
 
+    // Thrown exceptions carry their payload through `anyhow::Error`.
+    let eh_exref = if m.uses_eh {
+        quote! { + Send + Sync + 'static }
+    } else {
+        quote! {}
+    };
+
     let async_bounds = if core.flags.contains(Flags::ASYNC) {
         quote! { + Send + Sync }
     } else {
         quote! {}
     };
+    let meter_bounds = shared::meter_bounds(core);
+    let link_bounds = shared::link_bounds(core);
 
     Ok(quote! {
         // ── *Data ──────────────────────────────────────────────────────────
@@ -736,15 +1280,23 @@ fn emit(core: &OptsCore<'_>, m: &ParsedModule) -> anyhow::Result<TokenStream> {
                     #(#traverse_mut_chain)*
                 )
             }
+            fn visit(&self, v: &mut dyn #root::Visitor<Target>) {
+                #(#visits)*
+            }
+            fn visit_mut(&mut self, v: &mut dyn #root::VisitorMut<Target>) {
+                #(#visits_mut)*
+            }
         }
 
         // ── Host trait ────────────────────────────────────────────────────
         pub trait #name:
             #fp_ts::CtxSpec<ExternRef = Self::_ExternRef>
             #async_bounds
+            #meter_bounds
+            #link_bounds
             #(#extra_bounds)*
         {
-            type _ExternRef: Clone #(#extra_exref)*;
+            type _ExternRef: Clone #eh_exref #(#extra_exref)*;
             #(#trait_methods)*
         }
 
@@ -759,6 +1311,7 @@ fn emit(core: &OptsCore<'_>, m: &ParsedModule) -> anyhow::Result<TokenStream> {
             impl<C: #name> #impl_trait for C {
                 #(#blanket_methods)*
             }
+            #(#data_statics)*
             #(#free_fns)*
         };
 
@@ -772,8 +1325,8 @@ fn render_fun_ref(core: &OptsCore<'_>, m: &ParsedModule, func_idx: u32) -> Token
     let root = core.crate_path.clone();
     let fp_ts = fp(core);
     let sig = m.func_sig(func_idx);
-    let ctx_ts = quote! { c };
-    let generics = shared::render_generics(core, &ctx_ts, sig.as_ref());
+    let ctx_ts = quote! { C };
+    let generics = shared::render_generics(core, m, &ctx_ts, sig.as_ref());
     let fname = m.fname(func_idx);
     if core.flags.contains(Flags::ASYNC) {
         quote! {
@@ -790,12 +1343,45 @@ fn render_fun_ref(core: &OptsCore<'_>, m: &ParsedModule, func_idx: u32) -> Token
     }
 }
 
+/// Initial value for a local, block result or global of type `ty`, with
+/// `ctx` as the context type.  A non-nullable typed function reference has
+/// no `Default`; its placeholder traps with `Trap::NullReference` if it is
+/// called before being written.
+fn default_val(core: &OptsCore<'_>, m: &ParsedModule, ctx: &TokenStream, ty: ValType) -> TokenStream {
+    if let Some(sig) = ty.type_ref().filter(|(nullable, _)| !nullable).and_then(|(_, idx)| m.type_sig(idx)) {
+        let fp_ts = fp(core);
+        let root = core.crate_path.clone();
+        let generics = shared::render_generics(core, m, ctx, sig);
+        return quote! {
+            #fp_ts::da::<#generics, #ctx, _>(|_ctx, _arg| #fp_ts::ret(Err(#root::Trap::NullReference.into())))
+        };
+    }
+    quote! { Default::default() }
+}
+
+/// Render one element-segment item as a `Value<C>` expression (valid inside
+/// `init()`, where `ctx: &mut C`).
+fn render_elem_item(core: &OptsCore<'_>, m: &ParsedModule, item: &ElementItem) -> TokenStream {
+    let fp_ts = fp(core);
+    match item {
+        ElementItem::Func(f) => {
+            let fun_ref = render_fun_ref(core, m, *f);
+            quote! { #fp_ts::cast::<_, #fp_ts::Value<C>, C>(#fun_ref) }
+        }
+        ElementItem::Null => quote! { #fp_ts::Value::<C>::default() },
+        ElementItem::Global(g) => {
+            let gn = format_ident!("global{g}");
+            quote! { #fp_ts::cast::<_, #fp_ts::Value<C>, C>(ctx.#gn().clone()) }
+        }
+    }
+}
+
 // ─── Function body emission ───────────────────────────────────────────────────
 
 fn render_fn(core: &OptsCore<'_>, m: &ParsedModule, func_idx: u32) -> anyhow::Result<TokenStream> {
     let sig = m.func_sig(func_idx).clone();
     let fname = m.fname(func_idx);
-    let sig_ts = shared::render_fn_sig(core, fname.clone(), sig.as_ref());
+    let sig_ts = shared::render_fn_sig(core, m, fname.clone(), sig.as_ref());
     let root = core.crate_path.clone();
     let fp_ts = fp(core);
     let alloc_ts = alloc(core);
@@ -823,8 +1409,21 @@ fn render_fn(core: &OptsCore<'_>, m: &ParsedModule, func_idx: u32) -> anyhow::Re
                 quote! { return #ts; }
             }
         } else {
-            let call = quote! {
-                ctx.#mname(#root::_rexport::tuple_list::tuple_list!(#(#params),*))
+            let call = if core.flags.contains(Flags::DYN_IMPORTS) {
+                let n = shared::link_field(&imp.module, &imp.name);
+                let generics = shared::render_generics(core, m, &quote! { C }, sig.as_ref());
+                shared::render_dyn_import(
+                    core,
+                    &n,
+                    &generics,
+                    &imp.module,
+                    &imp.name,
+                    params.iter().map(|id| quote! { #id }),
+                )
+            } else {
+                quote! {
+                    ctx.#mname(#root::_rexport::tuple_list::tuple_list!(#(#params),*))
+                }
             };
             if core.flags.contains(Flags::ASYNC) {
                 quote! {
@@ -859,12 +1458,13 @@ fn render_fn(core: &OptsCore<'_>, m: &ParsedModule, func_idx: u32) -> anyhow::Re
     let mut local_decls: Vec<TokenStream> = vec![];
     for (i, ty) in local_types.iter().enumerate() {
         let ln = format_ident!("local_{i}");
-        let t = shared::render_ty(core, &quote! { C }, *ty);
+        let t = shared::render_ty(core, m, &quote! { C }, *ty);
         if i < param_count {
             let pi = format_ident!("p{i}");
             local_decls.push(quote! { let mut #ln: #t = #pi; });
         } else {
-            local_decls.push(quote! { let mut #ln: #t = Default::default(); });
+            let init = default_val(core, m, &quote! { C }, *ty);
+            local_decls.push(quote! { let mut #ln: #t = #init; });
         }
     }
 
@@ -916,6 +1516,10 @@ struct EmitCtx<'a> {
     /// Output buffer stack: `out_stack.last_mut()` is where we currently write.
     /// Pushed on Block/Loop/If entry, popped and merged on End/Else.
     out_stack: Vec<Vec<TokenStream>>,
+    /// Under `Flags::FUEL`, the per-iteration charge of each loop, in order.
+    loop_fuel: Vec<u64>,
+    /// How many `loop`s (live or dead) have been seen so far.
+    loops_seen: usize,
 }
 
 struct Frame {
@@ -932,6 +1536,8 @@ struct Frame {
     condition: Option<TokenStream>,
     /// For If/Else: tokens accumulated in the *if* branch before Else was seen.
     if_stmts: Option<Vec<TokenStream>>,
+    /// For Try/TryTable: where errors raised in the body are caught.
+    eh: Option<TryFrame>,
 }
 
 #[derive(PartialEq, Eq, Clone, Copy)]
@@ -940,6 +1546,27 @@ enum FrameKind {
     Loop,
     If,
     Else,
+    TryTable,
+    Try,
+}
+
+/// Handler state of a `try_table` or legacy `try` with label `N`.  The body
+/// is emitted as `'hN: { … }`; a failing operation inside stores its error
+/// in `_eN` and breaks to `'hN`, after which the catch clauses run against
+/// `_xN`.
+struct TryFrame {
+    /// `_eN`: the error being unwound.
+    err: Ident,
+    /// `_xN`: the caught exception, bound while the handlers run.
+    exn: Ident,
+    /// `try_table` catch clauses.
+    catches: Vec<wasmparser::Catch>,
+    /// Legacy `try`: the body, once the first `catch` has been seen.
+    try_stmts: Option<Vec<TokenStream>>,
+    /// Legacy `try`: finished clauses (`None` for `catch_all`).
+    clauses: Vec<(Option<u32>, Vec<TokenStream>)>,
+    /// Legacy `try`: the clause currently being emitted.
+    clause: Option<Option<u32>>,
 }
 
 impl<'a> EmitCtx<'a> {
@@ -960,9 +1587,18 @@ impl<'a> EmitCtx<'a> {
             label_counter: 0,
             unreachable_depth: 0,
             out_stack: vec![vec![]],
+            loop_fuel: vec![],
+            loops_seen: 0,
         }
     }
 
+    /// The charge of the next `loop` in the body.
+    fn next_loop_fuel(&mut self) -> u64 {
+        let n = self.loops_seen;
+        self.loops_seen += 1;
+        self.loop_fuel.get(n).copied().unwrap_or(0)
+    }
+
     fn fresh_tmp(&mut self) -> Ident {
         let n = self.tmp_counter;
         self.tmp_counter += 1;
@@ -1034,6 +1670,28 @@ impl<'a> EmitCtx<'a> {
         Lifetime::new(&format!("'l{label}"), Span::call_site())
     }
 
+    /// What a failing operation does with its error `e`: hand it to the
+    /// innermost enclosing try body, or return it to the caller.
+    fn on_err(&self) -> TokenStream {
+        self.on_err_from(self.frames.len())
+    }
+
+    /// Like [`Self::on_err`], but only looking at `frames[..n]`.  Catch
+    /// clauses of a legacy `try` are outside its body, so they are skipped.
+    fn on_err_from(&self, n: usize) -> TokenStream {
+        for f in self.frames[..n].iter().rev() {
+            if let Some(eh) = &f.eh {
+                if eh.try_stmts.is_none() {
+                    let err = &eh.err;
+                    let lt = Lifetime::new(&format!("'h{}", f.label), Span::call_site());
+                    return quote! { { #err = Some(e); break #lt; } };
+                }
+            }
+        }
+        let fp_ts = self.fp();
+        quote! { return #fp_ts::ret(Err(e)) }
+    }
+
     fn fp(&self) -> TokenStream { fp(self.core) }
     fn root(&self) -> &syn::Path { &self.core.crate_path }
     fn alloc(&self) -> TokenStream { alloc(self.core) }
@@ -1065,7 +1723,16 @@ fn emit_body(
         stack_height: 0,
         condition: None,
         if_stmts: None,
+        eh: None,
     });
+    if core.flags.contains(Flags::FUEL) {
+        let (entry, loops) = fuel_plan(&core.fuel, op_bytes)?;
+        ctx.loop_fuel = loops;
+        let on_err = ctx.on_err();
+        ctx.emit(shared::render_fuel(core, entry, &on_err));
+    }
+    let on_err = ctx.on_err();
+    ctx.emit(shared::render_epoch(core, &on_err));
 
     // Re-parse function body from stored bytes.
     // `op_bytes` is the full body bytes (includes locals varint prefix).
@@ -1079,77 +1746,210 @@ fn emit_body(
     Ok(ctx.finish())
 }
 
-fn br_target(ctx: &EmitCtx<'_>, depth: usize) -> TokenStream {
-    let idx = ctx.frames.len().saturating_sub(depth + 1);
-    if idx >= ctx.frames.len() {
-        return quote! { return; };
+// ─── Fuel ─────────────────────────────────────────────────────────────────────
+
+/// Which [`FuelCosts`] class `op` is charged under.
+fn op_class(op: &Operator<'_>) -> OpClass {
+    match op {
+        Operator::Block { .. } | Operator::Loop { .. } | Operator::If { .. } | Operator::Else
+        | Operator::End | Operator::Br { .. } | Operator::BrIf { .. }
+        | Operator::BrTable { .. } | Operator::Return | Operator::Unreachable | Operator::Nop
+        | Operator::Try { .. } | Operator::TryTable { .. } | Operator::Catch { .. }
+        | Operator::CatchAll | Operator::Delegate { .. } | Operator::Throw { .. }
+        | Operator::ThrowRef | Operator::Rethrow { .. } | Operator::BrOnNull { .. }
+        | Operator::BrOnNonNull { .. } | Operator::BrOnCast { .. }
+        | Operator::BrOnCastFail { .. } => OpClass::Control,
+        Operator::Call { .. } | Operator::CallIndirect { .. } | Operator::CallRef { .. }
+        | Operator::ReturnCall { .. } | Operator::ReturnCallIndirect { .. }
+        | Operator::ReturnCallRef { .. } => OpClass::Call,
+        Operator::F32Load { .. } | Operator::F32Store { .. } | Operator::F64Load { .. }
+        | Operator::F64Store { .. } | Operator::I32AtomicLoad { .. }
+        | Operator::I32AtomicLoad16U { .. } | Operator::I32AtomicLoad8U { .. }
+        | Operator::I32AtomicRmw16AddU { .. } | Operator::I32AtomicRmw16AndU { .. }
+        | Operator::I32AtomicRmw16CmpxchgU { .. } | Operator::I32AtomicRmw16OrU { .. }
+        | Operator::I32AtomicRmw16SubU { .. } | Operator::I32AtomicRmw16XchgU { .. }
+        | Operator::I32AtomicRmw16XorU { .. } | Operator::I32AtomicRmw8AddU { .. }
+        | Operator::I32AtomicRmw8AndU { .. } | Operator::I32AtomicRmw8CmpxchgU { .. }
+        | Operator::I32AtomicRmw8OrU { .. } | Operator::I32AtomicRmw8SubU { .. }
+        | Operator::I32AtomicRmw8XchgU { .. } | Operator::I32AtomicRmw8XorU { .. }
+        | Operator::I32AtomicRmwAdd { .. } | Operator::I32AtomicRmwAnd { .. }
+        | Operator::I32AtomicRmwCmpxchg { .. } | Operator::I32AtomicRmwOr { .. }
+        | Operator::I32AtomicRmwSub { .. } | Operator::I32AtomicRmwXchg { .. }
+        | Operator::I32AtomicRmwXor { .. } | Operator::I32AtomicStore { .. }
+        | Operator::I32AtomicStore16 { .. } | Operator::I32AtomicStore8 { .. }
+        | Operator::I32Load { .. } | Operator::I32Load16S { .. } | Operator::I32Load16U { .. }
+        | Operator::I32Load8S { .. } | Operator::I32Load8U { .. } | Operator::I32Store { .. }
+        | Operator::I32Store16 { .. } | Operator::I32Store8 { .. }
+        | Operator::I64AtomicLoad { .. } | Operator::I64AtomicLoad16U { .. }
+        | Operator::I64AtomicLoad32U { .. } | Operator::I64AtomicLoad8U { .. }
+        | Operator::I64AtomicRmw16AddU { .. } | Operator::I64AtomicRmw16AndU { .. }
+        | Operator::I64AtomicRmw16CmpxchgU { .. } | Operator::I64AtomicRmw16OrU { .. }
+        | Operator::I64AtomicRmw16SubU { .. } | Operator::I64AtomicRmw16XchgU { .. }
+        | Operator::I64AtomicRmw16XorU { .. } | Operator::I64AtomicRmw32AddU { .. }
+        | Operator::I64AtomicRmw32AndU { .. } | Operator::I64AtomicRmw32CmpxchgU { .. }
+        | Operator::I64AtomicRmw32OrU { .. } | Operator::I64AtomicRmw32SubU { .. }
+        | Operator::I64AtomicRmw32XchgU { .. } | Operator::I64AtomicRmw32XorU { .. }
+        | Operator::I64AtomicRmw8AddU { .. } | Operator::I64AtomicRmw8AndU { .. }
+        | Operator::I64AtomicRmw8CmpxchgU { .. } | Operator::I64AtomicRmw8OrU { .. }
+        | Operator::I64AtomicRmw8SubU { .. } | Operator::I64AtomicRmw8XchgU { .. }
+        | Operator::I64AtomicRmw8XorU { .. } | Operator::I64AtomicRmwAdd { .. }
+        | Operator::I64AtomicRmwAnd { .. } | Operator::I64AtomicRmwCmpxchg { .. }
+        | Operator::I64AtomicRmwOr { .. } | Operator::I64AtomicRmwSub { .. }
+        | Operator::I64AtomicRmwXchg { .. } | Operator::I64AtomicRmwXor { .. }
+        | Operator::I64AtomicStore { .. } | Operator::I64AtomicStore16 { .. }
+        | Operator::I64AtomicStore32 { .. } | Operator::I64AtomicStore8 { .. }
+        | Operator::I64Load { .. } | Operator::I64Load16S { .. } | Operator::I64Load16U { .. }
+        | Operator::I64Load32S { .. } | Operator::I64Load32U { .. }
+        | Operator::I64Load8S { .. } | Operator::I64Load8U { .. } | Operator::I64Store { .. }
+        | Operator::I64Store16 { .. } | Operator::I64Store32 { .. }
+        | Operator::I64Store8 { .. } | Operator::MemoryAtomicNotify { .. }
+        | Operator::MemoryAtomicWait32 { .. } | Operator::MemoryAtomicWait64 { .. }
+        | Operator::V128Load { .. } | Operator::V128Load16Lane { .. }
+        | Operator::V128Load16Splat { .. } | Operator::V128Load16x4S { .. }
+        | Operator::V128Load16x4U { .. } | Operator::V128Load32Lane { .. }
+        | Operator::V128Load32Splat { .. } | Operator::V128Load32Zero { .. }
+        | Operator::V128Load32x2S { .. } | Operator::V128Load32x2U { .. }
+        | Operator::V128Load64Lane { .. } | Operator::V128Load64Splat { .. }
+        | Operator::V128Load64Zero { .. } | Operator::V128Load8Lane { .. }
+        | Operator::V128Load8Splat { .. } | Operator::V128Load8x8S { .. }
+        | Operator::V128Load8x8U { .. } | Operator::V128Store { .. }
+        | Operator::V128Store16Lane { .. } | Operator::V128Store32Lane { .. }
+        | Operator::V128Store64Lane { .. } | Operator::V128Store8Lane { .. }
+        | Operator::MemorySize { .. } | Operator::MemoryGrow { .. }
+        | Operator::MemoryCopy { .. } | Operator::MemoryFill { .. }
+        | Operator::MemoryInit { .. } | Operator::DataDrop { .. } => OpClass::Memory,
+        Operator::AnyConvertExtern | Operator::ArrayCopy { .. } | Operator::ArrayFill { .. }
+        | Operator::ArrayGet { .. } | Operator::ArrayGetS { .. } | Operator::ArrayGetU { .. }
+        | Operator::ArrayInitData { .. } | Operator::ArrayInitElem { .. } | Operator::ArrayLen
+        | Operator::ArrayNew { .. } | Operator::ArrayNewData { .. }
+        | Operator::ArrayNewDefault { .. } | Operator::ArrayNewElem { .. }
+        | Operator::ArrayNewFixed { .. } | Operator::ArraySet { .. }
+        | Operator::ElemDrop { .. } | Operator::ExternConvertAny | Operator::I31GetS
+        | Operator::I31GetU | Operator::RefAsNonNull | Operator::RefCastNonNull { .. }
+        | Operator::RefCastNullable { .. } | Operator::RefEq | Operator::RefFunc { .. }
+        | Operator::RefI31 | Operator::RefIsNull | Operator::RefNull { .. }
+        | Operator::RefTestNonNull { .. } | Operator::RefTestNullable { .. }
+        | Operator::StructGet { .. } | Operator::StructGetS { .. }
+        | Operator::StructGetU { .. } | Operator::StructNew { .. }
+        | Operator::StructNewDefault { .. } | Operator::StructSet { .. }
+        | Operator::TableCopy { .. } | Operator::TableFill { .. } | Operator::TableGet { .. }
+        | Operator::TableGrow { .. } | Operator::TableInit { .. } | Operator::TableSet { .. }
+        | Operator::TableSize { .. } => OpClass::Other,
+        _ => OpClass::Numeric,
     }
-    let frame = &ctx.frames[idx];
-    match frame.kind {
-        FrameKind::Loop => {
-            let lt = Lifetime::new(&format!("'l{}", frame.label), Span::call_site());
-            quote! { continue #lt; }
+}
+
+/// Static fuel charges for one function body: what the entry charges, and
+/// what each loop (in order of appearance) charges per iteration.  A region
+/// pays for every operator in it, including untaken branches, but not for
+/// the bodies of nested loops.
+fn fuel_plan(costs: &FuelCosts, op_bytes: &[u8]) -> anyhow::Result<(u64, Vec<u64>)> {
+    let body = wasmparser::FunctionBody::new(wasmparser::BinaryReader::new(op_bytes, 0));
+    let mut ops = body.get_operators_reader()?;
+    let mut entry = 0u64;
+    let mut loops: Vec<u64> = vec![];
+    // For every open frame, the loop it charges to (`None` = the entry).
+    let mut owners: Vec<Option<usize>> = vec![None];
+    while !ops.eof() {
+        let op = ops.read()?;
+        let owner = owners.last().copied().flatten();
+        let cost = costs.cost(op_class(&op));
+        match owner {
+            Some(l) => loops[l] = loops[l].saturating_add(cost),
+            None => entry = entry.saturating_add(cost),
         }
-        _ => {
-            let lt = Lifetime::new(&format!("'l{}", frame.label), Span::call_site());
-            quote! { break #lt; }
+        match op {
+            Operator::Loop { .. } => {
+                owners.push(Some(loops.len()));
+                loops.push(0);
+            }
+            Operator::Block { .. }
+            | Operator::If { .. }
+            | Operator::Try { .. }
+            | Operator::TryTable { .. } => owners.push(owner),
+            Operator::End | Operator::Delegate { .. } => {
+                owners.pop();
+            }
+            _ => {}
         }
     }
+    Ok((entry, loops))
+}
+
+/// Branch to frame `depth` (0 = innermost) carrying `vals`.  A block's
+/// result temp is assigned before breaking out; the function frame returns.
+fn br_with(ctx: &EmitCtx<'_>, depth: usize, vals: &[TokenStream]) -> TokenStream {
+    let fp_ts = ctx.fp();
+    let idx = ctx.frames.len().saturating_sub(depth + 1);
+    if idx == 0 {
+        let root = ctx.root();
+        return quote! {
+            return #fp_ts::ret(Ok(#root::_rexport::tuple_list::tuple_list!(
+                #(#fp_ts::cast::<_,_,C>(#vals.clone())),*
+            )));
+        };
+    }
+    let frame = &ctx.frames[idx];
+    let lt = Lifetime::new(&format!("'l{}", frame.label), Span::call_site());
+    if frame.kind == FrameKind::Loop {
+        return quote! { continue #lt; };
+    }
+    let assign = match (&frame.result_tmp, vals.last()) {
+        (Some(rt), Some(v)) => quote! { #rt = #fp_ts::cast::<_,_,C>(#v.clone()); },
+        _ => quote! {},
+    };
+    quote! { { #assign break #lt; } }
+}
+
+/// The operands a `br` to frame `depth` carries off the top of the stack.
+fn br_vals(ctx: &EmitCtx<'_>, depth: usize) -> Vec<TokenStream> {
+    let idx = ctx.frames.len().saturating_sub(depth + 1);
+    let n = match ctx.frames.get(idx) {
+        Some(f) if f.kind != FrameKind::Loop => f.result_tys.len(),
+        _ => 0,
+    };
+    ctx.stack[ctx.stack.len().saturating_sub(n)..].to_vec()
 }
 
 fn process_op(ctx: &mut EmitCtx<'_>, op: Operator<'_>) -> anyhow::Result<()> {
     let root = ctx.root().clone();
     let fp_ts = ctx.fp();
+    let on_err = ctx.on_err();
     let alloc_ts = ctx.alloc();
 
-    // Handle unreachable tracking for control structures regardless.
+    // Inside dead code, nested structures are only counted.  Reaching the
+    // `else`, `catch` or `end` of the frame that went dead resumes emission;
+    // `dead` tells those arms the operand stack is meaningless.
+    let mut dead = false;
     match &op {
-        Operator::Block { .. } | Operator::Loop { .. } | Operator::If { .. } => {
-            if ctx.unreachable_depth > 0 {
-                ctx.unreachable_depth += 1;
-                ctx.push_buf(); // match every End pop
-                ctx.frames.push(Frame { // placeholder to balance End
-                    kind: FrameKind::Block,
-                    label: 0,
-                    result_tys: vec![],
-                    result_tmp: None,
-                    stack_height: 0,
-                    condition: None,
-                    if_stmts: None,
-                });
-                return Ok(());
+        Operator::Block { .. }
+        | Operator::Loop { .. }
+        | Operator::If { .. }
+        | Operator::Try { .. }
+        | Operator::TryTable { .. }
+            if ctx.unreachable_depth > 0 =>
+        {
+            if let Operator::Loop { .. } = op {
+                ctx.next_loop_fuel();
             }
+            ctx.unreachable_depth += 1;
+            return Ok(());
         }
-        Operator::Else => {
+        Operator::Else
+        | Operator::Catch { .. }
+        | Operator::CatchAll
+        | Operator::Delegate { .. }
+        | Operator::End
+            if ctx.unreachable_depth > 0 =>
+        {
             if ctx.unreachable_depth > 1 {
-                // still inside nested unreachable
-                return Ok(());
-            }
-            if ctx.unreachable_depth == 1 {
-                // The if-branch was unreachable, but else might be reachable.
-                ctx.unreachable_depth = 0;
-                // Snapshot if-branch (empty/unreachable) and start else buffer.
-                let if_body = ctx.pop_buf();
-                {
-                    let frame = ctx.frames.last_mut().expect("else without frame");
-                    frame.if_stmts = Some(if_body);
-                    frame.kind = FrameKind::Else;
-                }
-                ctx.push_buf();
-                return Ok(()); // fall through to normal code from here
-            }
-        }
-        Operator::End => {
-            if ctx.unreachable_depth > 0 {
-                ctx.unreachable_depth -= 1;
-                if ctx.unreachable_depth > 0 {
-                    return Ok(());
+                if let Operator::Delegate { .. } | Operator::End = op {
+                    ctx.unreachable_depth -= 1;
                 }
-                // Depth reaches 0: pop corresponding frame and its buffer.
-                ctx.frames.pop();
-                ctx.pop_buf(); // discard the unreachable body
                 return Ok(());
             }
+            ctx.unreachable_depth = 0;
+            dead = true;
         }
         _ if ctx.unreachable_depth > 0 => return Ok(()),
         _ => {}
@@ -1180,18 +1980,18 @@ fn process_op(ctx: &mut EmitCtx<'_>, op: Operator<'_>) -> anyhow::Result<()> {
         Operator::LocalSet { local_index } => {
             let val = ctx.pop();
             let ln = format_ident!("local_{local_index}");
-            ctx.emit(quote! { #ln = #fp_ts::cast::<_,_,C>(#val); });
+            ctx.emit(quote! { #ln = #fp_ts::cast::<_,_,C>(#val.clone()); });
         }
         Operator::LocalTee { local_index } => {
             let val = ctx.peek();
             let ln = format_ident!("local_{local_index}");
-            ctx.emit(quote! { #ln = #fp_ts::cast::<_,_,C>(#val); });
+            ctx.emit(quote! { #ln = #fp_ts::cast::<_,_,C>(#val.clone()); });
         }
 
         // ── Globals ──────────────────────────────────────────────────────────
         Operator::GlobalGet { global_index } => {
             let gn = format_ident!("global{global_index}");
-            ctx.push_tmp(quote! { *ctx.#gn() });
+            ctx.push_tmp(quote! { ctx.#gn().clone() });
         }
         Operator::GlobalSet { global_index } => {
             let val = ctx.pop();
@@ -1210,7 +2010,7 @@ fn process_op(ctx: &mut EmitCtx<'_>, op: Operator<'_>) -> anyhow::Result<()> {
 
         // ── Unreachable / Nop ─────────────────────────────────────────────────
         Operator::Unreachable => {
-            ctx.emit(quote! { unreachable!(); });
+            ctx.emit(quote! { return #fp_ts::ret(Err(#root::Trap::Unreachable.into())); });
             ctx.unreachable_depth = 1;
         }
         Operator::Nop => {}
@@ -1224,26 +2024,25 @@ fn process_op(ctx: &mut EmitCtx<'_>, op: Operator<'_>) -> anyhow::Result<()> {
             ctx.push_tmp(quote! {
                 ((match #root::Memory::size(ctx.#mn()) {
                     Ok(a) => a,
-                    Err(e) => return #fp_ts::ret(Err(e)),
+                    Err(e) => #on_err,
                 }) / #page_size) as #rt
             });
         }
         Operator::MemoryGrow { mem } => {
             let mn = format_ident!("memory{mem}");
+            let limit = format_ident!("memory{mem}_limit");
             let mem_ty = &ctx.m.memory_types[mem as usize];
             let page_size = 65536u64;
+            let max = shared::max_pages(mem_ty.maximum, mem_ty.memory64, page_size);
             let rt = if mem_ty.memory64 { quote! { u64 } } else { quote! { u32 } };
             let delta = ctx.pop();
             ctx.push_tmp(quote! {{
-                let _old = match #root::Memory::size(ctx.#mn()) {
-                    Ok(a) => a,
-                    Err(e) => return #fp_ts::ret(Err(e)),
-                };
-                match #root::Memory::grow(ctx.#mn(), (#delta as u64) * #page_size) {
-                    Ok(_) => {}
-                    Err(e) => return #fp_ts::ret(Err(e)),
-                };
-                (_old / #page_size) as #rt
+                let _cap = ctx.#limit();
+                match #root::memory_grow(ctx.#mn(), #delta as u64, #page_size, #max, _cap) {
+                    Ok(Some(a)) => a as #rt,
+                    Ok(None) => #rt::MAX,
+                    Err(e) => #on_err,
+                }
             }});
         }
         Operator::MemoryCopy { dst_mem, src_mem } => {
@@ -1256,33 +2055,61 @@ fn process_op(ctx: &mut EmitCtx<'_>, op: Operator<'_>) -> anyhow::Result<()> {
                 {
                     let _mc_buf = match #root::Memory::read(ctx.#smn(), #src_ptr as u64, #len as u64) {
                         Ok(a) => a.as_ref().as_ref().to_owned(),
-                        Err(e) => return #fp_ts::ret(Err(e)),
+                        Err(e) => #on_err,
                     };
                     match #root::Memory::write(ctx.#dmn(), #dst_ptr as u64, &_mc_buf) {
                         Ok(()) => {}
-                        Err(e) => return #fp_ts::ret(Err(e)),
+                        Err(e) => #on_err,
                     }
                 }
             });
         }
-        Operator::MemoryFill { mem } => {
+        Operator::MemoryInit { data_index, mem } => {
             let mn = format_ident!("memory{mem}");
             let len = ctx.pop();
-            let val = ctx.pop();
+            let src = ctx.pop();
             let dst = ctx.pop();
+            // Active segments are already dropped, so they read as empty.
+            let seg = if ctx.m.passive_data.iter().any(|(d, _)| *d == data_index) {
+                let n = format_ident!("data{data_index}");
+                quote! { ctx.data().#n }
+            } else {
+                quote! { &[] }
+            };
             ctx.emit(quote! {
                 {
-                    let _mf_buf = #alloc_ts::vec![(#val & 0xffu32) as u8; #len as usize];
-                    match #root::Memory::write(ctx.#mn(), #dst as u64, &_mf_buf) {
+                    let _mi_seg: &'static [u8] = #seg;
+                    match #root::memory_init(ctx.#mn(), _mi_seg, #dst as u64, #src as u64, #len as u64) {
                         Ok(()) => {}
-                        Err(e) => return #fp_ts::ret(Err(e)),
+                        Err(e) => #on_err,
                     }
                 }
             });
         }
-
-        // ── Load / Store (handled uniformly below by matching op name) ────────
-        // i32 loads
+        Operator::DataDrop { data_index } => {
+            if ctx.m.passive_data.iter().any(|(d, _)| *d == data_index) {
+                let n = format_ident!("data{data_index}");
+                ctx.emit(quote! { ctx.data().#n = &[]; });
+            }
+        }
+        Operator::MemoryFill { mem } => {
+            let mn = format_ident!("memory{mem}");
+            let len = ctx.pop();
+            let val = ctx.pop();
+            let dst = ctx.pop();
+            ctx.emit(quote! {
+                {
+                    let _mf_buf = #alloc_ts::vec![(#val & 0xffu32) as u8; #len as usize];
+                    match #root::Memory::write(ctx.#mn(), #dst as u64, &_mf_buf) {
+                        Ok(()) => {}
+                        Err(e) => #on_err,
+                    }
+                }
+            });
+        }
+
+        // ── Load / Store (handled uniformly below by matching op name) ────────
+        // i32 loads
         Operator::I32Load { memarg } => emit_load(ctx, "i32load", memarg, 0)?,
         Operator::I32Load8S { memarg } => emit_load(ctx, "i32load8s", memarg, 0)?,
         Operator::I32Load8U { memarg } => emit_load(ctx, "i32load8u", memarg, 0)?,
@@ -1387,61 +2214,69 @@ fn process_op(ctx: &mut EmitCtx<'_>, op: Operator<'_>) -> anyhow::Result<()> {
         Operator::I64GeU => bin_op(ctx, "i64geu"),
 
         // ── Numeric: f32 ─────────────────────────────────────────────────────
-        Operator::F32Add => { let b = ctx.pop(); let a = ctx.pop(); ctx.push_tmp(quote! { (#a + #b) }); }
-        Operator::F32Sub => { let b = ctx.pop(); let a = ctx.pop(); ctx.push_tmp(quote! { (#a - #b) }); }
-        Operator::F32Mul => { let b = ctx.pop(); let a = ctx.pop(); ctx.push_tmp(quote! { (#a * #b) }); }
-        Operator::F32Div => { let b = ctx.pop(); let a = ctx.pop(); ctx.push_tmp(quote! { (#a / #b) }); }
-        Operator::F32Min => { let b = ctx.pop(); let a = ctx.pop(); ctx.push_tmp(quote! { (#a.min(#b)) }); }
-        Operator::F32Max => { let b = ctx.pop(); let a = ctx.pop(); ctx.push_tmp(quote! { (#a.max(#b)) }); }
-        Operator::F32Abs => { let a = ctx.pop(); ctx.push_tmp(quote! { (#a.abs()) }); }
-        Operator::F32Neg => { let a = ctx.pop(); ctx.push_tmp(quote! { (-#a) }); }
-        Operator::F32Ceil => { let a = ctx.pop(); ctx.push_tmp(quote! { (#a.ceil()) }); }
-        Operator::F32Floor => { let a = ctx.pop(); ctx.push_tmp(quote! { (#a.floor()) }); }
-        Operator::F32Trunc => { let a = ctx.pop(); ctx.push_tmp(quote! { (#a.trunc()) }); }
-        Operator::F32Nearest => { let a = ctx.pop(); ctx.push_tmp(quote! { (#a.round()) }); }
-        Operator::F32Sqrt => { let a = ctx.pop(); ctx.push_tmp(quote! { (#a.sqrt()) }); }
-        Operator::F32Copysign => { let b = ctx.pop(); let a = ctx.pop(); ctx.push_tmp(quote! { (#a.copysign(#b)) }); }
-        Operator::F32Eq => { let b = ctx.pop(); let a = ctx.pop(); ctx.push_tmp(quote! { (if #a == #b { 1u32 } else { 0u32 }) }); }
-        Operator::F32Ne => { let b = ctx.pop(); let a = ctx.pop(); ctx.push_tmp(quote! { (if #a != #b { 1u32 } else { 0u32 }) }); }
-        Operator::F32Lt => { let b = ctx.pop(); let a = ctx.pop(); ctx.push_tmp(quote! { (if #a < #b { 1u32 } else { 0u32 }) }); }
-        Operator::F32Gt => { let b = ctx.pop(); let a = ctx.pop(); ctx.push_tmp(quote! { (if #a > #b { 1u32 } else { 0u32 }) }); }
-        Operator::F32Le => { let b = ctx.pop(); let a = ctx.pop(); ctx.push_tmp(quote! { (if #a <= #b { 1u32 } else { 0u32 }) }); }
-        Operator::F32Ge => { let b = ctx.pop(); let a = ctx.pop(); ctx.push_tmp(quote! { (if #a >= #b { 1u32 } else { 0u32 }) }); }
+        Operator::F32Add => bin_op(ctx, "f32add"),
+        Operator::F32Sub => bin_op(ctx, "f32sub"),
+        Operator::F32Mul => bin_op(ctx, "f32mul"),
+        Operator::F32Div => bin_op(ctx, "f32div"),
+        Operator::F32Min => bin_op(ctx, "f32min"),
+        Operator::F32Max => bin_op(ctx, "f32max"),
+        Operator::F32Abs => un_op(ctx, "f32abs"),
+        Operator::F32Neg => un_op(ctx, "f32neg"),
+        Operator::F32Ceil => un_op(ctx, "f32ceil"),
+        Operator::F32Floor => un_op(ctx, "f32floor"),
+        Operator::F32Trunc => un_op(ctx, "f32trunc"),
+        Operator::F32Nearest => un_op(ctx, "f32nearest"),
+        Operator::F32Sqrt => un_op(ctx, "f32sqrt"),
+        Operator::F32Copysign => bin_op(ctx, "f32copysign"),
+        Operator::F32Eq => bin_op(ctx, "f32eq"),
+        Operator::F32Ne => bin_op(ctx, "f32ne"),
+        Operator::F32Lt => bin_op(ctx, "f32lt"),
+        Operator::F32Gt => bin_op(ctx, "f32gt"),
+        Operator::F32Le => bin_op(ctx, "f32le"),
+        Operator::F32Ge => bin_op(ctx, "f32ge"),
 
         // ── Numeric: f64 ─────────────────────────────────────────────────────
-        Operator::F64Add => { let b = ctx.pop(); let a = ctx.pop(); ctx.push_tmp(quote! { (#a + #b) }); }
-        Operator::F64Sub => { let b = ctx.pop(); let a = ctx.pop(); ctx.push_tmp(quote! { (#a - #b) }); }
-        Operator::F64Mul => { let b = ctx.pop(); let a = ctx.pop(); ctx.push_tmp(quote! { (#a * #b) }); }
-        Operator::F64Div => { let b = ctx.pop(); let a = ctx.pop(); ctx.push_tmp(quote! { (#a / #b) }); }
-        Operator::F64Min => { let b = ctx.pop(); let a = ctx.pop(); ctx.push_tmp(quote! { (#a.min(#b)) }); }
-        Operator::F64Max => { let b = ctx.pop(); let a = ctx.pop(); ctx.push_tmp(quote! { (#a.max(#b)) }); }
-        Operator::F64Abs => { let a = ctx.pop(); ctx.push_tmp(quote! { (#a.abs()) }); }
-        Operator::F64Neg => { let a = ctx.pop(); ctx.push_tmp(quote! { (-#a) }); }
-        Operator::F64Ceil => { let a = ctx.pop(); ctx.push_tmp(quote! { (#a.ceil()) }); }
-        Operator::F64Floor => { let a = ctx.pop(); ctx.push_tmp(quote! { (#a.floor()) }); }
-        Operator::F64Trunc => { let a = ctx.pop(); ctx.push_tmp(quote! { (#a.trunc()) }); }
-        Operator::F64Nearest => { let a = ctx.pop(); ctx.push_tmp(quote! { (#a.round()) }); }
-        Operator::F64Sqrt => { let a = ctx.pop(); ctx.push_tmp(quote! { (#a.sqrt()) }); }
-        Operator::F64Copysign => { let b = ctx.pop(); let a = ctx.pop(); ctx.push_tmp(quote! { (#a.copysign(#b)) }); }
-        Operator::F64Eq => { let b = ctx.pop(); let a = ctx.pop(); ctx.push_tmp(quote! { (if #a == #b { 1u32 } else { 0u32 }) }); }
-        Operator::F64Ne => { let b = ctx.pop(); let a = ctx.pop(); ctx.push_tmp(quote! { (if #a != #b { 1u32 } else { 0u32 }) }); }
-        Operator::F64Lt => { let b = ctx.pop(); let a = ctx.pop(); ctx.push_tmp(quote! { (if #a < #b { 1u32 } else { 0u32 }) }); }
-        Operator::F64Gt => { let b = ctx.pop(); let a = ctx.pop(); ctx.push_tmp(quote! { (if #a > #b { 1u32 } else { 0u32 }) }); }
-        Operator::F64Le => { let b = ctx.pop(); let a = ctx.pop(); ctx.push_tmp(quote! { (if #a <= #b { 1u32 } else { 0u32 }) }); }
-        Operator::F64Ge => { let b = ctx.pop(); let a = ctx.pop(); ctx.push_tmp(quote! { (if #a >= #b { 1u32 } else { 0u32 }) }); }
+        Operator::F64Add => bin_op(ctx, "f64add"),
+        Operator::F64Sub => bin_op(ctx, "f64sub"),
+        Operator::F64Mul => bin_op(ctx, "f64mul"),
+        Operator::F64Div => bin_op(ctx, "f64div"),
+        Operator::F64Min => bin_op(ctx, "f64min"),
+        Operator::F64Max => bin_op(ctx, "f64max"),
+        Operator::F64Abs => un_op(ctx, "f64abs"),
+        Operator::F64Neg => un_op(ctx, "f64neg"),
+        Operator::F64Ceil => un_op(ctx, "f64ceil"),
+        Operator::F64Floor => un_op(ctx, "f64floor"),
+        Operator::F64Trunc => un_op(ctx, "f64trunc"),
+        Operator::F64Nearest => un_op(ctx, "f64nearest"),
+        Operator::F64Sqrt => un_op(ctx, "f64sqrt"),
+        Operator::F64Copysign => bin_op(ctx, "f64copysign"),
+        Operator::F64Eq => bin_op(ctx, "f64eq"),
+        Operator::F64Ne => bin_op(ctx, "f64ne"),
+        Operator::F64Lt => bin_op(ctx, "f64lt"),
+        Operator::F64Gt => bin_op(ctx, "f64gt"),
+        Operator::F64Le => bin_op(ctx, "f64le"),
+        Operator::F64Ge => bin_op(ctx, "f64ge"),
 
         // ── Conversions ───────────────────────────────────────────────────────
         Operator::I32WrapI64 => { let a = ctx.pop(); ctx.push_tmp(quote! { ((#a & 0xffffffff_u64) as u32) }); }
         Operator::I64ExtendI32U => { let a = ctx.pop(); ctx.push_tmp(quote! { (#a as u64) }); }
         Operator::I64ExtendI32S => { let a = ctx.pop(); ctx.push_tmp(quote! { (#a as i32 as i64 as u64) }); }
-        Operator::I32TruncF32S => { let a = ctx.pop(); ctx.push_tmp(quote! { (unsafe { #a.trunc().to_int_unchecked::<i32>() } as u32) }); }
-        Operator::I32TruncF32U => { let a = ctx.pop(); ctx.push_tmp(quote! { (#a as u32) }); }
-        Operator::I32TruncF64S => { let a = ctx.pop(); ctx.push_tmp(quote! { (unsafe { #a.trunc().to_int_unchecked::<i32>() } as u32) }); }
-        Operator::I32TruncF64U => { let a = ctx.pop(); ctx.push_tmp(quote! { (#a as u32) }); }
-        Operator::I64TruncF32S => { let a = ctx.pop(); ctx.push_tmp(quote! { (unsafe { #a.trunc().to_int_unchecked::<i64>() } as u64) }); }
-        Operator::I64TruncF32U => { let a = ctx.pop(); ctx.push_tmp(quote! { (#a as u64) }); }
-        Operator::I64TruncF64S => { let a = ctx.pop(); ctx.push_tmp(quote! { (unsafe { #a.trunc().to_int_unchecked::<i64>() } as u64) }); }
-        Operator::I64TruncF64U => { let a = ctx.pop(); ctx.push_tmp(quote! { (#a as u64) }); }
+        Operator::I32TruncF32S => un_op(ctx, "i32truncf32s"),
+        Operator::I32TruncF32U => un_op(ctx, "i32truncf32u"),
+        Operator::I32TruncF64S => un_op(ctx, "i32truncf64s"),
+        Operator::I32TruncF64U => un_op(ctx, "i32truncf64u"),
+        Operator::I64TruncF32S => un_op(ctx, "i64truncf32s"),
+        Operator::I64TruncF32U => un_op(ctx, "i64truncf32u"),
+        Operator::I64TruncF64S => un_op(ctx, "i64truncf64s"),
+        Operator::I64TruncF64U => un_op(ctx, "i64truncf64u"),
+        Operator::I32TruncSatF32S => un_op(ctx, "i32truncsatf32s"),
+        Operator::I32TruncSatF32U => un_op(ctx, "i32truncsatf32u"),
+        Operator::I32TruncSatF64S => un_op(ctx, "i32truncsatf64s"),
+        Operator::I32TruncSatF64U => un_op(ctx, "i32truncsatf64u"),
+        Operator::I64TruncSatF32S => un_op(ctx, "i64truncsatf32s"),
+        Operator::I64TruncSatF32U => un_op(ctx, "i64truncsatf32u"),
+        Operator::I64TruncSatF64S => un_op(ctx, "i64truncsatf64s"),
+        Operator::I64TruncSatF64U => un_op(ctx, "i64truncsatf64u"),
         Operator::F32ConvertI32S => { let a = ctx.pop(); ctx.push_tmp(quote! { (#a as i32 as f32) }); }
         Operator::F32ConvertI32U => { let a = ctx.pop(); ctx.push_tmp(quote! { (#a as f32) }); }
         Operator::F32ConvertI64S => { let a = ctx.pop(); ctx.push_tmp(quote! { (#a as i64 as f32) }); }
@@ -1483,13 +2318,25 @@ fn process_op(ctx: &mut EmitCtx<'_>, op: Operator<'_>) -> anyhow::Result<()> {
         Operator::TableGet { table } => {
             let tn = format_ident!("table{table}");
             let idx = ctx.pop();
-            ctx.push_tmp(quote! { ctx.#tn()[#idx as usize].clone() });
+            let tmp = ctx.fresh_tmp();
+            ctx.emit(quote! {
+                let #tmp = match #root::table_get(ctx.#tn(), #idx as u64) {
+                    Ok(a) => a,
+                    Err(e) => #on_err,
+                };
+            });
+            ctx.push(quote! { #tmp });
         }
         Operator::TableSet { table } => {
             let val = ctx.pop();
             let idx = ctx.pop();
             let tn = format_ident!("table{table}");
-            ctx.emit(quote! { ctx.#tn()[#idx as usize] = #fp_ts::cast::<_,_,C>(#val); });
+            ctx.emit(quote! {
+                match #root::table_set(ctx.#tn(), #idx as u64, #fp_ts::cast::<_,_,C>(#val)) {
+                    Ok(()) => {}
+                    Err(e) => #on_err,
+                }
+            });
         }
         Operator::TableSize { table } => {
             let tn = format_ident!("table{table}");
@@ -1514,197 +2361,273 @@ fn process_op(ctx: &mut EmitCtx<'_>, op: Operator<'_>) -> anyhow::Result<()> {
             let val = ctx.pop();
             let off = ctx.pop();
             ctx.emit(quote! {
-                for _tf_i in 0u32..#n {
-                    ctx.#tn()[(#off + _tf_i) as usize] = #fp_ts::cast::<_,_,C>(#val.clone());
+                match #root::table_fill(ctx.#tn(), #off as u64, #fp_ts::cast::<_,_,C>(#val), #n as u64) {
+                    Ok(()) => {}
+                    Err(e) => #on_err,
                 }
             });
         }
+        Operator::TableInit { elem_index, table } => {
+            let tn = format_ident!("table{table}");
+            let n = ctx.pop();
+            let src = ctx.pop();
+            let dst = ctx.pop();
+            // Active and declared segments are dropped after instantiation.
+            let seg = match ctx.m.elements.get(elem_index as usize).map(|e| &e.mode) {
+                Some(ElementMode::Passive) => {
+                    let en = format_ident!("elem{elem_index}");
+                    quote! { ctx.data().#en.clone() }
+                }
+                _ => quote! { #alloc_ts::vec::Vec::new() },
+            };
+            ctx.emit(quote! {
+                {
+                    let _ti_seg: #alloc_ts::vec::Vec<#fp_ts::Value<C>> = #seg;
+                    match #root::table_init(ctx.#tn(), &_ti_seg, #dst as u64, #src as u64, #n as u64) {
+                        Ok(()) => {}
+                        Err(e) => #on_err,
+                    }
+                }
+            });
+        }
+        Operator::ElemDrop { elem_index } => {
+            if let Some(ElementMode::Passive) = ctx.m.elements.get(elem_index as usize).map(|e| &e.mode) {
+                let en = format_ident!("elem{elem_index}");
+                ctx.emit(quote! { ctx.data().#en = #alloc_ts::vec::Vec::new(); });
+            }
+        }
         Operator::TableCopy { dst_table, src_table } => {
             let dtn = format_ident!("table{dst_table}");
             let stn = format_ident!("table{src_table}");
             let n = ctx.pop();
             let src = ctx.pop();
             let dst = ctx.pop();
+            // Copy through a temporary so overlapping ranges of one table work.
             ctx.emit(quote! {
-                for _tc_i in 0u32..#n {
-                    let _tc_v = ctx.#stn()[(#src + _tc_i) as usize].clone();
-                    ctx.#dtn()[(#dst + _tc_i) as usize] = _tc_v;
+                {
+                    let _tc_seg: #alloc_ts::vec::Vec<#fp_ts::Value<C>> = match #root::table_slice(ctx.#stn(), #src as u64, #n as u64) {
+                        Ok(a) => a.to_vec(),
+                        Err(e) => #on_err,
+                    };
+                    match #root::table_init(ctx.#dtn(), &_tc_seg, #dst as u64, 0, #n as u64) {
+                        Ok(()) => {}
+                        Err(e) => #on_err,
+                    }
                 }
             });
         }
 
         // ── Control flow ──────────────────────────────────────────────────────
-        Operator::Block { blockty } => {
-            let label = ctx.fresh_label();
-            let result_tys = blocktype_results(ctx.m, blockty);
-            let result_tmp = if result_tys.is_empty() {
-                None
-            } else {
-                let t = format_ident!("_b{label}");
-                let ty = shared::render_ty(ctx.core, &quote! { C }, result_tys[0]);
-                ctx.emit(quote! { let mut #t: #ty = Default::default(); });
-                Some(t)
-            };
-            let sh = ctx.stack.len();
-            ctx.push_buf();
-            ctx.frames.push(Frame {
-                kind: FrameKind::Block,
-                label,
-                result_tys,
-                result_tmp,
-                stack_height: sh,
-                condition: None,
-                if_stmts: None,
-            });
-        }
+        Operator::Block { blockty } => open_frame(ctx, FrameKind::Block, blockty, None, None),
         Operator::Loop { blockty } => {
-            let label = ctx.fresh_label();
-            let result_tys = blocktype_results(ctx.m, blockty);
-            let sh = ctx.stack.len();
-            ctx.push_buf();
-            ctx.frames.push(Frame {
-                kind: FrameKind::Loop,
-                label,
-                result_tys,
-                result_tmp: None,
-                stack_height: sh,
-                condition: None,
-                if_stmts: None,
-            });
+            open_frame(ctx, FrameKind::Loop, blockty, None, None);
+            // Charged at the top of the body, so every back-edge pays again.
+            let cost = ctx.next_loop_fuel();
+            let on_err = ctx.on_err();
+            ctx.emit(shared::render_fuel(ctx.core, cost, &on_err));
+            ctx.emit(shared::render_epoch(ctx.core, &on_err));
         }
         Operator::If { blockty } => {
             let cond = ctx.pop();
-            let label = ctx.fresh_label();
-            let result_tys = blocktype_results(ctx.m, blockty);
-            let result_tmp = if result_tys.is_empty() {
-                None
-            } else {
-                let t = format_ident!("_b{label}");
-                let ty = shared::render_ty(ctx.core, &quote! { C }, result_tys[0]);
-                ctx.emit(quote! { let mut #t: #ty = Default::default(); });
-                Some(t)
-            };
-            let sh = ctx.stack.len();
-            ctx.push_buf();
-            ctx.frames.push(Frame {
-                kind: FrameKind::If,
-                label,
-                result_tys,
-                result_tmp,
-                stack_height: sh,
-                condition: Some(cond),
-                if_stmts: None,
-            });
+            open_frame(ctx, FrameKind::If, blockty, Some(cond), None);
         }
         Operator::Else => {
             // Snapshot the if-branch buffer, start a fresh else buffer.
-            let if_body = ctx.pop_buf();
-            {
-                let frame = ctx.frames.last_mut().expect("else without frame");
-                frame.if_stmts = Some(if_body);
-                frame.kind = FrameKind::Else;
-            }
+            let frame = ctx.frames.last().expect("else without frame");
+            let assign = result_assign(ctx, frame.result_tmp.as_ref(), dead);
+            let mut if_body = ctx.pop_buf();
+            if_body.push(assign);
+            let frame = ctx.frames.last_mut().expect("else without frame");
+            frame.if_stmts = Some(if_body);
+            frame.kind = FrameKind::Else;
+            let sh = frame.stack_height;
+            ctx.stack.truncate(sh);
             ctx.push_buf();
         }
+        Operator::End if ctx.frames.len() == 1 => {
+            // The function body itself: falling off the end returns whatever
+            // is left on the stack.
+            ctx.frames.pop();
+            if !dead {
+                emit_return(ctx);
+            }
+        }
         Operator::End => {
-            if let Some(frame) = ctx.frames.pop() {
-                let body = ctx.pop_buf();
-                let stmts = quote! { #(#body)* };
-                match frame.kind {
-                    FrameKind::Block => {
-                        let lt = Lifetime::new(&format!("'l{}", frame.label), Span::call_site());
-                        // Assign result if any.
-                        let result_assign = frame.result_tmp.as_ref().and_then(|rt| {
-                            ctx.stack.last().map(|val| {
-                                let val = val.clone();
-                                quote! { #rt = #fp_ts::cast::<_,_,C>(#val); }
-                            })
-                        }).unwrap_or_default();
-                        ctx.emit(quote! {
-                            #lt: {
-                                #stmts
-                                #result_assign
-                            }
-                        });
-                        if let Some(rt) = frame.result_tmp {
-                            ctx.push(quote! { #rt });
+            let frame = ctx.frames.pop().expect("end without frame");
+            let body = ctx.pop_buf();
+            let result_assign = result_assign(ctx, frame.result_tmp.as_ref(), dead);
+            ctx.stack.truncate(frame.stack_height);
+            let stmts = quote! { #(#body)* };
+            let lt = Lifetime::new(&format!("'l{}", frame.label), Span::call_site());
+            match frame.kind {
+                FrameKind::Block => {
+                    ctx.emit(quote! {
+                        #lt: {
+                            #stmts
+                            #result_assign
                         }
-                    }
-                    FrameKind::Loop => {
-                        let lt = Lifetime::new(&format!("'l{}", frame.label), Span::call_site());
-                        ctx.emit(quote! {
-                            #lt: loop {
-                                #stmts
-                                break;
-                            }
-                        });
-                    }
-                    FrameKind::If => {
-                        // if without else
-                        let cond = frame.condition.clone().unwrap_or(quote! { 0u32 });
-                        let result_assign = frame.result_tmp.as_ref().and_then(|rt| {
-                            ctx.stack.last().map(|val| {
-                                let val = val.clone();
-                                quote! { #rt = #fp_ts::cast::<_,_,C>(#val); }
-                            })
-                        }).unwrap_or_default();
-                        ctx.emit(quote! {
+                    });
+                }
+                FrameKind::Loop => {
+                    ctx.emit(quote! {
+                        #lt: loop {
+                            #stmts
+                            #result_assign
+                            break;
+                        }
+                    });
+                }
+                FrameKind::If => {
+                    // if without else
+                    let cond = frame.condition.clone().unwrap_or(quote! { 0u32 });
+                    ctx.emit(quote! {
+                        #lt: {
                             if #cond != 0u32 {
                                 #stmts
                                 #result_assign
                             }
-                        });
-                        if let Some(rt) = frame.result_tmp {
-                            ctx.push(quote! { #rt });
                         }
-                    }
-                    FrameKind::Else => {
-                        // if + else
-                        let cond = frame.condition.clone().unwrap_or(quote! { 0u32 });
-                        let if_body_stmts = frame.if_stmts.unwrap_or_default();
-                        let if_stmts_ts = quote! { #(#if_body_stmts)* };
-                        let result_assign = frame.result_tmp.as_ref().and_then(|rt| {
-                            ctx.stack.last().map(|val| {
-                                let val = val.clone();
-                                quote! { #rt = #fp_ts::cast::<_,_,C>(#val); }
-                            })
-                        }).unwrap_or_default();
-                        ctx.emit(quote! {
+                    });
+                }
+                FrameKind::Else => {
+                    // if + else
+                    let cond = frame.condition.clone().unwrap_or(quote! { 0u32 });
+                    let if_body_stmts = frame.if_stmts.unwrap_or_default();
+                    ctx.emit(quote! {
+                        #lt: {
                             if #cond != 0u32 {
-                                #if_stmts_ts
+                                #(#if_body_stmts)*
                             } else {
                                 #stmts
                                 #result_assign
                             }
-                        });
-                        if let Some(rt) = frame.result_tmp {
-                            ctx.push(quote! { #rt });
                         }
+                    });
+                }
+                FrameKind::TryTable | FrameKind::Try => {
+                    let mut eh = frame.eh.expect("try frame without handler");
+                    let handler = Lifetime::new(&format!("'h{}", frame.label), Span::call_site());
+                    let (try_stmts, dispatch) = if frame.kind == FrameKind::TryTable {
+                        (quote! { #stmts #result_assign }, catch_table(ctx, &eh))
+                    } else {
+                        // The last clause (or the body, if there are none) is still open.
+                        let stmts = quote! { #stmts #result_assign };
+                        match eh.clause.take() {
+                            None => eh.try_stmts = Some(vec![stmts]),
+                            Some(tag) => eh.clauses.push((tag, vec![stmts])),
+                        }
+                        let try_stmts = eh.try_stmts.take().unwrap_or_default();
+                        (quote! { #(#try_stmts)* }, catch_clauses(ctx, &mut eh))
+                    };
+                    ctx.emit(quote! {
+                        #lt: {
+                            #handler: {
+                                #try_stmts
+                                break #lt;
+                            }
+                            #dispatch
+                        }
+                    });
+                }
+            }
+            if let Some(rt) = frame.result_tmp {
+                ctx.push(quote! { #rt });
+            }
+        }
+
+        // ── Exceptions ───────────────────────────────────────────────────────
+        Operator::TryTable { try_table } => {
+            open_frame(ctx, FrameKind::TryTable, try_table.ty, None, Some(try_table.catches));
+        }
+        Operator::Try { blockty } => open_frame(ctx, FrameKind::Try, blockty, None, Some(vec![])),
+        Operator::Catch { tag_index } => begin_clause(ctx, Some(tag_index), dead),
+        Operator::CatchAll => begin_clause(ctx, None, dead),
+        Operator::Delegate { relative_depth } => {
+            // Close the `try`, handing anything its body raises to the
+            // handler of label `relative_depth`, counted from outside it.
+            let frame = ctx.frames.pop().expect("delegate without try");
+            let body = ctx.pop_buf();
+            let result_assign = result_assign(ctx, frame.result_tmp.as_ref(), dead);
+            ctx.stack.truncate(frame.stack_height);
+            let outer = ctx.on_err_from(ctx.frames.len() - relative_depth as usize);
+            let err = &frame.eh.as_ref().expect("delegate without try").err;
+            let lt = Lifetime::new(&format!("'l{}", frame.label), Span::call_site());
+            let handler = Lifetime::new(&format!("'h{}", frame.label), Span::call_site());
+            ctx.emit(quote! {
+                #lt: {
+                    #handler: {
+                        #(#body)*
+                        #result_assign
+                        break #lt;
                     }
+                    let e = #err.take().unwrap();
+                    #outer
                 }
+            });
+            if let Some(rt) = frame.result_tmp {
+                ctx.push(quote! { #rt });
             }
         }
+        Operator::Throw { tag_index } => {
+            let n = tag_params(ctx.m, tag_index).len();
+            let mut args: Vec<TokenStream> = (0..n).map(|_| ctx.pop()).collect();
+            args.reverse();
+            let tn = format_ident!("tag{tag_index}");
+            ctx.emit(quote! {
+                {
+                    let e = #fp_ts::throw::<C>(ctx.#tn(), #alloc_ts::vec![
+                        #(#fp_ts::cast::<_, #fp_ts::Value<C>, C>(#args.clone())),*
+                    ]);
+                    #on_err
+                }
+            });
+            ctx.unreachable_depth = 1;
+        }
+        Operator::ThrowRef => {
+            let a = ctx.pop();
+            ctx.emit(quote! {
+                {
+                    let e = #fp_ts::throw_ref::<C>(#fp_ts::cast::<_,_,C>(#a.clone()));
+                    #on_err
+                }
+            });
+            ctx.unreachable_depth = 1;
+        }
+        Operator::Rethrow { relative_depth } => {
+            let idx = ctx.frames.len() - 1 - relative_depth as usize;
+            let exn = ctx.frames[idx].eh.as_ref().expect("rethrow outside catch").exn.clone();
+            ctx.emit(quote! {
+                {
+                    let e = #fp_ts::rethrow::<C>(#exn.clone());
+                    #on_err
+                }
+            });
+            ctx.unreachable_depth = 1;
+        }
 
         // ── Branches ─────────────────────────────────────────────────────────
         Operator::Br { relative_depth } => {
-            let br = br_target(ctx, relative_depth as usize);
-            ctx.emit(quote! { #br });
+            let vals = br_vals(ctx, relative_depth as usize);
+            let br = br_with(ctx, relative_depth as usize, &vals);
+            ctx.emit(br);
             ctx.unreachable_depth = 1;
         }
         Operator::BrIf { relative_depth } => {
             let cond = ctx.pop();
-            let br = br_target(ctx, relative_depth as usize);
+            let vals = br_vals(ctx, relative_depth as usize);
+            let br = br_with(ctx, relative_depth as usize, &vals);
             ctx.emit(quote! { if #cond != 0u32 { #br } });
         }
         Operator::BrTable { targets } => {
             let val = ctx.pop();
-            let def = br_target(ctx, targets.default() as usize);
+            let def = targets.default() as usize;
+            let def = br_with(ctx, def, &br_vals(ctx, def));
             let arms: Vec<TokenStream> = targets
                 .targets()
                 .enumerate()
                 .map(|(i, t)| {
-                    let t = t.unwrap();
-                    let br = br_target(ctx, t as usize);
+                    let t = t.unwrap() as usize;
+                    let br = br_with(ctx, t, &br_vals(ctx, t));
                     quote! { #i => { #br } }
                 })
                 .collect();
@@ -1738,43 +2661,315 @@ fn process_op(ctx: &mut EmitCtx<'_>, op: Operator<'_>) -> anyhow::Result<()> {
             }
         }
         Operator::CallIndirect { type_index, table_index } => {
-            let sig = ctx.m.types[type_index as usize].clone();
-            let n_params = sig.params.len();
             let idx = ctx.pop(); // table index is top of stack
-            let mut args: Vec<TokenStream> = (0..n_params).map(|_| ctx.pop()).collect();
-            args.reverse();
             let tn = format_ident!("table{table_index}");
-            let generics = shared::render_generics(ctx.core, &quote! { c }, sig.as_ref());
-            let call_ts = if ctx.core.flags.contains(Flags::ASYNC) {
-                quote! {
-                    #fp_ts::call_ref::<#generics, C>(
-                        ctx,
-                        #fp_ts::cast(ctx.#tn()[#idx as usize].clone()),
-                        #root::_rexport::tuple_list::tuple_list!(#(#fp_ts::cast::<_,_,C>(#args.clone())),*)
-                    ).go().await?
+            let generics = shared::render_generics(ctx.core, ctx.m, &quote! { C }, ctx.m.types[type_index as usize].as_ref());
+            let resolve = quote! {
+                #root::table_get(ctx.#tn(), #idx as u64).and_then(#fp_ts::indirect::<#generics, C>)
+            };
+            emit_call_df(ctx, type_index, resolve);
+        }
+        Operator::CallRef { type_index } => {
+            let r = ctx.pop();
+            let generics = shared::render_generics(ctx.core, ctx.m, &quote! { C }, ctx.m.types[type_index as usize].as_ref());
+            emit_call_df(ctx, type_index, quote! { #fp_ts::call_target::<#generics, C, _>(#r.clone()) });
+        }
+
+        // ── Typed function references ─────────────────────────────────────────
+        Operator::RefAsNonNull => {
+            let r = ctx.pop();
+            let tmp = ctx.fresh_tmp();
+            ctx.emit(quote! {
+                let #tmp = #r.clone();
+                if #fp_ts::cast::<_, Option<#fp_ts::Value<C>>, C>(#tmp.clone()).is_none() {
+                    let e: #root::_rexport::anyhow::Error = #root::Trap::NullReference.into();
+                    #on_err
                 }
-            } else {
-                quote! {
-                    match #root::_rexport::tramp::tramp(
-                        #fp_ts::call_ref::<#generics, C>(
-                            ctx,
-                            #fp_ts::cast(ctx.#tn()[#idx as usize].clone()),
-                            #root::_rexport::tuple_list::tuple_list!(#(#fp_ts::cast::<_,_,C>(#args.clone())),*)
-                        )
-                    ) {
-                        Ok(a) => a,
-                        Err(e) => return #fp_ts::ret(Err(e)),
+            });
+            ctx.push(quote! { #tmp });
+        }
+        Operator::BrOnNull { relative_depth } => {
+            let r = ctx.pop();
+            let vals = br_vals(ctx, relative_depth as usize);
+            let br = br_with(ctx, relative_depth as usize, &vals);
+            ctx.emit(quote! {
+                if #fp_ts::cast::<_, Option<#fp_ts::Value<C>>, C>(#r.clone()).is_none() { #br }
+            });
+            ctx.push(r);
+        }
+        Operator::BrOnNonNull { relative_depth } => {
+            // The reference travels with the branch; it is dropped otherwise.
+            let r = ctx.peek();
+            let vals = br_vals(ctx, relative_depth as usize);
+            let br = br_with(ctx, relative_depth as usize, &vals);
+            ctx.pop();
+            ctx.emit(quote! {
+                if #fp_ts::cast::<_, Option<#fp_ts::Value<C>>, C>(#r.clone()).is_some() { #br }
+            });
+        }
+
+        // ── GC: structs ───────────────────────────────────────────────────────
+        Operator::StructNew { struct_type_index } => {
+            let rtt = format_ident!("RTT{struct_type_index}");
+            let fields = ctx.m.struct_fields(struct_type_index)?.to_vec();
+            let mut vals: Vec<TokenStream> = (0..fields.len()).map(|_| ctx.pop()).collect();
+            vals.reverse();
+            let items: Vec<TokenStream> = fields.iter().zip(vals.iter()).map(|(f, v)| {
+                let mutable = f.mutable;
+                let v = gc_store(ctx, f.element_type, v);
+                quote! { #root::gc::Field::new(#mutable, #v) }
+            }).collect();
+            ctx.push_tmp(quote! {
+                #fp_ts::Value::<C>(#fp_ts::value::Value::new_struct(&#rtt, #alloc_ts::vec![#(#items),*]))
+            });
+        }
+        Operator::StructNewDefault { struct_type_index } => {
+            let rtt = format_ident!("RTT{struct_type_index}");
+            let fields = ctx.m.struct_fields(struct_type_index)?.to_vec();
+            let items: Vec<TokenStream> = fields.iter().map(|f| {
+                let mutable = f.mutable;
+                let v = gc_default(ctx, f.element_type);
+                quote! { #root::gc::Field::new(#mutable, #v) }
+            }).collect();
+            ctx.push_tmp(quote! {
+                #fp_ts::Value::<C>(#fp_ts::value::Value::new_struct(&#rtt, #alloc_ts::vec![#(#items),*]))
+            });
+        }
+        Operator::StructGet { struct_type_index, field_index }
+        | Operator::StructGetS { struct_type_index, field_index }
+        | Operator::StructGetU { struct_type_index, field_index } => {
+            let st = ctx.m.struct_fields(struct_type_index)?[field_index as usize].element_type;
+            let signed = matches!(op, Operator::StructGetS { .. });
+            let r = ctx.pop();
+            let r = gc_ref(ctx, r);
+            let g = emit_fallible(ctx, quote! { #r.struct_get(#field_index as usize) });
+            let v = gc_load(ctx, st, quote! { #g }, signed);
+            ctx.push_tmp(v);
+        }
+        Operator::StructSet { struct_type_index, field_index } => {
+            let st = ctx.m.struct_fields(struct_type_index)?[field_index as usize].element_type;
+            let v = ctx.pop();
+            let r = ctx.pop();
+            let r = gc_ref(ctx, r);
+            let v = gc_store(ctx, st, &v);
+            emit_fallible_unit(ctx, quote! { #r.struct_set(#field_index as usize, #v) });
+        }
+
+        // ── GC: arrays ────────────────────────────────────────────────────────
+        Operator::ArrayNew { array_type_index } => {
+            let rtt = format_ident!("RTT{array_type_index}");
+            let st = ctx.m.array_elem(array_type_index)?.element_type;
+            let kind = gc_storage(ctx, st);
+            let n = ctx.pop();
+            let v = ctx.pop();
+            let v = gc_store(ctx, st, &v);
+            let elems = emit_fallible(ctx, quote! { #fp_ts::value::Value::repeat(#kind, #v, #n) });
+            ctx.push_tmp(quote! {
+                #fp_ts::Value::<C>(#fp_ts::value::Value::new_array(&#rtt, #elems))
+            });
+        }
+        Operator::ArrayNewDefault { array_type_index } => {
+            let rtt = format_ident!("RTT{array_type_index}");
+            let st = ctx.m.array_elem(array_type_index)?.element_type;
+            let kind = gc_storage(ctx, st);
+            let n = ctx.pop();
+            let v = gc_default(ctx, st);
+            let elems = emit_fallible(ctx, quote! { #fp_ts::value::Value::repeat(#kind, #v, #n) });
+            ctx.push_tmp(quote! {
+                #fp_ts::Value::<C>(#fp_ts::value::Value::new_array(&#rtt, #elems))
+            });
+        }
+        Operator::ArrayNewFixed { array_type_index, array_size } => {
+            let rtt = format_ident!("RTT{array_type_index}");
+            let st = ctx.m.array_elem(array_type_index)?.element_type;
+            let kind = gc_storage(ctx, st);
+            let mut vals: Vec<TokenStream> = (0..array_size).map(|_| ctx.pop()).collect();
+            vals.reverse();
+            let items: Vec<TokenStream> = vals.iter().map(|v| gc_store(ctx, st, v)).collect();
+            ctx.push_tmp(quote! {
+                #fp_ts::Value::<C>(#fp_ts::value::Value::new_array(
+                    &#rtt,
+                    #fp_ts::value::Value::pack(#kind, [#(#items),*]),
+                ))
+            });
+        }
+        Operator::ArrayNewData { array_type_index, array_data_index } => {
+            let rtt = format_ident!("RTT{array_type_index}");
+            let kind = gc_storage(ctx, ctx.m.array_elem(array_type_index)?.element_type);
+            let n = ctx.pop();
+            let off = ctx.pop();
+            let seg = data_seg(ctx, array_data_index);
+            let g = emit_fallible(ctx, quote! {{
+                let _seg: &'static [u8] = #seg;
+                #fp_ts::value::Value::decode(#kind, _seg, #off, #n)
+            }});
+            ctx.push_tmp(quote! {
+                #fp_ts::Value::<C>(#fp_ts::value::Value::new_array(&#rtt, #g))
+            });
+        }
+        Operator::ArrayNewElem { array_type_index, array_elem_index } => {
+            let rtt = format_ident!("RTT{array_type_index}");
+            let n = ctx.pop();
+            let off = ctx.pop();
+            let seg = elem_seg(ctx, array_elem_index);
+            let tmp = ctx.fresh_tmp();
+            ctx.emit(quote! {
+                let #tmp = {
+                    let _seg: #alloc_ts::vec::Vec<#fp_ts::Value<C>> = #seg;
+                    match _seg.get(#off as usize..(#off as usize).saturating_add(#n as usize)) {
+                        Some(s) => #fp_ts::Value::<C>(#fp_ts::value::Value::new_array(
+                            &#rtt,
+                            #fp_ts::value::Value::pack(#root::gc::Storage::Ref, s.iter().map(|v| v.0.clone())),
+                        )),
+                        None => {
+                            let e: #root::_rexport::anyhow::Error = #root::Trap::OutOfBoundsTable.into();
+                            #on_err
+                        }
                     }
+                };
+            });
+            ctx.push(quote! { #tmp });
+        }
+        Operator::ArrayGet { array_type_index }
+        | Operator::ArrayGetS { array_type_index }
+        | Operator::ArrayGetU { array_type_index } => {
+            let st = ctx.m.array_elem(array_type_index)?.element_type;
+            let signed = matches!(op, Operator::ArrayGetS { .. });
+            let i = ctx.pop();
+            let r = ctx.pop();
+            let r = gc_ref(ctx, r);
+            let g = emit_fallible(ctx, quote! { #r.array_get(#i) });
+            let v = gc_load(ctx, st, quote! { #g }, signed);
+            ctx.push_tmp(v);
+        }
+        Operator::ArraySet { array_type_index } => {
+            let st = ctx.m.array_elem(array_type_index)?.element_type;
+            let v = ctx.pop();
+            let i = ctx.pop();
+            let r = ctx.pop();
+            let r = gc_ref(ctx, r);
+            let v = gc_store(ctx, st, &v);
+            emit_fallible_unit(ctx, quote! { #r.array_set(#i, #v) });
+        }
+        Operator::ArrayLen => {
+            let r = ctx.pop();
+            let r = gc_ref(ctx, r);
+            let g = emit_fallible(ctx, quote! { #r.array_len() });
+            ctx.push(quote! { #g });
+        }
+        Operator::ArrayFill { array_type_index } => {
+            let st = ctx.m.array_elem(array_type_index)?.element_type;
+            let n = ctx.pop();
+            let v = ctx.pop();
+            let off = ctx.pop();
+            let r = ctx.pop();
+            let r = gc_ref(ctx, r);
+            let v = gc_store(ctx, st, &v);
+            emit_fallible_unit(ctx, quote! { #r.array_fill(#off, #v, #n) });
+        }
+        Operator::ArrayCopy { .. } => {
+            let n = ctx.pop();
+            let src_off = ctx.pop();
+            let src = ctx.pop();
+            let src = gc_ref(ctx, src);
+            let dst_off = ctx.pop();
+            let dst = ctx.pop();
+            let dst = gc_ref(ctx, dst);
+            emit_fallible_unit(ctx, quote! { #dst.array_copy(#dst_off, &#src, #src_off, #n) });
+        }
+        Operator::ArrayInitData { array_type_index, array_data_index } => {
+            let kind = gc_storage(ctx, ctx.m.array_elem(array_type_index)?.element_type);
+            let n = ctx.pop();
+            let src = ctx.pop();
+            let dst = ctx.pop();
+            let r = ctx.pop();
+            let r = gc_ref(ctx, r);
+            let seg = data_seg(ctx, array_data_index);
+            emit_fallible_unit(ctx, quote! {{
+                let _seg: &'static [u8] = #seg;
+                #fp_ts::value::Value::decode(#kind, _seg, #src, #n)
+                    .and_then(|_v| #r.array_init(#dst, &_v, 0, #n))
+            }});
+        }
+        Operator::ArrayInitElem { array_elem_index, .. } => {
+            let n = ctx.pop();
+            let src = ctx.pop();
+            let dst = ctx.pop();
+            let r = ctx.pop();
+            let r = gc_ref(ctx, r);
+            let seg = elem_seg(ctx, array_elem_index);
+            emit_fallible_unit(ctx, quote! {{
+                let _seg: #alloc_ts::vec::Vec<#fp_ts::Value<C>> = #seg;
+                let _v = #fp_ts::value::Value::pack(#root::gc::Storage::Ref, _seg.into_iter().map(|v| v.0));
+                #r.array_init(#dst, &_v, #src, #n)
+            }});
+        }
+
+        // ── GC: i31, equality, casts ──────────────────────────────────────────
+        Operator::RefI31 => {
+            let a = ctx.pop();
+            ctx.push_tmp(quote! { #fp_ts::Value::<C>(#fp_ts::value::Value::i31(#a)) });
+        }
+        Operator::I31GetS | Operator::I31GetU => {
+            let signed = matches!(op, Operator::I31GetS);
+            let r = ctx.pop();
+            let r = gc_ref(ctx, r);
+            let g = emit_fallible(ctx, quote! { #r.i31_get(#signed) });
+            ctx.push(quote! { #g });
+        }
+        Operator::RefEq => {
+            let b = ctx.pop();
+            let b = gc_ref(ctx, b);
+            let a = ctx.pop();
+            let a = gc_ref(ctx, a);
+            ctx.push_tmp(quote! { if #a.ref_eq(&#b) { 1u32 } else { 0u32 } });
+        }
+        Operator::RefTestNonNull { hty } | Operator::RefTestNullable { hty } => {
+            let nullable = matches!(op, Operator::RefTestNullable { .. });
+            let test = heap_test(ctx, hty)?;
+            let r = ctx.pop();
+            let r = gc_ref(ctx, r);
+            ctx.push_tmp(quote! { if #r.ref_test(#test, #nullable) { 1u32 } else { 0u32 } });
+        }
+        Operator::RefCastNonNull { hty } | Operator::RefCastNullable { hty } => {
+            let nullable = matches!(op, Operator::RefCastNullable { .. });
+            let test = heap_test(ctx, hty)?;
+            let r = ctx.pop();
+            let tmp = ctx.fresh_tmp();
+            let rv = gc_ref(ctx, quote! { #tmp });
+            ctx.emit(quote! {
+                let #tmp = #r.clone();
+                if !#rv.ref_test(#test, #nullable) {
+                    let e: #root::_rexport::anyhow::Error = #root::Trap::CastFailure.into();
+                    #on_err
                 }
-            };
-            if sig.returns.is_empty() {
-                ctx.emit(call_ts);
+            });
+            ctx.push(quote! { #tmp });
+        }
+        Operator::BrOnCast { relative_depth, to_ref_type, .. }
+        | Operator::BrOnCastFail { relative_depth, to_ref_type, .. } => {
+            // The reference stays on the stack either way.
+            let fail = matches!(op, Operator::BrOnCastFail { .. });
+            let test = heap_test(ctx, to_ref_type.heap_type())?;
+            let nullable = to_ref_type.is_nullable();
+            let r = ctx.peek();
+            let r = gc_ref(ctx, r);
+            let vals = br_vals(ctx, relative_depth as usize);
+            let br = br_with(ctx, relative_depth as usize, &vals);
+            let cond = if fail { quote! { !#r.ref_test(#test, #nullable) } } else { quote! { #r.ref_test(#test, #nullable) } };
+            ctx.emit(quote! {
+                if #cond { #br }
+            });
+        }
+        Operator::AnyConvertExtern | Operator::ExternConvertAny => {
+            let f = if matches!(op, Operator::AnyConvertExtern) {
+                quote! { any_convert_extern }
             } else {
-                let results = unwrap_call_result(ctx, call_ts, &sig.returns);
-                for r in results {
-                    ctx.push(r);
-                }
-            }
+                quote! { extern_convert_any }
+            };
+            let r = ctx.pop();
+            let r = gc_ref(ctx, r);
+            ctx.push_tmp(quote! { #fp_ts::Value::<C>(#r.#f()) });
         }
 
         // ── Return-calls ──────────────────────────────────────────────────────
@@ -1787,43 +2982,360 @@ fn process_op(ctx: &mut EmitCtx<'_>, op: Operator<'_>) -> anyhow::Result<()> {
             ctx.unreachable_depth = 1;
         }
         Operator::ReturnCallIndirect { type_index, table_index } => {
-            let sig = ctx.m.types[type_index as usize].clone();
             let idx = ctx.pop();
-            let mut args: Vec<TokenStream> = (0..sig.params.len()).map(|_| ctx.pop()).collect();
-            args.reverse();
             let tn = format_ident!("table{table_index}");
-            let generics = shared::render_generics(ctx.core, &quote! { c }, sig.as_ref());
-            let call_ts = if ctx.core.flags.contains(Flags::ASYNC) {
-                quote! {
-                    return #fp_ts::call_ref::<#generics, C>(
-                        ctx,
-                        #fp_ts::cast(ctx.#tn()[#idx as usize].clone()),
-                        #root::_rexport::tuple_list::tuple_list!(#(#fp_ts::cast::<_,_,C>(#args.clone())),*)
-                    );
-                }
-            } else {
-                quote! {
-                    return #root::_rexport::tramp::BorrowRec::Call(
-                        #root::_rexport::tramp::Thunk::new(move || {
-                            #fp_ts::call_ref::<#generics, C>(
-                                ctx,
-                                #fp_ts::cast(ctx.#tn()[#idx as usize].clone()),
-                                #root::_rexport::tuple_list::tuple_list!(#(#fp_ts::cast::<_,_,C>(#args.clone())),*)
-                            )
-                        })
-                    );
-                }
+            let generics = shared::render_generics(ctx.core, ctx.m, &quote! { C }, ctx.m.types[type_index as usize].as_ref());
+            let resolve = quote! {
+                #root::table_get(ctx.#tn(), #idx as u64).and_then(#fp_ts::indirect::<#generics, C>)
             };
-            ctx.emit(call_ts);
+            emit_return_call_df(ctx, type_index, resolve);
+            ctx.unreachable_depth = 1;
+        }
+        Operator::ReturnCallRef { type_index } => {
+            let r = ctx.pop();
+            let generics = shared::render_generics(ctx.core, ctx.m, &quote! { C }, ctx.m.types[type_index as usize].as_ref());
+            emit_return_call_df(ctx, type_index, quote! { #fp_ts::call_target::<#generics, C, _>(#r.clone()) });
             ctx.unreachable_depth = 1;
         }
 
-        // ── SIMD (stub) ───────────────────────────────────────────────────────
-        // V128 operators: emit todo!() with a clear message.
-        _ => {
-            let msg = format!("wasmparser backend: unsupported operator in func {}", ctx.func_idx);
-            ctx.emit(quote! { todo!(#msg); });
+        // ── SIMD ──────────────────────────────────────────────────────────────
+        // Runtime helpers are named after the operator, lowercased.
+        Operator::V128Const { value } => {
+            ctx.push_tmp(v128_const(*value.bytes()));
         }
+        Operator::V128Load { memarg } => emit_load(ctx, "v128load", memarg, 0)?,
+        Operator::V128Load8x8S { memarg } => emit_load(ctx, "v128load8x8s", memarg, 0)?,
+        Operator::V128Load8x8U { memarg } => emit_load(ctx, "v128load8x8u", memarg, 0)?,
+        Operator::V128Load16x4S { memarg } => emit_load(ctx, "v128load16x4s", memarg, 0)?,
+        Operator::V128Load16x4U { memarg } => emit_load(ctx, "v128load16x4u", memarg, 0)?,
+        Operator::V128Load32x2S { memarg } => emit_load(ctx, "v128load32x2s", memarg, 0)?,
+        Operator::V128Load32x2U { memarg } => emit_load(ctx, "v128load32x2u", memarg, 0)?,
+        Operator::V128Load8Splat { memarg } => emit_load(ctx, "v128load8splat", memarg, 0)?,
+        Operator::V128Load16Splat { memarg } => emit_load(ctx, "v128load16splat", memarg, 0)?,
+        Operator::V128Load32Splat { memarg } => emit_load(ctx, "v128load32splat", memarg, 0)?,
+        Operator::V128Load64Splat { memarg } => emit_load(ctx, "v128load64splat", memarg, 0)?,
+        Operator::V128Load32Zero { memarg } => emit_load(ctx, "v128load32zero", memarg, 0)?,
+        Operator::V128Load64Zero { memarg } => emit_load(ctx, "v128load64zero", memarg, 0)?,
+        Operator::V128Store { memarg } => emit_store(ctx, "v128store", memarg, 0)?,
+        Operator::V128Load8Lane { memarg, lane } => emit_lane_mem(ctx, "v128load8lane", memarg, lane, false),
+        Operator::V128Load16Lane { memarg, lane } => emit_lane_mem(ctx, "v128load16lane", memarg, lane, false),
+        Operator::V128Load32Lane { memarg, lane } => emit_lane_mem(ctx, "v128load32lane", memarg, lane, false),
+        Operator::V128Load64Lane { memarg, lane } => emit_lane_mem(ctx, "v128load64lane", memarg, lane, false),
+        Operator::V128Store8Lane { memarg, lane } => emit_lane_mem(ctx, "v128store8lane", memarg, lane, true),
+        Operator::V128Store16Lane { memarg, lane } => emit_lane_mem(ctx, "v128store16lane", memarg, lane, true),
+        Operator::V128Store32Lane { memarg, lane } => emit_lane_mem(ctx, "v128store32lane", memarg, lane, true),
+        Operator::V128Store64Lane { memarg, lane } => emit_lane_mem(ctx, "v128store64lane", memarg, lane, true),
+        Operator::I8x16Shuffle { lanes } => simd_op(ctx, "i8x16shuffle", 2, u8x16(lanes)),
+        Operator::I8x16ExtractLaneS { lane } => simd_op(ctx, "i8x16extractlanes", 1, quote! { #lane }),
+        Operator::I8x16ExtractLaneU { lane } => simd_op(ctx, "i8x16extractlaneu", 1, quote! { #lane }),
+        Operator::I16x8ExtractLaneS { lane } => simd_op(ctx, "i16x8extractlanes", 1, quote! { #lane }),
+        Operator::I16x8ExtractLaneU { lane } => simd_op(ctx, "i16x8extractlaneu", 1, quote! { #lane }),
+        Operator::I32x4ExtractLane { lane } => simd_op(ctx, "i32x4extractlane", 1, quote! { #lane }),
+        Operator::I64x2ExtractLane { lane } => simd_op(ctx, "i64x2extractlane", 1, quote! { #lane }),
+        Operator::F32x4ExtractLane { lane } => simd_op(ctx, "f32x4extractlane", 1, quote! { #lane }),
+        Operator::F64x2ExtractLane { lane } => simd_op(ctx, "f64x2extractlane", 1, quote! { #lane }),
+        Operator::I8x16ReplaceLane { lane } => simd_op(ctx, "i8x16replacelane", 2, quote! { #lane }),
+        Operator::I16x8ReplaceLane { lane } => simd_op(ctx, "i16x8replacelane", 2, quote! { #lane }),
+        Operator::I32x4ReplaceLane { lane } => simd_op(ctx, "i32x4replacelane", 2, quote! { #lane }),
+        Operator::I64x2ReplaceLane { lane } => simd_op(ctx, "i64x2replacelane", 2, quote! { #lane }),
+        Operator::F32x4ReplaceLane { lane } => simd_op(ctx, "f32x4replacelane", 2, quote! { #lane }),
+        Operator::F64x2ReplaceLane { lane } => simd_op(ctx, "f64x2replacelane", 2, quote! { #lane }),
+        Operator::V128Bitselect => tern_op(ctx, "v128bitselect"),
+        Operator::I8x16Splat => un_op(ctx, "i8x16splat"),
+        Operator::I16x8Splat => un_op(ctx, "i16x8splat"),
+        Operator::I32x4Splat => un_op(ctx, "i32x4splat"),
+        Operator::I64x2Splat => un_op(ctx, "i64x2splat"),
+        Operator::F32x4Splat => un_op(ctx, "f32x4splat"),
+        Operator::F64x2Splat => un_op(ctx, "f64x2splat"),
+        Operator::V128Not => un_op(ctx, "v128not"),
+        Operator::V128AnyTrue => un_op(ctx, "v128anytrue"),
+        Operator::I8x16Abs => un_op(ctx, "i8x16abs"),
+        Operator::I8x16Neg => un_op(ctx, "i8x16neg"),
+        Operator::I8x16Popcnt => un_op(ctx, "i8x16popcnt"),
+        Operator::I8x16AllTrue => un_op(ctx, "i8x16alltrue"),
+        Operator::I8x16Bitmask => un_op(ctx, "i8x16bitmask"),
+        Operator::I16x8Abs => un_op(ctx, "i16x8abs"),
+        Operator::I16x8Neg => un_op(ctx, "i16x8neg"),
+        Operator::I16x8AllTrue => un_op(ctx, "i16x8alltrue"),
+        Operator::I16x8Bitmask => un_op(ctx, "i16x8bitmask"),
+        Operator::I32x4Abs => un_op(ctx, "i32x4abs"),
+        Operator::I32x4Neg => un_op(ctx, "i32x4neg"),
+        Operator::I32x4AllTrue => un_op(ctx, "i32x4alltrue"),
+        Operator::I32x4Bitmask => un_op(ctx, "i32x4bitmask"),
+        Operator::I64x2Abs => un_op(ctx, "i64x2abs"),
+        Operator::I64x2Neg => un_op(ctx, "i64x2neg"),
+        Operator::I64x2AllTrue => un_op(ctx, "i64x2alltrue"),
+        Operator::I64x2Bitmask => un_op(ctx, "i64x2bitmask"),
+        Operator::I16x8ExtAddPairwiseI8x16S => un_op(ctx, "i16x8extaddpairwisei8x16s"),
+        Operator::I16x8ExtAddPairwiseI8x16U => un_op(ctx, "i16x8extaddpairwisei8x16u"),
+        Operator::I32x4ExtAddPairwiseI16x8S => un_op(ctx, "i32x4extaddpairwisei16x8s"),
+        Operator::I32x4ExtAddPairwiseI16x8U => un_op(ctx, "i32x4extaddpairwisei16x8u"),
+        Operator::I16x8ExtendLowI8x16S => un_op(ctx, "i16x8extendlowi8x16s"),
+        Operator::I16x8ExtendHighI8x16S => un_op(ctx, "i16x8extendhighi8x16s"),
+        Operator::I16x8ExtendLowI8x16U => un_op(ctx, "i16x8extendlowi8x16u"),
+        Operator::I16x8ExtendHighI8x16U => un_op(ctx, "i16x8extendhighi8x16u"),
+        Operator::I32x4ExtendLowI16x8S => un_op(ctx, "i32x4extendlowi16x8s"),
+        Operator::I32x4ExtendHighI16x8S => un_op(ctx, "i32x4extendhighi16x8s"),
+        Operator::I32x4ExtendLowI16x8U => un_op(ctx, "i32x4extendlowi16x8u"),
+        Operator::I32x4ExtendHighI16x8U => un_op(ctx, "i32x4extendhighi16x8u"),
+        Operator::I64x2ExtendLowI32x4S => un_op(ctx, "i64x2extendlowi32x4s"),
+        Operator::I64x2ExtendHighI32x4S => un_op(ctx, "i64x2extendhighi32x4s"),
+        Operator::I64x2ExtendLowI32x4U => un_op(ctx, "i64x2extendlowi32x4u"),
+        Operator::I64x2ExtendHighI32x4U => un_op(ctx, "i64x2extendhighi32x4u"),
+        Operator::F32x4Ceil => un_op(ctx, "f32x4ceil"),
+        Operator::F32x4Floor => un_op(ctx, "f32x4floor"),
+        Operator::F32x4Trunc => un_op(ctx, "f32x4trunc"),
+        Operator::F32x4Nearest => un_op(ctx, "f32x4nearest"),
+        Operator::F32x4Abs => un_op(ctx, "f32x4abs"),
+        Operator::F32x4Neg => un_op(ctx, "f32x4neg"),
+        Operator::F32x4Sqrt => un_op(ctx, "f32x4sqrt"),
+        Operator::F64x2Ceil => un_op(ctx, "f64x2ceil"),
+        Operator::F64x2Floor => un_op(ctx, "f64x2floor"),
+        Operator::F64x2Trunc => un_op(ctx, "f64x2trunc"),
+        Operator::F64x2Nearest => un_op(ctx, "f64x2nearest"),
+        Operator::F64x2Abs => un_op(ctx, "f64x2abs"),
+        Operator::F64x2Neg => un_op(ctx, "f64x2neg"),
+        Operator::F64x2Sqrt => un_op(ctx, "f64x2sqrt"),
+        Operator::I32x4TruncSatF32x4S => un_op(ctx, "i32x4truncsatf32x4s"),
+        Operator::I32x4TruncSatF32x4U => un_op(ctx, "i32x4truncsatf32x4u"),
+        Operator::F32x4ConvertI32x4S => un_op(ctx, "f32x4converti32x4s"),
+        Operator::F32x4ConvertI32x4U => un_op(ctx, "f32x4converti32x4u"),
+        Operator::I32x4TruncSatF64x2SZero => un_op(ctx, "i32x4truncsatf64x2szero"),
+        Operator::I32x4TruncSatF64x2UZero => un_op(ctx, "i32x4truncsatf64x2uzero"),
+        Operator::F64x2ConvertLowI32x4S => un_op(ctx, "f64x2convertlowi32x4s"),
+        Operator::F64x2ConvertLowI32x4U => un_op(ctx, "f64x2convertlowi32x4u"),
+        Operator::F32x4DemoteF64x2Zero => un_op(ctx, "f32x4demotef64x2zero"),
+        Operator::F64x2PromoteLowF32x4 => un_op(ctx, "f64x2promotelowf32x4"),
+        Operator::V128And => bin_op(ctx, "v128and"),
+        Operator::V128AndNot => bin_op(ctx, "v128andnot"),
+        Operator::V128Or => bin_op(ctx, "v128or"),
+        Operator::V128Xor => bin_op(ctx, "v128xor"),
+        Operator::I8x16Swizzle => bin_op(ctx, "i8x16swizzle"),
+        Operator::I8x16Eq => bin_op(ctx, "i8x16eq"),
+        Operator::I8x16Ne => bin_op(ctx, "i8x16ne"),
+        Operator::I8x16LtS => bin_op(ctx, "i8x16lts"),
+        Operator::I8x16LtU => bin_op(ctx, "i8x16ltu"),
+        Operator::I8x16GtS => bin_op(ctx, "i8x16gts"),
+        Operator::I8x16GtU => bin_op(ctx, "i8x16gtu"),
+        Operator::I8x16LeS => bin_op(ctx, "i8x16les"),
+        Operator::I8x16LeU => bin_op(ctx, "i8x16leu"),
+        Operator::I8x16GeS => bin_op(ctx, "i8x16ges"),
+        Operator::I8x16GeU => bin_op(ctx, "i8x16geu"),
+        Operator::I16x8Eq => bin_op(ctx, "i16x8eq"),
+        Operator::I16x8Ne => bin_op(ctx, "i16x8ne"),
+        Operator::I16x8LtS => bin_op(ctx, "i16x8lts"),
+        Operator::I16x8LtU => bin_op(ctx, "i16x8ltu"),
+        Operator::I16x8GtS => bin_op(ctx, "i16x8gts"),
+        Operator::I16x8GtU => bin_op(ctx, "i16x8gtu"),
+        Operator::I16x8LeS => bin_op(ctx, "i16x8les"),
+        Operator::I16x8LeU => bin_op(ctx, "i16x8leu"),
+        Operator::I16x8GeS => bin_op(ctx, "i16x8ges"),
+        Operator::I16x8GeU => bin_op(ctx, "i16x8geu"),
+        Operator::I32x4Eq => bin_op(ctx, "i32x4eq"),
+        Operator::I32x4Ne => bin_op(ctx, "i32x4ne"),
+        Operator::I32x4LtS => bin_op(ctx, "i32x4lts"),
+        Operator::I32x4LtU => bin_op(ctx, "i32x4ltu"),
+        Operator::I32x4GtS => bin_op(ctx, "i32x4gts"),
+        Operator::I32x4GtU => bin_op(ctx, "i32x4gtu"),
+        Operator::I32x4LeS => bin_op(ctx, "i32x4les"),
+        Operator::I32x4LeU => bin_op(ctx, "i32x4leu"),
+        Operator::I32x4GeS => bin_op(ctx, "i32x4ges"),
+        Operator::I32x4GeU => bin_op(ctx, "i32x4geu"),
+        Operator::I64x2Eq => bin_op(ctx, "i64x2eq"),
+        Operator::I64x2Ne => bin_op(ctx, "i64x2ne"),
+        Operator::I64x2LtS => bin_op(ctx, "i64x2lts"),
+        Operator::I64x2GtS => bin_op(ctx, "i64x2gts"),
+        Operator::I64x2LeS => bin_op(ctx, "i64x2les"),
+        Operator::I64x2GeS => bin_op(ctx, "i64x2ges"),
+        Operator::F32x4Eq => bin_op(ctx, "f32x4eq"),
+        Operator::F32x4Ne => bin_op(ctx, "f32x4ne"),
+        Operator::F32x4Lt => bin_op(ctx, "f32x4lt"),
+        Operator::F32x4Gt => bin_op(ctx, "f32x4gt"),
+        Operator::F32x4Le => bin_op(ctx, "f32x4le"),
+        Operator::F32x4Ge => bin_op(ctx, "f32x4ge"),
+        Operator::F64x2Eq => bin_op(ctx, "f64x2eq"),
+        Operator::F64x2Ne => bin_op(ctx, "f64x2ne"),
+        Operator::F64x2Lt => bin_op(ctx, "f64x2lt"),
+        Operator::F64x2Gt => bin_op(ctx, "f64x2gt"),
+        Operator::F64x2Le => bin_op(ctx, "f64x2le"),
+        Operator::F64x2Ge => bin_op(ctx, "f64x2ge"),
+        Operator::I8x16Shl => bin_op(ctx, "i8x16shl"),
+        Operator::I8x16ShrS => bin_op(ctx, "i8x16shrs"),
+        Operator::I8x16ShrU => bin_op(ctx, "i8x16shru"),
+        Operator::I16x8Shl => bin_op(ctx, "i16x8shl"),
+        Operator::I16x8ShrS => bin_op(ctx, "i16x8shrs"),
+        Operator::I16x8ShrU => bin_op(ctx, "i16x8shru"),
+        Operator::I32x4Shl => bin_op(ctx, "i32x4shl"),
+        Operator::I32x4ShrS => bin_op(ctx, "i32x4shrs"),
+        Operator::I32x4ShrU => bin_op(ctx, "i32x4shru"),
+        Operator::I64x2Shl => bin_op(ctx, "i64x2shl"),
+        Operator::I64x2ShrS => bin_op(ctx, "i64x2shrs"),
+        Operator::I64x2ShrU => bin_op(ctx, "i64x2shru"),
+        Operator::I8x16NarrowI16x8S => bin_op(ctx, "i8x16narrowi16x8s"),
+        Operator::I8x16NarrowI16x8U => bin_op(ctx, "i8x16narrowi16x8u"),
+        Operator::I8x16Add => bin_op(ctx, "i8x16add"),
+        Operator::I8x16AddSatS => bin_op(ctx, "i8x16addsats"),
+        Operator::I8x16AddSatU => bin_op(ctx, "i8x16addsatu"),
+        Operator::I8x16Sub => bin_op(ctx, "i8x16sub"),
+        Operator::I8x16SubSatS => bin_op(ctx, "i8x16subsats"),
+        Operator::I8x16SubSatU => bin_op(ctx, "i8x16subsatu"),
+        Operator::I8x16MinS => bin_op(ctx, "i8x16mins"),
+        Operator::I8x16MinU => bin_op(ctx, "i8x16minu"),
+        Operator::I8x16MaxS => bin_op(ctx, "i8x16maxs"),
+        Operator::I8x16MaxU => bin_op(ctx, "i8x16maxu"),
+        Operator::I8x16AvgrU => bin_op(ctx, "i8x16avgru"),
+        Operator::I16x8NarrowI32x4S => bin_op(ctx, "i16x8narrowi32x4s"),
+        Operator::I16x8NarrowI32x4U => bin_op(ctx, "i16x8narrowi32x4u"),
+        Operator::I16x8Q15MulrSatS => bin_op(ctx, "i16x8q15mulrsats"),
+        Operator::I16x8Add => bin_op(ctx, "i16x8add"),
+        Operator::I16x8AddSatS => bin_op(ctx, "i16x8addsats"),
+        Operator::I16x8AddSatU => bin_op(ctx, "i16x8addsatu"),
+        Operator::I16x8Sub => bin_op(ctx, "i16x8sub"),
+        Operator::I16x8SubSatS => bin_op(ctx, "i16x8subsats"),
+        Operator::I16x8SubSatU => bin_op(ctx, "i16x8subsatu"),
+        Operator::I16x8Mul => bin_op(ctx, "i16x8mul"),
+        Operator::I16x8MinS => bin_op(ctx, "i16x8mins"),
+        Operator::I16x8MinU => bin_op(ctx, "i16x8minu"),
+        Operator::I16x8MaxS => bin_op(ctx, "i16x8maxs"),
+        Operator::I16x8MaxU => bin_op(ctx, "i16x8maxu"),
+        Operator::I16x8AvgrU => bin_op(ctx, "i16x8avgru"),
+        Operator::I16x8ExtMulLowI8x16S => bin_op(ctx, "i16x8extmullowi8x16s"),
+        Operator::I16x8ExtMulHighI8x16S => bin_op(ctx, "i16x8extmulhighi8x16s"),
+        Operator::I16x8ExtMulLowI8x16U => bin_op(ctx, "i16x8extmullowi8x16u"),
+        Operator::I16x8ExtMulHighI8x16U => bin_op(ctx, "i16x8extmulhighi8x16u"),
+        Operator::I32x4Add => bin_op(ctx, "i32x4add"),
+        Operator::I32x4Sub => bin_op(ctx, "i32x4sub"),
+        Operator::I32x4Mul => bin_op(ctx, "i32x4mul"),
+        Operator::I32x4MinS => bin_op(ctx, "i32x4mins"),
+        Operator::I32x4MinU => bin_op(ctx, "i32x4minu"),
+        Operator::I32x4MaxS => bin_op(ctx, "i32x4maxs"),
+        Operator::I32x4MaxU => bin_op(ctx, "i32x4maxu"),
+        Operator::I32x4DotI16x8S => bin_op(ctx, "i32x4doti16x8s"),
+        Operator::I32x4ExtMulLowI16x8S => bin_op(ctx, "i32x4extmullowi16x8s"),
+        Operator::I32x4ExtMulHighI16x8S => bin_op(ctx, "i32x4extmulhighi16x8s"),
+        Operator::I32x4ExtMulLowI16x8U => bin_op(ctx, "i32x4extmullowi16x8u"),
+        Operator::I32x4ExtMulHighI16x8U => bin_op(ctx, "i32x4extmulhighi16x8u"),
+        Operator::I64x2Add => bin_op(ctx, "i64x2add"),
+        Operator::I64x2Sub => bin_op(ctx, "i64x2sub"),
+        Operator::I64x2Mul => bin_op(ctx, "i64x2mul"),
+        Operator::I64x2ExtMulLowI32x4S => bin_op(ctx, "i64x2extmullowi32x4s"),
+        Operator::I64x2ExtMulHighI32x4S => bin_op(ctx, "i64x2extmulhighi32x4s"),
+        Operator::I64x2ExtMulLowI32x4U => bin_op(ctx, "i64x2extmullowi32x4u"),
+        Operator::I64x2ExtMulHighI32x4U => bin_op(ctx, "i64x2extmulhighi32x4u"),
+        Operator::F32x4Add => bin_op(ctx, "f32x4add"),
+        Operator::F32x4Sub => bin_op(ctx, "f32x4sub"),
+        Operator::F32x4Mul => bin_op(ctx, "f32x4mul"),
+        Operator::F32x4Div => bin_op(ctx, "f32x4div"),
+        Operator::F32x4Min => bin_op(ctx, "f32x4min"),
+        Operator::F32x4Max => bin_op(ctx, "f32x4max"),
+        Operator::F32x4PMin => bin_op(ctx, "f32x4pmin"),
+        Operator::F32x4PMax => bin_op(ctx, "f32x4pmax"),
+        Operator::F64x2Add => bin_op(ctx, "f64x2add"),
+        Operator::F64x2Sub => bin_op(ctx, "f64x2sub"),
+        Operator::F64x2Mul => bin_op(ctx, "f64x2mul"),
+        Operator::F64x2Div => bin_op(ctx, "f64x2div"),
+        Operator::F64x2Min => bin_op(ctx, "f64x2min"),
+        Operator::F64x2Max => bin_op(ctx, "f64x2max"),
+        Operator::F64x2PMin => bin_op(ctx, "f64x2pmin"),
+        Operator::F64x2PMax => bin_op(ctx, "f64x2pmax"),
+
+        // ── Relaxed SIMD ──────────────────────────────────────────────────────
+        // Each lowers to one deterministic choice the proposal allows; most
+        // are the matching fixed-width operator.
+        Operator::I8x16RelaxedSwizzle => bin_op(ctx, "i8x16swizzle"),
+        Operator::I32x4RelaxedTruncF32x4S => un_op(ctx, "i32x4truncsatf32x4s"),
+        Operator::I32x4RelaxedTruncF32x4U => un_op(ctx, "i32x4truncsatf32x4u"),
+        Operator::I32x4RelaxedTruncF64x2SZero => un_op(ctx, "i32x4truncsatf64x2szero"),
+        Operator::I32x4RelaxedTruncF64x2UZero => un_op(ctx, "i32x4truncsatf64x2uzero"),
+        Operator::F32x4RelaxedMadd => tern_op(ctx, "f32x4relaxedmadd"),
+        Operator::F32x4RelaxedNmadd => tern_op(ctx, "f32x4relaxednmadd"),
+        Operator::F64x2RelaxedMadd => tern_op(ctx, "f64x2relaxedmadd"),
+        Operator::F64x2RelaxedNmadd => tern_op(ctx, "f64x2relaxednmadd"),
+        Operator::I8x16RelaxedLaneselect => tern_op(ctx, "v128bitselect"),
+        Operator::I16x8RelaxedLaneselect => tern_op(ctx, "v128bitselect"),
+        Operator::I32x4RelaxedLaneselect => tern_op(ctx, "v128bitselect"),
+        Operator::I64x2RelaxedLaneselect => tern_op(ctx, "v128bitselect"),
+        Operator::F32x4RelaxedMin => bin_op(ctx, "f32x4min"),
+        Operator::F32x4RelaxedMax => bin_op(ctx, "f32x4max"),
+        Operator::F64x2RelaxedMin => bin_op(ctx, "f64x2min"),
+        Operator::F64x2RelaxedMax => bin_op(ctx, "f64x2max"),
+        Operator::I16x8RelaxedQ15mulrS => bin_op(ctx, "i16x8q15mulrsats"),
+        Operator::I16x8RelaxedDotI8x16I7x16S => bin_op(ctx, "i16x8relaxeddoti8x16i7x16s"),
+        Operator::I32x4RelaxedDotI8x16I7x16AddS => tern_op(ctx, "i32x4relaxeddoti8x16i7x16adds"),
+
+        // ── Threads ───────────────────────────────────────────────────────────
+        Operator::I32AtomicLoad { memarg } => emit_atomic(ctx, "i32atomicload", memarg, 0, true),
+        Operator::I64AtomicLoad { memarg } => emit_atomic(ctx, "i64atomicload", memarg, 0, true),
+        Operator::I32AtomicLoad8U { memarg } => emit_atomic(ctx, "i32atomicload8u", memarg, 0, true),
+        Operator::I32AtomicLoad16U { memarg } => emit_atomic(ctx, "i32atomicload16u", memarg, 0, true),
+        Operator::I64AtomicLoad8U { memarg } => emit_atomic(ctx, "i64atomicload8u", memarg, 0, true),
+        Operator::I64AtomicLoad16U { memarg } => emit_atomic(ctx, "i64atomicload16u", memarg, 0, true),
+        Operator::I64AtomicLoad32U { memarg } => emit_atomic(ctx, "i64atomicload32u", memarg, 0, true),
+        Operator::I32AtomicStore { memarg } => emit_atomic(ctx, "i32atomicstore", memarg, 1, false),
+        Operator::I64AtomicStore { memarg } => emit_atomic(ctx, "i64atomicstore", memarg, 1, false),
+        Operator::I32AtomicStore8 { memarg } => emit_atomic(ctx, "i32atomicstore8", memarg, 1, false),
+        Operator::I32AtomicStore16 { memarg } => emit_atomic(ctx, "i32atomicstore16", memarg, 1, false),
+        Operator::I64AtomicStore8 { memarg } => emit_atomic(ctx, "i64atomicstore8", memarg, 1, false),
+        Operator::I64AtomicStore16 { memarg } => emit_atomic(ctx, "i64atomicstore16", memarg, 1, false),
+        Operator::I64AtomicStore32 { memarg } => emit_atomic(ctx, "i64atomicstore32", memarg, 1, false),
+        Operator::I32AtomicRmwAdd { memarg } => emit_atomic(ctx, "i32atomicrmwadd", memarg, 1, true),
+        Operator::I64AtomicRmwAdd { memarg } => emit_atomic(ctx, "i64atomicrmwadd", memarg, 1, true),
+        Operator::I32AtomicRmw8AddU { memarg } => emit_atomic(ctx, "i32atomicrmw8addu", memarg, 1, true),
+        Operator::I32AtomicRmw16AddU { memarg } => emit_atomic(ctx, "i32atomicrmw16addu", memarg, 1, true),
+        Operator::I64AtomicRmw8AddU { memarg } => emit_atomic(ctx, "i64atomicrmw8addu", memarg, 1, true),
+        Operator::I64AtomicRmw16AddU { memarg } => emit_atomic(ctx, "i64atomicrmw16addu", memarg, 1, true),
+        Operator::I64AtomicRmw32AddU { memarg } => emit_atomic(ctx, "i64atomicrmw32addu", memarg, 1, true),
+        Operator::I32AtomicRmwSub { memarg } => emit_atomic(ctx, "i32atomicrmwsub", memarg, 1, true),
+        Operator::I64AtomicRmwSub { memarg } => emit_atomic(ctx, "i64atomicrmwsub", memarg, 1, true),
+        Operator::I32AtomicRmw8SubU { memarg } => emit_atomic(ctx, "i32atomicrmw8subu", memarg, 1, true),
+        Operator::I32AtomicRmw16SubU { memarg } => emit_atomic(ctx, "i32atomicrmw16subu", memarg, 1, true),
+        Operator::I64AtomicRmw8SubU { memarg } => emit_atomic(ctx, "i64atomicrmw8subu", memarg, 1, true),
+        Operator::I64AtomicRmw16SubU { memarg } => emit_atomic(ctx, "i64atomicrmw16subu", memarg, 1, true),
+        Operator::I64AtomicRmw32SubU { memarg } => emit_atomic(ctx, "i64atomicrmw32subu", memarg, 1, true),
+        Operator::I32AtomicRmwAnd { memarg } => emit_atomic(ctx, "i32atomicrmwand", memarg, 1, true),
+        Operator::I64AtomicRmwAnd { memarg } => emit_atomic(ctx, "i64atomicrmwand", memarg, 1, true),
+        Operator::I32AtomicRmw8AndU { memarg } => emit_atomic(ctx, "i32atomicrmw8andu", memarg, 1, true),
+        Operator::I32AtomicRmw16AndU { memarg } => emit_atomic(ctx, "i32atomicrmw16andu", memarg, 1, true),
+        Operator::I64AtomicRmw8AndU { memarg } => emit_atomic(ctx, "i64atomicrmw8andu", memarg, 1, true),
+        Operator::I64AtomicRmw16AndU { memarg } => emit_atomic(ctx, "i64atomicrmw16andu", memarg, 1, true),
+        Operator::I64AtomicRmw32AndU { memarg } => emit_atomic(ctx, "i64atomicrmw32andu", memarg, 1, true),
+        Operator::I32AtomicRmwOr { memarg } => emit_atomic(ctx, "i32atomicrmwor", memarg, 1, true),
+        Operator::I64AtomicRmwOr { memarg } => emit_atomic(ctx, "i64atomicrmwor", memarg, 1, true),
+        Operator::I32AtomicRmw8OrU { memarg } => emit_atomic(ctx, "i32atomicrmw8oru", memarg, 1, true),
+        Operator::I32AtomicRmw16OrU { memarg } => emit_atomic(ctx, "i32atomicrmw16oru", memarg, 1, true),
+        Operator::I64AtomicRmw8OrU { memarg } => emit_atomic(ctx, "i64atomicrmw8oru", memarg, 1, true),
+        Operator::I64AtomicRmw16OrU { memarg } => emit_atomic(ctx, "i64atomicrmw16oru", memarg, 1, true),
+        Operator::I64AtomicRmw32OrU { memarg } => emit_atomic(ctx, "i64atomicrmw32oru", memarg, 1, true),
+        Operator::I32AtomicRmwXor { memarg } => emit_atomic(ctx, "i32atomicrmwxor", memarg, 1, true),
+        Operator::I64AtomicRmwXor { memarg } => emit_atomic(ctx, "i64atomicrmwxor", memarg, 1, true),
+        Operator::I32AtomicRmw8XorU { memarg } => emit_atomic(ctx, "i32atomicrmw8xoru", memarg, 1, true),
+        Operator::I32AtomicRmw16XorU { memarg } => emit_atomic(ctx, "i32atomicrmw16xoru", memarg, 1, true),
+        Operator::I64AtomicRmw8XorU { memarg } => emit_atomic(ctx, "i64atomicrmw8xoru", memarg, 1, true),
+        Operator::I64AtomicRmw16XorU { memarg } => emit_atomic(ctx, "i64atomicrmw16xoru", memarg, 1, true),
+        Operator::I64AtomicRmw32XorU { memarg } => emit_atomic(ctx, "i64atomicrmw32xoru", memarg, 1, true),
+        Operator::I32AtomicRmwXchg { memarg } => emit_atomic(ctx, "i32atomicrmwxchg", memarg, 1, true),
+        Operator::I64AtomicRmwXchg { memarg } => emit_atomic(ctx, "i64atomicrmwxchg", memarg, 1, true),
+        Operator::I32AtomicRmw8XchgU { memarg } => emit_atomic(ctx, "i32atomicrmw8xchgu", memarg, 1, true),
+        Operator::I32AtomicRmw16XchgU { memarg } => emit_atomic(ctx, "i32atomicrmw16xchgu", memarg, 1, true),
+        Operator::I64AtomicRmw8XchgU { memarg } => emit_atomic(ctx, "i64atomicrmw8xchgu", memarg, 1, true),
+        Operator::I64AtomicRmw16XchgU { memarg } => emit_atomic(ctx, "i64atomicrmw16xchgu", memarg, 1, true),
+        Operator::I64AtomicRmw32XchgU { memarg } => emit_atomic(ctx, "i64atomicrmw32xchgu", memarg, 1, true),
+        Operator::I32AtomicRmwCmpxchg { memarg } => emit_atomic(ctx, "i32atomicrmwcmpxchg", memarg, 2, true),
+        Operator::I64AtomicRmwCmpxchg { memarg } => emit_atomic(ctx, "i64atomicrmwcmpxchg", memarg, 2, true),
+        Operator::I32AtomicRmw8CmpxchgU { memarg } => emit_atomic(ctx, "i32atomicrmw8cmpxchgu", memarg, 2, true),
+        Operator::I32AtomicRmw16CmpxchgU { memarg } => emit_atomic(ctx, "i32atomicrmw16cmpxchgu", memarg, 2, true),
+        Operator::I64AtomicRmw8CmpxchgU { memarg } => emit_atomic(ctx, "i64atomicrmw8cmpxchgu", memarg, 2, true),
+        Operator::I64AtomicRmw16CmpxchgU { memarg } => emit_atomic(ctx, "i64atomicrmw16cmpxchgu", memarg, 2, true),
+        Operator::I64AtomicRmw32CmpxchgU { memarg } => emit_atomic(ctx, "i64atomicrmw32cmpxchgu", memarg, 2, true),
+        Operator::MemoryAtomicNotify { memarg } => emit_atomic(ctx, "memoryatomicnotify", memarg, 1, true),
+        Operator::MemoryAtomicWait32 { memarg } => emit_atomic(ctx, "memoryatomicwait32", memarg, 2, true),
+        Operator::MemoryAtomicWait64 { memarg } => emit_atomic(ctx, "memoryatomicwait64", memarg, 2, true),
+        // Every access to a shared memory takes its lock, which already orders it.
+        Operator::AtomicFence { .. } => {}
+
+        // Anything else is not supported yet; fail the translation rather
+        // than emit code that panics when it gets there.
+        _ => anyhow::bail!("unsupported operator {op:?} in func {}", ctx.func_idx),
     }
 
     Ok(())
@@ -1839,6 +3351,177 @@ fn blocktype_results(m: &ParsedModule, blockty: wasmparser::BlockType) -> Vec<Va
     }
 }
 
+/// Open a block-like frame.  Frames that yield a value get a `_bN` temp
+/// declared ahead of them; `catches` makes it a try frame with an `_eN`.
+fn open_frame(
+    ctx: &mut EmitCtx<'_>,
+    kind: FrameKind,
+    blockty: wasmparser::BlockType,
+    condition: Option<TokenStream>,
+    catches: Option<Vec<wasmparser::Catch>>,
+) {
+    let root = ctx.root().clone();
+    let label = ctx.fresh_label();
+    let result_tys = blocktype_results(ctx.m, blockty);
+    let result_tmp = if result_tys.is_empty() {
+        None
+    } else {
+        let t = format_ident!("_b{label}");
+        let ty = shared::render_ty(ctx.core, ctx.m, &quote! { C }, result_tys[0]);
+        let init = default_val(ctx.core, ctx.m, &quote! { C }, result_tys[0]);
+        ctx.emit(quote! { let mut #t: #ty = #init; });
+        Some(t)
+    };
+    let eh = catches.map(|catches| {
+        let err = format_ident!("_e{label}");
+        ctx.emit(quote! { let mut #err: Option<#root::_rexport::anyhow::Error> = None; });
+        TryFrame {
+            err,
+            exn: format_ident!("_x{label}"),
+            catches,
+            try_stmts: None,
+            clauses: vec![],
+            clause: None,
+        }
+    });
+    let sh = ctx.stack.len();
+    ctx.push_buf();
+    ctx.frames.push(Frame {
+        kind,
+        label,
+        result_tys,
+        result_tmp,
+        stack_height: sh,
+        condition,
+        if_stmts: None,
+        eh,
+    });
+}
+
+/// `_bN = <top of stack>;` closing a frame that yields a value, unless its
+/// end is unreachable.
+fn result_assign(ctx: &EmitCtx<'_>, result_tmp: Option<&Ident>, dead: bool) -> TokenStream {
+    let fp_ts = ctx.fp();
+    match (result_tmp, ctx.stack.last()) {
+        (Some(rt), Some(val)) if !dead => quote! { #rt = #fp_ts::cast::<_,_,C>(#val.clone()); },
+        _ => quote! {},
+    }
+}
+
+/// Payload types of tag `tag`.
+fn tag_params(m: &ParsedModule, tag: u32) -> &[ValType] {
+    &m.types[m.tag_types[tag as usize] as usize].params
+}
+
+/// `catch` / `catch_all` of a legacy `try`: close the body or the previous
+/// clause and start a new one with the payload on the stack.
+fn begin_clause(ctx: &mut EmitCtx<'_>, tag: Option<u32>, dead: bool) {
+    let fp_ts = ctx.fp();
+    let frame = ctx.frames.last().expect("catch without try");
+    let assign = result_assign(ctx, frame.result_tmp.as_ref(), dead);
+    let mut stmts = ctx.pop_buf();
+    stmts.push(assign);
+    let frame = ctx.frames.last_mut().expect("catch without try");
+    let sh = frame.stack_height;
+    let eh = frame.eh.as_mut().expect("catch without try");
+    match eh.clause.replace(tag) {
+        None => eh.try_stmts = Some(stmts),
+        Some(prev) => eh.clauses.push((prev, stmts)),
+    }
+    let exn = eh.exn.clone();
+    ctx.stack.truncate(sh);
+    ctx.push_buf();
+    if let Some(tag) = tag {
+        for (i, ty) in tag_params(ctx.m, tag).iter().enumerate() {
+            let t = shared::render_ty(ctx.core, ctx.m, &quote! { C }, *ty);
+            ctx.push_tmp(quote! {
+                #fp_ts::cast::<_, #t, C>(#fp_ts::Value::<C>(#exn.payload[#i].clone()))
+            });
+        }
+    }
+}
+
+/// Bind the exception a try body broke out with; anything that is not a
+/// wasm exception (a trap, a host error) keeps unwinding.
+fn bind_exn(ctx: &EmitCtx<'_>, eh: &TryFrame) -> TokenStream {
+    let fp_ts = ctx.fp();
+    let on_err = ctx.on_err();
+    let (err, exn) = (&eh.err, &eh.exn);
+    quote! {
+        let #exn = match #fp_ts::catch::<C>(#err.take().unwrap()) {
+            Ok(x) => x,
+            Err(e) => #on_err,
+        };
+    }
+}
+
+/// Handlers of a `try_table`: branch to the first matching clause's label,
+/// else rethrow.  The frame is already popped, so labels resolve from
+/// outside it.
+fn catch_table(ctx: &EmitCtx<'_>, eh: &TryFrame) -> TokenStream {
+    let fp_ts = ctx.fp();
+    let on_err = ctx.on_err();
+    let exn = &eh.exn;
+    let mut arms: Vec<TokenStream> = vec![];
+    let mut rethrow = Some(quote! { { let e = #fp_ts::rethrow::<C>(#exn); #on_err } });
+    for c in eh.catches.iter() {
+        let (tag, label, with_ref) = match *c {
+            wasmparser::Catch::One { tag, label } => (Some(tag), label, false),
+            wasmparser::Catch::OneRef { tag, label } => (Some(tag), label, true),
+            wasmparser::Catch::All { label } => (None, label, false),
+            wasmparser::Catch::AllRef { label } => (None, label, true),
+        };
+        let n = tag.map_or(0, |t| tag_params(ctx.m, t).len());
+        let mut vals: Vec<TokenStream> = (0..n)
+            .map(|i| quote! { #fp_ts::Value::<C>(#exn.payload[#i].clone()) })
+            .collect();
+        if with_ref {
+            vals.push(quote! { #fp_ts::exnref::<C>(#exn.clone()) });
+        }
+        let br = br_with(ctx, label as usize, &vals);
+        match tag {
+            Some(t) => {
+                let tn = format_ident!("tag{t}");
+                arms.push(quote! { if #exn.tag == ctx.#tn() { #br } });
+            }
+            None => {
+                arms.push(br);
+                rethrow = None;
+                break;
+            }
+        }
+    }
+    let bind = bind_exn(ctx, eh);
+    quote! {
+        #bind
+        #(#arms)*
+        #rethrow
+    }
+}
+
+/// Handlers of a legacy `try`: run the first clause whose tag matches, else
+/// `catch_all`, else rethrow.
+fn catch_clauses(ctx: &EmitCtx<'_>, eh: &mut TryFrame) -> TokenStream {
+    let fp_ts = ctx.fp();
+    let on_err = ctx.on_err();
+    let exn = eh.exn.clone();
+    let mut chain = quote! { { let e = #fp_ts::rethrow::<C>(#exn); #on_err } };
+    for (tag, stmts) in std::mem::take(&mut eh.clauses).into_iter().rev() {
+        chain = match tag {
+            Some(t) => {
+                let tn = format_ident!("tag{t}");
+                quote! { if #exn.tag == ctx.#tn() { #(#stmts)* } else #chain }
+            }
+            None => quote! { { #(#stmts)* } },
+        };
+    }
+    let bind = bind_exn(ctx, eh);
+    quote! {
+        #bind
+        #chain
+    }
+}
+
 fn emit_return(ctx: &mut EmitCtx<'_>) {
     let fp_ts = ctx.fp();
     let sig = ctx.m.func_sig(ctx.func_idx);
@@ -1860,34 +3543,28 @@ fn emit_call(
 ) -> anyhow::Result<TokenStream> {
     let root = ctx.root().clone();
     let fp_ts = ctx.fp();
-    let sig = ctx.m.func_sig(func_idx).clone();
+    let on_err = ctx.on_err();
 
     if ctx.m.is_defined(func_idx) {
         let fname = ctx.m.fname(func_idx);
-        if ctx.core.flags.contains(Flags::ASYNC) {
-            Ok(quote! {
-                match #fname(ctx, #root::_rexport::tuple_list::tuple_list!(
+        let call = if ctx.core.flags.contains(Flags::ASYNC) {
+            quote! {
+                #fname(ctx, #root::_rexport::tuple_list::tuple_list!(
                     #(#fp_ts::cast::<_,_,C>(#args.clone())),*
-                )).go().await {
-                    Ok(a) => a,
-                    Err(e) => return #fp_ts::ret(Err(e)),
-                }
-            })
+                )).go().await
+            }
         } else {
-            Ok(quote! {
-                match #root::_rexport::tramp::tramp(
+            quote! {
+                #root::_rexport::tramp::tramp(
                     #fname(ctx, #root::_rexport::tuple_list::tuple_list!(
                         #(#fp_ts::cast::<_,_,C>(#args.clone())),*
                     ))
-                ) {
-                    Ok(a) => a,
-                    Err(e) => return #fp_ts::ret(Err(e)),
-                }
-            })
-        }
+                )
+            }
+        };
+        Ok(shared::render_call(ctx.core, call, &on_err))
     } else {
         let imp = ctx.m.import_for_func(func_idx).unwrap();
-        let mname = format_ident!("{}_{}", bindname(&imp.module), bindname(&imp.name));
         // Check plugin.
         let plugin_result: Option<TokenStream> = ctx.core.plugins.iter()
             .find_map(|p| {
@@ -1899,24 +3576,20 @@ fn emit_call(
         let call = if let Some(ts) = plugin_result {
             ts
         } else {
-            quote! {
-                ctx.#mname(#root::_rexport::tuple_list::tuple_list!(
-                    #(#fp_ts::cast::<_,_,C>(#args.clone())),*
-                ))
-            }
+            emit_import_call(ctx, func_idx, args)
         };
         if ctx.core.flags.contains(Flags::ASYNC) {
             Ok(quote! {
                 match #root::_rexport::alloc::boxed::Box::pin(#call.go()).await {
                     Ok(a) => a,
-                    Err(e) => return #fp_ts::ret(Err(e)),
+                    Err(e) => #on_err,
                 }
             })
         } else {
             Ok(quote! {
                 match #root::_rexport::tramp::tramp(#call) {
                     Ok(a) => a,
-                    Err(e) => return #fp_ts::ret(Err(e)),
+                    Err(e) => #on_err,
                 }
             })
         }
@@ -1935,9 +3608,11 @@ fn emit_return_call(
         let fname = ctx.m.fname(func_idx);
         if ctx.core.flags.contains(Flags::ASYNC) {
             Ok(quote! {
-                return #fname(ctx, #root::_rexport::tuple_list::tuple_list!(
-                    #(#fp_ts::cast::<_,_,C>(#args.clone())),*
-                ));
+                return #root::func::unsync::AsyncRec::wrap(
+                    #fname(ctx, #root::_rexport::tuple_list::tuple_list!(
+                        #(#fp_ts::cast::<_,_,C>(#args.clone())),*
+                    ))
+                );
             })
         } else {
             Ok(quote! {
@@ -1952,14 +3627,20 @@ fn emit_return_call(
         }
     } else {
         let imp = ctx.m.import_for_func(func_idx).unwrap();
-        let mname = format_ident!("{}_{}", bindname(&imp.module), bindname(&imp.name));
-        let call = quote! {
-            ctx.#mname(#root::_rexport::tuple_list::tuple_list!(
-                #(#fp_ts::cast::<_,_,C>(#args.clone())),*
-            ))
+        // Check plugin.
+        let plugin_result: Option<TokenStream> = ctx.core.plugins.iter()
+            .find_map(|p| {
+                p.import(ctx.core, &imp.module, &imp.name,
+                    args.iter().cloned().collect())
+                    .ok()
+                    .flatten()
+            });
+        let call = match plugin_result {
+            Some(ts) => ts,
+            None => emit_import_call(ctx, func_idx, args),
         };
         if ctx.core.flags.contains(Flags::ASYNC) {
-            Ok(quote! { return #call; })
+            Ok(quote! { return #root::func::unsync::AsyncRec::wrap(#call); })
         } else {
             Ok(quote! {
                 return #root::_rexport::tramp::BorrowRec::Call(
@@ -1970,6 +3651,105 @@ fn emit_return_call(
     }
 }
 
+/// Call imported function `func_idx` on the host, or through the linker
+/// under `Flags::DYN_IMPORTS`, where the host methods don't exist.
+fn emit_import_call(ctx: &EmitCtx<'_>, func_idx: u32, args: &[TokenStream]) -> TokenStream {
+    let root = ctx.root().clone();
+    let fp_ts = ctx.fp();
+    let imp = ctx.m.import_for_func(func_idx).unwrap();
+    let args = args.iter().map(|a| quote! { #fp_ts::cast::<_,_,C>(#a.clone()) });
+    if ctx.core.flags.contains(Flags::DYN_IMPORTS) {
+        let sig = ctx.m.func_sig(func_idx);
+        let generics = shared::render_generics(ctx.core, ctx.m, &quote! { C }, sig.as_ref());
+        shared::render_dyn_import(
+            ctx.core,
+            &shared::link_field(&imp.module, &imp.name),
+            &generics,
+            &imp.module,
+            &imp.name,
+            args,
+        )
+    } else {
+        let mname = format_ident!("{}_{}", bindname(&imp.module), bindname(&imp.name));
+        quote! {
+            ctx.#mname(#root::_rexport::tuple_list::tuple_list!(#(#args),*))
+        }
+    }
+}
+
+/// `call_indirect` / `call_ref`: bind the callee from `resolve`, an
+/// `anyhow::Result<Df<…>>`, and call it with the arguments off the stack.
+fn emit_call_df(ctx: &mut EmitCtx<'_>, type_index: u32, resolve: TokenStream) {
+    let root = ctx.root().clone();
+    let fp_ts = ctx.fp();
+    let on_err = ctx.on_err();
+    let sig = ctx.m.types[type_index as usize].clone();
+    let mut args: Vec<TokenStream> = (0..sig.params.len()).map(|_| ctx.pop()).collect();
+    args.reverse();
+    let generics = shared::render_generics(ctx.core, ctx.m, &quote! { C }, sig.as_ref());
+    ctx.emit(quote! {
+        let _ci_f = match #resolve {
+            Ok(a) => a,
+            Err(e) => #on_err,
+        };
+    });
+    let call = quote! {
+        #fp_ts::call_ref::<#generics, C>(
+            ctx,
+            _ci_f,
+            #root::_rexport::tuple_list::tuple_list!(#(#fp_ts::cast::<_,_,C>(#args.clone())),*)
+        )
+    };
+    let call = if ctx.core.flags.contains(Flags::ASYNC) {
+        quote! { #call.go().await }
+    } else {
+        quote! { #root::_rexport::tramp::tramp(#call) }
+    };
+    let call_ts = shared::render_call(ctx.core, call, &on_err);
+    if sig.returns.is_empty() {
+        ctx.emit(call_ts);
+    } else {
+        let results = unwrap_call_result(ctx, call_ts, &sig.returns);
+        for r in results {
+            ctx.push(r);
+        }
+    }
+}
+
+/// `return_call_indirect` / `return_call_ref`: like [`emit_call_df`], but
+/// the call replaces this frame.
+fn emit_return_call_df(ctx: &mut EmitCtx<'_>, type_index: u32, resolve: TokenStream) {
+    let root = ctx.root().clone();
+    let fp_ts = ctx.fp();
+    let on_err = ctx.on_err();
+    let sig = ctx.m.types[type_index as usize].clone();
+    let mut args: Vec<TokenStream> = (0..sig.params.len()).map(|_| ctx.pop()).collect();
+    args.reverse();
+    let generics = shared::render_generics(ctx.core, ctx.m, &quote! { C }, sig.as_ref());
+    ctx.emit(quote! {
+        let _ci_f = match #resolve {
+            Ok(a) => a,
+            Err(e) => #on_err,
+        };
+    });
+    let call = quote! {
+        #fp_ts::call_ref::<#generics, C>(
+            ctx,
+            _ci_f,
+            #root::_rexport::tuple_list::tuple_list!(#(#fp_ts::cast::<_,_,C>(#args.clone())),*)
+        )
+    };
+    if ctx.core.flags.contains(Flags::ASYNC) {
+        ctx.emit(quote! { return #call; });
+    } else {
+        ctx.emit(quote! {
+            return #root::_rexport::tramp::BorrowRec::Call(
+                #root::_rexport::tramp::Thunk::new(move || { #call })
+            );
+        });
+    }
+}
+
 /// Destructure a multi-value call result tuple into individual stack entries.
 fn unwrap_call_result(
     ctx: &mut EmitCtx<'_>,
@@ -2017,6 +3797,7 @@ fn emit_load(
 ) -> anyhow::Result<()> {
     let root = ctx.root().clone();
     let fp_ts = ctx.fp();
+    let on_err = ctx.on_err();
     let mn = format_ident!("memory{}", memarg.memory);
     let fn_id = format_ident!("{fn_name}");
     let off = memarg.offset;
@@ -2025,7 +3806,7 @@ fn emit_load(
     ctx.emit(quote! {
         let (#tmp, ()) = match #root::#fn_id(ctx.#mn(), (#ptr as u64).wrapping_add(#off)) {
             Ok(a) => a,
-            Err(e) => return #fp_ts::ret(Err(e)),
+            Err(e) => #on_err,
         };
     });
     ctx.push(quote! { #tmp });
@@ -2035,6 +3816,7 @@ fn emit_load(
 fn emit_load_f(ctx: &mut EmitCtx<'_>, is_f64: bool, memarg: wasmparser::MemArg) -> anyhow::Result<()> {
     let root = ctx.root().clone();
     let fp_ts = ctx.fp();
+    let on_err = ctx.on_err();
     let mn = format_ident!("memory{}", memarg.memory);
     let off = memarg.offset;
     let ptr = ctx.pop();
@@ -2043,7 +3825,7 @@ fn emit_load_f(ctx: &mut EmitCtx<'_>, is_f64: bool, memarg: wasmparser::MemArg)
         ctx.emit(quote! {
             let (#tmp, ()) = match #root::i64load(ctx.#mn(), (#ptr as u64).wrapping_add(#off)) {
                 Ok(a) => a,
-                Err(e) => return #fp_ts::ret(Err(e)),
+                Err(e) => #on_err,
             };
         });
         ctx.emit(quote! { let #tmp = f64::from_bits(#tmp); });
@@ -2051,7 +3833,7 @@ fn emit_load_f(ctx: &mut EmitCtx<'_>, is_f64: bool, memarg: wasmparser::MemArg)
         ctx.emit(quote! {
             let (#tmp, ()) = match #root::i32load(ctx.#mn(), (#ptr as u64).wrapping_add(#off)) {
                 Ok(a) => a,
-                Err(e) => return #fp_ts::ret(Err(e)),
+                Err(e) => #on_err,
             };
         });
         ctx.emit(quote! { let #tmp = f32::from_bits(#tmp); });
@@ -2068,6 +3850,7 @@ fn emit_store(
 ) -> anyhow::Result<()> {
     let root = ctx.root().clone();
     let fp_ts = ctx.fp();
+    let on_err = ctx.on_err();
     let mn = format_ident!("memory{}", memarg.memory);
     let fn_id = format_ident!("{fn_name}");
     let off = memarg.offset;
@@ -2076,7 +3859,7 @@ fn emit_store(
     ctx.emit(quote! {
         match #root::#fn_id(ctx.#mn(), (#ptr as u64).wrapping_add(#off), #fp_ts::cast::<_,_,C>(#val)) {
             Ok(()) => {}
-            Err(e) => return #fp_ts::ret(Err(e)),
+            Err(e) => #on_err,
         }
     });
     Ok(())
@@ -2085,6 +3868,7 @@ fn emit_store(
 fn emit_store_f(ctx: &mut EmitCtx<'_>, is_f64: bool, memarg: wasmparser::MemArg) -> anyhow::Result<()> {
     let root = ctx.root().clone();
     let fp_ts = ctx.fp();
+    let on_err = ctx.on_err();
     let mn = format_ident!("memory{}", memarg.memory);
     let off = memarg.offset;
     let val = ctx.pop();
@@ -2093,14 +3877,14 @@ fn emit_store_f(ctx: &mut EmitCtx<'_>, is_f64: bool, memarg: wasmparser::MemArg)
         ctx.emit(quote! {
             match #root::i64store(ctx.#mn(), (#ptr as u64).wrapping_add(#off), (#val).to_bits()) {
                 Ok(()) => {}
-                Err(e) => return #fp_ts::ret(Err(e)),
+                Err(e) => #on_err,
             }
         });
     } else {
         ctx.emit(quote! {
             match #root::i32store(ctx.#mn(), (#ptr as u64).wrapping_add(#off), (#val).to_bits()) {
                 Ok(()) => {}
-                Err(e) => return #fp_ts::ret(Err(e)),
+                Err(e) => #on_err,
             }
         });
     }
@@ -2110,6 +3894,7 @@ fn emit_store_f(ctx: &mut EmitCtx<'_>, is_f64: bool, memarg: wasmparser::MemArg)
 fn bin_op(ctx: &mut EmitCtx<'_>, fn_name: &str) {
     let root = ctx.root().clone();
     let fp_ts = ctx.fp();
+    let on_err = ctx.on_err();
     let fn_id = format_ident!("{fn_name}");
     let b = ctx.pop();
     let a = ctx.pop();
@@ -2117,7 +3902,25 @@ fn bin_op(ctx: &mut EmitCtx<'_>, fn_name: &str) {
     ctx.emit(quote! {
         let (#tmp, ()) = match #root::#fn_id(#fp_ts::cast::<_,_,C>(#a), #fp_ts::cast::<_,_,C>(#b)) {
             Ok(a) => a,
-            Err(e) => return #fp_ts::ret(Err(e)),
+            Err(e) => #on_err,
+        };
+    });
+    ctx.push(quote! { #tmp });
+}
+
+fn tern_op(ctx: &mut EmitCtx<'_>, fn_name: &str) {
+    let root = ctx.root().clone();
+    let fp_ts = ctx.fp();
+    let on_err = ctx.on_err();
+    let fn_id = format_ident!("{fn_name}");
+    let c = ctx.pop();
+    let b = ctx.pop();
+    let a = ctx.pop();
+    let tmp = ctx.fresh_tmp();
+    ctx.emit(quote! {
+        let (#tmp, ()) = match #root::#fn_id(#fp_ts::cast::<_,_,C>(#a), #fp_ts::cast::<_,_,C>(#b), #fp_ts::cast::<_,_,C>(#c)) {
+            Ok(a) => a,
+            Err(e) => #on_err,
         };
     });
     ctx.push(quote! { #tmp });
@@ -2126,20 +3929,288 @@ fn bin_op(ctx: &mut EmitCtx<'_>, fn_name: &str) {
 fn un_op(ctx: &mut EmitCtx<'_>, fn_name: &str) {
     let root = ctx.root().clone();
     let fp_ts = ctx.fp();
+    let on_err = ctx.on_err();
     let fn_id = format_ident!("{fn_name}");
     let a = ctx.pop();
     let tmp = ctx.fresh_tmp();
     ctx.emit(quote! {
         let (#tmp, ()) = match #root::#fn_id(#fp_ts::cast::<_,_,C>(#a)) {
             Ok(a) => a,
-            Err(e) => return #fp_ts::ret(Err(e)),
+            Err(e) => #on_err,
         };
     });
     ctx.push(quote! { #tmp });
 }
 
+// ── GC helpers ────────────────────────────────────────────────────────────────
+
+/// The `value::Value` behind a reference operand.
+fn gc_ref(ctx: &EmitCtx<'_>, r: TokenStream) -> TokenStream {
+    let fp_ts = ctx.fp();
+    quote! { #fp_ts::cast::<_, #fp_ts::Value<C>, C>(#r.clone()).0 }
+}
+
+/// The `value::Value` stored for operand `v` in a field or element of type
+/// `st`.  Packed types are truncated on the way in.
+fn gc_store(ctx: &EmitCtx<'_>, st: StorageType, v: &TokenStream) -> TokenStream {
+    let fp_ts = ctx.fp();
+    let v = match st {
+        StorageType::I8 => quote! { (#v & 0xffu32) },
+        StorageType::I16 => quote! { (#v & 0xffffu32) },
+        StorageType::Val(_) => quote! { #v.clone() },
+    };
+    quote! { #fp_ts::cast::<_, #fp_ts::Value<C>, C>(#v).0 }
+}
+
+/// The default value of a field or element of type `st`.
+fn gc_default(ctx: &EmitCtx<'_>, st: StorageType) -> TokenStream {
+    let fp_ts = ctx.fp();
+    let ty = match st {
+        StorageType::I8 | StorageType::I16 => quote! { u32 },
+        StorageType::Val(v) => shared::render_ty(ctx.core, ctx.m, &quote! { C }, v),
+    };
+    quote! { #fp_ts::cast::<#ty, #fp_ts::Value<C>, C>(Default::default()).0 }
+}
+
+/// Read back a stored `value::Value` (`get`) as an operand of type `st`.
+fn gc_load(ctx: &EmitCtx<'_>, st: StorageType, get: TokenStream, signed: bool) -> TokenStream {
+    let fp_ts = ctx.fp();
+    let ty = match st {
+        StorageType::I8 | StorageType::I16 => quote! { u32 },
+        StorageType::Val(v) => shared::render_ty(ctx.core, ctx.m, &quote! { C }, v),
+    };
+    let v = quote! { #fp_ts::cast::<_, #ty, C>(#fp_ts::Value::<C>(#get)) };
+    match st {
+        StorageType::I8 if signed => quote! { (#v as u8 as i8 as i32 as u32) },
+        StorageType::I16 if signed => quote! { (#v as u16 as i16 as i32 as u32) },
+        _ => v,
+    }
+}
+
+/// The `gc::Storage` of an array with element type `st`.
+fn gc_storage(ctx: &EmitCtx<'_>, st: StorageType) -> TokenStream {
+    let root = ctx.root();
+    let s = match st {
+        StorageType::I8 => quote! { I8 },
+        StorageType::I16 => quote! { I16 },
+        StorageType::Val(ValType::I32) => quote! { I32 },
+        StorageType::Val(ValType::I64) => quote! { I64 },
+        StorageType::Val(ValType::F32) => quote! { F32 },
+        StorageType::Val(ValType::F64) => quote! { F64 },
+        StorageType::Val(ValType::V128) => quote! { V128 },
+        StorageType::Val(ValType::Ref(_)) => quote! { Ref },
+    };
+    quote! { #root::gc::Storage::#s }
+}
+
+/// The `gc::HeapTest` for a `ref.test`/`ref.cast` target.
+fn heap_test(ctx: &EmitCtx<'_>, hty: HeapType) -> anyhow::Result<TokenStream> {
+    let root = ctx.root();
+    let t = match hty {
+        HeapType::Abstract { ty, .. } => match ty {
+            AbstractHeapType::Any => quote! { Any },
+            AbstractHeapType::Eq => quote! { Eq },
+            AbstractHeapType::I31 => quote! { I31 },
+            AbstractHeapType::Struct => quote! { Struct },
+            AbstractHeapType::Array => quote! { Array },
+            AbstractHeapType::Func => quote! { Func },
+            AbstractHeapType::Extern => quote! { Extern },
+            AbstractHeapType::Exn => quote! { Exn },
+            AbstractHeapType::None
+            | AbstractHeapType::NoFunc
+            | AbstractHeapType::NoExtern
+            | AbstractHeapType::NoExn => quote! { Bottom },
+            other => anyhow::bail!("unsupported heap type {other:?}"),
+        },
+        HeapType::Concrete(idx) => {
+            let Some(idx) = idx.as_module_index() else {
+                anyhow::bail!("unresolved heap type {hty:?}");
+            };
+            if ctx.m.is_func_type.get(idx as usize).copied().unwrap_or(false) {
+                quote! { Func }
+            } else {
+                let rtt = format_ident!("RTT{idx}");
+                quote! { Type(&#rtt) }
+            }
+        }
+        other => anyhow::bail!("unsupported heap type {other:?}"),
+    };
+    Ok(quote! { #root::gc::HeapTest::#t })
+}
+
+/// Passive data segment `idx`; active segments are already dropped.
+fn data_seg(ctx: &EmitCtx<'_>, idx: u32) -> TokenStream {
+    if ctx.m.passive_data.iter().any(|(d, _)| *d == idx) {
+        let n = format_ident!("data{idx}");
+        quote! { ctx.data().#n }
+    } else {
+        quote! { &[] }
+    }
+}
+
+/// Passive element segment `idx`; active and declared segments are dropped.
+fn elem_seg(ctx: &EmitCtx<'_>, idx: u32) -> TokenStream {
+    let alloc_ts = ctx.alloc();
+    match ctx.m.elements.get(idx as usize).map(|e| &e.mode) {
+        Some(ElementMode::Passive) => {
+            let en = format_ident!("elem{idx}");
+            quote! { ctx.data().#en.clone() }
+        }
+        _ => quote! { #alloc_ts::vec::Vec::new() },
+    }
+}
+
+/// Bind the `Ok` value of a fallible runtime call to a fresh temporary.
+fn emit_fallible(ctx: &mut EmitCtx<'_>, expr: TokenStream) -> Ident {
+    let on_err = ctx.on_err();
+    let tmp = ctx.fresh_tmp();
+    ctx.emit(quote! {
+        let #tmp = match #expr {
+            Ok(a) => a,
+            Err(e) => #on_err,
+        };
+    });
+    tmp
+}
+
+/// Run a fallible runtime call that returns `()`.
+fn emit_fallible_unit(ctx: &mut EmitCtx<'_>, expr: TokenStream) {
+    let on_err = ctx.on_err();
+    ctx.emit(quote! {
+        match #expr {
+            Ok(()) => {}
+            Err(e) => #on_err,
+        }
+    });
+}
+
+/// Atomic memory access: `n` operands after the address.  Unshared memories
+/// are wrapped in `Exclusive` so they satisfy `AtomicMemory`.
+fn emit_atomic(ctx: &mut EmitCtx<'_>, fn_name: &str, memarg: wasmparser::MemArg, n: usize, result: bool) {
+    let root = ctx.root().clone();
+    let fp_ts = ctx.fp();
+    let on_err = ctx.on_err();
+    let mn = format_ident!("memory{}", memarg.memory);
+    let mem = if ctx.m.memory_types[memarg.memory as usize].shared {
+        quote! { ctx.#mn() }
+    } else {
+        quote! { &mut #root::Exclusive(ctx.#mn()) }
+    };
+    // Async code yields to the executor instead of blocking in a wait.
+    let wait_async = fn_name.starts_with("memoryatomicwait") && ctx.core.flags.contains(Flags::ASYNC);
+    let fn_id = if wait_async {
+        format_ident!("{fn_name}async")
+    } else {
+        format_ident!("{fn_name}")
+    };
+    let off = memarg.offset;
+    let mut args: Vec<TokenStream> = (0..n).map(|_| ctx.pop()).collect();
+    args.reverse();
+    let ptr = ctx.pop();
+    let mut call = quote! {
+        #root::#fn_id(#mem, (#ptr as u64).wrapping_add(#off) #(, #fp_ts::cast::<_,_,C>(#args))*)
+    };
+    if wait_async {
+        call = quote! {
+            match #call {
+                Ok(f) => f.await,
+                Err(e) => Err(e),
+            }
+        };
+    }
+    if result {
+        let tmp = ctx.fresh_tmp();
+        ctx.emit(quote! {
+            let (#tmp, ()) = match #call {
+                Ok(a) => a,
+                Err(e) => #on_err,
+            };
+        });
+        ctx.push(quote! { #tmp });
+    } else {
+        ctx.emit(quote! {
+            match #call {
+                Ok(()) => {}
+                Err(e) => #on_err,
+            }
+        });
+    }
+}
+
+/// A `[u8; 16]` literal.
+fn u8x16(bytes: [u8; 16]) -> TokenStream {
+    let bytes = bytes.map(proc_macro2::Literal::u8_unsuffixed);
+    quote! { [#(#bytes),*] }
+}
+
+/// A `v128.const` operand, from its little-endian bytes.
+fn v128_const(bytes: [u8; 16]) -> TokenStream {
+    let bytes = u8x16(bytes);
+    quote! { u128::from_le_bytes(#bytes) }
+}
+
+/// Call a SIMD helper on `n` operands followed by an immediate (a lane index
+/// or the shuffle pattern).
+fn simd_op(ctx: &mut EmitCtx<'_>, fn_name: &str, n: usize, imm: TokenStream) {
+    let root = ctx.root().clone();
+    let fp_ts = ctx.fp();
+    let on_err = ctx.on_err();
+    let fn_id = format_ident!("{fn_name}");
+    let mut args: Vec<TokenStream> = (0..n).map(|_| ctx.pop()).collect();
+    args.reverse();
+    let tmp = ctx.fresh_tmp();
+    ctx.emit(quote! {
+        let (#tmp, ()) = match #root::#fn_id(#(#fp_ts::cast::<_,_,C>(#args),)* #imm) {
+            Ok(a) => a,
+            Err(e) => #on_err,
+        };
+    });
+    ctx.push(quote! { #tmp });
+}
+
+/// `v128.loadN_lane` / `v128.storeN_lane`: address and vector operands plus
+/// the lane immediate.
+fn emit_lane_mem(ctx: &mut EmitCtx<'_>, fn_name: &str, memarg: wasmparser::MemArg, lane: u8, store: bool) {
+    let root = ctx.root().clone();
+    let fp_ts = ctx.fp();
+    let on_err = ctx.on_err();
+    let mn = format_ident!("memory{}", memarg.memory);
+    let fn_id = format_ident!("{fn_name}");
+    let off = memarg.offset;
+    let v = ctx.pop();
+    let ptr = ctx.pop();
+    let call = quote! {
+        #root::#fn_id(ctx.#mn(), (#ptr as u64).wrapping_add(#off), #fp_ts::cast::<_,_,C>(#v), #lane)
+    };
+    if store {
+        ctx.emit(quote! {
+            match #call {
+                Ok(()) => {}
+                Err(e) => #on_err,
+            }
+        });
+    } else {
+        let tmp = ctx.fresh_tmp();
+        ctx.emit(quote! {
+            let (#tmp, ()) = match #call {
+                Ok(a) => a,
+                Err(e) => #on_err,
+            };
+        });
+        ctx.push(quote! { #tmp });
+    }
+}
+
 // ─── ToTokens impl ────────────────────────────────────────────────────────────
 
+impl<'a> OptsLt<'a, &'a [u8], WasmparserBackend> {
+    /// Translate the module, returning any error instead of embedding it as
+    /// a `compile_error!` the way [`ToTokens`] does.
+    pub fn render(&self) -> anyhow::Result<TokenStream> {
+        go(self)
+    }
+}
+
 impl<'a> ToTokens for OptsLt<'a, &'a [u8], WasmparserBackend> {
     fn to_tokens(&self, tokens: &mut TokenStream) {
         match go(self) {
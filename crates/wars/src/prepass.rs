@@ -0,0 +1,889 @@
+//! Wasm-to-wasm rewrite run before the waffle frontend.
+//!
+//! The waffle frontend has no IR for some operators.  [`run`] replaces each
+//! of them with a `call` to a stub function appended to the module, whose
+//! body is just `unreachable`, and records the [`Intrinsic`] the stub stands
+//! for.  `impl.rs` renders a call to a stub as the matching `wars_rt`
+//! helper, the same one the wasmparser backend uses, and never renders the
+//! stub itself.  What the stubs read (passive data and element segments)
+//! moves out of the module into [`Prepass`].
+//!
+//! Exception handling goes the same way.  Tags leave the module for
+//! [`Prepass`], and `throw` becomes a stub.  Inside a `try_table`, which
+//! becomes a plain `block`, every call is a stub returning the call's
+//! results, then the payload of each catch clause in reach, then which
+//! clause caught (0 for none).  A `br_table` after the stub branches to the
+//! clause's label.  `exnref` (`catch_ref`, `throw_ref`) and the legacy
+//! `try` instructions still need the wasmparser backend.
+//!
+//! Appending keeps every existing function and type index valid.  Modules
+//! that need no rewrite pass through unchanged.
+
+use std::{borrow::Cow, collections::BTreeMap, ops::Range};
+
+use wasmparser::{
+    AbstractHeapType, BinaryReader, BlockType, Catch, CompositeInnerType, DataKind, ElementItems,
+    ElementKind, ExternalKind, FromReader, FuncType, HeapType, MemArg, Operator, Parser, Payload,
+    RefType, SectionLimited, TypeRef, UnpackedIndex, ValType,
+};
+
+use crate::OpClass;
+
+/// What a call to a stub function does.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) enum Intrinsic {
+    /// `memory.init`: `(dst, src, len) -> ()`.
+    MemoryInit { data: u32, mem: u32 },
+    /// `data.drop`: `() -> ()`.
+    DataDrop { data: u32 },
+    /// `table.init`: `(dst, src, len) -> ()`.
+    TableInit { elem: u32, table: u32 },
+    /// `elem.drop`: `() -> ()`.
+    ElemDrop { elem: u32 },
+    /// An atomic access through the `wars_rt::atomic` helper `op`
+    /// (`i32atomicrmwadd`, …), `offset` bytes past its address operand.
+    Atomic { op: &'static str, mem: u32, offset: u64 },
+    /// `throw` outside any `try_table`: the payload `-> ()`.
+    Throw { tag: u32 },
+    /// A call or `throw` inside a `try_table`; see [`TrySite`].
+    TryCall { site: u32 },
+}
+
+/// What a [`Intrinsic::TryCall`] stub calls.
+#[derive(Clone, Copy, Debug)]
+pub(crate) enum Callee {
+    Func(u32),
+    /// `call_indirect`; the table index is the stub's last argument.
+    Indirect { ty: u32, table: u32 },
+    /// `call_ref`; the reference is the stub's last argument.
+    Ref { ty: u32 },
+    Throw { tag: u32 },
+}
+
+/// A call inside a `try_table`.  The stub takes the call's arguments and
+/// returns its results, then the payload slots of every clause, then the
+/// 1-based index of the clause that caught, or 0.
+#[derive(Clone, Debug)]
+pub(crate) struct TrySite {
+    pub(crate) callee: Callee,
+    /// How many results the call itself has.
+    pub(crate) results: usize,
+    /// Catch clauses, innermost first: the tag (`None` for `catch_all`) and
+    /// the result slots its payload fills.
+    pub(crate) clauses: Vec<(Option<u32>, Range<usize>)>,
+}
+
+/// An item of a passive element segment.
+#[derive(Clone, Copy, Debug)]
+pub(crate) enum ElemItem {
+    Func(u32),
+    Null,
+    Global(u32),
+}
+
+impl Intrinsic {
+    /// The [`OpClass`] of the operator this replaced.
+    pub(crate) fn class(&self) -> OpClass {
+        match self {
+            Intrinsic::MemoryInit { .. } | Intrinsic::DataDrop { .. } | Intrinsic::Atomic { .. } => {
+                OpClass::Memory
+            }
+            Intrinsic::TableInit { .. } | Intrinsic::ElemDrop { .. } => OpClass::Other,
+            Intrinsic::Throw { .. } => OpClass::Control,
+            Intrinsic::TryCall { .. } => OpClass::Call,
+        }
+    }
+}
+
+/// What [`run`] took out of the module.
+#[derive(Clone, Default)]
+pub(crate) struct Prepass {
+    /// Stub function index → the intrinsic a call to it performs.
+    pub(crate) intrinsics: BTreeMap<u32, Intrinsic>,
+    /// Passive data segments, by data index.
+    pub(crate) passive_data: BTreeMap<u32, Vec<u8>>,
+    /// Passive element segments, by element index.  They stay in the module
+    /// as declared segments, so `ref.func` still validates.
+    pub(crate) passive_elems: BTreeMap<u32, Vec<ElemItem>>,
+    /// Call sites inside a `try_table`, by [`Intrinsic::TryCall`] `site`.
+    pub(crate) sites: Vec<TrySite>,
+    /// Every tag, imports first, with its import's module and name.
+    pub(crate) tags: Vec<Option<(String, String)>>,
+    /// Tag exports: name and tag index.
+    pub(crate) tag_exports: Vec<(String, u32)>,
+}
+
+// ── Encoding ──────────────────────────────────────────────────────────────────
+
+const I32: u8 = 0x7f;
+const I64: u8 = 0x7e;
+
+const TYPE: u8 = 1;
+const IMPORT: u8 = 2;
+const FUNCTION: u8 = 3;
+const EXPORT: u8 = 7;
+const CODE: u8 = 10;
+const DATA: u8 = 11;
+const ELEMENT: u8 = 9;
+const DATA_COUNT: u8 = 12;
+const TAG: u8 = 13;
+
+fn leb(out: &mut Vec<u8>, mut v: u32) {
+    loop {
+        let b = (v & 0x7f) as u8;
+        v >>= 7;
+        if v == 0 {
+            out.push(b);
+            return;
+        }
+        out.push(b | 0x80);
+    }
+}
+
+fn sleb(out: &mut Vec<u8>, mut v: i64) {
+    loop {
+        let b = (v & 0x7f) as u8;
+        v >>= 7;
+        if (v == 0 && b & 0x40 == 0) || (v == -1 && b & 0x40 != 0) {
+            out.push(b);
+            return;
+        }
+        out.push(b | 0x80);
+    }
+}
+
+fn val(out: &mut Vec<u8>, t: ValType) -> anyhow::Result<()> {
+    match t {
+        ValType::I32 => out.push(I32),
+        ValType::I64 => out.push(I64),
+        ValType::F32 => out.push(0x7d),
+        ValType::F64 => out.push(0x7c),
+        ValType::V128 => out.push(0x7b),
+        ValType::Ref(r) => {
+            out.push(if r.is_nullable() { 0x63 } else { 0x64 });
+            match r.heap_type() {
+                HeapType::Concrete(i) => match i.as_module_index() {
+                    Some(i) => sleb(out, i.into()),
+                    None => anyhow::bail!("unsupported reference type {r}"),
+                },
+                HeapType::Abstract { shared, ty } => {
+                    if shared {
+                        out.push(0x65);
+                    }
+                    out.push(match ty {
+                        AbstractHeapType::Func => 0x70,
+                        AbstractHeapType::Extern => 0x6f,
+                        AbstractHeapType::Any => 0x6e,
+                        AbstractHeapType::Eq => 0x6d,
+                        AbstractHeapType::I31 => 0x6c,
+                        AbstractHeapType::Struct => 0x6b,
+                        AbstractHeapType::Array => 0x6a,
+                        AbstractHeapType::Exn => 0x69,
+                        AbstractHeapType::Cont => 0x68,
+                        AbstractHeapType::None => 0x71,
+                        AbstractHeapType::NoExtern => 0x72,
+                        AbstractHeapType::NoFunc => 0x73,
+                        AbstractHeapType::NoExn => 0x74,
+                        AbstractHeapType::NoCont => 0x75,
+                    });
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+fn vals(ts: &[ValType]) -> anyhow::Result<Vec<u8>> {
+    let mut out = vec![];
+    for t in ts {
+        val(&mut out, *t)?;
+    }
+    Ok(out)
+}
+
+fn section(out: &mut Vec<u8>, id: u8, content: &[u8]) {
+    out.push(id);
+    leb(out, content.len() as u32);
+    out.extend_from_slice(content);
+}
+
+/// `count` followed by `items`, with `count` re-encoded.
+fn vec_with(count: u32, items: &[u8]) -> Vec<u8> {
+    let mut out = vec![];
+    leb(&mut out, count);
+    out.extend_from_slice(items);
+    out
+}
+
+// ── Scan ──────────────────────────────────────────────────────────────────────
+
+#[derive(Default)]
+struct Scan {
+    /// Every type, `None` for GC types.
+    types: Vec<Option<FuncType>>,
+    /// The type of every function, imports first.
+    funcs: Vec<u32>,
+    func_imports: usize,
+    /// The type of every tag, imports first.
+    tags: Vec<u32>,
+    /// Whether each memory (imports first) is 64-bit.
+    mem64: Vec<bool>,
+    /// Whether each table (imports first) is 64-bit.
+    table64: Vec<bool>,
+    /// Defined function bodies, without their size prefix.
+    bodies: Vec<Range<usize>>,
+    /// Byte ranges to replace, with their replacements.
+    edits: Vec<(Range<usize>, Vec<u8>)>,
+    /// Byte ranges of the imports, exports and data segments to drop.
+    dropped: Vec<Range<usize>>,
+    /// Offsets of the flags of the element segments to declare.
+    declared_elems: Vec<usize>,
+    /// Stubs in the order they are appended, with their signatures.
+    stubs: BTreeMap<Intrinsic, u32>,
+    stub_sigs: Vec<(Vec<ValType>, Vec<ValType>)>,
+    sites: Vec<TrySite>,
+}
+
+/// A catch clause in reach of a call: its tag and the relative depth of
+/// its label from the call.
+type Handler = (Option<u32>, u32);
+
+/// The locals a function's try sites keep stub results in.  Each site
+/// stores them and reads them back before the next, so sites share them.
+struct Scratch {
+    next: u32,
+    pool: BTreeMap<Vec<u8>, Vec<u32>>,
+    added: Vec<Vec<u8>>,
+}
+
+impl Scratch {
+    /// A local of type `ty` not yet in `used` by the current site.
+    fn get(&mut self, ty: Vec<u8>, used: &mut BTreeMap<Vec<u8>, usize>) -> u32 {
+        let n = used.entry(ty.clone()).or_default();
+        let pool = self.pool.entry(ty.clone()).or_default();
+        if *n == pool.len() {
+            pool.push(self.next);
+            self.next += 1;
+            self.added.push(ty);
+        }
+        *n += 1;
+        pool[*n - 1]
+    }
+}
+
+impl Scan {
+    fn addr(&self, mem: u32) -> ValType {
+        if self.mem64.get(mem as usize).copied().unwrap_or(false) {
+            ValType::I64
+        } else {
+            ValType::I32
+        }
+    }
+
+    fn table_addr(&self, table: u32) -> ValType {
+        if self.table64.get(table as usize).copied().unwrap_or(false) {
+            ValType::I64
+        } else {
+            ValType::I32
+        }
+    }
+
+    fn func_type(&self, ty: u32) -> anyhow::Result<&FuncType> {
+        match self.types.get(ty as usize) {
+            Some(Some(f)) => Ok(f),
+            _ => anyhow::bail!("type {ty} is not a function type"),
+        }
+    }
+
+    fn tag_params(&self, tag: u32) -> anyhow::Result<&[ValType]> {
+        Ok(self.func_type(self.tags[tag as usize])?.params())
+    }
+
+    fn sig(&self, i: Intrinsic) -> anyhow::Result<(Vec<ValType>, Vec<ValType>)> {
+        Ok(match i {
+            Intrinsic::MemoryInit { mem, .. } => (vec![self.addr(mem), ValType::I32, ValType::I32], vec![]),
+            Intrinsic::TableInit { table, .. } => (vec![self.table_addr(table), ValType::I32, ValType::I32], vec![]),
+            Intrinsic::DataDrop { .. } | Intrinsic::ElemDrop { .. } => (vec![], vec![]),
+            Intrinsic::Atomic { op, mem, .. } => {
+                let a = self.addr(mem);
+                let w = if op.starts_with("i64") { ValType::I64 } else { ValType::I32 };
+                match op {
+                    "memoryatomicnotify" => (vec![a, ValType::I32], vec![ValType::I32]),
+                    "memoryatomicwait32" => (vec![a, ValType::I32, ValType::I64], vec![ValType::I32]),
+                    "memoryatomicwait64" => (vec![a, ValType::I64, ValType::I64], vec![ValType::I32]),
+                    _ if op.contains("load") => (vec![a], vec![w]),
+                    _ if op.contains("store") => (vec![a, w], vec![]),
+                    _ if op.contains("cmpxchg") => (vec![a, w, w], vec![w]),
+                    _ => (vec![a, w], vec![w]),
+                }
+            }
+            Intrinsic::Throw { tag } => (self.tag_params(tag)?.to_vec(), vec![]),
+            Intrinsic::TryCall { .. } => unreachable!("try sites are typed where they are found"),
+        })
+    }
+
+    fn intrinsic(&self, op: &Operator<'_>) -> Option<Intrinsic> {
+        Some(match *op {
+            Operator::MemoryInit { data_index, mem } => Intrinsic::MemoryInit { data: data_index, mem },
+            Operator::DataDrop { data_index } => Intrinsic::DataDrop { data: data_index },
+            Operator::TableInit { elem_index, table } => Intrinsic::TableInit { elem: elem_index, table },
+            Operator::ElemDrop { elem_index } => Intrinsic::ElemDrop { elem: elem_index },
+            _ => {
+                let (op, memarg) = atomic_op(op)?;
+                Intrinsic::Atomic { op, mem: memarg.memory, offset: memarg.offset }
+            }
+        })
+    }
+
+    /// A `call` of the stub for `i`, appending the stub on first use.
+    fn call(&mut self, i: Intrinsic, sig: (Vec<ValType>, Vec<ValType>)) -> Vec<u8> {
+        let n = *self.stubs.entry(i).or_insert_with(|| {
+            self.stub_sigs.push(sig);
+            self.stub_sigs.len() as u32 - 1
+        });
+        let mut out = vec![0x10];
+        leb(&mut out, self.funcs.len() as u32 + n);
+        out
+    }
+
+    /// The stub call for a call or `throw` inside a `try_table`, then the
+    /// dispatch on its clause index:
+    ///
+    /// ```text
+    /// call $stub  local.set …
+    /// block $ok  block $hN … block $h1
+    ///   local.get $code  br_table $ok $h1 … $hN
+    /// end  <payload of clause 1>  br <its label>
+    /// …
+    /// end  <results>
+    /// ```
+    fn try_site(
+        &mut self,
+        callee: Callee,
+        handlers: &[Handler],
+        scratch: &mut Scratch,
+    ) -> anyhow::Result<Vec<u8>> {
+        let (params, results) = match callee {
+            Callee::Func(f) => {
+                let t = self.func_type(self.funcs[f as usize])?;
+                (t.params().to_vec(), t.results().to_vec())
+            }
+            Callee::Indirect { ty, .. } | Callee::Ref { ty } => {
+                let t = self.func_type(ty)?;
+                (t.params().to_vec(), t.results().to_vec())
+            }
+            Callee::Throw { tag } => (self.tag_params(tag)?.to_vec(), vec![]),
+        };
+        let mut params = params;
+        match callee {
+            Callee::Indirect { table, .. } => params.push(self.table_addr(table)),
+            Callee::Ref { ty } => params.push(ValType::Ref(
+                RefType::new(true, HeapType::Concrete(UnpackedIndex::Module(ty)))
+                    .ok_or_else(|| anyhow::anyhow!("type index {ty} is too large"))?,
+            )),
+            _ => {}
+        }
+        let mut slots = results.clone();
+        let mut clauses = vec![];
+        for (tag, _) in handlers {
+            let start = slots.len();
+            if let Some(t) = tag {
+                slots.extend_from_slice(self.tag_params(*t)?);
+            }
+            clauses.push((*tag, start..slots.len()));
+        }
+        // Slots nothing wrote read as their default, so they need one.
+        for t in slots.iter() {
+            if let ValType::Ref(r) = t {
+                if !r.is_nullable() {
+                    anyhow::bail!("unsupported {r} result in a try_table: it needs the wasmparser backend");
+                }
+            }
+        }
+        slots.push(ValType::I32);
+        let site = self.sites.len() as u32;
+        self.sites.push(TrySite { callee, results: results.len(), clauses: clauses.clone() });
+        let mut code = self.call(Intrinsic::TryCall { site }, (params, slots.clone()));
+
+        let mut used = BTreeMap::new();
+        let mut locals = vec![];
+        for t in slots.iter() {
+            let mut ty = vec![];
+            val(&mut ty, *t)?;
+            locals.push(scratch.get(ty, &mut used));
+        }
+        for l in locals.iter().rev() {
+            code.push(0x21);
+            leb(&mut code, *l);
+        }
+        let n = handlers.len() as u32;
+        for _ in 0..=n {
+            code.extend_from_slice(&[0x02, 0x40]);
+        }
+        code.push(0x20);
+        leb(&mut code, locals[slots.len() - 1]);
+        code.push(0x0e);
+        leb(&mut code, n + 1);
+        leb(&mut code, n);
+        for k in 0..n {
+            leb(&mut code, k);
+        }
+        leb(&mut code, n);
+        code.push(0x0b);
+        for (k, ((_, label), (_, payload))) in handlers.iter().zip(clauses).enumerate() {
+            for slot in payload {
+                code.push(0x20);
+                leb(&mut code, locals[slot]);
+            }
+            // Out past the blocks of the later clauses and `$ok`.
+            code.push(0x0c);
+            leb(&mut code, n - k as u32 + label);
+            code.push(0x0b);
+        }
+        for l in &locals[..results.len()] {
+            code.push(0x20);
+            leb(&mut code, *l);
+        }
+        if let Callee::Throw { .. } = callee {
+            code.push(0x00);
+        }
+        Ok(code)
+    }
+
+    fn body(&mut self, body: wasmparser::FunctionBody<'_>) -> anyhow::Result<()> {
+        let range = body.range();
+        let first_edit = self.edits.len();
+        let ty = self.funcs[self.func_imports + self.bodies.len()];
+        self.bodies.push(range.clone());
+        let mut locals = body.get_locals_reader()?;
+        let decls = locals.get_count();
+        let decls_end = locals.original_position();
+        let mut next = self.func_type(ty)?.params().len() as u32;
+        for _ in 0..decls {
+            next += locals.read()?.0;
+        }
+        let mut scratch = Scratch { next, pool: BTreeMap::new(), added: vec![] };
+        // Catch clauses of every open frame, innermost last; the function
+        // itself is the outermost.
+        let mut frames: Vec<Vec<Catch>> = vec![vec![]];
+        let mut ops = body.get_operators_reader()?;
+        let ops_start = ops.original_position();
+        while !ops.eof() {
+            let start = ops.original_position();
+            let op = ops.read()?;
+            let at = start..ops.original_position();
+            match &op {
+                Operator::Block { .. } | Operator::Loop { .. } | Operator::If { .. } => {
+                    frames.push(vec![])
+                }
+                Operator::End => {
+                    frames.pop();
+                }
+                Operator::TryTable { try_table } => {
+                    let by_ref = |c: &&Catch| matches!(c, Catch::OneRef { .. } | Catch::AllRef { .. });
+                    if let Some(c) = try_table.catches.iter().find(by_ref) {
+                        anyhow::bail!("unsupported catch clause {c:?}: exnref needs the wasmparser backend");
+                    }
+                    let mut block = vec![0x02];
+                    match try_table.ty {
+                        BlockType::Empty => block.push(0x40),
+                        BlockType::Type(t) => val(&mut block, t)?,
+                        BlockType::FuncType(t) => sleb(&mut block, t.into()),
+                    }
+                    self.edits.push((at.clone(), block));
+                    frames.push(try_table.catches.clone());
+                }
+                // `atomic.fence` needs no code: shared memories already
+                // serialise every access behind their lock.
+                Operator::AtomicFence => self.edits.push((at.clone(), vec![])),
+                Operator::ThrowRef => {
+                    anyhow::bail!("unsupported operator throw_ref: exnref needs the wasmparser backend")
+                }
+                Operator::Try { .. }
+                | Operator::Catch { .. }
+                | Operator::CatchAll
+                | Operator::Delegate { .. }
+                | Operator::Rethrow { .. } => anyhow::bail!(
+                    "unsupported operator {op:?}: legacy exception handling needs the wasmparser backend"
+                ),
+                _ => {}
+            }
+            let callee = match op {
+                Operator::Call { function_index } => Callee::Func(function_index),
+                Operator::CallIndirect { type_index, table_index } => {
+                    Callee::Indirect { ty: type_index, table: table_index }
+                }
+                Operator::CallRef { type_index } => Callee::Ref { ty: type_index },
+                Operator::Throw { tag_index } => Callee::Throw { tag: tag_index },
+                _ => {
+                    if let Some(i) = self.intrinsic(&op) {
+                        let sig = self.sig(i)?;
+                        let code = self.call(i, sig);
+                        self.edits.push((at, code));
+                    }
+                    continue;
+                }
+            };
+            let handlers = handlers(&frames);
+            if !handlers.is_empty() {
+                let code = self.try_site(callee, &handlers, &mut scratch)?;
+                self.edits.push((at, code));
+            } else if let Callee::Throw { tag } = callee {
+                // The stub returns nothing, so the rest of the block stays
+                // unreachable.
+                let i = Intrinsic::Throw { tag };
+                let sig = self.sig(i)?;
+                let mut code = self.call(i, sig);
+                code.push(0x00);
+                self.edits.push((at, code));
+            }
+        }
+        if !scratch.added.is_empty() {
+            // One more declaration per scratch local.
+            let mut count = vec![];
+            leb(&mut count, decls + scratch.added.len() as u32);
+            self.edits.push((range.start..decls_end, count));
+            let mut added = vec![];
+            for t in scratch.added {
+                leb(&mut added, 1);
+                added.extend_from_slice(&t);
+            }
+            self.edits.push((ops_start..ops_start, added));
+            self.edits[first_edit..].sort_by_key(|(r, _)| (r.start, r.end));
+        }
+        Ok(())
+    }
+}
+
+/// The catch clauses in reach from the innermost of `frames`, up to the
+/// first `catch_all`.
+fn handlers(frames: &[Vec<Catch>]) -> Vec<Handler> {
+    let mut out = vec![];
+    for (depth, catches) in frames.iter().rev().enumerate() {
+        for c in catches {
+            let (tag, label) = match *c {
+                Catch::One { tag, label } => (Some(tag), label),
+                Catch::All { label } => (None, label),
+                Catch::OneRef { .. } | Catch::AllRef { .. } => unreachable!("rejected at the try_table"),
+            };
+            // Clause labels count from outside their `try_table`.
+            out.push((tag, depth as u32 + 1 + label));
+            if tag.is_none() {
+                return out;
+            }
+        }
+    }
+    out
+}
+
+/// The `wars_rt::atomic` helper for a threads-proposal access.
+fn atomic_op(op: &Operator<'_>) -> Option<(&'static str, MemArg)> {
+    Some(match *op {
+        Operator::I32AtomicLoad { memarg } => ("i32atomicload", memarg),
+        Operator::I64AtomicLoad { memarg } => ("i64atomicload", memarg),
+        Operator::I32AtomicLoad8U { memarg } => ("i32atomicload8u", memarg),
+        Operator::I32AtomicLoad16U { memarg } => ("i32atomicload16u", memarg),
+        Operator::I64AtomicLoad8U { memarg } => ("i64atomicload8u", memarg),
+        Operator::I64AtomicLoad16U { memarg } => ("i64atomicload16u", memarg),
+        Operator::I64AtomicLoad32U { memarg } => ("i64atomicload32u", memarg),
+        Operator::I32AtomicStore { memarg } => ("i32atomicstore", memarg),
+        Operator::I64AtomicStore { memarg } => ("i64atomicstore", memarg),
+        Operator::I32AtomicStore8 { memarg } => ("i32atomicstore8", memarg),
+        Operator::I32AtomicStore16 { memarg } => ("i32atomicstore16", memarg),
+        Operator::I64AtomicStore8 { memarg } => ("i64atomicstore8", memarg),
+        Operator::I64AtomicStore16 { memarg } => ("i64atomicstore16", memarg),
+        Operator::I64AtomicStore32 { memarg } => ("i64atomicstore32", memarg),
+        Operator::I32AtomicRmwAdd { memarg } => ("i32atomicrmwadd", memarg),
+        Operator::I64AtomicRmwAdd { memarg } => ("i64atomicrmwadd", memarg),
+        Operator::I32AtomicRmw8AddU { memarg } => ("i32atomicrmw8addu", memarg),
+        Operator::I32AtomicRmw16AddU { memarg } => ("i32atomicrmw16addu", memarg),
+        Operator::I64AtomicRmw8AddU { memarg } => ("i64atomicrmw8addu", memarg),
+        Operator::I64AtomicRmw16AddU { memarg } => ("i64atomicrmw16addu", memarg),
+        Operator::I64AtomicRmw32AddU { memarg } => ("i64atomicrmw32addu", memarg),
+        Operator::I32AtomicRmwSub { memarg } => ("i32atomicrmwsub", memarg),
+        Operator::I64AtomicRmwSub { memarg } => ("i64atomicrmwsub", memarg),
+        Operator::I32AtomicRmw8SubU { memarg } => ("i32atomicrmw8subu", memarg),
+        Operator::I32AtomicRmw16SubU { memarg } => ("i32atomicrmw16subu", memarg),
+        Operator::I64AtomicRmw8SubU { memarg } => ("i64atomicrmw8subu", memarg),
+        Operator::I64AtomicRmw16SubU { memarg } => ("i64atomicrmw16subu", memarg),
+        Operator::I64AtomicRmw32SubU { memarg } => ("i64atomicrmw32subu", memarg),
+        Operator::I32AtomicRmwAnd { memarg } => ("i32atomicrmwand", memarg),
+        Operator::I64AtomicRmwAnd { memarg } => ("i64atomicrmwand", memarg),
+        Operator::I32AtomicRmw8AndU { memarg } => ("i32atomicrmw8andu", memarg),
+        Operator::I32AtomicRmw16AndU { memarg } => ("i32atomicrmw16andu", memarg),
+        Operator::I64AtomicRmw8AndU { memarg } => ("i64atomicrmw8andu", memarg),
+        Operator::I64AtomicRmw16AndU { memarg } => ("i64atomicrmw16andu", memarg),
+        Operator::I64AtomicRmw32AndU { memarg } => ("i64atomicrmw32andu", memarg),
+        Operator::I32AtomicRmwOr { memarg } => ("i32atomicrmwor", memarg),
+        Operator::I64AtomicRmwOr { memarg } => ("i64atomicrmwor", memarg),
+        Operator::I32AtomicRmw8OrU { memarg } => ("i32atomicrmw8oru", memarg),
+        Operator::I32AtomicRmw16OrU { memarg } => ("i32atomicrmw16oru", memarg),
+        Operator::I64AtomicRmw8OrU { memarg } => ("i64atomicrmw8oru", memarg),
+        Operator::I64AtomicRmw16OrU { memarg } => ("i64atomicrmw16oru", memarg),
+        Operator::I64AtomicRmw32OrU { memarg } => ("i64atomicrmw32oru", memarg),
+        Operator::I32AtomicRmwXor { memarg } => ("i32atomicrmwxor", memarg),
+        Operator::I64AtomicRmwXor { memarg } => ("i64atomicrmwxor", memarg),
+        Operator::I32AtomicRmw8XorU { memarg } => ("i32atomicrmw8xoru", memarg),
+        Operator::I32AtomicRmw16XorU { memarg } => ("i32atomicrmw16xoru", memarg),
+        Operator::I64AtomicRmw8XorU { memarg } => ("i64atomicrmw8xoru", memarg),
+        Operator::I64AtomicRmw16XorU { memarg } => ("i64atomicrmw16xoru", memarg),
+        Operator::I64AtomicRmw32XorU { memarg } => ("i64atomicrmw32xoru", memarg),
+        Operator::I32AtomicRmwXchg { memarg } => ("i32atomicrmwxchg", memarg),
+        Operator::I64AtomicRmwXchg { memarg } => ("i64atomicrmwxchg", memarg),
+        Operator::I32AtomicRmw8XchgU { memarg } => ("i32atomicrmw8xchgu", memarg),
+        Operator::I32AtomicRmw16XchgU { memarg } => ("i32atomicrmw16xchgu", memarg),
+        Operator::I64AtomicRmw8XchgU { memarg } => ("i64atomicrmw8xchgu", memarg),
+        Operator::I64AtomicRmw16XchgU { memarg } => ("i64atomicrmw16xchgu", memarg),
+        Operator::I64AtomicRmw32XchgU { memarg } => ("i64atomicrmw32xchgu", memarg),
+        Operator::I32AtomicRmwCmpxchg { memarg } => ("i32atomicrmwcmpxchg", memarg),
+        Operator::I64AtomicRmwCmpxchg { memarg } => ("i64atomicrmwcmpxchg", memarg),
+        Operator::I32AtomicRmw8CmpxchgU { memarg } => ("i32atomicrmw8cmpxchgu", memarg),
+        Operator::I32AtomicRmw16CmpxchgU { memarg } => ("i32atomicrmw16cmpxchgu", memarg),
+        Operator::I64AtomicRmw8CmpxchgU { memarg } => ("i64atomicrmw8cmpxchgu", memarg),
+        Operator::I64AtomicRmw16CmpxchgU { memarg } => ("i64atomicrmw16cmpxchgu", memarg),
+        Operator::I64AtomicRmw32CmpxchgU { memarg } => ("i64atomicrmw32cmpxchgu", memarg),
+        Operator::MemoryAtomicNotify { memarg } => ("memoryatomicnotify", memarg),
+        Operator::MemoryAtomicWait32 { memarg } => ("memoryatomicwait32", memarg),
+        Operator::MemoryAtomicWait64 { memarg } => ("memoryatomicwait64", memarg),
+        _ => return None,
+    })
+}
+
+/// The items of a passive element segment.
+fn elem_items(items: ElementItems<'_>) -> anyhow::Result<Vec<ElemItem>> {
+    let mut out = vec![];
+    match items {
+        ElementItems::Functions(r) => {
+            for f in r {
+                out.push(ElemItem::Func(f?));
+            }
+        }
+        ElementItems::Expressions(_, r) => {
+            for e in r {
+                let mut ops = e?.get_operators_reader();
+                out.push(match ops.read()? {
+                    Operator::RefFunc { function_index } => ElemItem::Func(function_index),
+                    Operator::RefNull { .. } => ElemItem::Null,
+                    Operator::GlobalGet { global_index } => ElemItem::Global(global_index),
+                    op => anyhow::bail!("unsupported element item {op:?}"),
+                });
+            }
+        }
+    }
+    Ok(out)
+}
+
+// ── Rewrite ───────────────────────────────────────────────────────────────────
+
+/// The items of a section with the byte range of each.
+fn with_ranges<'a, T: FromReader<'a>>(
+    r: SectionLimited<'a, T>,
+) -> anyhow::Result<Vec<(Range<usize>, T)>> {
+    let end = r.range().end;
+    let mut out: Vec<(Range<usize>, T)> = vec![];
+    for i in r.into_iter_with_offsets() {
+        let (at, i) = i?;
+        if let Some(last) = out.last_mut() {
+            last.0.end = at;
+        }
+        out.push((at..end, i));
+    }
+    Ok(out)
+}
+
+/// Rewrite `bytes` so the waffle frontend accepts it.  See the module docs.
+pub(crate) fn run(bytes: &[u8]) -> anyhow::Result<(Cow<'_, [u8]>, Prepass)> {
+    let mut s = Scan::default();
+    let mut pre = Prepass::default();
+    for payload in Parser::new(0).parse_all(bytes) {
+        match payload? {
+            Payload::TypeSection(r) => {
+                for g in r {
+                    for t in g?.types() {
+                        s.types.push(match &t.composite_type.inner {
+                            CompositeInnerType::Func(f) => Some(f.clone()),
+                            _ => None,
+                        });
+                    }
+                }
+            }
+            Payload::ImportSection(r) => {
+                for (at, i) in with_ranges(r)? {
+                    match i.ty {
+                        TypeRef::Func(t) => {
+                            s.funcs.push(t);
+                            s.func_imports += 1;
+                        }
+                        TypeRef::Memory(m) => s.mem64.push(m.memory64),
+                        TypeRef::Table(t) => s.table64.push(t.table64),
+                        TypeRef::Tag(t) => {
+                            s.tags.push(t.func_type_idx);
+                            pre.tags.push(Some((i.module.to_owned(), i.name.to_owned())));
+                            s.dropped.push(at);
+                        }
+                        TypeRef::Global(_) => {}
+                    }
+                }
+            }
+            Payload::FunctionSection(r) => {
+                for f in r {
+                    s.funcs.push(f?);
+                }
+            }
+            Payload::TableSection(r) => {
+                for t in r {
+                    s.table64.push(t?.ty.table64);
+                }
+            }
+            Payload::MemorySection(r) => {
+                for m in r {
+                    s.mem64.push(m?.memory64);
+                }
+            }
+            Payload::TagSection(r) => {
+                for t in r {
+                    s.tags.push(t?.func_type_idx);
+                    pre.tags.push(None);
+                }
+            }
+            Payload::ExportSection(r) => {
+                for (at, e) in with_ranges(r)? {
+                    if let ExternalKind::Tag = e.kind {
+                        pre.tag_exports.push((e.name.to_owned(), e.index));
+                        s.dropped.push(at);
+                    }
+                }
+            }
+            Payload::ElementSection(r) => {
+                for (i, e) in r.into_iter().enumerate() {
+                    let e = e?;
+                    if let ElementKind::Passive = e.kind {
+                        pre.passive_elems.insert(i as u32, elem_items(e.items)?);
+                        s.declared_elems.push(e.range.start);
+                    }
+                }
+            }
+            Payload::DataSection(r) => {
+                for (i, d) in r.into_iter().enumerate() {
+                    let d = d?;
+                    if let DataKind::Passive = d.kind {
+                        pre.passive_data.insert(i as u32, d.data.to_vec());
+                        s.dropped.push(d.range);
+                    }
+                }
+            }
+            Payload::CodeSectionEntry(body) => s.body(body)?,
+            _ => {}
+        }
+    }
+    if s.edits.is_empty() && s.dropped.is_empty() && s.declared_elems.is_empty() && s.tags.is_empty() {
+        return Ok((Cow::Borrowed(bytes), pre));
+    }
+    let n_funcs = s.funcs.len() as u32;
+    for (i, n) in s.stubs.iter() {
+        pre.intrinsics.insert(n_funcs + n, *i);
+    }
+    pre.sites = std::mem::take(&mut s.sites);
+
+    // Stubs share their new types, but not the module's own; the type
+    // section is small next to the code.
+    let stubs = s.stub_sigs.len() as u32;
+    let mut sig_types = BTreeMap::new();
+    let mut new_types = vec![];
+    let mut new_funcs = vec![];
+    let mut new_code = vec![];
+    for sig @ (params, results) in s.stub_sigs.iter() {
+        let n = sig_types.len() as u32;
+        let t = match sig_types.get(sig) {
+            Some(t) => *t,
+            None => {
+                new_types.push(0x60);
+                leb(&mut new_types, params.len() as u32);
+                new_types.extend_from_slice(&vals(params)?);
+                leb(&mut new_types, results.len() as u32);
+                new_types.extend_from_slice(&vals(results)?);
+                sig_types.insert(sig, s.types.len() as u32 + n);
+                s.types.len() as u32 + n
+            }
+        };
+        leb(&mut new_funcs, t);
+        // No locals; `unreachable`; `end`.
+        new_code.extend_from_slice(&[3, 0x00, 0x00, 0x0b]);
+    }
+    let types = sig_types.len() as u32;
+
+    // Every stub replaces an operator in some body, so the type, function
+    // and code sections already exist.
+    let mut out = bytes[..8].to_vec();
+    let mut edits = s.edits.iter().peekable();
+    let mut r = BinaryReader::new(bytes, 0);
+    r.read_bytes(8)?;
+    while !r.eof() {
+        let id = r.read_u8()?;
+        let len = r.read_var_u32()? as usize;
+        let start = r.original_position();
+        let content = r.read_bytes(len)?;
+        let mut c = BinaryReader::new(content, start);
+        match id {
+            TYPE if stubs != 0 => {
+                let n = c.read_var_u32()?;
+                let mut v = vec_with(n + types, &bytes[c.original_position()..start + len]);
+                v.extend_from_slice(&new_types);
+                section(&mut out, id, &v);
+            }
+            FUNCTION if stubs != 0 => {
+                let n = c.read_var_u32()?;
+                let mut v = vec_with(n + stubs, &bytes[c.original_position()..start + len]);
+                v.extend_from_slice(&new_funcs);
+                section(&mut out, id, &v);
+            }
+            CODE => {
+                let mut v = vec![];
+                leb(&mut v, s.bodies.len() as u32 + stubs);
+                for b in s.bodies.iter() {
+                    let mut body = vec![];
+                    let mut at = b.start;
+                    while let Some((e, code)) = edits.next_if(|(e, _)| e.start < b.end) {
+                        body.extend_from_slice(&bytes[at..e.start]);
+                        body.extend_from_slice(code);
+                        at = e.end;
+                    }
+                    body.extend_from_slice(&bytes[at..b.end]);
+                    leb(&mut v, body.len() as u32);
+                    v.extend_from_slice(&body);
+                }
+                v.extend_from_slice(&new_code);
+                section(&mut out, id, &v);
+            }
+            IMPORT | EXPORT | DATA => {
+                // Tags live in `Prepass`, passive segments in `*Data`.
+                let n = c.read_var_u32()?;
+                let here = start..start + len;
+                let dropped: Vec<_> = s.dropped.iter().filter(|d| here.contains(&d.start)).collect();
+                let mut v = vec![];
+                leb(&mut v, n - dropped.len() as u32);
+                let mut at = c.original_position();
+                for d in dropped {
+                    v.extend_from_slice(&bytes[at..d.start]);
+                    at = d.end;
+                }
+                v.extend_from_slice(&bytes[at..start + len]);
+                section(&mut out, id, &v);
+            }
+            ELEMENT => {
+                // Flag bit 1 turns a passive segment (1 or 5) into a
+                // declared one (3 or 7).
+                let mut v = content.to_vec();
+                for at in s.declared_elems.iter() {
+                    v[at - start] |= 2;
+                }
+                section(&mut out, id, &v);
+            }
+            // Only `memory.init` and `data.drop` need it, and both are gone.
+            DATA_COUNT => {}
+            TAG => {}
+            _ => section(&mut out, id, content),
+        }
+    }
+    Ok((Cow::Owned(out), pre))
+}
@@ -47,6 +47,171 @@ pub(crate) fn fp(core: &OptsCore<'_>) -> TokenStream {
     }
 }
 
+// ── Memories ─────────────────────────────────────────────────────────────────
+
+/// Page limit of a memory: its declared `maximum`, or else what its address
+/// space can hold.
+pub(crate) fn max_pages(maximum: Option<u64>, memory64: bool, page_size: u64) -> u64 {
+    maximum.unwrap_or_else(|| {
+        let space: u128 = if memory64 { 1 << 64 } else { 1 << 32 };
+        (space / page_size as u128) as u64
+    })
+}
+
+// ── Metering ─────────────────────────────────────────────────────────────────
+
+/// Extra supertraits the host trait needs for the metering flags.
+pub(crate) fn meter_bounds(core: &OptsCore<'_>) -> TokenStream {
+    let root = core.crate_path.clone();
+    let mut b = quote! {};
+    if core.flags.contains(Flags::FUEL) {
+        b.extend(quote! { + #root::Fuel });
+    }
+    if core.flags.contains(Flags::EPOCH) {
+        b.extend(quote! { + #root::Epoch });
+    }
+    if core.flags.contains(Flags::CALL_DEPTH) {
+        b.extend(quote! { + #root::CallDepth });
+    }
+    b
+}
+
+/// Charge `cost` units of fuel, running `on_err` with `e` bound when the
+/// context is out.  Async code asks the host to refuel and retries instead.
+pub(crate) fn render_fuel(core: &OptsCore<'_>, cost: u64, on_err: &TokenStream) -> TokenStream {
+    let root = core.crate_path.clone();
+    if !core.flags.contains(Flags::FUEL) || cost == 0 {
+        return quote! {};
+    }
+    if core.flags.contains(Flags::ASYNC) {
+        quote! {
+            while !#root::Fuel::consume_fuel(ctx, #cost) {
+                if let Err(e) = #root::Fuel::refuel(ctx).go().await {
+                    #on_err
+                }
+            }
+        }
+    } else {
+        quote! {
+            if !#root::Fuel::consume_fuel(ctx, #cost) {
+                let e: #root::_rexport::anyhow::Error = #root::Trap::OutOfFuel.into();
+                #on_err
+            }
+        }
+    }
+}
+
+/// Check the epoch deadline, running `on_err` with `e` bound if it traps.
+/// Async code yields to the executor when the deadline is extended instead.
+pub(crate) fn render_epoch(core: &OptsCore<'_>, on_err: &TokenStream) -> TokenStream {
+    let root = core.crate_path.clone();
+    if !core.flags.contains(Flags::EPOCH) {
+        return quote! {};
+    }
+    let resume = if core.flags.contains(Flags::ASYNC) {
+        quote! { #root::epoch::YieldNow::default().await }
+    } else {
+        quote! {}
+    };
+    quote! {
+        if #root::Epoch::epoch_deadline(ctx).expired() {
+            match #root::Epoch::epoch_deadline(ctx).reached() {
+                Ok(()) => { #resume }
+                Err(e) => #on_err,
+            }
+        }
+    }
+}
+
+/// Unwrap `call`, an `anyhow::Result` expression that runs a wasm function,
+/// running `on_err` with `e` bound on failure.  Under `Flags::CALL_DEPTH`
+/// the call is counted against the context's depth limit until the guard
+/// drops, which also happens when an async caller is dropped mid-call.
+pub(crate) fn render_call(core: &OptsCore<'_>, call: TokenStream, on_err: &TokenStream) -> TokenStream {
+    let root = core.crate_path.clone();
+    if !core.flags.contains(Flags::CALL_DEPTH) {
+        return quote! {
+            match #call {
+                Ok(a) => a,
+                Err(e) => #on_err,
+            }
+        };
+    }
+    quote! {
+        {
+            let _cd = match #root::CallDepth::call_depth(ctx).enter() {
+                Ok(g) => g,
+                Err(e) => #on_err,
+            };
+            match #call {
+                Ok(a) => a,
+                Err(e) => #on_err,
+            }
+        }
+    }
+}
+
+// ── Dynamic imports ──────────────────────────────────────────────────────────
+
+/// The supertrait `Flags::DYN_IMPORTS` modules resolve their imports through.
+pub(crate) fn link_bounds(core: &OptsCore<'_>) -> TokenStream {
+    if !core.flags.contains(Flags::DYN_IMPORTS) {
+        return quote! {};
+    }
+    let root = core.crate_path.clone();
+    let fp_ts = fp(core);
+    quote! { + #root::Linker<#fp_ts::Value<Self>> }
+}
+
+/// The `*Data` field a resolved import is kept in.
+pub(crate) fn link_field(module: &str, name: &str) -> Ident {
+    format_ident!("link_{}_{}", bindname(module), bindname(name))
+}
+
+/// Resolve `module.name` during `init()` and keep it in `field` as the
+/// `Df` type `df` (rendered with `C` as the context).
+pub(crate) fn render_link(
+    core: &OptsCore<'_>,
+    field: &Ident,
+    df: &TokenStream,
+    module: &str,
+    name: &str,
+) -> TokenStream {
+    let root = core.crate_path.clone();
+    let fp_ts = fp(core);
+    quote! {
+        {
+            let v = #root::Linker::<#fp_ts::Value<C>>::resolve(ctx, #module, #name)
+                .ok_or_else(|| #root::linker::Unresolved::new(#module, #name))?;
+            ctx.data().#field = Some(<#df as #fp_ts::Coe<C>>::uncoe(v)?);
+        }
+    }
+}
+
+/// Call the import kept in `field`; `generics` are its `Df` arguments.
+/// Parenthesised, so callers can chain `.go()` onto it.
+pub(crate) fn render_dyn_import(
+    core: &OptsCore<'_>,
+    field: &Ident,
+    generics: &TokenStream,
+    module: &str,
+    name: &str,
+    params: impl Iterator<Item = TokenStream>,
+) -> TokenStream {
+    let root = core.crate_path.clone();
+    let fp_ts = fp(core);
+    quote! {
+        (match ctx.data().#field.clone() {
+            Some(f) => #fp_ts::call_ref::<#generics, C>(
+                ctx,
+                f,
+                #root::_rexport::tuple_list::tuple_list!(#(#params),*),
+            ),
+            None => #fp_ts::ret(Err(#root::linker::Unresolved::new(#module, #name).into())),
+        })
+    }
+}
+
 // ── WasmTy trait ─────────────────────────────────────────────────────────────
 
 /// Abstraction over a single WebAssembly value type that is sufficient for
@@ -67,14 +232,54 @@ pub(crate) trait WasmTy: Copy {
     fn is_v128(self) -> bool;
     /// Is this a reference type (funcref, externref, …)?
     fn is_ref(self) -> bool;
+    /// Is this `externref`?
+    fn is_extern(self) -> bool;
+    /// For a reference to a concrete type: whether it is nullable, and the
+    /// type index.  Abstract references (`funcref`, `externref`, …) give `None`.
+    fn type_ref(self) -> Option<(bool, u32)> {
+        None
+    }
+}
+
+/// A module's type section, as far as rendering typed function references
+/// needs it.  `()` knows no types, which renders every reference as
+/// `Value<C>`.
+pub(crate) trait TypeSpace<T> {
+    /// The signature of type `idx`, if it is a function type.
+    fn type_sig(&self, idx: u32) -> Option<FuncSig<'_, T>>;
+}
+
+impl<T> TypeSpace<T> for () {
+    fn type_sig(&self, _: u32) -> Option<FuncSig<'_, T>> {
+        None
+    }
 }
 
 /// Map a single value type (described via `WasmTy`) to the Rust token stream
 /// used in ABI v0 signatures.
 ///
 /// `ctx` is the token stream used as the context type parameter (e.g.
-/// `quote!{C}`).
-pub(crate) fn render_ty<T: WasmTy>(core: &OptsCore<'_>, ctx: &TokenStream, ty: T) -> TokenStream {
+/// `quote!{C}`).  References to function types found in `types` become
+/// `Df<…>`, wrapped in `Option` when nullable.
+pub(crate) fn render_ty<T: WasmTy>(
+    core: &OptsCore<'_>,
+    types: &impl TypeSpace<T>,
+    ctx: &TokenStream,
+    ty: T,
+) -> TokenStream {
+    render_ty_in(core, types, ctx, ty, &mut vec![])
+}
+
+/// `render_ty`, remembering which signatures are being expanded: a
+/// recursive function type has no Rust spelling, so the inner occurrence
+/// falls back to `Value<C>`.
+fn render_ty_in<T: WasmTy>(
+    core: &OptsCore<'_>,
+    types: &impl TypeSpace<T>,
+    ctx: &TokenStream,
+    ty: T,
+    seen: &mut Vec<u32>,
+) -> TokenStream {
     let fp_ts = fp(core);
     if ty.is_i32() {
         quote! { u32 }
@@ -87,11 +292,190 @@ pub(crate) fn render_ty<T: WasmTy>(core: &OptsCore<'_>, ctx: &TokenStream, ty: T
     } else if ty.is_v128() {
         quote! { u128 }
     } else {
-        // All reference types fall back to Value<C>.
+        if let Some((nullable, idx)) = ty.type_ref() {
+            if let Some(sig) = types.type_sig(idx).filter(|_| !seen.contains(&idx)) {
+                let root = core.crate_path.clone();
+                seen.push(idx);
+                let params: Vec<_> =
+                    sig.params.iter().map(|t| render_ty_in(core, types, ctx, *t, seen)).collect();
+                let returns: Vec<_> =
+                    sig.returns.iter().map(|t| render_ty_in(core, types, ctx, *t, seen)).collect();
+                seen.pop();
+                let df = quote! {
+                    #fp_ts::Df<
+                        #root::_rexport::tuple_list::tuple_list_type!(#(#params),*),
+                        #root::_rexport::tuple_list::tuple_list_type!(#(#returns),*),
+                        #ctx>
+                };
+                return if nullable { quote! { Option<#df> } } else { df };
+            }
+        }
+        // All other reference types fall back to Value<C>.
         quote! { #fp_ts::Value<#ctx> }
     }
 }
 
+/// The `MetaType` describing `ty` in an export table: `MetaType::Other`
+/// for v128, untyped function references, GC references and signatures
+/// that refer to themselves.
+pub(crate) fn render_meta<T: WasmTy>(
+    core: &OptsCore<'_>,
+    types: &impl TypeSpace<T>,
+    ty: T,
+) -> TokenStream {
+    render_meta_in(core, types, ty, &mut vec![])
+}
+
+fn render_meta_in<T: WasmTy>(
+    core: &OptsCore<'_>,
+    types: &impl TypeSpace<T>,
+    ty: T,
+    seen: &mut Vec<u32>,
+) -> TokenStream {
+    let root = core.crate_path.clone();
+    if ty.is_i32() {
+        return quote! { #root::MetaType::I32 };
+    } else if ty.is_i64() {
+        return quote! { #root::MetaType::I64 };
+    } else if ty.is_f32() {
+        return quote! { #root::MetaType::F32 };
+    } else if ty.is_f64() {
+        return quote! { #root::MetaType::F64 };
+    } else if ty.is_extern() {
+        return quote! { #root::MetaType::ExternRef };
+    }
+    let other = quote! { #root::MetaType::Other };
+    let Some((_, idx)) = ty.type_ref() else {
+        return other;
+    };
+    if seen.contains(&idx) {
+        return other;
+    }
+    let Some(sig) = types.type_sig(idx) else {
+        return other;
+    };
+    seen.push(idx);
+    let meta = render_sig_meta_in(core, types, sig, seen);
+    seen.pop();
+    meta
+}
+
+/// The `MetaType::FunRef` describing a function signature.
+pub(crate) fn render_sig_meta<T: WasmTy>(
+    core: &OptsCore<'_>,
+    types: &impl TypeSpace<T>,
+    sig: FuncSig<'_, T>,
+) -> TokenStream {
+    render_sig_meta_in(core, types, sig, &mut vec![])
+}
+
+fn render_sig_meta_in<T: WasmTy>(
+    core: &OptsCore<'_>,
+    types: &impl TypeSpace<T>,
+    sig: FuncSig<'_, T>,
+    seen: &mut Vec<u32>,
+) -> TokenStream {
+    let root = core.crate_path.clone();
+    let alloc_ts = alloc(core);
+    let params = sig
+        .params
+        .iter()
+        .map(|t| render_meta_in(core, types, *t, seen))
+        .collect::<Vec<_>>();
+    let returns = sig
+        .returns
+        .iter()
+        .map(|t| render_meta_in(core, types, *t, seen))
+        .collect::<Vec<_>>();
+    quote! {
+        #root::MetaType::FunRef {
+            params: #alloc_ts::vec![#(#params),*],
+            returns: #alloc_ts::vec![#(#returns),*],
+        }
+    }
+}
+
+/// One function export in the reflective table: its wasm name, the
+/// reference (built with `C` as the context type), its `MetaType::FunRef`
+/// and its parameter count.
+pub(crate) struct ExportEntry {
+    pub name: String,
+    pub fun_ref: TokenStream,
+    pub meta: TokenStream,
+    pub params: usize,
+}
+
+/// Emit the reflective export entry points `get_export`, `exports()` and
+/// `call_export` over every function export.  `call_export` takes its
+/// arguments and returns its results in declaration order, reversing them
+/// around the `CoeVec` call.
+pub(crate) fn render_export_table(
+    core: &OptsCore<'_>,
+    entries: &[ExportEntry],
+) -> (TokenStream, TokenStream) {
+    let root = core.crate_path.clone();
+    let fp_ts = fp(core);
+    let alloc_ts = alloc(core);
+    let rec = if core.flags.contains(Flags::ASYNC) {
+        quote! { #root::func::unsync::AsyncRec }
+    } else {
+        quote! { #root::_rexport::tramp::BorrowRec }
+    };
+    let names = entries.iter().map(|e| &e.name);
+    let refs = entries.iter().map(|e| &e.fun_ref);
+    let metas = entries.iter().map(|e| &e.meta);
+    let list = entries.iter().map(|ExportEntry { name, meta, .. }| quote! { (#name, #meta) });
+    let call_arms = entries.iter().map(|ExportEntry { name, fun_ref, params, .. }| {
+        quote! { #name => (#fp_ts::cast::<_, #fp_ts::Value<C>, C>(#fun_ref), #params) }
+    });
+    (
+        quote! {
+            fn get_export(&mut self, name: &str) -> Option<(#fp_ts::Value<Self>, #root::MetaType)>
+            where Self: 'static;
+            fn exports() -> #alloc_ts::vec::Vec<(&'static str, #root::MetaType)>;
+            fn call_export<'a>(
+                &'a mut self,
+                name: &str,
+                args: #alloc_ts::vec::Vec<#fp_ts::Value<Self>>,
+            ) -> #rec<'a, #root::_rexport::anyhow::Result<#alloc_ts::vec::Vec<#fp_ts::Value<Self>>>>
+            where Self: 'static;
+        },
+        quote! {
+            fn get_export(&mut self, name: &str) -> Option<(#fp_ts::Value<Self>, #root::MetaType)>
+            where Self: 'static {
+                match name {
+                    #(#names => Some((#fp_ts::cast::<_, #fp_ts::Value<C>, C>(#refs), #metas)),)*
+                    _ => None,
+                }
+            }
+            fn exports() -> #alloc_ts::vec::Vec<(&'static str, #root::MetaType)> {
+                #alloc_ts::vec![#(#list),*]
+            }
+            fn call_export<'a>(
+                &'a mut self,
+                name: &str,
+                mut args: #alloc_ts::vec::Vec<#fp_ts::Value<Self>>,
+            ) -> #rec<'a, #root::_rexport::anyhow::Result<#alloc_ts::vec::Vec<#fp_ts::Value<Self>>>>
+            where Self: 'static {
+                let (f, n): (#fp_ts::Value<C>, usize) = match name {
+                    #(#call_arms,)*
+                    _ => return #fp_ts::ret(Err(#root::_rexport::anyhow::anyhow!("no function export named {name}"))),
+                };
+                if args.len() != n {
+                    return #fp_ts::ret(Err(#root::Trap::IndirectCallTypeMismatch.into()));
+                }
+                args.reverse();
+                #fp_ts::map_rec(#fp_ts::call_value(self, f, args), |r| {
+                    r.map(|mut v| {
+                        v.reverse();
+                        v
+                    })
+                })
+            }
+        },
+    )
+}
+
 // ── Type description ──────────────────────────────────────────────────────────
 
 /// A wasm function signature over an abstract value type `T`.
@@ -123,12 +507,13 @@ impl<T: Clone> FuncSigOwned<T> {
 /// arguments for `Df` / `call_ref`.
 pub(crate) fn render_generics<T: WasmTy>(
     core: &OptsCore<'_>,
+    types: &impl TypeSpace<T>,
     ctx: &TokenStream,
     sig: FuncSig<'_, T>,
 ) -> TokenStream {
     let root = core.crate_path.clone();
-    let params = sig.params.iter().map(|t| render_ty(core, ctx, *t));
-    let returns = sig.returns.iter().map(|t| render_ty(core, ctx, *t));
+    let params = sig.params.iter().map(|t| render_ty(core, types, ctx, *t));
+    let returns = sig.returns.iter().map(|t| render_ty(core, types, ctx, *t));
     quote! {
         #root::_rexport::tuple_list::tuple_list_type!(#(#params),*),
         #root::_rexport::tuple_list::tuple_list_type!(#(#returns),*)
@@ -143,20 +528,21 @@ pub(crate) fn render_generics<T: WasmTy>(
 /// ```
 pub(crate) fn render_fn_sig<T: WasmTy>(
     core: &OptsCore<'_>,
+    types: &impl TypeSpace<T>,
     name: Ident,
     sig: FuncSig<'_, T>,
 ) -> TokenStream {
     let root = core.crate_path.clone();
     let base = core.name.clone();
     let ctx = quote! { C };
-    let params2: Vec<_> = sig.params.iter().map(|t| render_ty(core, &ctx, *t)).collect();
+    let params2: Vec<_> = sig.params.iter().map(|t| render_ty(core, types, &ctx, *t)).collect();
     let param_ids: Vec<_> = sig
         .params
         .iter()
         .enumerate()
         .map(|(i, _)| format_ident!("p{i}"))
         .collect();
-    let returns: Vec<_> = sig.returns.iter().map(|t| render_ty(core, &ctx, *t)).collect();
+    let returns: Vec<_> = sig.returns.iter().map(|t| render_ty(core, types, &ctx, *t)).collect();
     let mut x = if core.flags.contains(Flags::ASYNC) {
         quote! {
             fn #name<'a, C: #base + 'static>(
@@ -191,20 +577,21 @@ pub(crate) fn render_fn_sig<T: WasmTy>(
 /// FooImpl for C`), delegating to the free function `wrapped`.
 pub(crate) fn render_export<T: WasmTy>(
     core: &OptsCore<'_>,
+    types: &impl TypeSpace<T>,
     name: Ident,
     wrapped: Ident,
     sig: FuncSig<'_, T>,
 ) -> TokenStream {
     let root = core.crate_path.clone();
     let ctx = quote! { Self };
-    let params2: Vec<_> = sig.params.iter().map(|t| render_ty(core, &ctx, *t)).collect();
+    let params2: Vec<_> = sig.params.iter().map(|t| render_ty(core, types, &ctx, *t)).collect();
     let param_ids: Vec<_> = sig
         .params
         .iter()
         .enumerate()
         .map(|(i, _)| format_ident!("p{i}"))
         .collect();
-    let returns: Vec<_> = sig.returns.iter().map(|t| render_ty(core, &ctx, *t)).collect();
+    let returns: Vec<_> = sig.returns.iter().map(|t| render_ty(core, types, &ctx, *t)).collect();
     if core.flags.contains(Flags::ASYNC) {
         quote! {
             fn #name<'a>(
@@ -239,13 +626,14 @@ pub(crate) fn render_export<T: WasmTy>(
 /// Emit an export method *declaration* (inside the `FooImpl` trait).
 pub(crate) fn render_self_sig_import<T: WasmTy>(
     core: &OptsCore<'_>,
+    types: &impl TypeSpace<T>,
     name: Ident,
     sig: FuncSig<'_, T>,
 ) -> TokenStream {
     let root = core.crate_path.clone();
     let ctx = quote! { Self };
-    let params2: Vec<_> = sig.params.iter().map(|t| render_ty(core, &ctx, *t)).collect();
-    let returns: Vec<_> = sig.returns.iter().map(|t| render_ty(core, &ctx, *t)).collect();
+    let params2: Vec<_> = sig.params.iter().map(|t| render_ty(core, types, &ctx, *t)).collect();
+    let returns: Vec<_> = sig.returns.iter().map(|t| render_ty(core, types, &ctx, *t)).collect();
     if core.flags.contains(Flags::ASYNC) {
         quote! {
             fn #name<'a>(
@@ -279,6 +667,12 @@ impl WasmTy for wasmparser::ValType {
     #[inline] fn is_f64(self) -> bool { matches!(self, wasmparser::ValType::F64) }
     #[inline] fn is_v128(self) -> bool { matches!(self, wasmparser::ValType::V128) }
     #[inline] fn is_ref(self) -> bool { matches!(self, wasmparser::ValType::Ref(_)) }
+    #[inline] fn is_extern(self) -> bool { matches!(self, wasmparser::ValType::Ref(r) if r.is_extern_ref()) }
+    fn type_ref(self) -> Option<(bool, u32)> {
+        let wasmparser::ValType::Ref(r) = self else { return None };
+        let idx = r.type_index()?.as_module_index()?;
+        Some((r.is_nullable(), idx))
+    }
 }
 
 // ── WasmTy impl for waffle::Type ─────────────────────────────────────────────
@@ -291,4 +685,7 @@ impl WasmTy for waffle::Type {
     #[inline] fn is_f64(self) -> bool { matches!(self, waffle::Type::F64) }
     #[inline] fn is_v128(self) -> bool { matches!(self, waffle::Type::V128) }
     #[inline] fn is_ref(self) -> bool { matches!(self, waffle::Type::Heap(_)) }
+    #[inline] fn is_extern(self) -> bool {
+        matches!(self, waffle::Type::Heap(waffle::WithNullable { value: waffle::HeapType::ExternRef, .. }))
+    }
 }